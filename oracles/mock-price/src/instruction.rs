@@ -0,0 +1,90 @@
+//! Instructions of the mock price program.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+use std::mem::size_of;
+
+pub enum MockPriceInstruction {
+    /// Publishes a price in the current slot. The first price set in an account records its
+    /// signer as the account's authority, which must sign every later price.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` The price account, owned by this program and at least
+    ///    `PRICE_ACCOUNT_LEN` long.
+    /// 2. `[signer]` The price account's authority.
+    SetPrice {
+        /// The price, scaled by `10^expo`.
+        price: i64,
+        /// Confidence interval around the price, in the same units.
+        conf: u64,
+        expo: i32,
+        /// Pyth's `PriceStatus`, `Vault::oracle::STATUS_TRADING` when trading.
+        status: u32,
+    },
+}
+
+impl MockPriceInstruction {
+    /// Unpacks a byte buffer into a [MockPriceInstruction](enum.MockPriceInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        Ok(match (tag, rest.len()) {
+            (0, 24) => Self::SetPrice {
+                price: i64::from_le_bytes(rest[..8].try_into().unwrap()),
+                conf: u64::from_le_bytes(rest[8..16].try_into().unwrap()),
+                expo: i32::from_le_bytes(rest[16..20].try_into().unwrap()),
+                status: u32::from_le_bytes(rest[20..].try_into().unwrap()),
+            },
+            _ => return Err(ProgramError::InvalidInstructionData),
+        })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            &Self::SetPrice {
+                price,
+                conf,
+                expo,
+                status,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&conf.to_le_bytes());
+                buf.extend_from_slice(&expo.to_le_bytes());
+                buf.extend_from_slice(&status.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    pub fn set_price(
+        program_id: &Pubkey,
+        price_account: &Pubkey,
+        authority: &Pubkey,
+        price: i64,
+        conf: u64,
+        expo: i32,
+        status: u32,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(*price_account, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+            data: Self::SetPrice {
+                price,
+                conf,
+                expo,
+                status,
+            }
+            .pack(),
+        })
+    }
+}
@@ -0,0 +1,15 @@
+//! Mock oracle writing prices in the layout of Pyth price accounts, as read by
+//! `Vault::oracle::Price`. Whoever sets an account's first price may set any price after, so it
+//! exists only to exercise vaults' price feeds & triggers, e.g. under `solana-program-test`, and
+//! vaults only read it when built with the `mock-oracle` feature.
+
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
+pub mod instruction;
+pub mod processor;
+
+solana_program::declare_id!("7mzTR6g3SAfJhfL8nW6Jir98EWu6iL5agS2HML1aP47g");
+
+/// Length of the program's price accounts: a Pyth price account up to its aggregate price, then
+/// the authority setting its prices.
+pub const PRICE_ACCOUNT_LEN: usize = Vault::oracle::PRICE_ACCOUNT_LEN + 32;
@@ -0,0 +1,62 @@
+use solana_program::{
+  account_info::{next_account_info, AccountInfo},
+  entrypoint::ProgramResult,
+  msg,
+  program_error::ProgramError,
+  pubkey::Pubkey,
+  sysvar::{clock::Clock, Sysvar},
+};
+
+use Vault::oracle::{self, Price};
+
+use crate::{instruction::MockPriceInstruction, PRICE_ACCOUNT_LEN};
+
+/// Offset of the authority, after the Pyth price account's aggregate price.
+const AUTHORITY_OFFSET: usize = oracle::PRICE_ACCOUNT_LEN;
+
+pub struct Processor;
+impl Processor {
+  pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+  ) -> ProgramResult {
+    match MockPriceInstruction::unpack(instruction_data)? {
+      MockPriceInstruction::SetPrice {
+        price,
+        conf,
+        expo,
+        status,
+      } => {
+        msg!("Instruction: SetPrice");
+        let account_info_iter = &mut accounts.iter();
+        let price_account = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        if price_account.owner != program_id {
+          return Err(ProgramError::IncorrectProgramId);
+        }
+        if !authority.is_signer {
+          return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut data = price_account.data.borrow_mut();
+        if data.len() < PRICE_ACCOUNT_LEN {
+          return Err(ProgramError::AccountDataTooSmall);
+        }
+        // An account holding no price yet is claimed by its first authority.
+        if Price::unpack(&data).is_err() {
+          data[AUTHORITY_OFFSET..PRICE_ACCOUNT_LEN].copy_from_slice(authority.key.as_ref());
+        } else if data[AUTHORITY_OFFSET..PRICE_ACCOUNT_LEN] != *authority.key.as_ref() {
+          return Err(ProgramError::MissingRequiredSignature);
+        }
+        Price {
+          price,
+          conf,
+          expo,
+          status,
+          publish_slot: Clock::get()?.slot,
+        }
+        .pack(&mut data)
+      }
+    }
+  }
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "test-bpf")]
+
+use {
+  ::Vault::{error::VaultError, oracle},
+  assert_matches::*,
+  mock_price::{instruction::MockPriceInstruction, processor::Processor},
+  solana_program::{rent::Rent, system_instruction},
+  solana_program_test::{processor, ProgramTest},
+  solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+  },
+};
+
+/// Tests that set prices read back through the vault's oracle adapter, which rejects them once
+/// stale, & that only a price account's authority sets its prices.
+#[tokio::test]
+async fn test_set_price() {
+  let mut program_test_context = ProgramTest::new(
+    "mock_price",
+    mock_price::id(),
+    processor!(Processor::process),
+  )
+  .start_with_context()
+  .await;
+  let payer = program_test_context.payer.pubkey();
+  let price_account = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &price_account.pubkey(),
+        Rent::default().minimum_balance(mock_price::PRICE_ACCOUNT_LEN),
+        mock_price::PRICE_ACCOUNT_LEN as u64,
+        &mock_price::id(),
+      ),
+      MockPriceInstruction::set_price(
+        &mock_price::id(),
+        &price_account.pubkey(),
+        &payer,
+        2_000_000,
+        1_000,
+        -6,
+        oracle::STATUS_TRADING,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &price_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let data = program_test_context
+    .banks_client
+    .get_account(price_account.pubkey())
+    .await
+    .unwrap()
+    .unwrap()
+    .data;
+  let price = oracle::Price::unpack(&data).unwrap();
+  assert_eq!(
+    (price.price, price.conf, price.expo, price.status),
+    (2_000_000, 1_000, -6, oracle::STATUS_TRADING)
+  );
+  assert_matches!(price.check_current(price.publish_slot), Ok(()));
+  assert_matches!(
+    price.check_current(price.publish_slot + oracle::MAX_PRICE_AGE_SLOTS + 1),
+    Err(VaultError::StalePrice)
+  );
+
+  // Only accounts owned by the program hold prices.
+  let mut transaction = Transaction::new_with_payer(
+    &[MockPriceInstruction::set_price(&mock_price::id(), &payer, &payer, 1, 0, 0, 1).unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::IncorrectProgramId
+    )))
+  );
+
+  // Only the price account's authority, its first price's signer, may set its price.
+  let other_authority = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[MockPriceInstruction::set_price(
+      &mock_price::id(),
+      &price_account.pubkey(),
+      &other_authority.pubkey(),
+      1,
+      0,
+      0,
+      1,
+    )
+    .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &other_authority],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::MissingRequiredSignature
+    )))
+  );
+}
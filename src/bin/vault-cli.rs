@@ -0,0 +1,1569 @@
+//! Command-line client for creating, funding and inspecting vaults over RPC, e.g. against a local
+//! `solana-test-validator`.
+//!
+//! The RPC URL & fee payer default to those of the Solana CLI config. The fee payer also signs as
+//! the authority of deposited & withdrawn tokens, and becomes the governance of created vaults.
+//! Amounts are in base units of the source token.
+
+use std::{error, process::exit, str::FromStr};
+
+use clap::{
+    crate_description, crate_version, value_t_or_exit, App, AppSettings, Arg, ArgGroup, ArgMatches,
+    SubCommand,
+};
+use solana_clap_utils::{
+    input_parsers::{pubkey_of, pubkeys_of},
+    input_validators::{is_keypair, is_parsable, is_pubkey, is_url},
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use Vault::{
+    client::{self, VaultClient},
+    graph::{self, InvestmentGraph},
+    instruction::{StrategyInstruction, VaultInstruction},
+    pda,
+    state::{
+        FeeRecipient, InstructionTemplate, PriceFeed, StrategyAccount, TriggerAction,
+        TriggerCondition, Vault as VaultState,
+    },
+    token,
+};
+
+type Error = Box<dyn error::Error>;
+
+struct Config {
+    rpc_client: RpcClient,
+    fee_payer: Keypair,
+    program_id: Pubkey,
+}
+
+/// Parses an instruction template given as `tag:<N>`, for a one-byte tag as in
+/// `StrategyInstruction`, or `anchor:<NAME>`, for an Anchor instruction's sighash.
+fn parse_template(template: &str) -> Result<InstructionTemplate, String> {
+    let template = match template.split_once(':') {
+        Some(("tag", tag)) => InstructionTemplate::tag(
+            tag.parse()
+                .map_err(|_| format!("Invalid instruction tag {}", tag))?,
+        ),
+        Some(("anchor", name)) => InstructionTemplate::anchor(name),
+        _ => {
+            return Err(format!(
+                "Expected tag:<N> or anchor:<NAME>, got {}",
+                template
+            ))
+        }
+    };
+    if template.is_valid() {
+        Ok(template)
+    } else {
+        Err("Instruction template too long".to_string())
+    }
+}
+
+/// Parses a strategy account given as its address, suffixed with `:r` if read-only.
+fn parse_strategy_account(account: &str) -> Result<StrategyAccount, String> {
+    let (address, is_writable) = match account.strip_suffix(":r") {
+        Some(address) => (address, false),
+        None => (account, true),
+    };
+    Pubkey::from_str(address)
+        .map(|pubkey| StrategyAccount::new(pubkey, is_writable))
+        .map_err(|e| format!("Invalid strategy account {}: {}", address, e))
+}
+
+/// Parses a fee recipient given as `ADDRESS:BPS`.
+fn parse_fee_recipient(recipient: &str) -> Result<FeeRecipient, String> {
+    let (address, bps) = recipient
+        .split_once(':')
+        .ok_or_else(|| format!("Expected ADDRESS:BPS, got {}", recipient))?;
+    let token_account = Pubkey::from_str(address)
+        .map_err(|e| format!("Invalid fee recipient {}: {}", address, e))?;
+    let bps = bps
+        .parse()
+        .map_err(|_| format!("Invalid fee recipient bps {}", bps))?;
+    Ok(FeeRecipient::new(token_account, bps))
+}
+
+/// Parses a referral given as `REFERRED:TOKEN_ACCOUNT`, the referred llX account and the llX
+/// account of its referrer to pay.
+fn parse_referral(referral: &str) -> Result<(Pubkey, Pubkey), String> {
+    let (referred, token_account) = referral
+        .split_once(':')
+        .ok_or_else(|| format!("Expected REFERRED:TOKEN_ACCOUNT, got {}", referral))?;
+    let referred = Pubkey::from_str(referred)
+        .map_err(|e| format!("Invalid referred token account {}: {}", referred, e))?;
+    let token_account = Pubkey::from_str(token_account)
+        .map_err(|e| format!("Invalid referrer token account {}: {}", token_account, e))?;
+    Ok((referred, token_account))
+}
+
+/// Parses a trigger condition given as `slots:<N>`, `seconds:<N>`, `price-below:<ORACLE>:<PRICE>`,
+/// `price-above:<ORACLE>:<PRICE>` or `drawdown:<BPS>`. Prices are in the oracle's own exponent.
+fn parse_condition(condition: &str) -> Result<TriggerCondition, String> {
+    let invalid = || format!("Invalid trigger condition {}", condition);
+    let (kind, value) = condition.split_once(':').ok_or_else(invalid)?;
+    let price_condition = || {
+        let (oracle, price) = value.split_once(':').ok_or_else(invalid)?;
+        let oracle =
+            Pubkey::from_str(oracle).map_err(|e| format!("Invalid oracle {}: {}", oracle, e))?;
+        let price = price.parse().map_err(|_| invalid())?;
+        Ok::<_, String>((oracle, price))
+    };
+    Ok(match kind {
+        "slots" => TriggerCondition::SlotsElapsed {
+            slots: value.parse().map_err(|_| invalid())?,
+        },
+        "seconds" => TriggerCondition::TimeElapsed {
+            seconds: value.parse().map_err(|_| invalid())?,
+        },
+        "price-below" => {
+            let (oracle, price) = price_condition()?;
+            TriggerCondition::PriceBelow { oracle, price }
+        }
+        "price-above" => {
+            let (oracle, price) = price_condition()?;
+            TriggerCondition::PriceAbove { oracle, price }
+        }
+        "drawdown" => TriggerCondition::Drawdown {
+            bps: value.parse().map_err(|_| invalid())?,
+        },
+        _ => return Err(invalid()),
+    })
+}
+
+fn is_template(template: String) -> Result<(), String> {
+    parse_template(&template).map(|_| ())
+}
+
+fn is_strategy_account(account: String) -> Result<(), String> {
+    parse_strategy_account(&account).map(|_| ())
+}
+
+fn is_fee_recipient(recipient: String) -> Result<(), String> {
+    parse_fee_recipient(&recipient).map(|_| ())
+}
+
+fn is_referral(referral: String) -> Result<(), String> {
+    parse_referral(&referral).map(|_| ())
+}
+
+fn is_condition(condition: String) -> Result<(), String> {
+    parse_condition(&condition).map(|_| ())
+}
+
+fn referrals_of(matches: &ArgMatches<'_>) -> Vec<(Pubkey, Pubkey)> {
+    matches
+        .values_of("referral")
+        .map(|referrals| {
+            referrals
+                .map(|referral| parse_referral(referral).unwrap())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn send(config: &Config, instructions: &[Instruction]) -> Result<Signature, Error> {
+    send_signed(config, instructions, &[])
+}
+
+/// Sends `instructions` signed by the fee payer and `signers`, e.g. new accounts.
+fn send_signed(
+    config: &Config,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<Signature, Error> {
+    let (recent_blockhash, _) = config.rpc_client.get_recent_blockhash()?;
+    let mut all_signers = vec![&config.fee_payer];
+    all_signers.extend(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&config.fee_payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    Ok(config
+        .rpc_client
+        .send_and_confirm_transaction_with_spinner(&transaction)?)
+}
+
+fn owner_of(config: &Config, address: &Pubkey) -> Result<Pubkey, Error> {
+    Ok(config.rpc_client.get_account(address)?.owner)
+}
+
+async fn command_create_vault(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let x_token_mint = pubkey_of(matches, "x_mint").unwrap();
+    let lx_token_mint = pubkey_of(matches, "lx_mint").unwrap();
+    let strategy_program = pubkey_of(matches, "strategy_program").unwrap();
+    let nonce = value_t_or_exit!(matches, "nonce", u64);
+    let llx_decimals = value_t_or_exit!(matches, "llx_decimals", u8);
+    let strategy_accounts: Vec<_> = matches
+        .values_of("strategy_account")
+        .map(|accounts| {
+            accounts
+                .map(|account| parse_strategy_account(account).unwrap())
+                .collect()
+        })
+        .unwrap_or_default();
+    let deposit_template = parse_template(matches.value_of("deposit_instruction").unwrap())?;
+    let withdraw_template = parse_template(matches.value_of("withdraw_instruction").unwrap())?;
+    let hodl = strategy_accounts.is_empty();
+    // Vaults investing through another vault pass the chain below it for the graph checks.
+    let child_vaults = match strategy_accounts.first() {
+        Some(child) if strategy_program == config.program_id => {
+            graph::child_vault_metas(&mut config.rpc_client, &config.program_id, &child.pubkey)
+                .await?
+        }
+        _ => vec![],
+    };
+
+    let token_program = owner_of(config, &x_token_mint)?;
+    let mut create_vault = VaultInstruction::create_vault(
+        &config.program_id,
+        &config.fee_payer.pubkey(),
+        &x_token_mint,
+        &lx_token_mint,
+        &token_program,
+        &strategy_program,
+        nonce,
+        hodl,
+        strategy_accounts,
+        llx_decimals,
+        deposit_template,
+        withdraw_template,
+    )?;
+    let lx_token_program = owner_of(config, &lx_token_mint)?;
+    if lx_token_program != token_program {
+        create_vault
+            .accounts
+            .push(AccountMeta::new_readonly(lx_token_program, false));
+    }
+    create_vault.accounts.extend(child_vaults);
+    let (vault, _) =
+        pda::find_vault_address(&config.program_id, &x_token_mint, &strategy_program, nonce);
+    let register_vault = client::register_vault(
+        &mut config.rpc_client,
+        &config.program_id,
+        &config.fee_payer.pubkey(),
+        &vault,
+        &x_token_mint,
+        &strategy_program,
+    )
+    .await?;
+    let signature = send(config, &[create_vault, register_vault])?;
+    println!("Vault: {}", vault);
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+async fn command_transfer(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+    deposit: bool,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let source = pubkey_of(matches, "source").unwrap();
+    let target = pubkey_of(matches, "target").unwrap();
+    let amount = value_t_or_exit!(matches, "amount", u64);
+
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let source_authority = config.fee_payer.pubkey();
+    let mut instructions = vec![];
+    let instruction = if deposit {
+        match pubkey_of(matches, "referrer") {
+            Some(referrer) => {
+                vault.deposit_referred(&source, &target, &source_authority, amount, &referrer)?
+            }
+            None => vault.deposit(&source, &target, &source_authority, amount)?,
+        }
+    } else if matches.is_present("max_loss_bps") {
+        let max_loss_bps = value_t_or_exit!(matches, "max_loss_bps", u16);
+        // Losses are measured against the value rolled up in the same transaction.
+        instructions =
+            roll_up_instructions(config, &vault, pubkey_of(matches, "peek_result")).await?;
+        vault.withdraw_with_max_loss(&source, &target, &source_authority, amount, max_loss_bps)?
+    } else {
+        vault.withdraw(&source, &target, &source_authority, amount)?
+    };
+    instructions.push(instruction);
+    println!("Signature: {}", send(config, &instructions)?);
+    Ok(())
+}
+
+async fn command_show(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let vault_client =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let share_price = vault_client.share_price(&mut config.rpc_client).await?;
+    let vault = vault_client.vault;
+    println!("Vault: {}", vault_address);
+    println!("  Initialized: {}", vault.is_initialized);
+    println!("  Hodl: {}", vault.hodl);
+    println!("  Governance: {}", vault.governance);
+    println!(
+        "  X mint: {} ({} decimals)",
+        vault.x_token_mint_id, vault.x_decimals
+    );
+    println!(
+        "  llX mint: {} ({} decimals)",
+        vault.llx_token_mint_id, vault.llx_decimals
+    );
+    println!("  lX token account: {}", vault.lx_token_account);
+    if let COption::Some(x_token_account) = vault.x_token_account {
+        println!("  X token account: {}", x_token_account);
+    }
+    println!(
+        "  Shares: {} llX backed by {} {}",
+        share_price.share_supply,
+        share_price.total_assets,
+        if vault.hodl { "X" } else { "lX" }
+    );
+    if vault.total_assets_slot != 0 {
+        println!(
+            "  Rolled up: {} X at slot {}",
+            vault.total_assets, vault.total_assets_slot
+        );
+    }
+    println!("  Management fee: {} bps", vault.management_fee_bps);
+    println!("  Referral fee: {} bps", vault.referral_fee_bps);
+    for recipient in vault.fee_recipients.iter() {
+        println!("    {} bps to {}", recipient.bps, recipient.token_account);
+    }
+    if let COption::Some(price_feed) = vault.price_feed {
+        println!(
+            "  Priced by: {} ({} decimals) against X by {}",
+            price_feed.asset_oracle, price_feed.asset_decimals, price_feed.x_oracle
+        );
+    }
+    if vault.max_drawdown_bps != 0 {
+        println!(
+            "  Max drawdown: {} bps below a high-water share price of {}",
+            vault.max_drawdown_bps, vault.high_water_share_price
+        );
+    }
+    println!("  Strategy program: {}", vault.strategy_program_id);
+    if !vault.hodl {
+        println!(
+            "  Deposit instruction: {:?}",
+            vault.strategy_program_deposit_instruction
+        );
+        println!(
+            "  Withdraw instruction: {:?}",
+            vault.strategy_program_withdraw_instruction
+        );
+        println!("  Strategy accounts:");
+        for account in vault.strategy_accounts.iter() {
+            println!(
+                "    {}{}",
+                account.pubkey,
+                if account.is_writable {
+                    " (writable)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    for (index, strategy) in vault.strategies.iter().enumerate() {
+        println!(
+            "  Strategy {}: {} owing {} X, lent up to {} bps in {}-{} X per harvest",
+            index,
+            strategy.strategy_program_id,
+            strategy.total_debt,
+            strategy.debt_ratio_bps,
+            strategy.min_debt_per_harvest,
+            strategy.max_debt_per_harvest
+        );
+        println!("    lX token account: {}", strategy.lx_token_account);
+    }
+    if !vault.withdrawal_queue.is_empty() {
+        println!("  Withdrawal queue: {:?}", vault.withdrawal_queue);
+    }
+    Ok(())
+}
+
+async fn command_list(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vaults = if let Some(x_token_mint) = pubkey_of(matches, "mint") {
+        client::list_vaults_for_mint(&mut config.rpc_client, &config.program_id, &x_token_mint)
+            .await?
+    } else {
+        let strategy_program = pubkey_of(matches, "strategy_program").unwrap();
+        client::list_vaults_for_strategy(
+            &mut config.rpc_client,
+            &config.program_id,
+            &strategy_program,
+        )
+        .await?
+    };
+    if vaults.is_empty() {
+        println!("No vaults registered");
+    }
+    for (address, vault) in vaults {
+        println!(
+            "{} X mint {} strategy {}{}",
+            address,
+            vault.x_token_mint_id,
+            vault.strategy_program_id,
+            if vault.hodl { " (hodl)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+async fn command_harvest(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    if let Some(index) = matches.value_of("strategy") {
+        let index = index.parse::<u8>()?;
+        let peek_result = pubkey_of(matches, "peek_result")
+            .ok_or("Harvesting a strategy lent X needs a peek result")?;
+        let instructions = [
+            peek_strategy_instruction(config, &vault, index, &peek_result).await?,
+            vault.harvest_strategy(index, &peek_result)?,
+        ];
+        println!("Signature: {}", send(config, &instructions)?);
+        return Ok(());
+    }
+    if vault.vault.hodl {
+        return Err("Hodl vaults have no strategy to harvest".into());
+    }
+    // Vaults with a drawdown limit are harvested against a share price rolled up in the same
+    // slot, as by a harvest trigger.
+    let mut instructions = if vault.vault.max_drawdown_bps != 0 {
+        roll_up_instructions(config, &vault, pubkey_of(matches, "peek_result")).await?
+    } else {
+        vec![]
+    };
+    instructions.push(vault.harvest()?);
+    println!("Signature: {}", send(config, &instructions)?);
+    Ok(())
+}
+
+/// The instruction peeking at what the lX of `vault`'s `index`th strategy is worth into
+/// `peek_result`, which its debt is marked to by a harvest in the same slot.
+async fn peek_strategy_instruction(
+    config: &mut Config,
+    vault: &VaultClient,
+    index: u8,
+    peek_result: &Pubkey,
+) -> Result<Instruction, Error> {
+    let strategy = vault
+        .vault
+        .strategies
+        .get(index as usize)
+        .ok_or_else(|| format!("{} has no strategy {}", vault.address, index))?;
+    let lx_held = client::get_token_account(&mut config.rpc_client, &strategy.lx_token_account)
+        .await?
+        .amount;
+    let (data_account, strategy_accounts) = strategy.strategy_accounts.split_first().unwrap();
+    Ok(StrategyInstruction::peek(
+        &strategy.strategy_program_id,
+        peek_result,
+        &data_account.pubkey,
+        strategy_accounts
+            .iter()
+            .map(StrategyAccount::account_meta)
+            .collect(),
+        lx_held,
+        &vault.address,
+    )?)
+}
+
+/// Instructions rolling up `vault`, peeking at the bottom vault's strategy into `peek_result`
+/// first if given.
+async fn roll_up_instructions(
+    config: &mut Config,
+    vault: &VaultClient,
+    peek_result: Option<Pubkey>,
+) -> Result<Vec<Instruction>, Error> {
+    let mut instructions = Vec::with_capacity(2);
+    if let Some(peek_result) = peek_result {
+        let chain = vault.load_chain(&mut config.rpc_client).await?;
+        let (bottom_address, bottom) = chain.last().unwrap();
+        let (data_account, strategy_accounts) = match bottom.strategy_accounts.split_first() {
+            Some(accounts) if !bottom.hodl => accounts,
+            _ => return Err(format!("{} hodls, so needs no peek result", bottom_address).into()),
+        };
+        let lx_held = client::get_token_account(&mut config.rpc_client, &bottom.lx_token_account)
+            .await?
+            .amount;
+        instructions.push(StrategyInstruction::peek(
+            &bottom.strategy_program_id,
+            &peek_result,
+            &data_account.pubkey,
+            strategy_accounts
+                .iter()
+                .map(StrategyAccount::account_meta)
+                .collect(),
+            lx_held,
+            bottom_address,
+        )?);
+    }
+    instructions.push(
+        vault
+            .roll_up(&mut config.rpc_client, peek_result.as_ref())
+            .await?,
+    );
+    Ok(instructions)
+}
+
+async fn command_roll_up(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instructions =
+        roll_up_instructions(config, &vault, pubkey_of(matches, "peek_result")).await?;
+    println!("Signature: {}", send(config, &instructions)?);
+    Ok(())
+}
+
+async fn command_set_fees(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let management_fee_bps = value_t_or_exit!(matches, "management_fee_bps", u16);
+    let referral_fee_bps = value_t_or_exit!(matches, "referral_fee_bps", u16);
+    let fee_recipients = matches
+        .values_of("recipient")
+        .map(|recipients| {
+            recipients
+                .map(|recipient| parse_fee_recipient(recipient).unwrap())
+                .collect()
+        })
+        .unwrap_or_default();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    // What's accrued at the old rate must be collected in the same slot.
+    let instructions = [
+        vault.collect_fees(&referrals_of(matches))?,
+        VaultInstruction::set_fees(
+            &config.program_id,
+            &config.fee_payer.pubkey(),
+            &vault_address,
+            management_fee_bps,
+            referral_fee_bps,
+            fee_recipients,
+        )?,
+    ];
+    println!("Signature: {}", send(config, &instructions)?);
+    Ok(())
+}
+
+async fn command_set_price_feed(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let price_feed = match pubkey_of(matches, "asset_oracle") {
+        Some(asset_oracle) => COption::Some(PriceFeed {
+            asset_oracle,
+            x_oracle: pubkey_of(matches, "x_oracle").unwrap(),
+            asset_decimals: value_t_or_exit!(matches, "asset_decimals", u8),
+        }),
+        None => COption::None,
+    };
+    let instruction = VaultInstruction::set_price_feed(
+        &config.program_id,
+        &config.fee_payer.pubkey(),
+        &vault_address,
+        price_feed,
+    )?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_set_max_drawdown(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let max_drawdown_bps = value_t_or_exit!(matches, "max_drawdown_bps", u16);
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instruction = vault.set_max_drawdown(max_drawdown_bps)?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_enable_strategy(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instruction = vault.enable_strategy()?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_migrate(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let lx_token_mint = pubkey_of(matches, "lx_mint").unwrap();
+    let strategy_program = pubkey_of(matches, "strategy_program").unwrap();
+    let strategy_accounts: Vec<_> = matches
+        .values_of("strategy_account")
+        .unwrap()
+        .map(|account| parse_strategy_account(account).unwrap())
+        .collect();
+    let deposit_template = parse_template(matches.value_of("deposit_instruction").unwrap())?;
+    let withdraw_template = parse_template(matches.value_of("withdraw_instruction").unwrap())?;
+    let child_vaults = if strategy_program == config.program_id {
+        graph::child_vault_metas(
+            &mut config.rpc_client,
+            &config.program_id,
+            &strategy_accounts[0].pubkey,
+        )
+        .await?
+    } else {
+        vec![]
+    };
+
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    // The new strategy's lX is credited to a fresh account owned by the vault's authority.
+    let lx_token_account = Keypair::new();
+    let lx_token_program = owner_of(config, &lx_token_mint)?;
+    let lx_account_len = token::account_len(&config.rpc_client.get_account_data(&lx_token_mint)?)?;
+    let instructions = [
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &lx_token_account.pubkey(),
+            config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(lx_account_len)?,
+            lx_account_len as u64,
+            &lx_token_program,
+        ),
+        token::initialize_account(
+            &lx_token_program,
+            &lx_token_account.pubkey(),
+            &lx_token_mint,
+            &vault.authority,
+        )?,
+        vault.migrate_strategy(
+            &strategy_program,
+            &lx_token_account.pubkey(),
+            strategy_accounts,
+            deposit_template,
+            withdraw_template,
+            child_vaults,
+        )?,
+    ];
+    let signature = send_signed(config, &instructions, &[&lx_token_account])?;
+    println!("lX token account: {}", lx_token_account.pubkey());
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+async fn command_add_strategy(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let lx_token_mint = pubkey_of(matches, "lx_mint").unwrap();
+    let strategy_program = pubkey_of(matches, "strategy_program").unwrap();
+    let strategy_accounts: Vec<_> = matches
+        .values_of("strategy_account")
+        .unwrap()
+        .map(|account| parse_strategy_account(account).unwrap())
+        .collect();
+    let deposit_template = parse_template(matches.value_of("deposit_instruction").unwrap())?;
+    let withdraw_template = parse_template(matches.value_of("withdraw_instruction").unwrap())?;
+    let debt_ratio_bps = value_t_or_exit!(matches, "debt_ratio_bps", u16);
+    let min_debt_per_harvest = value_t_or_exit!(matches, "min_debt_per_harvest", u64);
+    let max_debt_per_harvest = value_t_or_exit!(matches, "max_debt_per_harvest", u64);
+
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    // As when migrating, the strategy's lX is credited to a fresh account owned by the vault's
+    // authority.
+    let lx_token_account = Keypair::new();
+    let lx_token_program = owner_of(config, &lx_token_mint)?;
+    let lx_account_len = token::account_len(&config.rpc_client.get_account_data(&lx_token_mint)?)?;
+    let instructions = [
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &lx_token_account.pubkey(),
+            config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(lx_account_len)?,
+            lx_account_len as u64,
+            &lx_token_program,
+        ),
+        token::initialize_account(
+            &lx_token_program,
+            &lx_token_account.pubkey(),
+            &lx_token_mint,
+            &vault.authority,
+        )?,
+        vault.add_strategy(
+            &strategy_program,
+            &lx_token_account.pubkey(),
+            strategy_accounts,
+            deposit_template,
+            withdraw_template,
+            debt_ratio_bps,
+            min_debt_per_harvest,
+            max_debt_per_harvest,
+        )?,
+    ];
+    let signature = send_signed(config, &instructions, &[&lx_token_account])?;
+    println!("lX token account: {}", lx_token_account.pubkey());
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+async fn command_update_strategy(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let index = value_t_or_exit!(matches, "strategy", u8);
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instruction = vault.update_strategy(
+        index,
+        value_t_or_exit!(matches, "debt_ratio_bps", u16),
+        value_t_or_exit!(matches, "min_debt_per_harvest", u64),
+        value_t_or_exit!(matches, "max_debt_per_harvest", u64),
+    )?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_set_withdrawal_queue(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let withdrawal_queue = matches
+        .values_of("strategy")
+        .unwrap()
+        .map(|index| index.parse().unwrap())
+        .collect();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instruction = vault.set_withdrawal_queue(withdrawal_queue)?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_collect_fees(config: &mut Config, matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let instruction = vault.collect_fees(&referrals_of(matches))?;
+    println!("Signature: {}", send(config, &[instruction])?);
+    Ok(())
+}
+
+async fn command_create_trigger(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let condition = parse_condition(matches.value_of("condition").unwrap())?;
+    let action = match matches.value_of("action").unwrap() {
+        "harvest" => TriggerAction::Harvest,
+        "rebalance" => TriggerAction::Rebalance,
+        _ => TriggerAction::MoveToHodl,
+    };
+    let bounty = value_t_or_exit!(matches, "bounty", u64);
+    let fund = value_t_or_exit!(matches, "fund", u64);
+    let nonce = value_t_or_exit!(matches, "nonce", u64);
+
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let (trigger, _) = pda::find_trigger_address(&config.program_id, &vault_address, nonce);
+    let mut instructions = vec![vault.create_trigger(nonce, condition, action, bounty)?];
+    if fund != 0 {
+        instructions.push(system_instruction::transfer(
+            &config.fee_payer.pubkey(),
+            &trigger,
+            fund,
+        ));
+    }
+    let signature = send(config, &instructions)?;
+    println!("Trigger: {}", trigger);
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+async fn command_execute_trigger(
+    config: &mut Config,
+    matches: &ArgMatches<'_>,
+) -> Result<(), Error> {
+    let vault_address = pubkey_of(matches, "vault").unwrap();
+    let nonce = value_t_or_exit!(matches, "nonce", u64);
+    let vault =
+        VaultClient::load(&mut config.rpc_client, &config.program_id, &vault_address).await?;
+    let trigger = vault
+        .get_trigger(&mut config.rpc_client, nonce)
+        .await?
+        .ok_or_else(|| format!("Vault {} has no trigger {}", vault_address, nonce))?;
+    // Drawdowns are measured against a share price rolled up in the same slot, as are harvests
+    // of vaults with a drawdown limit.
+    let rolls_up = matches!(trigger.condition, TriggerCondition::Drawdown { .. })
+        || (trigger.action == TriggerAction::Harvest && vault.vault.max_drawdown_bps != 0);
+    let mut instructions = if rolls_up {
+        roll_up_instructions(config, &vault, pubkey_of(matches, "peek_result")).await?
+    } else {
+        vec![]
+    };
+    // Rebalances mark each strategy's debt to a peek of its own in the same slot.
+    let peek_results = if trigger.action == TriggerAction::Rebalance {
+        let peek_results = pubkeys_of(matches, "peek_result").unwrap_or_default();
+        if peek_results.len() != vault.vault.strategies.len() {
+            return Err("Rebalancing needs a peek result for each strategy".into());
+        }
+        for (index, peek_result) in peek_results.iter().enumerate() {
+            instructions
+                .push(peek_strategy_instruction(config, &vault, index as u8, peek_result).await?);
+        }
+        peek_results
+    } else {
+        vec![]
+    };
+    let (trigger_address, _) = pda::find_trigger_address(&config.program_id, &vault_address, nonce);
+    instructions.push(vault.execute_trigger(
+        &trigger_address,
+        &trigger,
+        &config.fee_payer.pubkey(),
+        &peek_results,
+    )?);
+    println!("Signature: {}", send(config, &instructions)?);
+    Ok(())
+}
+
+fn command_graph(config: &Config) -> Result<(), Error> {
+    let vaults = config
+        .rpc_client
+        .get_program_accounts(&config.program_id)?
+        .into_iter()
+        .filter(|(_, account)| account.data.len() == VaultState::LEN)
+        .filter_map(|(address, account)| {
+            VaultState::unpack(&account.data)
+                .ok()
+                .map(|vault| (address, vault))
+        });
+    print!(
+        "{}",
+        InvestmentGraph::new(&config.program_id, vaults).to_dot()
+    );
+    Ok(())
+}
+
+fn vault_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("vault")
+        .value_name("VAULT_ADDRESS")
+        .validator(is_pubkey)
+        .takes_value(true)
+        .required(true)
+        .help("The vault storage account")
+}
+
+fn nonce_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("nonce")
+        .long("nonce")
+        .value_name("NONCE")
+        .validator(is_parsable::<u64>)
+        .takes_value(true)
+        .default_value("0")
+        .help("Nonce the trigger's address is derived from, telling apart a vault's triggers")
+}
+
+fn referral_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("referral")
+        .long("referral")
+        .value_name("REFERRED:TOKEN_ACCOUNT")
+        .validator(is_referral)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Referred llX token account whose referrer to pay, into their llX token account")
+}
+
+fn transfer_subcommand<'a, 'b>(
+    name: &'a str,
+    about: &'a str,
+    source: &'a str,
+    target: &'a str,
+) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(vault_arg().index(1))
+        .arg(
+            Arg::with_name("source")
+                .value_name("SOURCE_TOKEN_ACCOUNT")
+                .validator(is_pubkey)
+                .takes_value(true)
+                .required(true)
+                .index(2)
+                .help(source),
+        )
+        .arg(
+            Arg::with_name("target")
+                .value_name("TARGET_TOKEN_ACCOUNT")
+                .validator(is_pubkey)
+                .takes_value(true)
+                .required(true)
+                .index(3)
+                .help(target),
+        )
+        .arg(
+            Arg::with_name("amount")
+                .value_name("AMOUNT")
+                .validator(is_parsable::<u64>)
+                .takes_value(true)
+                .required(true)
+                .index(4)
+                .help("Amount in base units of the source token"),
+        )
+}
+
+/// The debt ratio & per-harvest limits of a strategy a hodl vault lends X to.
+fn debt_limit_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+    [
+        Arg::with_name("debt_ratio_bps")
+            .long("debt-ratio-bps")
+            .value_name("BPS")
+            .validator(is_parsable::<u16>)
+            .takes_value(true)
+            .required(true)
+            .help("Share of the vault's assets lent to the strategy"),
+        Arg::with_name("min_debt_per_harvest")
+            .long("min-debt-per-harvest")
+            .value_name("AMOUNT")
+            .validator(is_parsable::<u64>)
+            .takes_value(true)
+            .default_value("0")
+            .help("Least X lent by a harvest, which lends nothing below it"),
+        Arg::with_name("max_debt_per_harvest")
+            .long("max-debt-per-harvest")
+            .value_name("AMOUNT")
+            .validator(is_parsable::<u64>)
+            .takes_value(true)
+            .default_value("18446744073709551615")
+            .help("Most X lent by a harvest"),
+    ]
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let app_matches = App::new("vault-cli")
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .short("u")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .global(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster [default: value from configuration file]"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .short("k")
+                .long("keypair")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .global(true)
+                .validator(is_keypair)
+                .help("Fee payer & token authority [default: value from configuration file]"),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .global(true)
+                .validator(is_pubkey)
+                .help("Vault program [default: Vault::id()]"),
+        )
+        .subcommand(
+            SubCommand::with_name("create-vault")
+                .about("Create a vault at its derived address and register it")
+                .arg(
+                    Arg::with_name("x_mint")
+                        .long("x-mint")
+                        .value_name("MINT_ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Mint of the token deposited into the vault"),
+                )
+                .arg(
+                    Arg::with_name("lx_mint")
+                        .long("lx-mint")
+                        .value_name("MINT_ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Mint of the token the strategy returns for X"),
+                )
+                .arg(
+                    Arg::with_name("strategy_program")
+                        .long("strategy-program")
+                        .value_name("PROGRAM_ID")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Strategy program the vault invests through"),
+                )
+                .arg(
+                    Arg::with_name("strategy_account")
+                        .long("strategy-account")
+                        .value_name("ADDRESS[:r]")
+                        .validator(is_strategy_account)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "Account passed to the strategy, read-only if suffixed with :r. The \
+                             first is the strategy's data account. Without any the vault hodls.",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("nonce")
+                        .long("nonce")
+                        .value_name("NONCE")
+                        .validator(is_parsable::<u64>)
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Distinguishes vaults over the same mint & strategy"),
+                )
+                .arg(
+                    Arg::with_name("llx_decimals")
+                        .long("llx-decimals")
+                        .value_name("DECIMALS")
+                        .validator(is_parsable::<u8>)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Decimals of the vault's llX mint"),
+                )
+                .arg(
+                    Arg::with_name("deposit_instruction")
+                        .long("deposit-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:0")
+                        .help("Strategy deposit instruction, as tag:<N> or anchor:<NAME>"),
+                )
+                .arg(
+                    Arg::with_name("withdraw_instruction")
+                        .long("withdraw-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:1")
+                        .help("Strategy withdraw instruction, as tag:<N> or anchor:<NAME>"),
+                ),
+        )
+        .subcommand(
+            transfer_subcommand(
+                "deposit",
+                "Deposit X into a vault for llX",
+                "X token account to deposit from",
+                "llX token account to receive shares",
+            )
+            .arg(
+                Arg::with_name("referrer")
+                    .long("referrer")
+                    .value_name("REFERRER_ADDRESS")
+                    .validator(is_pubkey)
+                    .takes_value(true)
+                    .help("Whoever referred the deposit, credited with it for a share of the fees"),
+            ),
+        )
+        .subcommand(
+            transfer_subcommand(
+                "withdraw",
+                "Burn llX for X from a vault",
+                "llX token account to burn from",
+                "X token account to receive tokens",
+            )
+            .arg(
+                Arg::with_name("max_loss_bps")
+                    .long("max-loss-bps")
+                    .value_name("BPS")
+                    .validator(is_parsable::<u16>)
+                    .takes_value(true)
+                    .help("Fail if the X received falls this far short of the llX's value"),
+            )
+            .arg(
+                Arg::with_name("peek_result")
+                    .long("peek-result")
+                    .value_name("ACCOUNT")
+                    .validator(is_pubkey)
+                    .takes_value(true)
+                    .requires("max_loss_bps")
+                    .help("Result account for peeking when rolling up to value the llX"),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Decode and display a vault")
+                .arg(vault_arg().index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List the vaults registered for a mint or strategy")
+                .arg(
+                    Arg::with_name("mint")
+                        .long("mint")
+                        .value_name("MINT_ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .help("List vaults over this X mint"),
+                )
+                .arg(
+                    Arg::with_name("strategy_program")
+                        .long("strategy-program")
+                        .value_name("PROGRAM_ID")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .help("List vaults investing through this strategy program"),
+                )
+                .group(
+                    ArgGroup::with_name("registry")
+                        .args(&["mint", "strategy_program"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("harvest")
+                .about(
+                    "Harvest a vault's strategy, or move it to hodl if its drawdown limit is \
+                     breached",
+                )
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("peek_result")
+                        .long("peek-result")
+                        .value_name("ACCOUNT")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .help(
+                            "Result account for peeking when rolling up to check a drawdown, or \
+                             at the strategy harvested",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("strategy")
+                        .long("strategy")
+                        .value_name("INDEX")
+                        .validator(is_parsable::<u8>)
+                        .takes_value(true)
+                        .requires("peek_result")
+                        .help(
+                            "Harvest this strategy of a hodl vault, lending it X up to its debt \
+                             ratio or taking back what it was lent beyond",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("roll-up")
+                .about("Value a vault and every vault below it in X, caching their total assets")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("peek_result")
+                        .long("peek-result")
+                        .value_name("ACCOUNT")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .help(
+                            "Result account for peeking at the bottom vault's strategy, passed \
+                             that vault's strategy accounts, when it isn't another vault",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Print the investment graph of every vault in Graphviz DOT"),
+        )
+        .subcommand(
+            SubCommand::with_name("set-fees")
+                .about("Collect a vault's fees, then set its fee & who it's split between")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("management_fee_bps")
+                        .long("management-fee-bps")
+                        .value_name("BPS")
+                        .validator(is_parsable::<u16>)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Yearly fee on the vault's holdings, taken in llX"),
+                )
+                .arg(
+                    Arg::with_name("referral_fee_bps")
+                        .long("referral-fee-bps")
+                        .value_name("BPS")
+                        .validator(is_parsable::<u16>)
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Share of the fees on referred deposits paid to their referrer"),
+                )
+                .arg(
+                    Arg::with_name("recipient")
+                        .long("recipient")
+                        .value_name("ADDRESS:BPS")
+                        .validator(is_fee_recipient)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "llX token account receiving BPS of the fees. The recipients' BPS \
+                             must add up to 10000.",
+                        ),
+                )
+                .arg(referral_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("set-price-feed")
+                .about("Set the oracles valuing a vault's strategy holdings, or clear them")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("asset_oracle")
+                        .long("asset-oracle")
+                        .value_name("PRICE_ACCOUNT")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .requires_all(&["x_oracle", "asset_decimals"])
+                        .help("Pyth price account of what the strategy's peek reports"),
+                )
+                .arg(
+                    Arg::with_name("x_oracle")
+                        .long("x-oracle")
+                        .value_name("PRICE_ACCOUNT")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .help("Pyth price account of X, in the same currency"),
+                )
+                .arg(
+                    Arg::with_name("asset_decimals")
+                        .long("asset-decimals")
+                        .value_name("DECIMALS")
+                        .validator(is_parsable::<u8>)
+                        .takes_value(true)
+                        .help("Decimals of the asset amounts the strategy's peek reports"),
+                )
+                .arg(
+                    Arg::with_name("clear")
+                        .long("clear")
+                        .takes_value(false)
+                        .help("Value the strategy's holdings in X, without oracles"),
+                )
+                .group(
+                    ArgGroup::with_name("feed")
+                        .args(&["asset_oracle", "clear"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-max-drawdown")
+                .about("Set how far a vault's share price may fall before it moves to hodl")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("max_drawdown_bps")
+                        .index(2)
+                        .value_name("BPS")
+                        .validator(is_parsable::<u16>)
+                        .required(true)
+                        .help("Bps below the high-water share price, or 0 for no limit"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("enable-strategy")
+                .about("Invest a vault moved to hodl through its strategy again")
+                .arg(vault_arg().index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Move an unregistered vault and its holdings to another strategy")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("lx_mint")
+                        .long("lx-mint")
+                        .value_name("MINT_ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Mint of the token the new strategy returns for X"),
+                )
+                .arg(
+                    Arg::with_name("strategy_program")
+                        .long("strategy-program")
+                        .value_name("PROGRAM_ID")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Strategy program the vault invests through from now on"),
+                )
+                .arg(
+                    Arg::with_name("strategy_account")
+                        .long("strategy-account")
+                        .value_name("ADDRESS[:r]")
+                        .validator(is_strategy_account)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help(
+                            "Account passed to the new strategy, read-only if suffixed with :r. \
+                             The first is the strategy's data account.",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("deposit_instruction")
+                        .long("deposit-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:0")
+                        .help("Strategy deposit instruction, as tag:<N> or anchor:<NAME>"),
+                )
+                .arg(
+                    Arg::with_name("withdraw_instruction")
+                        .long("withdraw-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:1")
+                        .help("Strategy withdraw instruction, as tag:<N> or anchor:<NAME>"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("add-strategy")
+                .about("Add a strategy a hodl vault lends X to on harvest")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("lx_mint")
+                        .long("lx-mint")
+                        .value_name("MINT_ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Mint of the token the strategy returns for X"),
+                )
+                .arg(
+                    Arg::with_name("strategy_program")
+                        .long("strategy-program")
+                        .value_name("PROGRAM_ID")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Strategy program the vault lends X to"),
+                )
+                .arg(
+                    Arg::with_name("strategy_account")
+                        .long("strategy-account")
+                        .value_name("ADDRESS[:r]")
+                        .validator(is_strategy_account)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help(
+                            "Account passed to the strategy, read-only if suffixed with :r. The \
+                             first is the strategy's data account.",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("deposit_instruction")
+                        .long("deposit-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:0")
+                        .help("Strategy deposit instruction, as tag:<N> or anchor:<NAME>"),
+                )
+                .arg(
+                    Arg::with_name("withdraw_instruction")
+                        .long("withdraw-instruction")
+                        .value_name("TEMPLATE")
+                        .validator(is_template)
+                        .takes_value(true)
+                        .default_value("tag:1")
+                        .help("Strategy withdraw instruction, as tag:<N> or anchor:<NAME>"),
+                )
+                .args(&debt_limit_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("update-strategy")
+                .about("Set the debt ratio & per-harvest limits of a hodl vault's strategy")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("strategy")
+                        .index(2)
+                        .value_name("INDEX")
+                        .validator(is_parsable::<u8>)
+                        .required(true)
+                        .help("Index of the strategy, as listed by show"),
+                )
+                .args(&debt_limit_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("set-withdrawal-queue")
+                .about("Set the order withdrawals take back X lent to a hodl vault's strategies")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("strategy")
+                        .index(2)
+                        .value_name("INDEX")
+                        .validator(is_parsable::<u8>)
+                        .multiple(true)
+                        .required(true)
+                        .help("Indices of the strategies, as listed by show, in withdrawal order"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("collect-fees")
+                .about("Mint a vault's accrued fees to its fee recipients")
+                .arg(vault_arg().index(1))
+                .arg(referral_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("create-trigger")
+                .about("Create a trigger any keeper can execute once its condition is met")
+                .arg(vault_arg().index(1))
+                .arg(
+                    Arg::with_name("condition")
+                        .long("condition")
+                        .value_name("CONDITION")
+                        .validator(is_condition)
+                        .takes_value(true)
+                        .required(true)
+                        .help(
+                            "slots:<N> or seconds:<N> since last executed, \
+                             price-below:<ORACLE>:<PRICE>, price-above:<ORACLE>:<PRICE>, or \
+                             drawdown:<BPS> below the high-water share price",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("action")
+                        .long("action")
+                        .value_name("ACTION")
+                        .possible_values(&["harvest", "move-to-hodl", "rebalance"])
+                        .takes_value(true)
+                        .required(true)
+                        .help("What executing the trigger does"),
+                )
+                .arg(
+                    Arg::with_name("bounty")
+                        .long("bounty")
+                        .value_name("LAMPORTS")
+                        .validator(is_parsable::<u64>)
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Lamports paid from the trigger's balance to whoever executes it"),
+                )
+                .arg(
+                    Arg::with_name("fund")
+                        .long("fund")
+                        .value_name("LAMPORTS")
+                        .validator(is_parsable::<u64>)
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Lamports to send the trigger to pay bounties from"),
+                )
+                .arg(nonce_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("execute-trigger")
+                .about("Execute a vault's trigger, collecting its bounty")
+                .arg(vault_arg().index(1))
+                .arg(nonce_arg())
+                .arg(
+                    Arg::with_name("peek_result")
+                        .long("peek-result")
+                        .value_name("ACCOUNT")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "Result account for peeking when rolling up to check a drawdown, or \
+                             one for each strategy, in order, when rebalancing",
+                        ),
+                ),
+        )
+        .get_matches();
+
+    let (sub_command, sub_matches) = app_matches.subcommand();
+    let matches = sub_matches.unwrap();
+
+    let mut config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = matches
+            .value_of("json_rpc_url")
+            .unwrap_or(&cli_config.json_rpc_url)
+            .to_string();
+        let keypair_path = matches
+            .value_of("keypair")
+            .unwrap_or(&cli_config.keypair_path);
+        let fee_payer = read_keypair_file(keypair_path).unwrap_or_else(|e| {
+            eprintln!("error: Unable to read keypair {}: {}", keypair_path, e);
+            exit(1);
+        });
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            fee_payer,
+            program_id: pubkey_of(matches, "program_id").unwrap_or_else(Vault::id),
+        }
+    };
+
+    let result = match sub_command {
+        "create-vault" => command_create_vault(&mut config, matches).await,
+        "deposit" => command_transfer(&mut config, matches, true).await,
+        "withdraw" => command_transfer(&mut config, matches, false).await,
+        "show" => command_show(&mut config, matches).await,
+        "list" => command_list(&mut config, matches).await,
+        "harvest" => command_harvest(&mut config, matches).await,
+        "roll-up" => command_roll_up(&mut config, matches).await,
+        "graph" => command_graph(&config),
+        "set-fees" => command_set_fees(&mut config, matches).await,
+        "set-price-feed" => command_set_price_feed(&mut config, matches).await,
+        "set-max-drawdown" => command_set_max_drawdown(&mut config, matches).await,
+        "enable-strategy" => command_enable_strategy(&mut config, matches).await,
+        "migrate" => command_migrate(&mut config, matches).await,
+        "add-strategy" => command_add_strategy(&mut config, matches).await,
+        "update-strategy" => command_update_strategy(&mut config, matches).await,
+        "set-withdrawal-queue" => command_set_withdrawal_queue(&mut config, matches).await,
+        "collect-fees" => command_collect_fees(&mut config, matches).await,
+        "create-trigger" => command_create_trigger(&mut config, matches).await,
+        "execute-trigger" => command_execute_trigger(&mut config, matches).await,
+        _ => unreachable!(),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+//! Replays a vault scenario file, printing the share price, fees minted & each user's PnL after
+//! every step. See `Vault::sim` for the scenario format.
+
+use std::{env, fs, process::exit};
+
+use Vault::sim::{self, Scenario};
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("usage: vault-sim <SCENARIO.json>");
+        exit(2);
+    });
+    let reports = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| Scenario::from_json(&json).map_err(|e| e.to_string()))
+        .and_then(|scenario| sim::run(&scenario).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}: {}", path, e);
+            exit(1);
+        });
+
+    println!(
+        "{:>4} {:>12} {:>20} {:>20} {:>12} {:>12}  user PnL",
+        "step", "share price", "total assets", "share supply", "fee llX", "fee value"
+    );
+    for report in reports {
+        let share_price = report
+            .share_price
+            .map_or_else(|| "-".to_string(), |price| format!("{:.6}", price));
+        let user_pnl = report
+            .user_pnl
+            .iter()
+            .map(|(user, pnl)| format!("{} {:+}", user, pnl))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{:>4} {:>12} {:>20} {:>20} {:>12} {:>12}  {}",
+            report.step,
+            share_price,
+            report.total_assets,
+            report.share_supply,
+            report.fee_shares,
+            report.fee_value,
+            user_pnl
+        );
+    }
+}
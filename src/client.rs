@@ -0,0 +1,921 @@
+//! Off-chain helpers for locating, decoding & operating vaults.
+//!
+//! Accounts are read through [AccountFetcher](trait.AccountFetcher.html), which is implemented
+//! for `RpcClient` (feature `client-rpc`) and `BanksClient` (feature `client-banks`).
+//! [VaultClient](struct.VaultClient.html) builds a loaded vault's instructions with the accounts
+//! its program expects, in order.
+
+use async_trait::async_trait;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use thiserror::Error;
+
+use crate::{
+    error::VaultError,
+    instruction::VaultInstruction,
+    math, pda,
+    state::{
+        InstructionTemplate, Referral, Registration, Registry, StrategyAccount, Trigger,
+        TriggerAction, TriggerCondition, Vault, MAX_REGISTERED_VAULTS, MAX_VAULT_DEPTH,
+    },
+    token,
+};
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("Invalid account data: {0}")]
+    Program(#[from] ProgramError),
+    #[error("Transport error: {0}")]
+    Transport(String),
+}
+
+impl From<VaultError> for ClientError {
+    fn from(e: VaultError) -> Self {
+        ClientError::Program(e.into())
+    }
+}
+
+/// An on-chain account's owning program & data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FetchedAccount {
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// Source of on-chain account data.
+#[async_trait(?Send)]
+pub trait AccountFetcher {
+    /// Returns the account at `address`, or `None` if no such account exists.
+    async fn fetch_account(
+        &mut self,
+        address: &Pubkey,
+    ) -> Result<Option<FetchedAccount>, ClientError>;
+
+    /// Returns the data held by `address`, or `None` if no such account exists.
+    async fn get_account_data(&mut self, address: &Pubkey) -> Result<Option<Vec<u8>>, ClientError> {
+        Ok(self.fetch_account(address).await?.map(|account| account.data))
+    }
+}
+
+#[cfg(feature = "client-rpc")]
+#[async_trait(?Send)]
+impl AccountFetcher for solana_client::rpc_client::RpcClient {
+    async fn fetch_account(
+        &mut self,
+        address: &Pubkey,
+    ) -> Result<Option<FetchedAccount>, ClientError> {
+        self.get_account_with_commitment(address, self.commitment())
+            .map(|response| {
+                response.value.map(|account| FetchedAccount {
+                    owner: account.owner,
+                    data: account.data,
+                })
+            })
+            .map_err(|e| ClientError::Transport(e.to_string()))
+    }
+}
+
+#[cfg(feature = "client-banks")]
+#[async_trait(?Send)]
+impl AccountFetcher for solana_banks_client::BanksClient {
+    async fn fetch_account(
+        &mut self,
+        address: &Pubkey,
+    ) -> Result<Option<FetchedAccount>, ClientError> {
+        self.get_account(*address)
+            .await
+            .map(|account| {
+                account.map(|account| FetchedAccount {
+                    owner: account.owner,
+                    data: account.data,
+                })
+            })
+            .map_err(|e| ClientError::Transport(e.to_string()))
+    }
+}
+
+/// Loads & decodes the vault stored at `vault`.
+pub async fn get_vault<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    vault: &Pubkey,
+) -> Result<Vault, ClientError> {
+    let data = fetcher
+        .get_account_data(vault)
+        .await?
+        .ok_or(ClientError::AccountNotFound(*vault))?;
+    Ok(Vault::unpack(&data)?)
+}
+
+/// Loads the registry at `address`, which doesn't exist until a vault is registered in it.
+pub async fn get_registry<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    address: &Pubkey,
+) -> Result<Option<Registry>, ClientError> {
+    match fetcher.get_account_data(address).await? {
+        Some(data) => Ok(Some(Registry::unpack(&data)?)),
+        None => Ok(None),
+    }
+}
+
+/// Lists all registered vaults over `x_token_mint_id`.
+pub async fn list_vaults_for_mint<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    program_id: &Pubkey,
+    x_token_mint_id: &Pubkey,
+) -> Result<Vec<(Pubkey, Vault)>, ClientError> {
+    list_registered_vaults(fetcher, |page| {
+        pda::find_mint_registry_address(program_id, x_token_mint_id, page).0
+    })
+    .await
+}
+
+/// Lists all registered vaults investing through `strategy_program_id`.
+pub async fn list_vaults_for_strategy<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    program_id: &Pubkey,
+    strategy_program_id: &Pubkey,
+) -> Result<Vec<(Pubkey, Vault)>, ClientError> {
+    list_registered_vaults(fetcher, |page| {
+        pda::find_strategy_registry_address(program_id, strategy_program_id, page).0
+    })
+    .await
+}
+
+/// Lists the vaults in every page of a registry, whose pages are at `page_address`, skipping any
+/// closed but still registered.
+async fn list_registered_vaults<F: AccountFetcher + ?Sized, A: Fn(u32) -> Pubkey>(
+    fetcher: &mut F,
+    page_address: A,
+) -> Result<Vec<(Pubkey, Vault)>, ClientError> {
+    let mut vaults: Vec<(Pubkey, Vault)> = vec![];
+    let mut page = 0;
+    while let Some(registry) = get_registry(fetcher, &page_address(page)).await? {
+        for vault in registry.vaults.iter() {
+            if vaults.iter().any(|(listed, _)| listed == vault) {
+                continue;
+            }
+            if let Some(data) = fetcher.get_account_data(vault).await? {
+                let storage = Vault::unpack_unchecked(&data)?;
+                if storage.is_initialized() {
+                    vaults.push((*vault, storage));
+                }
+            }
+        }
+        page += 1;
+    }
+    Ok(vaults)
+}
+
+/// The first page of a registry, whose pages are at `page_address`, with room for another vault.
+pub async fn open_registry_page<F: AccountFetcher + ?Sized, A: Fn(u32) -> Pubkey>(
+    fetcher: &mut F,
+    page_address: A,
+) -> Result<u32, ClientError> {
+    let mut page = 0;
+    while let Some(registry) = get_registry(fetcher, &page_address(page)).await? {
+        if registry.vaults.len() < MAX_REGISTERED_VAULTS {
+            break;
+        }
+        page += 1;
+    }
+    Ok(page)
+}
+
+/// Builds a `RegisterVault` instruction adding `vault`, over `x_token_mint_id` & investing through
+/// `strategy_program_id`, to the open page of each of its registries.
+pub async fn register_vault<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    vault: &Pubkey,
+    x_token_mint_id: &Pubkey,
+    strategy_program_id: &Pubkey,
+) -> Result<Instruction, ClientError> {
+    let mint_registry_page = open_registry_page(fetcher, |page| {
+        pda::find_mint_registry_address(program_id, x_token_mint_id, page).0
+    })
+    .await?;
+    let strategy_registry_page = open_registry_page(fetcher, |page| {
+        pda::find_strategy_registry_address(program_id, strategy_program_id, page).0
+    })
+    .await?;
+    Ok(VaultInstruction::register_vault(
+        program_id,
+        payer,
+        vault,
+        x_token_mint_id,
+        strategy_program_id,
+        mint_registry_page,
+        strategy_registry_page,
+    )?)
+}
+
+/// Builds a `DeregisterVault` instruction removing the closed `vault`, over `x_token_mint_id` &
+/// investing through `strategy_program_id`, from the registry pages its `Registration` records.
+pub async fn deregister_vault<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    program_id: &Pubkey,
+    vault: &Pubkey,
+    x_token_mint_id: &Pubkey,
+    strategy_program_id: &Pubkey,
+) -> Result<Instruction, ClientError> {
+    let address = pda::find_registration_address(program_id, vault).0;
+    let data = fetcher
+        .get_account_data(&address)
+        .await?
+        .ok_or(ClientError::AccountNotFound(address))?;
+    let registration = Registration::unpack(&data)?;
+    Ok(VaultInstruction::deregister_vault(
+        program_id,
+        vault,
+        x_token_mint_id,
+        strategy_program_id,
+        registration.mint_registry_page,
+        registration.strategy_registry_page,
+        &registration.payer,
+    )?)
+}
+
+/// Returns the program owning `address`, e.g. the token program of a mint.
+pub async fn get_owner<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    address: &Pubkey,
+) -> Result<Pubkey, ClientError> {
+    fetcher
+        .fetch_account(address)
+        .await?
+        .map(|account| account.owner)
+        .ok_or(ClientError::AccountNotFound(*address))
+}
+
+/// Loads a token account owned by either token program.
+pub async fn get_token_account<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    address: &Pubkey,
+) -> Result<spl_token::state::Account, ClientError> {
+    let data = fetcher
+        .get_account_data(address)
+        .await?
+        .ok_or(ClientError::AccountNotFound(*address))?;
+    Ok(token::unpack_account_data(&data)?)
+}
+
+/// Loads a mint owned by either token program.
+pub async fn get_mint<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    address: &Pubkey,
+) -> Result<spl_token::state::Mint, ClientError> {
+    let data = fetcher
+        .get_account_data(address)
+        .await?
+        .ok_or(ClientError::AccountNotFound(*address))?;
+    Ok(token::unpack_mint_data(&data)?)
+}
+
+/// What backs a vault's llX shares: X for hodl vaults, held or lent to their strategies, and lX
+/// for vaults investing through a strategy, since those price shares in lX as the strategy
+/// appreciates it against X.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SharePrice {
+    pub total_assets: u64,
+    pub share_supply: u64,
+}
+
+impl SharePrice {
+    /// Assets redeemed for `shares` llX tokens, as priced by `Withdraw`.
+    pub fn assets_for_shares(&self, shares: u64) -> Option<u64> {
+        math::assets_for_shares(shares, self.total_assets, self.share_supply)
+    }
+}
+
+/// A holder's llX shares in a vault and the assets they redeem for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub shares: u64,
+    pub assets: u64,
+}
+
+/// A vault loaded from its storage account, building its instructions with the accounts the
+/// vault program expects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VaultClient {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+    pub vault: Vault,
+    /// The vault program's derived authority, owning every vault's accounts.
+    pub authority: Pubkey,
+    /// Token program owning the X mint.
+    pub x_token_program: Pubkey,
+    /// Token program owning the llX mint.
+    pub llx_token_program: Pubkey,
+}
+
+impl VaultClient {
+    /// Loads the vault at `address`, owned by `program_id`.
+    pub async fn load<F: AccountFetcher + ?Sized>(
+        fetcher: &mut F,
+        program_id: &Pubkey,
+        address: &Pubkey,
+    ) -> Result<Self, ClientError> {
+        let vault = get_vault(fetcher, address).await?;
+        let x_token_program = get_owner(fetcher, &vault.x_token_mint_id).await?;
+        let llx_token_program = get_owner(fetcher, &vault.llx_token_mint_id).await?;
+        Ok(Self {
+            program_id: *program_id,
+            address: *address,
+            authority: pda::find_vault_authority_address(program_id, address).0,
+            vault,
+            x_token_program,
+            llx_token_program,
+        })
+    }
+
+    /// Accounts expected by `Deposit` & `Withdraw` after the source & target accounts, with
+    /// `source_authority` signing for the source.
+    pub fn transfer_account_metas(&self, source_authority: &Pubkey) -> Vec<AccountMeta> {
+        let vault = &self.vault;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*source_authority, true),
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(vault.x_token_mint_id, false),
+            AccountMeta::new(vault.llx_token_mint_id, false),
+        ];
+        if vault.hodl {
+            if let COption::Some(x_token_account) = vault.x_token_account {
+                accounts.push(AccountMeta::new(x_token_account, false));
+            }
+            // Withdrawals take back X from the strategies in the withdrawal queue, writing off
+            // their debt, which deposits ignore.
+            if !vault.withdrawal_queue.is_empty() {
+                accounts[1].is_writable = true;
+            }
+            for &index in vault.withdrawal_queue.iter() {
+                let strategy = &vault.strategies[index as usize];
+                accounts.push(AccountMeta::new_readonly(strategy.strategy_program_id, false));
+                accounts.push(AccountMeta::new(strategy.lx_token_account, false));
+                accounts.extend(strategy.strategy_account_metas());
+            }
+        } else {
+            // Transfers keep a roll-up from the same slot current.
+            accounts[1].is_writable = true;
+            accounts.push(AccountMeta::new(vault.lx_token_account, false));
+            if let COption::Some(x_token_account) = vault.x_token_account {
+                accounts.push(AccountMeta::new(x_token_account, false));
+            }
+            accounts.push(AccountMeta::new_readonly(vault.strategy_program_id, false));
+            accounts.extend(vault.strategy_account_metas());
+        }
+        if self.llx_token_program != self.x_token_program {
+            accounts.push(AccountMeta::new_readonly(self.llx_token_program, false));
+        }
+        accounts
+    }
+
+    /// Strategy accounts for a vault investing through this one, with this vault's `Deposit` &
+    /// `Withdraw` (tags 1 & 2) as its strategy instructions: the accounts those expect after the
+    /// source authority, starting with this vault's storage account as the data account.
+    pub fn as_strategy_accounts(&self) -> Vec<StrategyAccount> {
+        let mut accounts: Vec<StrategyAccount> = self.transfer_account_metas(&self.authority)[1..]
+            .iter()
+            .cloned()
+            .map(StrategyAccount::from)
+            .collect();
+        accounts[0].is_writable = true;
+        accounts
+    }
+
+    /// Deposits `amount` X from `source` for llX minted to `target`.
+    pub fn deposit(
+        &self,
+        source: &Pubkey,
+        target: &Pubkey,
+        source_authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::deposit(
+            &self.program_id,
+            &self.x_token_program,
+            source,
+            target,
+            self.transfer_account_metas(source_authority),
+            amount,
+        )
+    }
+
+    /// Like `deposit`, crediting `referrer` with the deposit. The source authority pays for the
+    /// target's `Referral` if this is its first referred deposit.
+    pub fn deposit_referred(
+        &self,
+        source: &Pubkey,
+        target: &Pubkey,
+        source_authority: &Pubkey,
+        amount: u64,
+        referrer: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::deposit_referred(
+            &self.program_id,
+            &self.x_token_program,
+            source,
+            target,
+            self.transfer_account_metas(source_authority),
+            amount,
+            referrer,
+        )
+    }
+
+    /// Burns `amount` llX from `source` for X sent to `target`.
+    pub fn withdraw(
+        &self,
+        source: &Pubkey,
+        target: &Pubkey,
+        source_authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::withdraw(
+            &self.program_id,
+            &self.x_token_program,
+            source,
+            target,
+            self.transfer_account_metas(source_authority),
+            amount,
+        )
+    }
+
+    /// Like `withdraw`, failing if the X paid out falls more than `max_loss_bps` short of the
+    /// llX's value. Must follow the vault's `RollUp` in the same transaction.
+    pub fn withdraw_with_max_loss(
+        &self,
+        source: &Pubkey,
+        target: &Pubkey,
+        source_authority: &Pubkey,
+        amount: u64,
+        max_loss_bps: u16,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::withdraw_with_max_loss(
+            &self.program_id,
+            &self.x_token_program,
+            source,
+            target,
+            self.transfer_account_metas(source_authority),
+            amount,
+            max_loss_bps,
+        )
+    }
+
+    /// Harvests the vault's strategy, or moves it to hodl if its drawdown limit is breached, which
+    /// needs a roll-up earlier in the slot (see `roll_up`). Hodl vaults have no strategy to
+    /// harvest.
+    pub fn harvest(&self) -> Result<Instruction, ProgramError> {
+        if self.vault.hodl {
+            return Err(ProgramError::InvalidArgument);
+        }
+        VaultInstruction::harvest(
+            &self.program_id,
+            &self.address,
+            COption::None,
+            self.harvest_account_metas()?,
+        )
+    }
+
+    /// Harvests the hodl vault's `index`th strategy, marking its debt to the worth of its lX as
+    /// written to `peek_result` by a `Peek` earlier in the transaction, then lending it X up to its
+    /// debt ratio or taking back what it was lent beyond.
+    pub fn harvest_strategy(
+        &self,
+        index: u8,
+        peek_result: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::harvest(
+            &self.program_id,
+            &self.address,
+            COption::Some(index),
+            self.harvest_strategy_account_metas(index, peek_result)?,
+        )
+    }
+
+    /// The accounts harvesting the `index`th strategy takes after the vault's.
+    fn harvest_strategy_account_metas(
+        &self,
+        index: u8,
+        peek_result: &Pubkey,
+    ) -> Result<Vec<AccountMeta>, ProgramError> {
+        let strategy = self
+            .vault
+            .strategies
+            .get(index as usize)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let x_token_account = self
+            .vault
+            .x_token_account
+            .ok_or(ProgramError::InvalidArgument)?;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(self.x_token_program, false),
+            AccountMeta::new(x_token_account, false),
+            AccountMeta::new_readonly(strategy.strategy_program_id, false),
+            AccountMeta::new(strategy.lx_token_account, false),
+        ];
+        accounts.extend(strategy.strategy_account_metas());
+        accounts.push(AccountMeta::new_readonly(*peek_result, false));
+        Ok(accounts)
+    }
+
+    /// Mints the fees accrued since they were last collected to the vault's fee recipients, and
+    /// settles each of `referrals`, a referred llX token account and the llX token account of its
+    /// referrer to pay what it's owed.
+    pub fn collect_fees(
+        &self,
+        referrals: &[(Pubkey, Pubkey)],
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::collect_fees(
+            &self.program_id,
+            &self.address,
+            &self.vault.llx_token_mint_id,
+            &self.llx_token_program,
+            &self.vault.fee_recipients,
+            referrals,
+        )
+    }
+
+    /// The deposits referred into the llX token account `llx_token_account`, if any.
+    pub async fn get_referral<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+        llx_token_account: &Pubkey,
+    ) -> Result<Option<Referral>, ClientError> {
+        let address =
+            pda::find_referral_address(&self.program_id, &self.address, llx_token_account).0;
+        match fetcher.get_account_data(&address).await? {
+            Some(data) => Ok(Some(Referral::unpack(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates the trigger derived from `nonce`, signed by the vault's governance. Moving a vault
+    /// to hodl creates its derived X token account if it has none.
+    pub fn create_trigger(
+        &self,
+        nonce: u64,
+        condition: TriggerCondition,
+        action: TriggerAction,
+        bounty_lamports: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let mut instruction = VaultInstruction::create_trigger(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            nonce,
+            condition,
+            action,
+            bounty_lamports,
+        )?;
+        if action == TriggerAction::MoveToHodl && self.vault.x_token_account.is_none() {
+            instruction
+                .accounts
+                .extend(self.create_x_token_account_metas());
+        }
+        Ok(instruction)
+    }
+
+    /// Sets the vault's drawdown limit, signed by the vault's governance. Limiting a vault
+    /// creates its derived X token account if it has none.
+    pub fn set_max_drawdown(&self, max_drawdown_bps: u16) -> Result<Instruction, ProgramError> {
+        let mut instruction = VaultInstruction::set_max_drawdown(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            max_drawdown_bps,
+        )?;
+        if max_drawdown_bps != 0 && self.vault.x_token_account.is_none() {
+            instruction.accounts.extend(vec![
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ]);
+            instruction
+                .accounts
+                .extend(self.create_x_token_account_metas());
+        }
+        Ok(instruction)
+    }
+
+    /// Invests the X of a vault moved to hodl through its strategy again, signed by the vault's
+    /// governance.
+    pub fn enable_strategy(&self) -> Result<Instruction, ProgramError> {
+        VaultInstruction::enable_strategy(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            &self.x_token_program,
+            &self
+                .vault
+                .x_token_account
+                .ok_or(ProgramError::InvalidArgument)?,
+            &self.vault.lx_token_account,
+            &self.vault.strategy_program_id,
+            self.vault.strategy_account_metas(),
+        )
+    }
+
+    /// Adds a strategy the hodl vault lends X to on harvest, signed by the vault's governance,
+    /// crediting the lX it's issued to `lx_token_account`, which the vault's authority must own.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_strategy(
+        &self,
+        strategy_program: &Pubkey,
+        lx_token_account: &Pubkey,
+        strategy_accounts: Vec<StrategyAccount>,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::add_strategy(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            strategy_program,
+            lx_token_account,
+            strategy_accounts,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            debt_ratio_bps,
+            min_debt_per_harvest,
+            max_debt_per_harvest,
+        )
+    }
+
+    /// Sets the debt ratio & per-harvest limits of the vault's `index`th strategy, signed by the
+    /// vault's governance.
+    pub fn update_strategy(
+        &self,
+        index: u8,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::update_strategy(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            index,
+            debt_ratio_bps,
+            min_debt_per_harvest,
+            max_debt_per_harvest,
+        )
+    }
+
+    /// Sets the order in which withdrawals take back the X lent to the vault's strategies, as
+    /// indices among them, signed by the vault's governance.
+    pub fn set_withdrawal_queue(
+        &self,
+        withdrawal_queue: Vec<u8>,
+    ) -> Result<Instruction, ProgramError> {
+        VaultInstruction::set_withdrawal_queue(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            withdrawal_queue,
+        )
+    }
+
+    /// Moves the vault to another strategy, signed by the vault's governance, crediting the lX
+    /// it's issued to `lx_token_account`, which the vault's authority must own. When the new
+    /// strategy is a vault, `child_vault_metas` are the vaults below it (see
+    /// `graph::child_vault_metas`).
+    pub fn migrate_strategy(
+        &self,
+        strategy_program: &Pubkey,
+        lx_token_account: &Pubkey,
+        strategy_accounts: Vec<StrategyAccount>,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        child_vault_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut instruction = VaultInstruction::migrate_strategy(
+            &self.program_id,
+            &self.vault.governance,
+            &self.address,
+            strategy_program,
+            lx_token_account,
+            strategy_accounts,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+        )?;
+        if !self.vault.hodl {
+            instruction
+                .accounts
+                .extend(self.move_to_hodl_account_metas()?);
+        }
+        instruction.accounts.extend(child_vault_metas);
+        Ok(instruction)
+    }
+
+    /// Metas for the accounts creating the vault's derived X token account.
+    fn create_x_token_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.vault.x_token_mint_id, false),
+            AccountMeta::new(
+                pda::find_x_token_address(&self.program_id, &self.address).0,
+                false,
+            ),
+            AccountMeta::new_readonly(self.x_token_program, false),
+        ]
+    }
+
+    /// Metas for the accounts withdrawing everything from the vault's strategy to hodl, as
+    /// `ExecuteTrigger` & `RollUp` expect them.
+    fn move_to_hodl_account_metas(&self) -> Result<Vec<AccountMeta>, ProgramError> {
+        let x_token_account = self
+            .vault
+            .x_token_account
+            .ok_or(ProgramError::InvalidArgument)?;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new_readonly(self.x_token_program, false),
+            AccountMeta::new(self.vault.lx_token_account, false),
+            AccountMeta::new(x_token_account, false),
+            AccountMeta::new_readonly(self.vault.strategy_program_id, false),
+        ];
+        accounts.extend(self.vault.strategy_account_metas());
+        Ok(accounts)
+    }
+
+    /// Metas for the accounts harvesting the vault's strategy, as `Harvest` & `ExecuteTrigger`
+    /// expect them.
+    fn harvest_account_metas(&self) -> Result<Vec<AccountMeta>, ProgramError> {
+        let mut accounts = vec![AccountMeta::new_readonly(
+            self.vault.strategy_program_id,
+            false,
+        )];
+        accounts.extend(self.vault.strategy_account_metas());
+        if self.vault.max_drawdown_bps != 0 {
+            accounts.push(AccountMeta::new_readonly(
+                self.vault.llx_token_mint_id,
+                false,
+            ));
+            accounts.extend(self.move_to_hodl_account_metas()?);
+        }
+        Ok(accounts)
+    }
+
+    /// The trigger derived from `nonce`, if any.
+    pub async fn get_trigger<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+        nonce: u64,
+    ) -> Result<Option<Trigger>, ClientError> {
+        let address = pda::find_trigger_address(&self.program_id, &self.address, nonce).0;
+        match fetcher.get_account_data(&address).await? {
+            Some(data) => Ok(Some(Trigger::unpack(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Executes `trigger`, at `trigger_address`, paying its bounty to `bounty_recipient`. A
+    /// drawdown trigger must follow the vault's `RollUp` in the same transaction. A rebalance
+    /// trigger takes the accounts each strategy's `Peek` wrote to earlier in the transaction, by
+    /// index, as `peek_results`.
+    pub fn execute_trigger(
+        &self,
+        trigger_address: &Pubkey,
+        trigger: &Trigger,
+        bounty_recipient: &Pubkey,
+        peek_results: &[Pubkey],
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = match trigger.condition {
+            TriggerCondition::PriceBelow { oracle, .. }
+            | TriggerCondition::PriceAbove { oracle, .. } => {
+                vec![AccountMeta::new_readonly(oracle, false)]
+            }
+            TriggerCondition::Drawdown { .. } => vec![AccountMeta::new_readonly(
+                self.vault.llx_token_mint_id,
+                false,
+            )],
+            _ => vec![],
+        };
+        match trigger.action {
+            TriggerAction::Harvest => accounts.extend(self.harvest_account_metas()?),
+            TriggerAction::MoveToHodl => accounts.extend(self.move_to_hodl_account_metas()?),
+            TriggerAction::Rebalance => {
+                if peek_results.len() != self.vault.strategies.len() {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                for (index, peek_result) in peek_results.iter().enumerate() {
+                    accounts.extend(self.harvest_strategy_account_metas(index as u8, peek_result)?);
+                }
+            }
+        }
+        VaultInstruction::execute_trigger(
+            &self.program_id,
+            bounty_recipient,
+            trigger_address,
+            &self.address,
+            accounts,
+        )
+    }
+
+    /// Loads this vault and each vault below it, which it invests through, from the top down.
+    pub async fn load_chain<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+    ) -> Result<Vec<(Pubkey, Vault)>, ClientError> {
+        let mut chain = vec![(self.address, self.vault.clone())];
+        while let Some(child) = chain.last().unwrap().1.child_vault(&self.program_id) {
+            if chain.len() == MAX_VAULT_DEPTH {
+                return Err(VaultError::InvestmentGraphTooDeep.into());
+            }
+            let vault = get_vault(fetcher, &child).await?;
+            chain.push((child, vault));
+        }
+        Ok(chain)
+    }
+
+    /// Rolls up the chain of vaults from this one down. When the bottom vault invests through a
+    /// strategy other than a vault, `peek_result` is the account the strategy's `Peek` of its lX
+    /// holdings writes to earlier in the transaction, followed by the vault's price feed if any.
+    /// A vault with a drawdown limit also passes the accounts moving it to hodl.
+    pub async fn roll_up<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+        peek_result: Option<&Pubkey>,
+    ) -> Result<Instruction, ClientError> {
+        let chain = self.load_chain(fetcher).await?;
+        let mut account_metas: Vec<AccountMeta> = chain
+            .iter()
+            .flat_map(|(address, vault)| vault.roll_up_account_metas(address))
+            .collect();
+        let bottom = &chain.last().unwrap().1;
+        if !bottom.hodl {
+            let peek_result = peek_result.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            account_metas.push(AccountMeta::new_readonly(*peek_result, false));
+            if let COption::Some(price_feed) = bottom.price_feed {
+                account_metas.push(AccountMeta::new_readonly(price_feed.asset_oracle, false));
+                account_metas.push(AccountMeta::new_readonly(price_feed.x_oracle, false));
+            }
+        }
+        if !self.vault.hodl && self.vault.max_drawdown_bps != 0 {
+            account_metas.extend(self.move_to_hodl_account_metas()?);
+        }
+        Ok(VaultInstruction::roll_up(&self.program_id, account_metas)?)
+    }
+
+    /// Loads the vault's current share price.
+    pub async fn share_price<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+    ) -> Result<SharePrice, ClientError> {
+        let assets_account = if self.vault.hodl {
+            match self.vault.x_token_account {
+                COption::Some(x_token_account) => x_token_account,
+                COption::None => return Err(VaultError::IncorrectTokenAccount.into()),
+            }
+        } else {
+            self.vault.lx_token_account
+        };
+        let mut total_assets = get_token_account(fetcher, &assets_account).await?.amount;
+        if self.vault.hodl {
+            total_assets = self
+                .vault
+                .total_debt()
+                .and_then(|debt| debt.checked_add(total_assets))
+                .ok_or(VaultError::MathOverflow)?;
+        }
+        Ok(SharePrice {
+            total_assets,
+            share_supply: get_mint(fetcher, &self.vault.llx_token_mint_id)
+                .await?
+                .supply,
+        })
+    }
+
+    /// Loads the position held by `llx_token_account`.
+    pub async fn position<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &mut F,
+        llx_token_account: &Pubkey,
+    ) -> Result<Position, ClientError> {
+        let account = get_token_account(fetcher, llx_token_account).await?;
+        if account.mint != self.vault.llx_token_mint_id {
+            return Err(VaultError::IncorrectMint.into());
+        }
+        let assets = self
+            .share_price(fetcher)
+            .await?
+            .assets_for_shares(account.amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(Position {
+            shares: account.amount,
+            assets,
+        })
+    }
+}
@@ -1,8 +1,10 @@
+use num_enum::TryFromPrimitive;
 use thiserror::Error;
 
 use solana_program::program_error::ProgramError;
 
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, TryFromPrimitive)]
+#[repr(u32)]
 pub enum VaultError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
@@ -11,6 +13,56 @@ pub enum VaultError {
     NotRentExempt,
     #[error("Not Implemented")]
     NotImplemented,
+    #[error("Math Overflow")]
+    MathOverflow,
+    #[error("Incorrect Mint")]
+    IncorrectMint,
+    #[error("Incorrect Token Account")]
+    IncorrectTokenAccount,
+    #[error("Invalid Vault Authority")]
+    InvalidAuthority,
+    #[error("Unsupported Mint Extension")]
+    UnsupportedMintExtension,
+    #[error("Registry Full")]
+    RegistryFull,
+    #[error("Vault Already Registered")]
+    VaultAlreadyRegistered,
+    #[error("Vault Not Registered")]
+    VaultNotRegistered,
+    #[error("Vault Still Active")]
+    VaultStillActive,
+    #[error("Invalid Governance")]
+    InvalidGovernance,
+    #[error("Vault Not Empty")]
+    VaultNotEmpty,
+    #[error("Incorrect Strategy Account")]
+    IncorrectStrategyAccount,
+    #[error("Investment Cycle")]
+    InvestmentCycle,
+    #[error("Investment Graph Too Deep")]
+    InvestmentGraphTooDeep,
+    #[error("Stale Valuation")]
+    StaleValuation,
+    #[error("Invalid Fee Config")]
+    InvalidFeeConfig,
+    #[error("Trigger Condition Not Met")]
+    TriggerConditionNotMet,
+    #[error("Invalid Trigger")]
+    InvalidTrigger,
+    #[error("Stale Price")]
+    StalePrice,
+    #[error("Withdrawal Loss Exceeded")]
+    WithdrawalLossExceeded,
+    #[error("Uncollected Fees")]
+    UncollectedFees,
+    #[error("Deposit Too Small")]
+    DepositTooSmall,
+    #[error("Self Referral")]
+    SelfReferral,
+    #[error("Too Many Strategies")]
+    TooManyStrategies,
+    #[error("Invalid Debt Ratio")]
+    InvalidDebtRatio,
 }
 
 impl From<VaultError> for ProgramError {
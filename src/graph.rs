@@ -0,0 +1,112 @@
+//! Off-chain view of the investment graph vaults form by investing through strategies, which may
+//! themselves be vaults of this program.
+//!
+//! [InvestmentGraph](struct.InvestmentGraph.html) renders vaults as Graphviz DOT, with an edge from
+//! each vault to the vault or strategy instance it invests through.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+
+use crate::{
+    client::{get_vault, AccountFetcher, ClientError},
+    state::{Vault, MAX_VAULT_DEPTH},
+};
+
+pub struct InvestmentGraph {
+    program_id: Pubkey,
+    vaults: BTreeMap<Pubkey, Vault>,
+}
+
+impl InvestmentGraph {
+    /// Builds the graph of `vaults`, owned by `program_id`.
+    pub fn new(program_id: &Pubkey, vaults: impl IntoIterator<Item = (Pubkey, Vault)>) -> Self {
+        Self {
+            program_id: *program_id,
+            vaults: vaults.into_iter().collect(),
+        }
+    }
+
+    /// Loads `roots` and every vault below them.
+    pub async fn load<F: AccountFetcher + ?Sized>(
+        fetcher: &mut F,
+        program_id: &Pubkey,
+        roots: &[Pubkey],
+    ) -> Result<Self, ClientError> {
+        let mut vaults = BTreeMap::new();
+        let mut pending = roots.to_vec();
+        while let Some(address) = pending.pop() {
+            if vaults.contains_key(&address) {
+                continue;
+            }
+            let vault = get_vault(fetcher, &address).await?;
+            pending.extend(vault.child_vault(program_id));
+            vaults.insert(address, vault);
+        }
+        Ok(Self::new(program_id, vaults))
+    }
+
+    pub fn vaults(&self) -> &BTreeMap<Pubkey, Vault> {
+        &self.vaults
+    }
+
+    /// Renders the graph in Graphviz DOT. Vaults are boxes, labelled with their X mint, and
+    /// strategy instances other than vaults are ellipses, labelled with their program.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph vaults {\n");
+        let mut strategies = BTreeSet::new();
+        for (address, vault) in self.vaults.iter() {
+            writeln!(
+                dot,
+                "  \"{}\" [shape=box, label=\"vault {}\\nX {}{}\"];",
+                address,
+                address,
+                vault.x_token_mint_id,
+                if vault.hodl { "\\nhodl" } else { "" }
+            )
+            .unwrap();
+            if let Some(data_account) = vault.strategy_accounts.first() {
+                if vault.child_vault(&self.program_id).is_none() {
+                    strategies.insert((data_account.pubkey, vault.strategy_program_id));
+                }
+                writeln!(dot, "  \"{}\" -> \"{}\";", address, data_account.pubkey).unwrap();
+            }
+        }
+        for (data_account, program) in strategies {
+            writeln!(
+                dot,
+                "  \"{}\" [shape=ellipse, label=\"strategy {}\\n{}\"];",
+                data_account, program, data_account
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Metas for the vaults below `child`, as `InitializeVault`, `CreateVault` & `MigrateStrategy`
+/// expect them from a vault investing through `child`. Stops short of any cycle or at
+/// `MAX_VAULT_DEPTH`, beyond which the program rejects the vault anyway.
+pub async fn child_vault_metas<F: AccountFetcher + ?Sized>(
+    fetcher: &mut F,
+    program_id: &Pubkey,
+    child: &Pubkey,
+) -> Result<Vec<AccountMeta>, ClientError> {
+    let mut chain = vec![*child];
+    let mut vault = get_vault(fetcher, child).await?;
+    while let Some(next) = vault.child_vault(program_id) {
+        if chain.contains(&next) || chain.len() >= MAX_VAULT_DEPTH {
+            break;
+        }
+        chain.push(next);
+        vault = get_vault(fetcher, &next).await?;
+    }
+    Ok(chain[1..]
+        .iter()
+        .map(|vault| AccountMeta::new_readonly(*vault, false))
+        .collect())
+}
@@ -0,0 +1,840 @@
+//! Anchor-compatible IDLs for the vault program and the strategy interface, for clients which
+//! can't use the Rust instruction builders.
+//!
+//! Neither is an Anchor program, so instructions are identified by a one-byte `discriminant`, as
+//! in Shank IDLs, rather than a sighash. Account & argument layouts follow the `Pack`
+//! implementations byte for byte, with `COption`s spelled out as types, the bounded lists as
+//! `vec`s with a one-byte `lenType` and arguments an instruction may end before marked
+//! `isOptional`.
+//! The generated IDLs are checked in under `idl/`, and `tests/idl.rs` fails when they drift from
+//! the code; run it with `UPDATE_IDL=1` to regenerate them.
+
+use num_enum::TryFromPrimitive;
+use serde_json::{json, Value};
+
+use crate::{
+    error::VaultError,
+    state::{
+        MAX_FEE_RECIPIENTS, MAX_INSTRUCTION_PREFIX_LEN, MAX_REGISTERED_VAULTS, MAX_STRATEGIES,
+        MAX_STRATEGY_ACCOUNTS,
+    },
+};
+
+/// The vault program's IDL.
+pub fn vault_idl() -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": "vault",
+        "instructions": [
+            instruction(
+                "initializeVault",
+                0,
+                "Initializes a vault over existing accounts. Further token programs may follow \
+                 when X, lX and llX aren't all owned by the one given, and the vaults further \
+                 down the chain when the strategy is another vault.",
+                vec![
+                    account("initializer", false, true),
+                    account("vault", true, false),
+                    account("lxTokenAccount", true, false),
+                    account("llxTokenMint", true, false),
+                    account("xTokenMint", false, false),
+                    account("tokenProgram", false, false),
+                    account("strategyProgram", false, false),
+                    account("rent", false, false),
+                    account("xTokenAccount", true, false),
+                    documented(
+                        optional(account("strategyDataAccount", false, false)),
+                        "Unless hodling.",
+                    ),
+                ],
+                vec![
+                    field("hodl", json!("bool")),
+                    field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                    field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                    field(
+                        "strategyAccounts",
+                        list(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                    ),
+                ],
+            ),
+            instruction(
+                "deposit",
+                1,
+                "Deposits X for llX. Vaults investing through a strategy instead must be \
+                 writable, keeping a roll-up from the same slot current, and expect the lX token \
+                 account, the X token account deposits pass through, the strategy program and \
+                 the vault's strategy accounts after the llX mint. Given a referrer, the vault \
+                 must be writable, and the destination's Referral account and the system program \
+                 follow all of the above.",
+                transfer_accounts(false),
+                vec![
+                    field("amount", json!("u64")),
+                    optional(field("referrer", json!("publicKey"))),
+                ],
+            ),
+            instruction(
+                "withdraw",
+                2,
+                "Burns llX for X, expecting the same accounts as deposit. `maxLossBps` is the \
+                 most the X received may fall short of the llX's rolled-up value. Hodl vaults \
+                 with a withdrawal queue must be writable and expect, after the X token account, \
+                 each queued strategy's program, lX token account & strategy accounts, taking \
+                 back X lent to them in order when short.",
+                transfer_accounts(false),
+                vec![
+                    field("amount", json!("u64")),
+                    optional(field("maxLossBps", json!("u16"))),
+                ],
+            ),
+            instruction(
+                "depositNative",
+                3,
+                "Deposits lamports into a wrapped SOL vault for llX.",
+                {
+                    let mut accounts = transfer_accounts(true);
+                    accounts.push(account("systemProgram", false, false));
+                    accounts
+                },
+                vec![field("lamports", json!("u64"))],
+            ),
+            instruction(
+                "withdrawNative",
+                4,
+                "Burns llX from a wrapped SOL vault, unwrapping the SOL to the source authority.",
+                {
+                    let mut accounts = transfer_accounts(false);
+                    accounts[3] = account("sourceAuthority", true, true);
+                    accounts
+                },
+                vec![field("amount", json!("u64"))],
+            ),
+            instruction(
+                "createVault",
+                5,
+                "Creates a vault with its accounts at program derived addresses. Further token \
+                 programs may follow when the X and lX mints aren't owned by the one given, and \
+                 the vaults further down the chain when the strategy is another vault.",
+                vec![
+                    account("payer", true, true),
+                    account("vault", true, false),
+                    account("llxTokenMint", true, false),
+                    account("lxTokenAccount", true, false),
+                    account("lxTokenMint", false, false),
+                    account("xTokenMint", false, false),
+                    account("tokenProgram", false, false),
+                    account("strategyProgram", false, false),
+                    account("systemProgram", false, false),
+                    account("rent", false, false),
+                    account("xTokenAccount", true, false),
+                    documented(
+                        optional(account("strategyDataAccount", true, false)),
+                        "Unless hodling.",
+                    ),
+                ],
+                vec![
+                    field("nonce", json!("u64")),
+                    field("hodl", json!("bool")),
+                    field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                    field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                    field("llxDecimals", json!("u8")),
+                    field(
+                        "strategyAccounts",
+                        list(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                    ),
+                ],
+            ),
+            instruction(
+                "registerVault",
+                6,
+                "Adds a vault to a page of the registry for its X mint and one of the registry \
+                 for its strategy program. A page after the first only takes vaults once the \
+                 page before it is full. The vault's registration keeps it from being \
+                 registered twice.",
+                vec![
+                    account("payer", true, true),
+                    account("vault", false, false),
+                    account("mintRegistry", true, false),
+                    account("strategyRegistry", true, false),
+                    account("registration", true, false),
+                    account("systemProgram", false, false),
+                    account("rent", false, false),
+                    optional(documented(
+                        account("previousMintRegistry", false, false),
+                        "The mint registry's previous page, unless registering in the first.",
+                    )),
+                    optional(documented(
+                        account("previousStrategyRegistry", false, false),
+                        "The strategy registry's previous page, unless registering in the first.",
+                    )),
+                ],
+                vec![
+                    field("mintRegistryPage", json!("u32")),
+                    field("strategyRegistryPage", json!("u32")),
+                ],
+            ),
+            instruction(
+                "deregisterVault",
+                7,
+                "Removes a closed vault from the registry pages listing it and closes its \
+                 registration, refunding its payer.",
+                vec![
+                    account("vault", false, false),
+                    account("mintRegistry", true, false),
+                    account("strategyRegistry", true, false),
+                    account("registration", true, false),
+                    account("registrationPayer", true, false),
+                ],
+                vec![],
+            ),
+            instruction(
+                "closeVault",
+                8,
+                "Closes a vault with no llX, sweeping its tokens and lamports to the recipient.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                    account("vaultAuthority", false, false),
+                    account("recipient", true, false),
+                    account("llxTokenMint", false, false),
+                    account("lxTokenMint", false, false),
+                    account("lxTokenAccount", true, false),
+                    account("recipientLxTokenAccount", true, false),
+                    optional(account("xTokenMint", false, false)),
+                    optional(account("xTokenAccount", true, false)),
+                    optional(account("recipientXTokenAccount", true, false)),
+                    account("tokenProgram", false, false),
+                ],
+                vec![],
+            ),
+            instruction(
+                "rollUp",
+                9,
+                "Values a chain of vaults in X bottom-up, caching each vault's total assets. \
+                 These accounts repeat for each vault in the chain, from the top down.",
+                vec![
+                    account("vault", true, false),
+                    account("llxTokenMint", false, false),
+                    documented(
+                        account("assetsTokenAccount", false, false),
+                        "The X token account if hodling, else the lX token account.",
+                    ),
+                    optional(documented(
+                        account("peekResult", false, false),
+                        "After the last vault, when it invests through a strategy other than a \
+                         vault: a PeekResult of its strategy data account, written for it by the \
+                         strategy in the same slot.",
+                    )),
+                    optional(documented(
+                        account("assetOracle", false, false),
+                        "With the next, after the PeekResult when the vault has a price feed.",
+                    )),
+                    optional(account("xOracle", false, false)),
+                    optional(documented(
+                        account("vaultAuthority", false, false),
+                        "Last, when the top vault has a drawdown limit & isn't hodling, the \
+                         accounts moving it to hodl: this, the X token program, lX & X token \
+                         accounts, strategy program & accounts.",
+                    )),
+                ],
+                vec![],
+            ),
+            instruction(
+                "setFees",
+                10,
+                "Sets a vault's management fee, the referrers' share & the fee recipients, \
+                 which follow as llX token accounts in order. A vault charging a fee must have \
+                 collected it in the same slot.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                ],
+                vec![
+                    field("managementFeeBps", json!("u16")),
+                    field("referralFeeBps", json!("u16")),
+                    field(
+                        "feeRecipients",
+                        list(defined("FeeRecipient"), MAX_FEE_RECIPIENTS),
+                    ),
+                ],
+            ),
+            instruction(
+                "collectFees",
+                11,
+                "Mints the llX accrued in fees to each of the vault's fee recipients, which \
+                 follow in order, after paying any referrers their share. Each referral to \
+                 settle is given as a Referral account, the llX token account it's for and an \
+                 llX token account of its referrer's.",
+                vec![
+                    account("vault", true, false),
+                    account("vaultAuthority", false, false),
+                    account("llxTokenMint", true, false),
+                    account("tokenProgram", false, false),
+                ],
+                vec![],
+            ),
+            instruction(
+                "createTrigger",
+                12,
+                "Creates a trigger any keeper may execute once its condition holds, derived from \
+                 the vault & nonce.",
+                vec![
+                    account("governance", true, true),
+                    account("vault", true, false),
+                    account("trigger", true, false),
+                    account("systemProgram", false, false),
+                    account("rent", false, false),
+                    optional(documented(
+                        account("xTokenMint", false, false),
+                        "With the next two, when moving a vault without an X token account to \
+                         hodl.",
+                    )),
+                    optional(account("xTokenAccount", true, false)),
+                    optional(account("xTokenProgram", false, false)),
+                ],
+                vec![
+                    field("nonce", json!("u64")),
+                    field("condition", defined("TriggerCondition")),
+                    field("action", defined("TriggerAction")),
+                    field("bountyLamports", json!("u64")),
+                ],
+            ),
+            instruction(
+                "executeTrigger",
+                13,
+                "Performs a trigger's action if its condition holds, paying its bounty. Price \
+                 conditions are followed by the oracle, drawdowns by the llX mint; harvests then \
+                 by the strategy program & accounts, and moves to hodl by the vault authority, \
+                 X token program, lX & X token accounts, strategy program & accounts. Harvests \
+                 of vaults with a drawdown limit follow the strategy accounts with the llX mint \
+                 and the accounts moving to hodl, which happens instead once it's breached. \
+                 Rebalances take the accounts harvesting each of the hodl vault's strategies, \
+                 in turn.",
+                vec![
+                    account("bountyRecipient", true, false),
+                    account("trigger", true, false),
+                    account("vault", true, false),
+                ],
+                vec![],
+            ),
+            instruction(
+                "setPriceFeed",
+                14,
+                "Sets or clears the oracles valuing a vault's strategy holdings in X.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                    optional(documented(
+                        account("assetOracle", false, false),
+                        "With the next, when setting a price feed.",
+                    )),
+                    optional(account("xOracle", false, false)),
+                ],
+                vec![field("priceFeed", json!({ "option": defined("PriceFeed") }))],
+            ),
+            instruction(
+                "setMaxDrawdown",
+                15,
+                "Sets how far a vault's share price may fall below its high-water mark before \
+                 rolling it up moves it to hodl, or 0 for no limit.",
+                vec![
+                    account("governance", true, true),
+                    account("vault", true, false),
+                    optional(documented(
+                        account("systemProgram", false, false),
+                        "With the next four, when limiting a vault without an X token account.",
+                    )),
+                    optional(account("rent", false, false)),
+                    optional(account("xTokenMint", false, false)),
+                    optional(account("xTokenAccount", true, false)),
+                    optional(account("xTokenProgram", false, false)),
+                ],
+                vec![field("maxDrawdownBps", json!("u16"))],
+            ),
+            instruction(
+                "enableStrategy",
+                16,
+                "Deposits the X of a vault moved to hodl into its strategy again. The strategy \
+                 accounts follow.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                    account("vaultAuthority", false, false),
+                    account("xTokenProgram", false, false),
+                    account("xTokenAccount", true, false),
+                    account("lxTokenAccount", true, false),
+                    account("strategyProgram", false, false),
+                ],
+                vec![],
+            ),
+            instruction(
+                "migrateStrategy",
+                17,
+                "Moves a vault to another strategy, checking the investment graph below it. The \
+                 rest of the new strategy accounts follow, then, unless the vault is in hodl, the \
+                 accounts moving it to hodl, then the vaults further down the chain when the new \
+                 strategy is another vault. A registered vault can't migrate.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                    account("registration", false, false),
+                    account("strategyProgram", false, false),
+                    account("lxTokenAccount", true, false),
+                    account("strategyDataAccount", true, false),
+                ],
+                vec![
+                    field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                    field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                    field(
+                        "strategyAccounts",
+                        list(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                    ),
+                ],
+            ),
+            instruction(
+                "harvest",
+                18,
+                "Harvests a vault's strategy, as a harvest trigger does. The vault is followed by \
+                 the strategy program & accounts, then, for a vault with a drawdown limit, by the \
+                 llX mint and the accounts moving it to hodl, which happens instead once it's \
+                 breached. Given a `strategy`, harvests that strategy of a hodl vault instead, \
+                 marking its debt to market and lending it X up to its debt ratio or taking back \
+                 what's lent beyond; the vault is then followed by the vault authority, X token \
+                 program, X token account, strategy program, the strategy's lX token account, its \
+                 accounts and the result of a `Peek` of its lX earlier in the slot.",
+                vec![account("vault", true, false)],
+                vec![optional(field("strategy", json!("u8")))],
+            ),
+            instruction(
+                "addStrategy",
+                19,
+                "Adds a strategy a hodl vault lends X to on harvest, up to `debtRatioBps` of its \
+                 assets. The rest of the strategy accounts follow.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                    account("strategyProgram", false, false),
+                    account("lxTokenAccount", false, false),
+                    account("strategyDataAccount", true, false),
+                ],
+                vec![
+                    field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                    field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                    field("debtRatioBps", json!("u16")),
+                    field("minDebtPerHarvest", json!("u64")),
+                    field("maxDebtPerHarvest", json!("u64")),
+                    field(
+                        "strategyAccounts",
+                        list(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                    ),
+                ],
+            ),
+            instruction(
+                "updateStrategy",
+                20,
+                "Sets the debt ratio & per-harvest limits of a hodl vault's strategy.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                ],
+                vec![
+                    field("strategy", json!("u8")),
+                    field("debtRatioBps", json!("u16")),
+                    field("minDebtPerHarvest", json!("u64")),
+                    field("maxDebtPerHarvest", json!("u64")),
+                ],
+            ),
+            instruction(
+                "setWithdrawalQueue",
+                21,
+                "Sets the order in which withdrawals take back the X a hodl vault lent to its \
+                 strategies, as distinct indices among them.",
+                vec![
+                    account("governance", false, true),
+                    account("vault", true, false),
+                ],
+                vec![field(
+                    "withdrawalQueue",
+                    list(json!("u8"), MAX_STRATEGIES),
+                )],
+            ),
+        ],
+        "accounts": [
+            {
+                "name": "Vault",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("hodl", json!("bool")),
+                        field("llxTokenMintId", json!("publicKey")),
+                        field("lxTokenAccount", json!("publicKey")),
+                        field("xTokenAccount", defined("COptionPubkey")),
+                        field("strategyProgramId", json!("publicKey")),
+                        field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                        field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                        field("strategyAccountCount", json!("u8")),
+                        field(
+                            "strategyAccounts",
+                            array(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                        ),
+                        field("xTokenMintId", json!("publicKey")),
+                        field("xDecimals", json!("u8")),
+                        field("llxDecimals", json!("u8")),
+                        field("governance", json!("publicKey")),
+                        field("totalAssets", json!("u64")),
+                        field("totalAssetsSlot", json!("u64")),
+                        field("managementFeeBps", json!("u16")),
+                        field("feeRecipientCount", json!("u8")),
+                        field(
+                            "feeRecipients",
+                            array(defined("FeeRecipient"), MAX_FEE_RECIPIENTS),
+                        ),
+                        field("feesCollectedSlot", json!("u64")),
+                        field("referralFeeBps", json!("u16")),
+                        field("highWaterSharePrice", json!("u64")),
+                        field("priceFeed", defined("COptionPriceFeed")),
+                        field("maxDrawdownBps", json!("u16")),
+                        field("referredShares", json!("u64")),
+                        field("referralFeeIndex", json!("u128")),
+                        field("strategyCount", json!("u8")),
+                        field("strategies", array(defined("StrategyEntry"), MAX_STRATEGIES)),
+                        field("withdrawalQueueLen", json!("u8")),
+                        field("withdrawalQueue", array(json!("u8"), MAX_STRATEGIES)),
+                    ],
+                },
+            },
+            {
+                "name": "Registry",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("kind", defined("RegistryKind")),
+                        field("key", json!("publicKey")),
+                        field("vaultCount", json!("u8")),
+                        field("vaults", array(json!("publicKey"), MAX_REGISTERED_VAULTS)),
+                    ],
+                },
+            },
+            {
+                "name": "Registration",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("vault", json!("publicKey")),
+                        field("payer", json!("publicKey")),
+                        field("mintRegistryPage", json!("u32")),
+                        field("strategyRegistryPage", json!("u32")),
+                    ],
+                },
+            },
+            {
+                "name": "Referral",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("vault", json!("publicKey")),
+                        field("referrer", json!("publicKey")),
+                        field("llxTokenAccount", json!("publicKey")),
+                        field("principal", json!("u64")),
+                        field("shares", json!("u64")),
+                        field("feeIndex", json!("u128")),
+                        field("accruedFees", json!("u64")),
+                    ],
+                },
+            },
+            {
+                "name": "Trigger",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("vault", json!("publicKey")),
+                        field("condition", defined("TriggerCondition")),
+                        field("action", defined("TriggerAction")),
+                        field("bountyLamports", json!("u64")),
+                        field("executedSlot", json!("u64")),
+                        field("executedTimestamp", json!("i64")),
+                    ],
+                },
+            },
+        ],
+        "types": [
+            {
+                "name": "COptionPubkey",
+                "docs": ["A public key preceded by a 4-byte tag, 1 if present and 0 if not."],
+                "type": {
+                    "kind": "struct",
+                    "fields": [field("tag", json!("u32")), field("value", json!("publicKey"))],
+                },
+            },
+            {
+                "name": "StrategyAccount",
+                "type": {
+                    "kind": "struct",
+                    "fields": [field("pubkey", json!("publicKey")), field("isWritable", json!("bool"))],
+                },
+            },
+            {
+                "name": "StrategyEntry",
+                "docs": ["A strategy a hodl vault lends X to, owing it `totalDebt` X."],
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("strategyProgramId", json!("publicKey")),
+                        field("strategyProgramDepositInstruction", defined("InstructionTemplate")),
+                        field("strategyProgramWithdrawInstruction", defined("InstructionTemplate")),
+                        field("strategyAccountCount", json!("u8")),
+                        field(
+                            "strategyAccounts",
+                            array(defined("StrategyAccount"), MAX_STRATEGY_ACCOUNTS),
+                        ),
+                        field("lxTokenAccount", json!("publicKey")),
+                        field("debtRatioBps", json!("u16")),
+                        field("minDebtPerHarvest", json!("u64")),
+                        field("maxDebtPerHarvest", json!("u64")),
+                        field("totalDebt", json!("u64")),
+                    ],
+                },
+            },
+            {
+                "name": "FeeRecipient",
+                "type": {
+                    "kind": "struct",
+                    "fields": [field("tokenAccount", json!("publicKey")), field("bps", json!("u16"))],
+                },
+            },
+            {
+                "name": "AmountEncoding",
+                "type": {
+                    "kind": "enum",
+                    "variants": [{ "name": "U64Le" }, { "name": "U64Be" }],
+                },
+            },
+            {
+                "name": "InstructionTemplate",
+                "docs": ["Only the first `prefixLen` bytes of `prefix` are used."],
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("prefixLen", json!("u8")),
+                        field("prefix", array(json!("u8"), MAX_INSTRUCTION_PREFIX_LEN)),
+                        field("amountOffset", json!("u8")),
+                        field("amountEncoding", defined("AmountEncoding")),
+                    ],
+                },
+            },
+            {
+                "name": "RegistryKind",
+                "type": {
+                    "kind": "enum",
+                    "variants": [{ "name": "Mint" }, { "name": "Strategy" }],
+                },
+            },
+            {
+                "name": "PriceFeed",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("assetOracle", json!("publicKey")),
+                        field("xOracle", json!("publicKey")),
+                        field("assetDecimals", json!("u8")),
+                    ],
+                },
+            },
+            {
+                "name": "COptionPriceFeed",
+                "docs": ["A PriceFeed preceded by a 4-byte tag, 1 if present and 0 if not."],
+                "type": {
+                    "kind": "struct",
+                    "fields": [field("tag", json!("u32")), field("value", defined("PriceFeed"))],
+                },
+            },
+            {
+                "name": "TriggerCondition",
+                "docs": [
+                    "`kind` 0 waits `value` slots & 1 `value` seconds since last executed, 2 & 3 \
+                     for the `oracle` price to go below & above the i64 `value`, and 4 for the \
+                     share price to fall `value` bps below its high-water mark."
+                ],
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("kind", json!("u8")),
+                        field("oracle", json!("publicKey")),
+                        field("value", json!("u64")),
+                    ],
+                },
+            },
+            {
+                "name": "TriggerAction",
+                "type": {
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Harvest" },
+                        { "name": "MoveToHodl" },
+                        { "name": "Rebalance" },
+                    ],
+                },
+            },
+        ],
+        "errors": errors(),
+        "metadata": {
+            "origin": "shank",
+            "address": crate::id().to_string(),
+        },
+    })
+}
+
+/// The IDL of `StrategyInstruction`, the interface strategy programs implement. Strategies
+/// append their own accounts after those listed.
+pub fn strategy_idl() -> Value {
+    let transfer_accounts = || {
+        vec![
+            account("tokenProgram", false, false),
+            account("source", true, false),
+            account("target", true, false),
+            account("sourceAuthority", false, true),
+            account("dataAccount", true, false),
+        ]
+    };
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": "strategy",
+        "instructions": [
+            instruction(
+                "deposit",
+                0,
+                "Deposits X into the strategy for lX.",
+                transfer_accounts(),
+                vec![field("amount", json!("u64"))],
+            ),
+            instruction(
+                "withdraw",
+                1,
+                "Withdraws X from the strategy for lX.",
+                transfer_accounts(),
+                vec![field("amount", json!("u64"))],
+            ),
+            instruction(
+                "peek",
+                2,
+                "Values the vault's lX in X, writing a PeekResult into the result account, a \
+                 dedicated PeekResult-sized account.",
+                vec![account("result", true, false), account("dataAccount", false, false)],
+                vec![field("amount", json!("u64")), field("vault", json!("publicKey"))],
+            ),
+            instruction(
+                "harvest",
+                3,
+                "Realizes any pending rewards.",
+                vec![account("dataAccount", true, false)],
+                vec![],
+            ),
+            instruction(
+                "emergencyExit",
+                4,
+                "Unwinds the strategy's positions.",
+                vec![account("authority", false, true), account("dataAccount", true, false)],
+                vec![],
+            ),
+        ],
+        "accounts": [
+            {
+                "name": "PeekResult",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        field("isInitialized", json!("bool")),
+                        field("dataAccount", json!("publicKey")),
+                        field("vault", json!("publicKey")),
+                        field("lxAmount", json!("u64")),
+                        field("xAmount", json!("u64")),
+                        field("slot", json!("u64")),
+                    ],
+                },
+            },
+        ],
+        "metadata": {
+            "origin": "shank",
+        },
+    })
+}
+
+/// Accounts shared by the vault's transfer instructions, as for a hodl vault.
+fn transfer_accounts(source_is_signer: bool) -> Vec<Value> {
+    vec![
+        account("tokenProgram", false, false),
+        account("source", true, source_is_signer),
+        account("target", true, false),
+        account("sourceAuthority", false, true),
+        account("vault", false, false),
+        account("vaultAuthority", false, false),
+        account("xTokenMint", false, false),
+        account("llxTokenMint", true, false),
+        account("xTokenAccount", true, false),
+    ]
+}
+
+fn errors() -> Vec<Value> {
+    (0..)
+        .map_while(|code| VaultError::try_from_primitive(code).ok())
+        .map(|error| {
+            json!({
+                "code": error as u32,
+                "name": format!("{:?}", error),
+                "msg": error.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn instruction(
+    name: &str,
+    discriminant: u8,
+    doc: &str,
+    accounts: Vec<Value>,
+    args: Vec<Value>,
+) -> Value {
+    json!({
+        "name": name,
+        "docs": [doc],
+        "accounts": accounts,
+        "args": args,
+        "discriminant": { "type": "u8", "value": discriminant },
+    })
+}
+
+fn account(name: &str, is_mut: bool, is_signer: bool) -> Value {
+    json!({ "name": name, "isMut": is_mut, "isSigner": is_signer })
+}
+
+fn documented(mut account: Value, doc: &str) -> Value {
+    account["docs"] = json!([doc]);
+    account
+}
+
+/// Marks an account, or an argument, that may be left off the end of an instruction.
+fn optional(mut value: Value) -> Value {
+    value["isOptional"] = json!(true);
+    value
+}
+
+fn field(name: &str, ty: Value) -> Value {
+    json!({ "name": name, "type": ty })
+}
+
+fn defined(name: &str) -> Value {
+    json!({ "defined": name })
+}
+
+fn array(ty: Value, len: usize) -> Value {
+    json!({ "array": [ty, len] })
+}
+
+/// A list of at most `max_len` items, preceded by its length in one byte.
+fn list(ty: Value, max_len: usize) -> Value {
+    json!({ "vec": ty, "lenType": "u8", "maxLen": max_len })
+}
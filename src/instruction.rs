@@ -1,10 +1,19 @@
-use crate::error::{VaultError, VaultError::InvalidInstruction};
+use crate::{
+    error::{VaultError, VaultError::InvalidInstruction},
+    pda,
+    state::{
+        FeeRecipient, InstructionTemplate, PriceFeed, StrategyAccount, TriggerAction,
+        TriggerCondition, MAX_FEE_RECIPIENTS, MAX_STRATEGIES, MAX_STRATEGY_ACCOUNTS,
+    },
+};
+use num_enum::TryFromPrimitive;
 use solana_program::program_error::ProgramError;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
-    sysvar,
+    system_program, sysvar,
 };
 
 use std::convert::TryInto;
@@ -23,67 +32,558 @@ pub enum VaultInstruction {
     ///
     /// Deposit:
     ///   User sends X to Vault, Vault sends X to the strategy and gets back an lX token, which it
-    ///   stores, and then mints a corresponding llX token which it gives to the user.
+    ///   stores, and then mints a corresponding llX token which it gives to the user. The X passes
+    ///   through the vault's own X token account, so only the vault signs for the strategy.
     /// Withdraw:
     ///   User sends llX to Vault, Vault burns the tokens and sends the corresponding lX to the
     ///   strategy and gets back X tokens, which it forwards to the user, minus a fee.
     ///
     /// Strategies should be contained within a single program and should implement the
     /// StrategyInstruction interface below. If a Strategy requires additional data, it can specify
-    /// it in a data account which will be included in calls to the strategy instance.
+    /// it in a data account which will be included in calls to the strategy instance, along with
+    /// any further accounts the strategy needs (pools, reserves, oracles, mints...). The vault
+    /// stores these as `strategy_accounts`, data account first, and passes exactly them through
+    /// on every deposit & withdrawal. The instruction data sent to the strategy is built from
+    /// `InstructionTemplate`s, so vaults can also call third-party programs taking the same
+    /// accounts, e.g. Anchor programs, without a wrapper strategy.
     ///
     /// Accounts expected:
-    /// `[signer]` initializer of the lx token account
+    /// `[signer]` initializer of the lx token account, who becomes the vault's governance.
     /// `[writeable]` Vault storage account (vault ID)
     /// `[]` lX token account
     /// `[]` The llX Token ID with this program is a mint authority.
+    /// `[]` The X Token ID. Its decimals are recorded alongside the llX decimals so that shares
+    ///      can be priced across mints with differing decimals.
+    /// `[]` Token program, either the legacy SPL Token program or Token-2022.
     /// `[]` The strategy program's pubkey.
     /// `[]` The rent sysvar
-    /// `[]` (Optional) Strategy instance data account
-    /// `[]` (Optional) X token account if hodling.
+    /// `[writeable]` X token account
+    /// `[]` (Optional) Unless hodling, the strategy instance data account, owned by the strategy
+    ///      program, which must be the first of `strategy_accounts`.
+    /// `[]` (Optional) Further token programs, when X, lX and llX aren't all owned by the one
+    ///      above.
+    /// `[]` (Optional) When the strategy program is this program, i.e. the vault invests through
+    ///      another vault, every vault further down that chain, so the program can check the
+    ///      investment graph stays acyclic and at most `MAX_VAULT_DEPTH` vaults deep.
     InitializeVault {
         // TODO: Governance address, strategist address, keeper address.
         // TODO: Withdrawal fee.
         // https://github.com/yearn/yearn-vaults/blob/master/contracts/BaseStrategy.sol#L781
-        strategy_program_deposit_instruction_id: u8,
-        strategy_program_withdraw_instruction_id: u8,
+        /// Instruction data depositing into the strategy, unused if hodling.
+        strategy_program_deposit_instruction: InstructionTemplate,
+        /// Instruction data withdrawing from the strategy, unused if hodling.
+        strategy_program_withdraw_instruction: InstructionTemplate,
         // TODO: Maybe change from bool to float percentage for holding.
         hodl: bool,
+        /// Empty if hodling, otherwise up to `MAX_STRATEGY_ACCOUNTS` accounts starting with the
+        /// writeable strategy instance data account.
+        strategy_accounts: Vec<StrategyAccount>,
     },
 
-    /// Deposits a given token into the vault.
+    /// Deposits a given token into the vault in exchange for llX tokens priced against the
+    /// vault's current X holdings. Fails if too small to mint any llX.
     ///
     /// Accounts expected:
-    /// 1. `[signer]` The source wallet containing X tokens.
-    /// 2. `[]` The destination wallet for llX tokens.
-    /// 4. `[]` SPL Token program
-    /// 3. `[]` The Vault storage account.
-    /// `[]` (Optional) X SPL account owned by Vault if hodling.
+    /// 1. `[]` Token program owning the X mint.
+    /// 2. `[writeable]` The source wallet containing X tokens.
+    /// 3. `[writeable]` The destination wallet for llX tokens.
+    /// 4. `[signer]` Authority of the source X wallet.
+    /// 5. `[]` The Vault storage account.
+    /// 6. `[]` The Vault's derived authority.
+    /// 7. `[]` The X Token ID.
+    /// 8. `[writeable]` The llX Token ID.
+    /// 9. `[writeable]` (Optional) X SPL account owned by Vault if hodling.
+    /// 10. `[]` (Optional) Token program owning the llX mint, if different from the X one.
+    ///
+    /// Vaults investing through a strategy instead expect the Vault storage account to be
+    /// writeable, keeping a `RollUp` from the same slot current, and after the llX Token ID:
+    /// 9. `[writeable]` The vault's lX token account.
+    /// 10. `[writeable]` The vault's X token account, which deposits pass through.
+    /// 11. `[]` The strategy program.
+    /// 12. `[]` Exactly the vault's `strategy_accounts`, in order and writeable where stored so,
+    ///     passed through to the strategy (see `Vault::strategy_account_metas`).
+    /// 13. `[]` (Optional) Token program owning the llX mint, if not among the above.
+    ///
+    /// Deposits with a referrer also expect the Vault storage account to be writeable, and after
+    /// the above:
+    /// 1. `[writeable]` The destination wallet's `Referral` account for the vault (see
+    ///    `pda::find_referral_address`), created on its first referred deposit, in which case the
+    ///    source authority must also be writeable to pay for it.
+    /// 2. `[]` System program
     /// TODO: Signer pubkeys for multisignature wallets.
-    Deposit { amount: u64 },
+    Deposit {
+        amount: u64, // # of X tokens.
+        /// Whoever brought in the depositor, credited with the deposit's llX so they're owed part
+        /// of the fees on it while the destination wallet holds it. Must match the referrer of
+        /// any earlier referred deposit into the destination wallet, and be neither the source
+        /// authority nor the destination wallet's owner.
+        referrer: COption<Pubkey>,
+    },
 
-    /// Withdraws a token from the strategy.
+    /// Burns llX tokens and returns the corresponding share of the vault's X tokens.
     ///
     /// Accounts expected:
-    /// 2. `[signer]` Source Wallet for derivative token (lX).
-    /// 1. `[]` Target token (X) wallet destination.
-    /// 4. `[]` SPL Token program
-    /// 3. `[]` The Vault storage account.
-    /// `[]` (Optional) X SPL account owned by Vault if hodling.
+    /// 1. `[]` Token program owning the X mint.
+    /// 2. `[writeable]` Source wallet for derivative token (llX).
+    /// 3. `[writeable]` Target token (X) wallet destination.
+    /// 4. `[signer]` Authority of the source llX wallet.
+    /// 5. `[]` The Vault storage account.
+    /// 6. `[]` The Vault's derived authority.
+    /// 7. `[]` The X Token ID.
+    /// 8. `[writeable]` The llX Token ID.
+    /// 9. `[writeable]` (Optional) X SPL account owned by Vault if hodling.
+    /// 10. `[]` (Optional) Token program owning the llX mint, if different from the X one.
+    ///
+    /// Hodl vaults with a `withdrawal_queue` expect the Vault storage account to be writeable,
+    /// and after their X SPL account, for each strategy in the queue, in order:
+    /// 1. `[]` The strategy program.
+    /// 2. `[writeable]` The strategy's lX token account.
+    /// 3. `[]` Exactly the strategy's `strategy_accounts`, in order and writeable where stored so.
+    ///
+    /// Withdrawing more X than such a vault holds takes back the X lent to the strategies in the
+    /// queue's order until enough is held, any loss they realise coming out of the X paid.
+    ///
+    /// Vaults investing through a strategy expect the same accounts as for `Deposit`.
     Withdraw {
         amount: u64, // # of derivative tokens.
+        /// Most the X received may fall short of the llX's share of the vault's `total_assets`,
+        /// in bps of that share, as when the strategy can only exit at a loss or the X mint takes
+        /// a transfer fee. The vault must have been rolled up in the same slot.
+        max_loss_bps: COption<u16>,
+    },
+
+    /// Deposits lamports into a wrapped SOL vault in exchange for llX tokens. The lamports are
+    /// moved directly into the vault's native-mint X account, which is then synced.
+    ///
+    /// Accounts expected:
+    /// 1. `[]` SPL Token program
+    /// 2. `[writeable, signer]` The source system account funding the deposit.
+    /// 3. `[writeable]` The destination wallet for llX tokens.
+    /// 4. `[signer]` Authority of the source account, i.e. the source account itself.
+    /// 5. `[]` The Vault storage account.
+    /// 6. `[]` The Vault's derived authority.
+    /// 7. `[]` The native mint.
+    /// 8. `[writeable]` The llX Token ID.
+    /// 9. `[writeable]` Native-mint X SPL account owned by Vault.
+    /// 10. `[]` System program
+    /// 11. `[]` (Optional) Token program owning the llX mint, if different from the X one.
+    DepositNative {
+        lamports: u64,
+    },
+
+    /// Burns llX tokens from a wrapped SOL vault and unwraps the returned SOL. The X tokens are
+    /// sent to a temporary native-mint account owned by the llX authority, which is then closed
+    /// to that authority. Wrapped SOL vaults only hodl, so this pays exactly the llX's share of
+    /// the vault's SOL and takes no loss limit.
+    ///
+    /// Accounts expected:
+    /// 1. `[]` SPL Token program
+    /// 2. `[writeable]` Source wallet for derivative token (llX).
+    /// 3. `[writeable]` Temporary native-mint X account owned by the llX authority.
+    /// 4. `[writeable, signer]` Authority of the source llX wallet; receives the unwrapped SOL.
+    /// 5. `[]` The Vault storage account.
+    /// 6. `[]` The Vault's derived authority.
+    /// 7. `[]` The native mint.
+    /// 8. `[writeable]` The llX Token ID.
+    /// 9. `[writeable]` Native-mint X SPL account owned by Vault.
+    /// 10. `[]` (Optional) Token program owning the llX mint, if different from the X one.
+    WithdrawNative {
+        amount: u64, // # of derivative tokens.
+    },
+
+    /// Creates a Vault, like InitializeVault, but with the storage account, llX mint and the
+    /// vault's token accounts all created by the program at derived addresses (see `pda`). Vaults
+    /// created this way are discoverable from their X mint, strategy program and nonce, and can't
+    /// be initialized against accounts the program doesn't control.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable, signer]` Payer funding the new accounts, who becomes the vault's governance.
+    /// 2. `[writeable]` Vault storage account, derived from the X mint, strategy program & nonce.
+    /// 3. `[writeable]` llX Token ID, derived from the vault.
+    /// 4. `[writeable]` lX token account, derived from the vault.
+    /// 5. `[]` lX Token ID.
+    /// 6. `[]` X Token ID.
+    /// 7. `[]` Token program for the llX mint, either the legacy SPL Token program or Token-2022.
+    /// 8. `[]` The strategy program's pubkey.
+    /// 9. `[]` System program
+    /// 10. `[]` The rent sysvar
+    /// 11. `[writeable]` X token account, derived from the vault.
+    /// 12. `[writeable]` (Optional) Unless hodling, the strategy instance data account, owned by
+    ///     the strategy program, which must be the first of `strategy_accounts`.
+    /// 13. `[]` (Optional) Further token programs, when the X and lX mints aren't owned by the
+    ///     one above.
+    /// 14. `[]` (Optional) The vaults further down the chain, when investing through another
+    ///     vault, as for `InitializeVault`.
+    CreateVault {
+        nonce: u64,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        hodl: bool,
+        llx_decimals: u8,
+        /// As for `InitializeVault`.
+        strategy_accounts: Vec<StrategyAccount>,
+    },
+
+    /// Adds a vault to the registries indexing vaults by X mint and by strategy program, creating
+    /// either registry page if this is the first vault registered in it. Each registry is split
+    /// into pages of `MAX_REGISTERED_VAULTS`, filled in order: a page after the first only takes
+    /// vaults once the page before it is full. The vault's `Registration` records the pages, and
+    /// keeps it from being registered again until it's deregistered.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable, signer]` Payer funding the registration & any new registry account.
+    /// 2. `[]` The Vault storage account.
+    /// 3. `[writeable]` Page `mint_registry_page` of the registry for the vault's X mint (see
+    ///    `pda::find_mint_registry_address`).
+    /// 4. `[writeable]` Page `strategy_registry_page` of the registry for the vault's strategy
+    ///    program (see `pda::find_strategy_registry_address`).
+    /// 5. `[writeable]` The vault's registration, created here (see
+    ///    `pda::find_registration_address`).
+    /// 6. `[]` System program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` (Optional) The previous page of the X mint's registry, if `mint_registry_page` isn't
+    ///    the first.
+    /// 9. `[]` (Optional) The previous page of the strategy program's registry, if
+    ///    `strategy_registry_page` isn't the first.
+    RegisterVault {
+        mint_registry_page: u32,
+        strategy_registry_page: u32,
+    },
+
+    /// Removes a vault which is no longer initialized from the registries it's listed in, and
+    /// closes its registration.
+    ///
+    /// Accounts expected:
+    /// 1. `[]` The former Vault storage account.
+    /// 2. `[writeable]` The page of the registry for the vault's X mint listing it.
+    /// 3. `[writeable]` The page of the registry for the vault's strategy program listing it.
+    /// 4. `[writeable]` The vault's registration.
+    /// 5. `[writeable]` The registration's payer, refunded its lamports.
+    DeregisterVault,
+
+    /// Closes a vault with no outstanding llX, closing its token accounts and returning all of
+    /// their lamports, and the storage account's, to a recipient. Any tokens left in the vault's
+    /// token accounts, e.g. donated, are first swept to the recipient's token accounts. Only the
+    /// vault's governance may close it. The vault should be deregistered beforehand.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[]` The Vault's derived authority.
+    /// 4. `[writeable]` Recipient of the reclaimed lamports.
+    /// 5. `[]` The llX Token ID.
+    /// 6. `[]` The lX Token ID.
+    /// 7. `[writeable]` The vault's lX token account.
+    /// 8. `[writeable]` The recipient's lX token account.
+    /// 9. `[]` (Optional) The X Token ID if hodling.
+    /// 10. `[writeable]` (Optional) The vault's X token account if hodling.
+    /// 11. `[writeable]` (Optional) The recipient's X token account if hodling.
+    /// 12. `[]` Token program(s) owning the vault's token accounts.
+    CloseVault,
+
+    /// Values a chain of vaults investing through one another in X, bottom-up, caching each
+    /// vault's `total_assets` along with the current slot. Anyone may roll up. Vaults investing
+    /// through a strategy other than a vault are valued by a `PeekResult` of their lX holdings,
+    /// which the strategy must have written in the same slot, e.g. earlier in the transaction.
+    ///
+    /// Accounts expected, for each vault from the top of the chain down:
+    /// 1. `[writeable]` The Vault storage account.
+    /// 2. `[]` The llX Token ID.
+    /// 3. `[]` The vault's X token account if hodling, else its lX token account.
+    ///
+    /// Then, if the bottom vault invests through a strategy other than a vault:
+    /// 4. `[]` The strategy's `PeekResult` account, for the vault & its first strategy account.
+    ///
+    /// Then, if that vault has a `price_feed`, valuing the asset peeked at in X:
+    /// 5. `[]` The asset's price account.
+    /// 6. `[]` X's price account.
+    ///
+    /// Then, if the top vault has a `max_drawdown_bps` & isn't hodling, the accounts to move it to
+    /// hodl, which it does if its share price has fallen that far below its high-water mark:
+    /// 7. `[]` The Vault's derived authority.
+    /// 8. `[]` Token program owning the X mint.
+    /// 9. `[writeable]` The vault's lX token account.
+    /// 10. `[writeable]` The vault's X token account.
+    /// 11. `[]` The strategy program.
+    /// 12. `[]` Exactly the vault's `strategy_accounts`, as for `Withdraw`.
+    RollUp,
+
+    /// Sets a vault's management fee, the referrers' share of it & the accounts the rest is split
+    /// between, restarting its accrual.
+    /// A vault charging a fee must have collected it in the same slot, e.g. earlier in the
+    /// transaction, so none accrued at the old rate is lost. Only the vault's governance may set
+    /// fees.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[]` Each of the new fee recipients, llX token accounts, in order.
+    SetFees {
+        /// Yearly fee on the vault's holdings, at most `math::MAX_BPS`.
+        management_fee_bps: u16,
+        /// Share of the fees on referred principal paid to its referrer, at most `math::MAX_BPS`.
+        referral_fee_bps: u16,
+        /// At most `MAX_FEE_RECIPIENTS` llX token accounts, whose `bps` add up to
+        /// `math::MAX_BPS`. Required if there's a fee.
+        fee_recipients: Vec<FeeRecipient>,
+    },
+
+    /// Mints the llX accrued to a vault's fee recipients since fees were last collected. Anyone
+    /// may collect fees.
+    ///
+    /// The fees are attributed to referrers by the llX minted for their referred deposits, of
+    /// which referrers are owed `referral_fee_bps` before the rest is split between the fee
+    /// recipients. What a referral is owed accrues to it until it's passed, when it's paid to its
+    /// referrer, so referrals left out are paid at a later collection. The referrals passed are
+    /// settled against their token accounts before the split, so llX since withdrawn from them
+    /// isn't counted as referred.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` The Vault storage account.
+    /// 2. `[]` The Vault's derived authority.
+    /// 3. `[writeable]` The llX Token ID.
+    /// 4. `[]` Token program owning the llX mint.
+    /// 5. `[writeable]` Each of the vault's fee recipients, in order.
+    /// 6. `[]` (Optional) Any number of triples of:
+    ///    1. `[writeable]` A `Referral` account for the vault.
+    ///    2. `[]` The llX token account it's for, whose balance caps its referred llX.
+    ///    3. `[writeable]` An llX token account owned by its referrer.
+    CollectFees,
+
+    /// Creates a trigger letting anyone perform `action` on a vault while `condition` holds, for
+    /// a bounty (see `Trigger`). Only the vault's governance may create triggers. Bounties are
+    /// paid out of the lamports the trigger holds beyond its rent exemption, so fund it by
+    /// transferring lamports to it.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable, signer]` The vault's governance, paying for the trigger account.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[writeable]` The trigger account, derived from the vault & nonce (see
+    ///    `pda::find_trigger_address`).
+    /// 4. `[]` System program
+    /// 5. `[]` The rent sysvar
+    ///
+    /// Then, to move a vault without an X token account to hodl, the accounts to create one:
+    /// 6. `[]` The X Token ID.
+    /// 7. `[writeable]` The vault's X token account, derived from the vault.
+    /// 8. `[]` Token program owning the X mint.
+    CreateTrigger {
+        nonce: u64,
+        condition: TriggerCondition,
+        action: TriggerAction,
+        bounty_lamports: u64,
+    },
+
+    /// Performs a trigger's action if its condition holds, paying the trigger's bounty to the
+    /// caller. Anyone may execute triggers.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` Recipient of the bounty.
+    /// 2. `[writeable]` The trigger account.
+    /// 3. `[writeable]` The Vault storage account.
+    ///
+    /// Then, for price conditions:
+    /// 4. `[]` The oracle's price account.
+    ///
+    /// Or, for drawdown conditions, after rolling the vault up in the same slot:
+    /// 4. `[]` The llX Token ID.
+    ///
+    /// Then, to harvest:
+    /// 5. `[]` The strategy program.
+    /// 6. `[]` Exactly the vault's `strategy_accounts`, as for `Deposit`.
+    ///
+    /// Then, to harvest a vault with a drawdown limit, after rolling it up in the same slot:
+    /// 7. `[]` The llX Token ID.
+    /// 8. `[]` The accounts moving it to hodl below, instead of harvesting, if the limit is
+    ///    breached. Nothing is harvested nor paid if the roll-up itself moved it to hodl.
+    ///
+    /// Or, to move to hodl:
+    /// 5. `[]` The Vault's derived authority.
+    /// 6. `[]` Token program owning the X mint.
+    /// 7. `[writeable]` The vault's lX token account.
+    /// 8. `[writeable]` The vault's X token account.
+    /// 9. `[]` The strategy program.
+    /// 10. `[]` Exactly the vault's `strategy_accounts`, as for `Withdraw`.
+    ///
+    /// Or, to rebalance a hodl vault, for each of its strategies in turn, the accounts `Harvest`
+    /// takes for it after the Vault storage account, each peek in the same slot.
+    ExecuteTrigger,
+
+    /// Sets or clears the oracles valuing a vault's strategy holdings, for strategies holding
+    /// something other than X. Only the vault's governance may set them.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    ///
+    /// Then, when setting a price feed:
+    /// 3. `[]` The asset's price account.
+    /// 4. `[]` X's price account.
+    SetPriceFeed {
+        /// The oracles to value the strategy's holdings with, or none if they're in X.
+        price_feed: COption<PriceFeed>,
+    },
+
+    /// Sets how far a vault's share price may fall below its high-water mark before `RollUp`
+    /// moves it to hodl. Only the vault's governance may set it.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable, signer]` The vault's governance, paying for any X token account.
+    /// 2. `[writeable]` The Vault storage account.
+    ///
+    /// Then, to set a limit on a vault without an X token account, the accounts to create one:
+    /// 3. `[]` System program
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The X Token ID.
+    /// 6. `[writeable]` The vault's X token account, derived from the vault.
+    /// 7. `[]` Token program owning the X mint.
+    SetMaxDrawdown {
+        /// At most `math::MAX_BPS`, or 0 for no limit.
+        max_drawdown_bps: u16,
+    },
+
+    /// Invests a vault moved to hodl through its strategy again, depositing all the X it holds.
+    /// Its high-water mark restarts from the next `RollUp`. Only the vault's governance may
+    /// enable the strategy.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[]` The Vault's derived authority.
+    /// 4. `[]` Token program owning the X mint.
+    /// 5. `[writeable]` The vault's X token account.
+    /// 6. `[writeable]` The vault's lX token account.
+    /// 7. `[]` The strategy program.
+    /// 8. `[]` Exactly the vault's `strategy_accounts`, as for `Deposit`.
+    EnableStrategy,
+
+    /// Moves a vault to another strategy, checking the investment graph below it as
+    /// `CreateVault` does. An invested vault's lX is withdrawn from its strategy & the X deposited
+    /// into the new one, while a vault in hodl only has its strategy replaced, to invest through
+    /// with `EnableStrategy`. Its high-water mark restarts from the next `RollUp` and its price
+    /// feed is cleared, for governance to set again for the new strategy's holdings. Only the
+    /// vault's governance may migrate it.
+    ///
+    /// A registered vault can't migrate, as its strategy registry would list it under the wrong
+    /// strategy. Vaults investing through the vault store its accounts, so don't need migrating,
+    /// but depth is only checked from the vault down.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[]` The vault's registration, derived from the vault (see
+    ///    `pda::find_registration_address`).
+    /// 4. `[]` The new strategy program.
+    /// 5. `[writeable]` The new lX token account, owned by the Vault's derived authority.
+    /// 6. `[]` Exactly the new `strategy_accounts`.
+    ///
+    /// Then, unless the vault is in hodl:
+    /// 7. `[]` The Vault's derived authority.
+    /// 8. `[]` Token program owning the X mint.
+    /// 9. `[writeable]` The vault's lX token account.
+    /// 10. `[writeable]` The vault's X token account.
+    /// 11. `[]` The strategy program.
+    /// 12. `[]` Exactly the vault's `strategy_accounts`, as for `Withdraw`.
+    ///
+    /// Then, if the new strategy is a vault:
+    /// 13. `[]` Every vault below it, as for `CreateVault`.
+    MigrateStrategy {
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        strategy_accounts: Vec<StrategyAccount>,
+    },
+
+    /// Harvests a vault's strategy, or moves it to hodl instead if its `max_drawdown_bps` is
+    /// breached, as a `Harvest` trigger does. Given one of the strategies a hodl vault lends to,
+    /// instead marks its debt to the worth of the vault's lX in it, then lends it X up to its
+    /// debt ratio of the vault's total assets, or takes back the X lent beyond it. Anyone may
+    /// harvest a vault.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` The Vault storage account.
+    ///
+    /// Then, for the vault's own strategy:
+    /// 2. `[]` The strategy program.
+    /// 3. `[]` Exactly the vault's `strategy_accounts`, as for `Deposit`.
+    ///
+    /// Then, for a vault with a drawdown limit, after rolling it up in the same slot:
+    /// 4. `[]` The llX Token ID.
+    /// 5. `[]` The accounts moving it to hodl, as for `ExecuteTrigger`, instead of harvesting if
+    ///    the limit is breached. Nothing is harvested if the roll-up itself moved it to hodl.
+    ///
+    /// Or, for a strategy the vault lends to:
+    /// 2. `[]` The Vault's derived authority.
+    /// 3. `[]` Token program owning the X mint.
+    /// 4. `[writeable]` The vault's X token account.
+    /// 5. `[]` The strategy program.
+    /// 6. `[writeable]` The vault's lX token account for the strategy.
+    /// 7. `[]` Exactly the strategy's `strategy_accounts`.
+    /// 8. `[]` The result of the strategy's `Peek` of all that lX for the vault, earlier in the
+    ///    slot.
+    Harvest {
+        /// The index of the strategy among the vault's `strategies`, or none for its own.
+        strategy: COption<u8>,
+    },
+
+    /// Adds a strategy for a hodl vault to lend its X to, without debt until harvested, at the
+    /// end of its withdrawal queue. Only the vault's governance may add strategies, up to
+    /// `state::MAX_STRATEGIES`, whose debt ratios add up to at most the whole.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    /// 3. `[]` The strategy program, other than the vault program.
+    /// 4. `[]` The vault's lX token account for the strategy, owned by the Vault's derived
+    ///    authority.
+    /// 5. `[]` Exactly the strategy's `strategy_accounts`.
+    AddStrategy {
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+        strategy_accounts: Vec<StrategyAccount>,
+    },
+
+    /// Sets how much of a hodl vault's X a strategy it lends to may borrow, which the strategy's
+    /// next harvest moves towards. Only the vault's governance may set it.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    UpdateStrategy {
+        /// The index of the strategy among the vault's `strategies`.
+        strategy: u8,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+    },
+
+    /// Sets the order in which withdrawals take back the X a hodl vault lent to its strategies.
+    /// Only the vault's governance may set it.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` The vault's governance.
+    /// 2. `[writeable]` The Vault storage account.
+    SetWithdrawalQueue {
+        /// Distinct indices among the vault's `strategies`; strategies left out aren't withdrawn
+        /// from.
+        withdrawal_queue: Vec<u8>,
     },
 }
 
-// Strategy programs should implement the following interface for strategies.
+// Strategy programs should implement the following interface for strategies. The
+// `strategy` module dispatches these to a `StrategyProcessor` implementation.
 pub enum StrategyInstruction {
     /// Deposits a token into the strategy.
     ///
     /// Accounts expected:
-    /// 1. `[signer]` Source token (X) wallet
-    /// 2. `[]` Target wallet for derivative token (lX)
-    ///  `[]` SPL Token program
-    /// 3. `[]` (Optional) Strategy instance data account
+    /// 1. `[]` SPL Token program
+    /// 2. `[writeable]` Source token (X) wallet
+    /// 3. `[writeable]` Target wallet for derivative token (lX)
+    /// 4. `[signer]` Authority of the source X wallet.
+    /// 5. `[writeable]` Strategy instance data account
+    /// 6. `[]` (Optional) Further accounts required by the strategy.
     /// TODO: Additional signers.
     Deposit {
         amount: u64, // # of X tokens.
@@ -91,88 +591,206 @@ pub enum StrategyInstruction {
     /// Withdraws a token from the strategy.
     ///
     /// Accounts expected:
-    /// 1. `[signer]` Source Wallet for derivative token (lX).
-    /// 2. `[]` Target token (X) wallet destination.
-    ///  `[]` SPL Token program
-    /// 3. `[]` (Optional) Strategy instance data account
+    /// 1. `[]` SPL Token program
+    /// 2. `[writeable]` Source Wallet for derivative token (lX).
+    /// 3. `[writeable]` Target token (X) wallet destination.
+    /// 4. `[signer]` Authority of the source lX wallet.
+    /// 5. `[writeable]` Strategy instance data account
+    /// 6. `[]` (Optional) Further accounts required by the strategy.
     /// TODO: Additional signers.
     Withdraw {
         amount: u64, // # of lX tokens.
     },
+    /// Values lX tokens in X, writing a `PeekResult` into the given account. The result records
+    /// the data account & the vault, so a `RollUp` can't value a vault by another strategy
+    /// instance's or another vault's holdings.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` Result account owned by the strategy program, `PeekResult::LEN` bytes long
+    ///    & distinct from the other accounts.
+    /// 2. `[]` Strategy instance data account
+    /// 3. `[]` (Optional) Further accounts required by the strategy.
+    Peek {
+        amount: u64, // # of lX tokens.
+        /// The vault holding the lX.
+        vault: Pubkey,
+    },
+    /// Realizes any pending rewards. Anyone may harvest.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` Strategy instance data account
+    /// 2. `[]` (Optional) Further accounts required by the strategy.
+    Harvest,
+    /// Unwinds the strategy's positions, e.g. when an underlying protocol is compromised.
+    ///
+    /// Accounts expected:
+    /// 1. `[signer]` Authority allowed to exit, checked by the strategy.
+    /// 2. `[writeable]` Strategy instance data account
+    /// 3. `[]` (Optional) Further accounts required by the strategy.
+    EmergencyExit,
 }
 
 impl StrategyInstruction {
-    /// Unpacks a byte buffer into a [VaultInstruction](enum.VaultInstruction.html).
+    /// Unpacks a byte buffer into a [StrategyInstruction](enum.StrategyInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
         Ok(match tag {
-            0 | 1 => {
+            0..=2 => {
                 let amount = rest
                     .get(..8)
                     .and_then(|slice| slice.try_into().ok())
                     .map(u64::from_le_bytes)
                     .ok_or(InvalidInstruction)?;
                 match tag {
-                    1 => Self::Deposit { amount },
-                    2 => Self::Withdraw { amount },
-                    _ => return Err(VaultError::InvalidInstruction.into()),
+                    0 => Self::Deposit { amount },
+                    1 => Self::Withdraw { amount },
+                    _ => Self::Peek {
+                        amount,
+                        vault: rest
+                            .get(8..)
+                            .filter(|vault| vault.len() == 32)
+                            .map(Pubkey::new)
+                            .ok_or(InvalidInstruction)?,
+                    },
                 }
             }
+            3 => Self::Harvest,
+            4 => Self::EmergencyExit,
             _ => return Err(VaultError::InvalidInstruction.into()),
         })
     }
 
-    fn pack(&self) -> Vec<u8> {
+    pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
         match self {
             &Self::Deposit { amount } => {
-                buf.push(2);
+                buf.push(0);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
 
             &Self::Withdraw { amount } => {
-                buf.push(3);
+                buf.push(1);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+
+            &Self::Peek { amount, vault } => {
+                buf.push(2);
                 buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(vault.as_ref());
             }
+            &Self::Harvest => buf.push(3),
+            &Self::EmergencyExit => buf.push(4),
         }
         buf
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn deposit(
         program_id: &Pubkey,
         token_program_id: &Pubkey,
         source_pubkey: &Pubkey,
         target_pubkey: &Pubkey,
+        source_authority_pubkey: &Pubkey,
+        data_account_pubkey: &Pubkey,
         additional_account_metas: Vec<AccountMeta>,
         amount: u64,
     ) -> Result<Instruction, ProgramError> {
-        return create_transfer(
+        let mut account_metas = vec![
+            AccountMeta::new_readonly(*source_authority_pubkey, true),
+            AccountMeta::new(*data_account_pubkey, false),
+        ];
+        account_metas.extend(additional_account_metas);
+        create_transfer(
             Self::Deposit { amount }.pack(),
             program_id,
             token_program_id,
             source_pubkey,
             target_pubkey,
-            additional_account_metas,
-        );
+            account_metas,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn withdraw(
         program_id: &Pubkey,
         token_program_id: &Pubkey,
         source_pubkey: &Pubkey,
         target_pubkey: &Pubkey,
+        source_authority_pubkey: &Pubkey,
+        data_account_pubkey: &Pubkey,
         additional_account_metas: Vec<AccountMeta>,
         amount: u64,
     ) -> Result<Instruction, ProgramError> {
-        return create_transfer(
+        let mut account_metas = vec![
+            AccountMeta::new_readonly(*source_authority_pubkey, true),
+            AccountMeta::new(*data_account_pubkey, false),
+        ];
+        account_metas.extend(additional_account_metas);
+        create_transfer(
             Self::Withdraw { amount }.pack(),
             program_id,
             token_program_id,
             source_pubkey,
             target_pubkey,
-            additional_account_metas,
-        );
+            account_metas,
+        )
+    }
+
+    pub fn peek(
+        program_id: &Pubkey,
+        result_account_pubkey: &Pubkey,
+        data_account_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        amount: u64,
+        vault_pubkey: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new(*result_account_pubkey, false),
+            AccountMeta::new_readonly(*data_account_pubkey, false),
+        ];
+        accounts.extend(additional_account_metas);
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: Self::Peek {
+                amount,
+                vault: *vault_pubkey,
+            }
+            .pack(),
+        })
+    }
+
+    pub fn harvest(
+        program_id: &Pubkey,
+        data_account_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![AccountMeta::new(*data_account_pubkey, false)];
+        accounts.extend(additional_account_metas);
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: Self::Harvest.pack(),
+        })
+    }
+
+    pub fn emergency_exit(
+        program_id: &Pubkey,
+        authority_pubkey: &Pubkey,
+        data_account_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*authority_pubkey, true),
+            AccountMeta::new(*data_account_pubkey, false),
+        ];
+        accounts.extend(additional_account_metas);
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: Self::EmergencyExit.pack(),
+        })
     }
 }
 
@@ -183,24 +801,181 @@ impl VaultInstruction {
 
         Ok(match tag {
             0 => {
-                let hodl = *rest.get(0).unwrap();
-                let strategy_program_deposit_instruction_id = *rest.get(0).unwrap();
-                let strategy_program_withdraw_instruction_id = *rest.get(1).unwrap();
+                let (&hodl, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (strategy_program_deposit_instruction, rest) = unpack_template(rest)?;
+                let (strategy_program_withdraw_instruction, rest) = unpack_template(rest)?;
                 Self::InitializeVault {
-                    hodl: if hodl == 1 { true } else { false },
-                    strategy_program_deposit_instruction_id,
-                    strategy_program_withdraw_instruction_id,
+                    hodl: hodl == 1,
+                    strategy_program_deposit_instruction,
+                    strategy_program_withdraw_instruction,
+                    strategy_accounts: unpack_strategy_accounts(rest)?,
+                }
+            }
+            5 => {
+                let nonce = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (&hodl, rest) = rest[8..].split_first().ok_or(InvalidInstruction)?;
+                let (strategy_program_deposit_instruction, rest) = unpack_template(rest)?;
+                let (strategy_program_withdraw_instruction, rest) = unpack_template(rest)?;
+                let (&llx_decimals, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                Self::CreateVault {
+                    nonce,
+                    strategy_program_deposit_instruction,
+                    strategy_program_withdraw_instruction,
+                    hodl: hodl == 1,
+                    llx_decimals,
+                    strategy_accounts: unpack_strategy_accounts(rest)?,
+                }
+            }
+            6 => {
+                if rest.len() != 8 {
+                    return Err(InvalidInstruction.into());
+                }
+                Self::RegisterVault {
+                    mint_registry_page: u32::from_le_bytes(rest[..4].try_into().unwrap()),
+                    strategy_registry_page: u32::from_le_bytes(rest[4..].try_into().unwrap()),
+                }
+            }
+            7 => Self::DeregisterVault,
+            8 => Self::CloseVault,
+            9 => Self::RollUp,
+            10 => {
+                let (management_fee_bps, referral_fee_bps) = rest
+                    .get(..4)
+                    .map(|slice| {
+                        (
+                            u16::from_le_bytes([slice[0], slice[1]]),
+                            u16::from_le_bytes([slice[2], slice[3]]),
+                        )
+                    })
+                    .ok_or(InvalidInstruction)?;
+                Self::SetFees {
+                    management_fee_bps,
+                    referral_fee_bps,
+                    fee_recipients: unpack_fee_recipients(&rest[4..])?,
+                }
+            }
+            11 => Self::CollectFees,
+            12 => {
+                let nonce = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let rest = &rest[8..];
+                if rest.len() != TriggerCondition::LEN + 1 + 8 {
+                    return Err(InvalidInstruction.into());
+                }
+                let (condition, rest) = rest.split_at(TriggerCondition::LEN);
+                Self::CreateTrigger {
+                    nonce,
+                    condition: TriggerCondition::unpack_from_slice(condition)
+                        .map_err(|_| InvalidInstruction)?,
+                    action: TriggerAction::try_from_primitive(rest[0])
+                        .map_err(|_| InvalidInstruction)?,
+                    bounty_lamports: u64::from_le_bytes(rest[1..].try_into().unwrap()),
                 }
             }
-            1 | 2 => {
+            13 => Self::ExecuteTrigger,
+            14 => Self::SetPriceFeed {
+                price_feed: match rest.split_first() {
+                    Some((0, [])) => COption::None,
+                    Some((1, price_feed)) if price_feed.len() == PriceFeed::LEN => {
+                        COption::Some(PriceFeed::unpack_from_slice(price_feed)?)
+                    }
+                    _ => return Err(InvalidInstruction.into()),
+                },
+            },
+            15 => Self::SetMaxDrawdown {
+                max_drawdown_bps: rest
+                    .get(..2)
+                    .map(|slice| u16::from_le_bytes([slice[0], slice[1]]))
+                    .ok_or(InvalidInstruction)?,
+            },
+            16 => Self::EnableStrategy,
+            17 => {
+                let (strategy_program_deposit_instruction, rest) = unpack_template(rest)?;
+                let (strategy_program_withdraw_instruction, rest) = unpack_template(rest)?;
+                Self::MigrateStrategy {
+                    strategy_program_deposit_instruction,
+                    strategy_program_withdraw_instruction,
+                    strategy_accounts: unpack_strategy_accounts(rest)?,
+                }
+            }
+            18 => Self::Harvest {
+                // Harvesting the vault's own strategy ends with the tag.
+                strategy: match rest {
+                    [] => COption::None,
+                    [strategy] => COption::Some(*strategy),
+                    _ => return Err(InvalidInstruction.into()),
+                },
+            },
+            19 => {
+                let (strategy_program_deposit_instruction, rest) = unpack_template(rest)?;
+                let (strategy_program_withdraw_instruction, rest) = unpack_template(rest)?;
+                let (debt_ratio_bps, min_debt_per_harvest, max_debt_per_harvest, rest) =
+                    unpack_debt_limits(rest)?;
+                Self::AddStrategy {
+                    strategy_program_deposit_instruction,
+                    strategy_program_withdraw_instruction,
+                    debt_ratio_bps,
+                    min_debt_per_harvest,
+                    max_debt_per_harvest,
+                    strategy_accounts: unpack_strategy_accounts(rest)?,
+                }
+            }
+            20 => {
+                let (&strategy, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (debt_ratio_bps, min_debt_per_harvest, max_debt_per_harvest, _) =
+                    unpack_debt_limits(rest)?;
+                Self::UpdateStrategy {
+                    strategy,
+                    debt_ratio_bps,
+                    min_debt_per_harvest,
+                    max_debt_per_harvest,
+                }
+            }
+            21 => {
+                let (&len, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                if len as usize > MAX_STRATEGIES || rest.len() != len as usize {
+                    return Err(InvalidInstruction.into());
+                }
+                Self::SetWithdrawalQueue {
+                    withdrawal_queue: rest.to_vec(),
+                }
+            }
+            1..=4 => {
                 let amount = rest
                     .get(..8)
                     .and_then(|slice| slice.try_into().ok())
                     .map(u64::from_le_bytes)
                     .ok_or(InvalidInstruction)?;
                 match tag {
-                    1 => Self::Deposit { amount },
-                    2 => Self::Withdraw { amount },
+                    // Deposits from before referrals, or without one, end with the amount.
+                    1 => Self::Deposit {
+                        amount,
+                        referrer: match rest.get(8..) {
+                            Some([]) => COption::None,
+                            Some(referrer) if referrer.len() == 32 => {
+                                COption::Some(Pubkey::new(referrer))
+                            }
+                            _ => return Err(InvalidInstruction.into()),
+                        },
+                    },
+                    // As are withdrawals without a loss limit.
+                    2 => Self::Withdraw {
+                        amount,
+                        max_loss_bps: match rest.get(8..) {
+                            Some([]) => COption::None,
+                            Some(&[low, high]) => COption::Some(u16::from_le_bytes([low, high])),
+                            _ => return Err(InvalidInstruction.into()),
+                        },
+                    },
+                    3 => Self::DepositNative { lamports: amount },
+                    4 => Self::WithdrawNative { amount },
                     _ => return Err(VaultError::InvalidInstruction.into()),
                 }
             }
@@ -208,27 +983,170 @@ impl VaultInstruction {
         })
     }
 
-    fn pack(&self) -> Vec<u8> {
+    /// Packs a [VaultInstruction](enum.VaultInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
         match self {
             &Self::InitializeVault {
                 hodl,
-                strategy_program_deposit_instruction_id,
-                strategy_program_withdraw_instruction_id,
+                ref strategy_program_deposit_instruction,
+                ref strategy_program_withdraw_instruction,
+                ref strategy_accounts,
             } => {
                 buf.push(0);
                 buf.push(hodl as u8);
-                buf.push(strategy_program_deposit_instruction_id);
-                buf.push(strategy_program_withdraw_instruction_id);
+                pack_template(strategy_program_deposit_instruction, &mut buf);
+                pack_template(strategy_program_withdraw_instruction, &mut buf);
+                pack_strategy_accounts(strategy_accounts, &mut buf);
             }
-            &Self::Deposit { amount } => {
+            &Self::Deposit { amount, referrer } => {
                 buf.push(1);
                 buf.extend_from_slice(&amount.to_le_bytes());
+                if let COption::Some(referrer) = referrer {
+                    buf.extend_from_slice(referrer.as_ref());
+                }
             }
 
-            &Self::Withdraw { amount } => {
+            &Self::Withdraw {
+                amount,
+                max_loss_bps,
+            } => {
                 buf.push(2);
                 buf.extend_from_slice(&amount.to_le_bytes());
+                if let COption::Some(max_loss_bps) = max_loss_bps {
+                    buf.extend_from_slice(&max_loss_bps.to_le_bytes());
+                }
+            }
+
+            &Self::DepositNative { lamports } => {
+                buf.push(3);
+                buf.extend_from_slice(&lamports.to_le_bytes());
+            }
+
+            &Self::WithdrawNative { amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+
+            &Self::CreateVault {
+                nonce,
+                ref strategy_program_deposit_instruction,
+                ref strategy_program_withdraw_instruction,
+                hodl,
+                llx_decimals,
+                ref strategy_accounts,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(&nonce.to_le_bytes());
+                buf.push(hodl as u8);
+                pack_template(strategy_program_deposit_instruction, &mut buf);
+                pack_template(strategy_program_withdraw_instruction, &mut buf);
+                buf.push(llx_decimals);
+                pack_strategy_accounts(strategy_accounts, &mut buf);
+            }
+
+            &Self::RegisterVault {
+                mint_registry_page,
+                strategy_registry_page,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(&mint_registry_page.to_le_bytes());
+                buf.extend_from_slice(&strategy_registry_page.to_le_bytes());
+            }
+            &Self::DeregisterVault => buf.push(7),
+            &Self::CloseVault => buf.push(8),
+            &Self::RollUp => buf.push(9),
+            &Self::SetFees {
+                management_fee_bps,
+                referral_fee_bps,
+                ref fee_recipients,
+            } => {
+                buf.push(10);
+                buf.extend_from_slice(&management_fee_bps.to_le_bytes());
+                buf.extend_from_slice(&referral_fee_bps.to_le_bytes());
+                pack_fee_recipients(fee_recipients, &mut buf);
+            }
+            &Self::CollectFees => buf.push(11),
+            &Self::CreateTrigger {
+                nonce,
+                condition,
+                action,
+                bounty_lamports,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(&nonce.to_le_bytes());
+                let mut dst = [0; TriggerCondition::LEN];
+                condition.pack_into_slice(&mut dst);
+                buf.extend_from_slice(&dst);
+                buf.push(action as u8);
+                buf.extend_from_slice(&bounty_lamports.to_le_bytes());
+            }
+            &Self::ExecuteTrigger => buf.push(13),
+            &Self::SetPriceFeed { price_feed } => {
+                buf.push(14);
+                match price_feed {
+                    COption::Some(price_feed) => {
+                        buf.push(1);
+                        let mut dst = [0; PriceFeed::LEN];
+                        price_feed.pack_into_slice(&mut dst);
+                        buf.extend_from_slice(&dst);
+                    }
+                    COption::None => buf.push(0),
+                }
+            }
+            &Self::SetMaxDrawdown { max_drawdown_bps } => {
+                buf.push(15);
+                buf.extend_from_slice(&max_drawdown_bps.to_le_bytes());
+            }
+            &Self::EnableStrategy => buf.push(16),
+            Self::MigrateStrategy {
+                strategy_program_deposit_instruction,
+                strategy_program_withdraw_instruction,
+                strategy_accounts,
+            } => {
+                buf.push(17);
+                pack_template(strategy_program_deposit_instruction, &mut buf);
+                pack_template(strategy_program_withdraw_instruction, &mut buf);
+                pack_strategy_accounts(strategy_accounts, &mut buf);
+            }
+            &Self::Harvest { strategy } => {
+                buf.push(18);
+                if let COption::Some(strategy) = strategy {
+                    buf.push(strategy);
+                }
+            }
+            Self::AddStrategy {
+                strategy_program_deposit_instruction,
+                strategy_program_withdraw_instruction,
+                debt_ratio_bps,
+                min_debt_per_harvest,
+                max_debt_per_harvest,
+                strategy_accounts,
+            } => {
+                buf.push(19);
+                pack_template(strategy_program_deposit_instruction, &mut buf);
+                pack_template(strategy_program_withdraw_instruction, &mut buf);
+                buf.extend_from_slice(&debt_ratio_bps.to_le_bytes());
+                buf.extend_from_slice(&min_debt_per_harvest.to_le_bytes());
+                buf.extend_from_slice(&max_debt_per_harvest.to_le_bytes());
+                pack_strategy_accounts(strategy_accounts, &mut buf);
+            }
+            &Self::UpdateStrategy {
+                strategy,
+                debt_ratio_bps,
+                min_debt_per_harvest,
+                max_debt_per_harvest,
+            } => {
+                buf.push(20);
+                buf.push(strategy);
+                buf.extend_from_slice(&debt_ratio_bps.to_le_bytes());
+                buf.extend_from_slice(&min_debt_per_harvest.to_le_bytes());
+                buf.extend_from_slice(&max_debt_per_harvest.to_le_bytes());
+            }
+            Self::SetWithdrawalQueue { withdrawal_queue } => {
+                buf.push(21);
+                buf.push(withdrawal_queue.len() as u8);
+                buf.extend_from_slice(withdrawal_queue);
             }
         }
         buf
@@ -240,30 +1158,37 @@ impl VaultInstruction {
         vault_storage_account: &Pubkey,
         lx_token_account: &Pubkey,
         llx_token_mint_id: &Pubkey,
+        x_token_mint_id: &Pubkey,
         token_program: &Pubkey,
         strategy_program: &Pubkey,
         hodl: bool,
-        x_token_account: COption<Pubkey>,
-        strategy_program_deposit_instruction_id: u8,
-        strategy_program_withdraw_instruction_id: u8,
+        x_token_account: &Pubkey,
+        strategy_accounts: Vec<StrategyAccount>,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
     ) -> Result<Instruction, ProgramError> {
         let mut accounts = vec![
             AccountMeta::new_readonly(*initializer, true),
             AccountMeta::new(*vault_storage_account, false),
             AccountMeta::new(*lx_token_account, false),
             AccountMeta::new(*llx_token_mint_id, false),
+            AccountMeta::new_readonly(*x_token_mint_id, false),
             AccountMeta::new_readonly(*token_program, false),
             AccountMeta::new_readonly(*strategy_program, false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(*x_token_account, false),
         ];
-        assert_eq!(hodl, x_token_account.is_some());
-        if hodl {
-            accounts.push(AccountMeta::new(x_token_account.unwrap(), false));
+        assert_eq!(hodl, strategy_accounts.is_empty());
+        assert!(strategy_program_deposit_instruction.is_valid());
+        assert!(strategy_program_withdraw_instruction.is_valid());
+        if !hodl {
+            accounts.push(AccountMeta::new_readonly(strategy_accounts[0].pubkey, false));
         }
         let data = VaultInstruction::InitializeVault {
             hodl,
-            strategy_program_deposit_instruction_id,
-            strategy_program_withdraw_instruction_id,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            strategy_accounts,
         }
         .pack();
         Ok(Instruction {
@@ -273,34 +1198,614 @@ impl VaultInstruction {
         })
     }
 
-    pub fn deposit(
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_vault(
         vault_program_id: &Pubkey,
-        token_program_id: &Pubkey,
-        source_pubkey: &Pubkey,
-        target_pubkey: &Pubkey,
-        additional_account_metas: Vec<AccountMeta>,
-        amount: u64,
+        payer: &Pubkey,
+        x_token_mint_id: &Pubkey,
+        lx_token_mint_id: &Pubkey,
+        token_program: &Pubkey,
+        strategy_program: &Pubkey,
+        nonce: u64,
+        hodl: bool,
+        strategy_accounts: Vec<StrategyAccount>,
+        llx_decimals: u8,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
     ) -> Result<Instruction, ProgramError> {
-        return create_transfer(
-            Self::Deposit { amount }.pack(),
-            vault_program_id,
-            token_program_id,
-            source_pubkey,
-            target_pubkey,
-            additional_account_metas,
-        );
+        let (vault, _) =
+            pda::find_vault_address(vault_program_id, x_token_mint_id, strategy_program, nonce);
+        let mut accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(pda::find_llx_mint_address(vault_program_id, &vault).0, false),
+            AccountMeta::new(pda::find_lx_token_address(vault_program_id, &vault).0, false),
+            AccountMeta::new_readonly(*lx_token_mint_id, false),
+            AccountMeta::new_readonly(*x_token_mint_id, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(*strategy_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(pda::find_x_token_address(vault_program_id, &vault).0, false),
+        ];
+        assert_eq!(hodl, strategy_accounts.is_empty());
+        assert!(strategy_program_deposit_instruction.is_valid());
+        assert!(strategy_program_withdraw_instruction.is_valid());
+        if !hodl {
+            accounts.push(AccountMeta::new(strategy_accounts[0].pubkey, false));
+        }
+        let data = VaultInstruction::CreateVault {
+            nonce,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            hodl,
+            llx_decimals,
+            strategy_accounts,
+        }
+        .pack();
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data,
+        })
     }
 
-    pub fn withdraw(
+    /// Registers a vault in the given registry pages, which `client::open_registry_page` finds.
+    pub fn register_vault(
         vault_program_id: &Pubkey,
-        token_program_id: &Pubkey,
-        source_pubkey: &Pubkey,
-        target_pubkey: &Pubkey,
-        additional_account_metas: Vec<AccountMeta>,
+        payer: &Pubkey,
+        vault_storage_account: &Pubkey,
+        x_token_mint_id: &Pubkey,
+        strategy_program: &Pubkey,
+        mint_registry_page: u32,
+        strategy_registry_page: u32,
+    ) -> Result<Instruction, ProgramError> {
+        let mint_registry = |page| {
+            pda::find_mint_registry_address(vault_program_id, x_token_mint_id, page).0
+        };
+        let strategy_registry = |page| {
+            pda::find_strategy_registry_address(vault_program_id, strategy_program, page).0
+        };
+        let mut accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*vault_storage_account, false),
+            AccountMeta::new(mint_registry(mint_registry_page), false),
+            AccountMeta::new(strategy_registry(strategy_registry_page), false),
+            AccountMeta::new(
+                pda::find_registration_address(vault_program_id, vault_storage_account).0,
+                false,
+            ),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ];
+        if let Some(page) = mint_registry_page.checked_sub(1) {
+            accounts.push(AccountMeta::new_readonly(mint_registry(page), false));
+        }
+        if let Some(page) = strategy_registry_page.checked_sub(1) {
+            accounts.push(AccountMeta::new_readonly(strategy_registry(page), false));
+        }
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::RegisterVault {
+                mint_registry_page,
+                strategy_registry_page,
+            }
+            .pack(),
+        })
+    }
+
+    /// Deregisters a closed vault from the registry pages listing it, as recorded by its
+    /// `Registration`, refunding the registration's `payer`.
+    pub fn deregister_vault(
+        vault_program_id: &Pubkey,
+        vault_storage_account: &Pubkey,
+        x_token_mint_id: &Pubkey,
+        strategy_program: &Pubkey,
+        mint_registry_page: u32,
+        strategy_registry_page: u32,
+        registration_payer: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*vault_storage_account, false),
+                AccountMeta::new(
+                    pda::find_mint_registry_address(
+                        vault_program_id,
+                        x_token_mint_id,
+                        mint_registry_page,
+                    )
+                    .0,
+                    false,
+                ),
+                AccountMeta::new(
+                    pda::find_strategy_registry_address(
+                        vault_program_id,
+                        strategy_program,
+                        strategy_registry_page,
+                    )
+                    .0,
+                    false,
+                ),
+                AccountMeta::new(
+                    pda::find_registration_address(vault_program_id, vault_storage_account).0,
+                    false,
+                ),
+                AccountMeta::new(*registration_payer, false),
+            ],
+            data: VaultInstruction::DeregisterVault.pack(),
+        })
+    }
+
+    /// `x_token_accounts` are the X Token ID, the vault's X token account & the recipient's, if
+    /// hodling.
+    #[allow(clippy::too_many_arguments)]
+    pub fn close_vault(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        recipient: &Pubkey,
+        llx_token_mint_id: &Pubkey,
+        lx_token_mint_id: &Pubkey,
+        lx_token_account: &Pubkey,
+        recipient_lx_token_account: &Pubkey,
+        x_token_accounts: COption<(Pubkey, Pubkey, Pubkey)>,
+        token_programs: &[Pubkey],
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+            AccountMeta::new_readonly(
+                pda::find_vault_authority_address(vault_program_id, vault_storage_account).0,
+                false,
+            ),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new_readonly(*llx_token_mint_id, false),
+            AccountMeta::new_readonly(*lx_token_mint_id, false),
+            AccountMeta::new(*lx_token_account, false),
+            AccountMeta::new(*recipient_lx_token_account, false),
+        ];
+        if let COption::Some((x_token_mint_id, x_token_account, recipient_x_token_account)) =
+            x_token_accounts
+        {
+            accounts.extend(vec![
+                AccountMeta::new_readonly(x_token_mint_id, false),
+                AccountMeta::new(x_token_account, false),
+                AccountMeta::new(recipient_x_token_account, false),
+            ]);
+        }
+        accounts.extend(
+            token_programs
+                .iter()
+                .map(|token_program| AccountMeta::new_readonly(*token_program, false)),
+        );
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::CloseVault.pack(),
+        })
+    }
+
+    /// `account_metas` are those of each vault in the chain, from the top down (see
+    /// `Vault::roll_up_account_metas`), followed by any `PeekResult`, price & drawdown accounts
+    /// (see `VaultClient::roll_up`).
+    pub fn roll_up(
+        vault_program_id: &Pubkey,
+        account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: account_metas,
+            data: VaultInstruction::RollUp.pack(),
+        })
+    }
+
+    pub fn set_fees(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        management_fee_bps: u16,
+        referral_fee_bps: u16,
+        fee_recipients: Vec<FeeRecipient>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+        ];
+        accounts.extend(
+            fee_recipients
+                .iter()
+                .map(|recipient| AccountMeta::new_readonly(recipient.token_account, false)),
+        );
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::SetFees {
+                management_fee_bps,
+                referral_fee_bps,
+                fee_recipients,
+            }
+            .pack(),
+        })
+    }
+
+    /// `referrals` are each referred llX token account to settle, with the llX token account to
+    /// pay its referrer in.
+    pub fn collect_fees(
+        vault_program_id: &Pubkey,
+        vault_storage_account: &Pubkey,
+        llx_token_mint_id: &Pubkey,
+        llx_token_program: &Pubkey,
+        fee_recipients: &[FeeRecipient],
+        referrals: &[(Pubkey, Pubkey)],
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new(*vault_storage_account, false),
+            AccountMeta::new_readonly(
+                pda::find_vault_authority_address(vault_program_id, vault_storage_account).0,
+                false,
+            ),
+            AccountMeta::new(*llx_token_mint_id, false),
+            AccountMeta::new_readonly(*llx_token_program, false),
+        ];
+        accounts.extend(
+            fee_recipients
+                .iter()
+                .map(|recipient| AccountMeta::new(recipient.token_account, false)),
+        );
+        for (llx_token_account, referrer_token_account) in referrals {
+            accounts.push(AccountMeta::new(
+                pda::find_referral_address(
+                    vault_program_id,
+                    vault_storage_account,
+                    llx_token_account,
+                )
+                .0,
+                false,
+            ));
+            accounts.push(AccountMeta::new_readonly(*llx_token_account, false));
+            accounts.push(AccountMeta::new(*referrer_token_account, false));
+        }
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::CollectFees.pack(),
+        })
+    }
+
+    /// Creating a trigger which moves a vault without an X token account to hodl also takes the
+    /// accounts creating one, which the caller appends (see `VaultClient::create_trigger`).
+    pub fn create_trigger(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        nonce: u64,
+        condition: TriggerCondition,
+        action: TriggerAction,
+        bounty_lamports: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: vec![
+                AccountMeta::new(*governance, true),
+                AccountMeta::new(*vault_storage_account, false),
+                AccountMeta::new(
+                    pda::find_trigger_address(vault_program_id, vault_storage_account, nonce).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+            data: VaultInstruction::CreateTrigger {
+                nonce,
+                condition,
+                action,
+                bounty_lamports,
+            }
+            .pack(),
+        })
+    }
+
+    pub fn set_price_feed(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        price_feed: COption<PriceFeed>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+        ];
+        if let COption::Some(price_feed) = price_feed {
+            accounts.push(AccountMeta::new_readonly(price_feed.asset_oracle, false));
+            accounts.push(AccountMeta::new_readonly(price_feed.x_oracle, false));
+        }
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::SetPriceFeed { price_feed }.pack(),
+        })
+    }
+
+    /// Setting a limit on a vault without an X token account also takes the accounts creating
+    /// one, which the caller appends (see `VaultClient::set_max_drawdown`).
+    pub fn set_max_drawdown(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        max_drawdown_bps: u16,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: vec![
+                AccountMeta::new(*governance, true),
+                AccountMeta::new(*vault_storage_account, false),
+            ],
+            data: VaultInstruction::SetMaxDrawdown { max_drawdown_bps }.pack(),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn enable_strategy(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        x_token_program: &Pubkey,
+        x_token_account: &Pubkey,
+        lx_token_account: &Pubkey,
+        strategy_program: &Pubkey,
+        strategy_account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+            AccountMeta::new_readonly(
+                pda::find_vault_authority_address(vault_program_id, vault_storage_account).0,
+                false,
+            ),
+            AccountMeta::new_readonly(*x_token_program, false),
+            AccountMeta::new(*x_token_account, false),
+            AccountMeta::new(*lx_token_account, false),
+            AccountMeta::new_readonly(*strategy_program, false),
+        ];
+        accounts.extend(strategy_account_metas);
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::EnableStrategy.pack(),
+        })
+    }
+
+    /// Migrating an invested vault also takes the accounts moving it to hodl, and migrating to a
+    /// vault the vaults below it, which the caller appends (see `VaultClient::migrate_strategy`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn migrate_strategy(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        strategy_program: &Pubkey,
+        lx_token_account: &Pubkey,
+        strategy_accounts: Vec<StrategyAccount>,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+    ) -> Result<Instruction, ProgramError> {
+        assert!(strategy_program_deposit_instruction.is_valid());
+        assert!(strategy_program_withdraw_instruction.is_valid());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+            AccountMeta::new_readonly(
+                pda::find_registration_address(vault_program_id, vault_storage_account).0,
+                false,
+            ),
+            AccountMeta::new_readonly(*strategy_program, false),
+            AccountMeta::new(*lx_token_account, false),
+        ];
+        accounts.extend(strategy_accounts.iter().map(StrategyAccount::account_meta));
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::MigrateStrategy {
+                strategy_program_deposit_instruction,
+                strategy_program_withdraw_instruction,
+                strategy_accounts,
+            }
+            .pack(),
+        })
+    }
+
+    /// `additional_account_metas` are the strategy's accounts, and any checking the vault's
+    /// drawdown limit (see `VaultClient::harvest` & `VaultClient::harvest_strategy`).
+    pub fn harvest(
+        vault_program_id: &Pubkey,
+        vault_storage_account: &Pubkey,
+        strategy: COption<u8>,
+        additional_account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![AccountMeta::new(*vault_storage_account, false)];
+        accounts.extend(additional_account_metas);
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::Harvest { strategy }.pack(),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_strategy(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        strategy_program: &Pubkey,
+        lx_token_account: &Pubkey,
+        strategy_accounts: Vec<StrategyAccount>,
+        strategy_program_deposit_instruction: InstructionTemplate,
+        strategy_program_withdraw_instruction: InstructionTemplate,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+    ) -> Result<Instruction, ProgramError> {
+        assert!(strategy_program_deposit_instruction.is_valid());
+        assert!(strategy_program_withdraw_instruction.is_valid());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*governance, true),
+            AccountMeta::new(*vault_storage_account, false),
+            AccountMeta::new_readonly(*strategy_program, false),
+            AccountMeta::new_readonly(*lx_token_account, false),
+        ];
+        accounts.extend(strategy_accounts.iter().map(StrategyAccount::account_meta));
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::AddStrategy {
+                strategy_program_deposit_instruction,
+                strategy_program_withdraw_instruction,
+                debt_ratio_bps,
+                min_debt_per_harvest,
+                max_debt_per_harvest,
+                strategy_accounts,
+            }
+            .pack(),
+        })
+    }
+
+    pub fn update_strategy(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        strategy: u8,
+        debt_ratio_bps: u16,
+        min_debt_per_harvest: u64,
+        max_debt_per_harvest: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*governance, true),
+                AccountMeta::new(*vault_storage_account, false),
+            ],
+            data: VaultInstruction::UpdateStrategy {
+                strategy,
+                debt_ratio_bps,
+                min_debt_per_harvest,
+                max_debt_per_harvest,
+            }
+            .pack(),
+        })
+    }
+
+    pub fn set_withdrawal_queue(
+        vault_program_id: &Pubkey,
+        governance: &Pubkey,
+        vault_storage_account: &Pubkey,
+        withdrawal_queue: Vec<u8>,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*governance, true),
+                AccountMeta::new(*vault_storage_account, false),
+            ],
+            data: VaultInstruction::SetWithdrawalQueue { withdrawal_queue }.pack(),
+        })
+    }
+
+    /// `additional_account_metas` are those the trigger's condition & action expect (see
+    /// `VaultClient::execute_trigger`).
+    pub fn execute_trigger(
+        vault_program_id: &Pubkey,
+        bounty_recipient: &Pubkey,
+        trigger: &Pubkey,
+        vault_storage_account: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+    ) -> Result<Instruction, ProgramError> {
+        let mut accounts = vec![
+            AccountMeta::new(*bounty_recipient, false),
+            AccountMeta::new(*trigger, false),
+            AccountMeta::new(*vault_storage_account, false),
+        ];
+        accounts.extend(additional_account_metas);
+        Ok(Instruction {
+            program_id: *vault_program_id,
+            accounts,
+            data: VaultInstruction::ExecuteTrigger.pack(),
+        })
+    }
+
+    pub fn deposit(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
         amount: u64,
     ) -> Result<Instruction, ProgramError> {
         return create_transfer(
-            Self::Withdraw { amount }.pack(),
+            Self::Deposit {
+                amount,
+                referrer: COption::None,
+            }
+            .pack(),
+            vault_program_id,
+            token_program_id,
+            source_pubkey,
+            target_pubkey,
+            additional_account_metas,
+        );
+    }
+
+    /// Like `deposit`, crediting `referrer` with the deposit. `additional_account_metas` are as
+    /// for `deposit`, starting with the source authority, which pays for any new `Referral`.
+    pub fn deposit_referred(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        amount: u64,
+        referrer: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        let mut instruction = create_transfer(
+            Self::Deposit {
+                amount,
+                referrer: COption::Some(*referrer),
+            }
+            .pack(),
+            vault_program_id,
+            token_program_id,
+            source_pubkey,
+            target_pubkey,
+            additional_account_metas,
+        )?;
+        // The source authority & the vault follow the source & target accounts.
+        instruction.accounts[3].is_writable = true;
+        instruction.accounts[4].is_writable = true;
+        let vault_storage_account = instruction.accounts[4].pubkey;
+        instruction.accounts.push(AccountMeta::new(
+            pda::find_referral_address(vault_program_id, &vault_storage_account, target_pubkey).0,
+            false,
+        ));
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(system_program::id(), false));
+        Ok(instruction)
+    }
+
+    pub fn withdraw(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        return create_transfer(
+            Self::Withdraw {
+                amount,
+                max_loss_bps: COption::None,
+            }
+            .pack(),
             vault_program_id,
             token_program_id,
             source_pubkey,
@@ -308,6 +1813,70 @@ impl VaultInstruction {
             additional_account_metas,
         );
     }
+
+    /// Like `withdraw`, failing if the X paid out falls more than `max_loss_bps` short of the
+    /// llX's value as of the vault's `RollUp` earlier in the slot.
+    pub fn withdraw_with_max_loss(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        amount: u64,
+        max_loss_bps: u16,
+    ) -> Result<Instruction, ProgramError> {
+        create_transfer(
+            Self::Withdraw {
+                amount,
+                max_loss_bps: COption::Some(max_loss_bps),
+            }
+            .pack(),
+            vault_program_id,
+            token_program_id,
+            source_pubkey,
+            target_pubkey,
+            additional_account_metas,
+        )
+    }
+
+    pub fn deposit_native(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        lamports: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let mut instruction = create_transfer(
+            Self::DepositNative { lamports }.pack(),
+            vault_program_id,
+            token_program_id,
+            source_pubkey,
+            target_pubkey,
+            additional_account_metas,
+        )?;
+        // The source is a system account which funds the deposit directly.
+        instruction.accounts[1] = AccountMeta::new(*source_pubkey, true);
+        Ok(instruction)
+    }
+
+    pub fn withdraw_native(
+        vault_program_id: &Pubkey,
+        token_program_id: &Pubkey,
+        source_pubkey: &Pubkey,
+        target_pubkey: &Pubkey,
+        additional_account_metas: Vec<AccountMeta>,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        create_transfer(
+            Self::WithdrawNative { amount }.pack(),
+            vault_program_id,
+            token_program_id,
+            source_pubkey,
+            target_pubkey,
+            additional_account_metas,
+        )
+    }
 }
 
 pub fn create_transfer(
@@ -331,3 +1900,78 @@ pub fn create_transfer(
         data,
     })
 }
+
+/// Packs a vault's strategy accounts as a count followed by each account.
+fn pack_strategy_accounts(strategy_accounts: &[StrategyAccount], buf: &mut Vec<u8>) {
+    buf.push(strategy_accounts.len() as u8);
+    for account in strategy_accounts {
+        let mut dst = [0; StrategyAccount::LEN];
+        account.pack_into_slice(&mut dst);
+        buf.extend_from_slice(&dst);
+    }
+}
+
+fn unpack_strategy_accounts(input: &[u8]) -> Result<Vec<StrategyAccount>, ProgramError> {
+    let (&count, rest) = input.split_first().ok_or(InvalidInstruction)?;
+    let count = count as usize;
+    if count > MAX_STRATEGY_ACCOUNTS || rest.len() < count * StrategyAccount::LEN {
+        return Err(InvalidInstruction.into());
+    }
+    rest.chunks(StrategyAccount::LEN)
+        .take(count)
+        .map(|src| StrategyAccount::unpack_from_slice(src).map_err(|_| InvalidInstruction.into()))
+        .collect()
+}
+
+fn pack_fee_recipients(fee_recipients: &[FeeRecipient], buf: &mut Vec<u8>) {
+    buf.push(fee_recipients.len() as u8);
+    for recipient in fee_recipients {
+        let mut dst = [0; FeeRecipient::LEN];
+        recipient.pack_into_slice(&mut dst);
+        buf.extend_from_slice(&dst);
+    }
+}
+
+fn unpack_fee_recipients(input: &[u8]) -> Result<Vec<FeeRecipient>, ProgramError> {
+    let (&count, rest) = input.split_first().ok_or(InvalidInstruction)?;
+    let count = count as usize;
+    if count > MAX_FEE_RECIPIENTS || rest.len() < count * FeeRecipient::LEN {
+        return Err(InvalidInstruction.into());
+    }
+    rest.chunks(FeeRecipient::LEN)
+        .take(count)
+        .map(|src| FeeRecipient::unpack_from_slice(src).map_err(|_| InvalidInstruction.into()))
+        .collect()
+}
+
+/// Unpacks a strategy's `debt_ratio_bps`, `min_debt_per_harvest` & `max_debt_per_harvest`.
+fn unpack_debt_limits(input: &[u8]) -> Result<(u16, u64, u64, &[u8]), ProgramError> {
+    if input.len() < 2 + 8 + 8 {
+        return Err(InvalidInstruction.into());
+    }
+    let (debt_ratio_bps, rest) = input.split_at(2);
+    let (min_debt_per_harvest, rest) = rest.split_at(8);
+    let (max_debt_per_harvest, rest) = rest.split_at(8);
+    Ok((
+        u16::from_le_bytes(debt_ratio_bps.try_into().unwrap()),
+        u64::from_le_bytes(min_debt_per_harvest.try_into().unwrap()),
+        u64::from_le_bytes(max_debt_per_harvest.try_into().unwrap()),
+        rest,
+    ))
+}
+
+fn pack_template(template: &InstructionTemplate, buf: &mut Vec<u8>) {
+    let mut dst = [0; InstructionTemplate::LEN];
+    template.pack_into_slice(&mut dst);
+    buf.extend_from_slice(&dst);
+}
+
+fn unpack_template(input: &[u8]) -> Result<(InstructionTemplate, &[u8]), ProgramError> {
+    if input.len() < InstructionTemplate::LEN {
+        return Err(InvalidInstruction.into());
+    }
+    let (template, rest) = input.split_at(InstructionTemplate::LEN);
+    let template =
+        InstructionTemplate::unpack_from_slice(template).map_err(|_| InvalidInstruction)?;
+    Ok((template, rest))
+}
@@ -1,10 +1,24 @@
 use solana_program;
 
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
+#[cfg(feature = "client")]
+pub mod graph;
+#[cfg(feature = "idl")]
+pub mod idl;
 pub mod instruction;
+pub mod math;
+pub mod oracle;
+pub mod pda;
 pub mod processor;
+#[cfg(feature = "sim")]
+pub mod sim;
 pub mod state;
+pub mod strategy;
+pub mod token;
 
 // Random based on Token ID's ID. Defines Vault::id().
 solana_program::declare_id!("VscrowegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
@@ -0,0 +1,141 @@
+//! Share accounting for vaults.
+//!
+//! X amounts are denominated in the X mint's decimals and llX amounts in the llX mint's decimals.
+//! All calculations are done in u128 and round down, in favor of the vault.
+
+/// Basis points in a whole.
+pub const MAX_BPS: u64 = 10_000;
+
+/// Scale of share prices, in X per llX.
+pub const SHARE_PRICE_SCALE: u64 = 1_000_000_000;
+
+/// Slots in a year of 400ms slots, over which a management fee accrues in full.
+pub const SLOTS_PER_YEAR: u64 = 78_840_000;
+
+/// Scale of fee indices, in llX owed per llX held.
+pub const FEE_INDEX_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Converts `amount` expressed with `from_decimals` into the same value expressed with
+/// `to_decimals`.
+pub fn normalize_amount(amount: u64, from_decimals: u8, to_decimals: u8) -> Option<u64> {
+    let amount = amount as u128;
+    let normalized = if to_decimals >= from_decimals {
+        amount.checked_mul(10u128.checked_pow((to_decimals - from_decimals) as u32)?)?
+    } else {
+        amount.checked_div(10u128.checked_pow((from_decimals - to_decimals) as u32)?)?
+    };
+    to_u64(normalized)
+}
+
+/// Computes `a * b / c` without intermediate overflow.
+pub fn mul_div(a: u64, b: u64, c: u64) -> Option<u64> {
+    to_u64((a as u128).checked_mul(b as u128)?.checked_div(c as u128)?)
+}
+
+/// Computes `a * b / c`, rounding up, without intermediate overflow.
+pub fn mul_div_ceil(a: u64, b: u64, c: u64) -> Option<u64> {
+    let product = (a as u128).checked_mul(b as u128)?;
+    let quotient = product.checked_div(c as u128)?;
+    to_u64(quotient + (product % c as u128 != 0) as u128)
+}
+
+/// Returns the number of llX shares to mint for a deposit of `amount` X tokens.
+///
+/// `total_assets` is the X-denominated value held by the vault before the deposit and
+/// `share_supply` the outstanding llX supply. The first deposit into an empty vault is priced 1:1,
+/// normalised across the X and llX decimals.
+pub fn shares_for_deposit(
+    amount: u64,
+    total_assets: u64,
+    share_supply: u64,
+    x_decimals: u8,
+    llx_decimals: u8,
+) -> Option<u64> {
+    if share_supply == 0 || total_assets == 0 {
+        return normalize_amount(amount, x_decimals, llx_decimals);
+    }
+    shares_for_value(amount, total_assets, share_supply)
+}
+
+/// Returns the llX to mint for `value` added to a vault holding `total_value` against
+/// `share_supply` llX.
+///
+/// Both are offset by one virtual share & unit of value, so that inflating the price of a share
+/// with a donation costs the donor more than rounding can take from the depositors after them.
+pub fn shares_for_value(value: u64, total_value: u64, share_supply: u64) -> Option<u64> {
+    to_u64(
+        (value as u128)
+            .checked_mul(share_supply as u128 + 1)?
+            .checked_div(total_value as u128 + 1)?,
+    )
+}
+
+/// Returns the number of X tokens owed for redeeming `shares` llX tokens.
+pub fn assets_for_shares(shares: u64, total_assets: u64, share_supply: u64) -> Option<u64> {
+    if share_supply == 0 {
+        return Some(0);
+    }
+    mul_div(shares, total_assets, share_supply)
+}
+
+/// Returns the X `total_assets` backing each llX of `share_supply`, scaled by
+/// `SHARE_PRICE_SCALE`, or 0 if there are no shares.
+pub fn share_price(total_assets: u64, share_supply: u64) -> Option<u64> {
+    if share_supply == 0 {
+        return Some(0);
+    }
+    mul_div(total_assets, SHARE_PRICE_SCALE, share_supply)
+}
+
+/// Returns `bps` basis points of `amount`, rounding down.
+pub fn bps_of(amount: u64, bps: u64) -> Option<u64> {
+    mul_div(amount, bps, MAX_BPS)
+}
+
+/// Returns the llX to mint for a yearly management fee of `bps` over `elapsed_slots`, as a share
+/// of the `share_supply`.
+pub fn management_fee_shares(share_supply: u64, bps: u16, elapsed_slots: u64) -> Option<u64> {
+    to_u64(
+        (share_supply as u128)
+            .checked_mul(bps as u128)?
+            .checked_mul(elapsed_slots as u128)?
+            .checked_div(MAX_BPS as u128 * SLOTS_PER_YEAR as u128)?,
+    )
+}
+
+/// Returns the part of `fee_shares` llX, charged on `share_supply` llX, paid to a referrer taking
+/// `bps` of the fees on the `referred_shares` they brought in.
+pub fn referral_fee_shares(
+    fee_shares: u64,
+    referred_shares: u64,
+    share_supply: u64,
+    bps: u16,
+) -> Option<u64> {
+    let attributed =
+        assets_for_shares(referred_shares.min(share_supply), fee_shares, share_supply)?;
+    bps_of(attributed, bps as u64)
+}
+
+/// Returns how far a fee index grows by spreading `fee_shares` llX over `shares` llX, scaled by
+/// `FEE_INDEX_SCALE`, or 0 if there are no shares.
+pub fn fee_index_delta(fee_shares: u64, shares: u64) -> Option<u128> {
+    if shares == 0 {
+        return Some(0);
+    }
+    (fee_shares as u128)
+        .checked_mul(FEE_INDEX_SCALE)?
+        .checked_div(shares as u128)
+}
+
+/// Returns the llX owed on `shares` llX held while a fee index grew by `index_delta`.
+pub fn fees_for_index(shares: u64, index_delta: u128) -> Option<u64> {
+    to_u64((shares as u128).checked_mul(index_delta)? / FEE_INDEX_SCALE)
+}
+
+fn to_u64(value: u128) -> Option<u64> {
+    if value > u64::MAX as u128 {
+        None
+    } else {
+        Some(value as u64)
+    }
+}
@@ -0,0 +1,181 @@
+//! Price feeds read by vaults, in the layout of Pyth v2 price accounts.
+//!
+//! Only the fields vaults use are read: the price exponent and the aggregate price, which Pyth
+//! publishes as an `i64` scaled by `10^expo`, along with its confidence interval, status & the
+//! slot it was published in. [Price::load_current](struct.Price.html#method.load_current) rejects
+//! prices that aren't trading, are older than `MAX_PRICE_AGE_SLOTS` or whose confidence interval
+//! is wider than `MAX_CONFIDENCE_BPS` of the price. Price accounts are only read from the Pyth
+//! oracle programs, and from the `mock-price` program in builds with the `mock-oracle` feature.
+
+use std::convert::TryInto;
+
+use arrayref::{array_mut_ref, array_ref};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{error::VaultError, math};
+
+pub mod pyth_mainnet {
+    solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kkr3aRvUPDLYHP5hXvAph");
+}
+
+pub mod pyth_devnet {
+    solana_program::declare_id!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+}
+
+pub mod pyth_testnet {
+    solana_program::declare_id!("8tfDNiaEyrV6Q1U4DEXrEigs9DoDtkugzFbybENEbCDz");
+}
+
+/// The `mock-price` program under `oracles/`, publishing prices for tests.
+#[cfg(feature = "mock-oracle")]
+pub mod mock_price {
+    solana_program::declare_id!("7mzTR6g3SAfJhfL8nW6Jir98EWu6iL5agS2HML1aP47g");
+}
+
+/// Magic number starting every Pyth account.
+pub const MAGIC: u32 = 0xa1b2_c3d4;
+/// Version of the Pyth account layout read.
+pub const VERSION: u32 = 2;
+/// Pyth account type of price accounts.
+pub const ACCOUNT_TYPE_PRICE: u32 = 3;
+/// `PriceStatus` of prices currently trading.
+pub const STATUS_TRADING: u32 = 1;
+
+/// Slots after which a published price is stale.
+pub const MAX_PRICE_AGE_SLOTS: u64 = 25;
+/// Widest confidence interval accepted, in bps of the price.
+pub const MAX_CONFIDENCE_BPS: u64 = 200;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPO_OFFSET: usize = 20;
+const AGGREGATE_PRICE_OFFSET: usize = 208;
+const AGGREGATE_CONF_OFFSET: usize = 216;
+const AGGREGATE_STATUS_OFFSET: usize = 224;
+const AGGREGATE_PUBLISH_SLOT_OFFSET: usize = 232;
+/// Length of a price account up to the end of its aggregate price.
+pub const PRICE_ACCOUNT_LEN: usize = 240;
+
+/// Returns whether `program_id` is an oracle program whose price accounts are read.
+pub fn is_oracle_program(program_id: &Pubkey) -> bool {
+    #[cfg(feature = "mock-oracle")]
+    if *program_id == mock_price::id() {
+        return true;
+    }
+    *program_id == pyth_mainnet::id()
+        || *program_id == pyth_devnet::id()
+        || *program_id == pyth_testnet::id()
+}
+
+/// The aggregate price of a feed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Price {
+    /// The price, scaled by `10^expo`.
+    pub price: i64,
+    /// Confidence interval around the price, in the same units.
+    pub conf: u64,
+    pub expo: i32,
+    /// Pyth's `PriceStatus`, `STATUS_TRADING` when trading.
+    pub status: u32,
+    /// The slot the price was published in.
+    pub publish_slot: u64,
+}
+
+impl Price {
+    /// Reads the aggregate price from a price account's data, which must be in the layout version
+    /// read.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < PRICE_ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let u32_at = |offset| u32::from_le_bytes(*array_ref![data, offset, 4]);
+        if u32_at(MAGIC_OFFSET) != MAGIC
+            || u32_at(VERSION_OFFSET) != VERSION
+            || u32_at(ACCOUNT_TYPE_OFFSET) != ACCOUNT_TYPE_PRICE
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Price {
+            price: i64::from_le_bytes(*array_ref![data, AGGREGATE_PRICE_OFFSET, 8]),
+            conf: u64::from_le_bytes(*array_ref![data, AGGREGATE_CONF_OFFSET, 8]),
+            expo: i32::from_le_bytes(*array_ref![data, EXPO_OFFSET, 4]),
+            status: u32_at(AGGREGATE_STATUS_OFFSET),
+            publish_slot: u64::from_le_bytes(*array_ref![data, AGGREGATE_PUBLISH_SLOT_OFFSET, 8]),
+        })
+    }
+
+    /// Writes the price into a price account's data, as mock oracles do. Fields other than those
+    /// read are left as they are.
+    pub fn pack(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() < PRICE_ACCOUNT_LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        *array_mut_ref![data, MAGIC_OFFSET, 4] = MAGIC.to_le_bytes();
+        *array_mut_ref![data, VERSION_OFFSET, 4] = VERSION.to_le_bytes();
+        *array_mut_ref![data, ACCOUNT_TYPE_OFFSET, 4] = ACCOUNT_TYPE_PRICE.to_le_bytes();
+        *array_mut_ref![data, EXPO_OFFSET, 4] = self.expo.to_le_bytes();
+        *array_mut_ref![data, AGGREGATE_PRICE_OFFSET, 8] = self.price.to_le_bytes();
+        *array_mut_ref![data, AGGREGATE_CONF_OFFSET, 8] = self.conf.to_le_bytes();
+        *array_mut_ref![data, AGGREGATE_STATUS_OFFSET, 4] = self.status.to_le_bytes();
+        *array_mut_ref![data, AGGREGATE_PUBLISH_SLOT_OFFSET, 8] = self.publish_slot.to_le_bytes();
+        Ok(())
+    }
+
+    /// Reads the aggregate price from a price account, failing with `IncorrectProgramId` unless
+    /// an oracle program owns it.
+    pub fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+        if !is_oracle_program(account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::unpack(&account.data.borrow())
+    }
+
+    /// Reads the aggregate price from a price account, failing with `StalePrice` unless it's
+    /// current as of `slot`.
+    pub fn load_current(account: &AccountInfo, slot: u64) -> Result<Self, ProgramError> {
+        let price = Self::load(account)?;
+        price.check_current(slot)?;
+        Ok(price)
+    }
+
+    /// Checks that the price is trading & positive, was published at most `MAX_PRICE_AGE_SLOTS`
+    /// before `slot`, and is known to within `MAX_CONFIDENCE_BPS`.
+    pub fn check_current(&self, slot: u64) -> Result<(), VaultError> {
+        let max_conf = math::bps_of(self.price.max(0) as u64, MAX_CONFIDENCE_BPS)
+            .ok_or(VaultError::MathOverflow)?;
+        if self.status != STATUS_TRADING
+            || self.price <= 0
+            || slot.saturating_sub(self.publish_slot) > MAX_PRICE_AGE_SLOTS
+            || self.conf > max_conf
+        {
+            return Err(VaultError::StalePrice);
+        }
+        Ok(())
+    }
+
+    /// Values `amount` of the priced token, with `decimals`, in the token priced by `quote`, with
+    /// `quote_decimals`, both prices being in the same currency. Rounds down.
+    pub fn value_in(
+        &self,
+        amount: u64,
+        decimals: u8,
+        quote: &Price,
+        quote_decimals: u8,
+    ) -> Option<u64> {
+        if self.price < 0 || quote.price <= 0 {
+            return None;
+        }
+        // amount * price * 10^(expo - decimals) / (quote.price * 10^(quote.expo - quote_decimals))
+        let exponent =
+            (self.expo as i64 - quote.expo as i64) + (quote_decimals as i64 - decimals as i64);
+        let scale = 10u128.checked_pow(exponent.unsigned_abs().try_into().ok()?)?;
+        let value = (amount as u128).checked_mul(self.price as u128)?;
+        let value = if exponent >= 0 {
+            value.checked_mul(scale)?.checked_div(quote.price as u128)?
+        } else {
+            value.checked_div((quote.price as u128).checked_mul(scale)?)?
+        };
+        value.try_into().ok()
+    }
+}
@@ -0,0 +1,118 @@
+//! Program derived addresses used by vaults.
+//!
+//! Vaults created through `CreateVault` live at deterministic addresses seeded by their X mint,
+//! strategy program and a nonce, with their llX mint and token accounts derived from the vault
+//! address. Each vault's accounts & llX mint are owned by an authority derived from the vault, so
+//! the program only ever signs for one vault's accounts at a time.
+
+use solana_program::pubkey::Pubkey;
+
+pub const AUTHORITY_SEED: &[u8] = b"vault";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const LLX_MINT_SEED: &[u8] = b"llx_mint";
+pub const LX_TOKEN_ACCOUNT_SEED: &[u8] = b"lx";
+pub const X_TOKEN_ACCOUNT_SEED: &[u8] = b"x";
+pub const REGISTRY_SEED: &[u8] = b"registry";
+pub const MINT_REGISTRY_SEED: &[u8] = b"mint";
+pub const STRATEGY_REGISTRY_SEED: &[u8] = b"strategy";
+pub const REGISTRATION_SEED: &[u8] = b"registration";
+pub const REFERRAL_SEED: &[u8] = b"referral";
+pub const TRIGGER_SEED: &[u8] = b"trigger";
+
+/// Authority owning `vault`'s token accounts and llX mint.
+pub fn find_vault_authority_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED, vault.as_ref()], program_id)
+}
+
+pub fn find_vault_address(
+    program_id: &Pubkey,
+    x_token_mint_id: &Pubkey,
+    strategy_program_id: &Pubkey,
+    nonce: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            VAULT_SEED,
+            x_token_mint_id.as_ref(),
+            strategy_program_id.as_ref(),
+            &nonce.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_llx_mint_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LLX_MINT_SEED, vault.as_ref()], program_id)
+}
+
+pub fn find_lx_token_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LX_TOKEN_ACCOUNT_SEED, vault.as_ref()], program_id)
+}
+
+pub fn find_x_token_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[X_TOKEN_ACCOUNT_SEED, vault.as_ref()], program_id)
+}
+
+/// Page `page` of the registry of all vaults over `x_token_mint_id`. Pages fill up in order.
+pub fn find_mint_registry_address(
+    program_id: &Pubkey,
+    x_token_mint_id: &Pubkey,
+    page: u32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            REGISTRY_SEED,
+            MINT_REGISTRY_SEED,
+            x_token_mint_id.as_ref(),
+            &page.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Page `page` of the registry of all vaults investing through `strategy_program_id`.
+pub fn find_strategy_registry_address(
+    program_id: &Pubkey,
+    strategy_program_id: &Pubkey,
+    page: u32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            REGISTRY_SEED,
+            STRATEGY_REGISTRY_SEED,
+            strategy_program_id.as_ref(),
+            &page.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Where `vault` is listed in the registries, for as long as it's registered (see
+/// `state::Registration`).
+pub fn find_registration_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REGISTRY_SEED, REGISTRATION_SEED, vault.as_ref()],
+        program_id,
+    )
+}
+
+/// Deposits into `vault` referred to the llX token account `llx_token_account` (see
+/// `state::Referral`).
+pub fn find_referral_address(
+    program_id: &Pubkey,
+    vault: &Pubkey,
+    llx_token_account: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REFERRAL_SEED, vault.as_ref(), llx_token_account.as_ref()],
+        program_id,
+    )
+}
+
+/// A vault's trigger at `nonce` (see `state::Trigger`).
+pub fn find_trigger_address(program_id: &Pubkey, vault: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TRIGGER_SEED, vault.as_ref(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
@@ -1,16 +1,30 @@
 use solana_program::{
   account_info::{next_account_info, AccountInfo},
   entrypoint::ProgramResult,
+  instruction::{AccountMeta, Instruction},
   msg,
   program::{invoke, invoke_signed},
   program_error::ProgramError,
   program_option::COption,
   program_pack::{IsInitialized, Pack},
   pubkey::Pubkey,
-  sysvar::{rent::Rent, Sysvar},
+  system_instruction, system_program,
+  sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
-use crate::{error::VaultError, instruction::VaultInstruction, state::Vault};
+use spl_token::{instruction::AuthorityType, native_mint, state::Mint};
+
+use crate::{
+  error::VaultError,
+  instruction::{StrategyInstruction, VaultInstruction},
+  math, oracle, pda,
+  state::{
+    FeeRecipient, InstructionTemplate, PeekResult, PriceFeed, Referral, Registration, Registry,
+    RegistryKind, StrategyAccount, StrategyEntry, Trigger, TriggerAction, TriggerCondition, Vault,
+    MAX_REGISTERED_VAULTS, MAX_STRATEGIES, MAX_VAULT_DEPTH,
+  },
+  token,
+};
 
 pub struct Processor;
 impl Processor {
@@ -25,35 +39,1909 @@ impl Processor {
     match instruction {
       VaultInstruction::InitializeVault {
         hodl,
-        strategy_program_deposit_instruction_id,
-        strategy_program_withdraw_instruction_id,
+        strategy_program_deposit_instruction,
+        strategy_program_withdraw_instruction,
+        strategy_accounts,
       } => {
         msg!("Instruction: InitializeVault");
         Self::process_initialize_vault(
           program_id,
           accounts,
           hodl,
-          strategy_program_deposit_instruction_id,
-          strategy_program_withdraw_instruction_id,
+          strategy_program_deposit_instruction,
+          strategy_program_withdraw_instruction,
+          strategy_accounts,
         )
       }
-      VaultInstruction::Deposit { amount } => {
+      VaultInstruction::Deposit { amount, referrer } => {
         msg!("Instruction: Deposit");
-        Self::process_transfer(program_id, accounts, amount, true)
+        Self::process_transfer(
+          program_id,
+          accounts,
+          amount,
+          true,
+          false,
+          referrer,
+          COption::None,
+        )
       }
-      VaultInstruction::Withdraw { amount } => {
+      VaultInstruction::Withdraw {
+        amount,
+        max_loss_bps,
+      } => {
         msg!("Instruction: Withdraw");
-        Self::process_transfer(program_id, accounts, amount, false)
+        Self::process_transfer(
+          program_id,
+          accounts,
+          amount,
+          false,
+          false,
+          COption::None,
+          max_loss_bps,
+        )
+      }
+      VaultInstruction::DepositNative { lamports } => {
+        msg!("Instruction: DepositNative");
+        Self::process_transfer(
+          program_id,
+          accounts,
+          lamports,
+          true,
+          true,
+          COption::None,
+          COption::None,
+        )
+      }
+      VaultInstruction::WithdrawNative { amount } => {
+        msg!("Instruction: WithdrawNative");
+        Self::process_transfer(
+          program_id,
+          accounts,
+          amount,
+          false,
+          true,
+          COption::None,
+          COption::None,
+        )
+      }
+      VaultInstruction::CreateVault {
+        nonce,
+        strategy_program_deposit_instruction,
+        strategy_program_withdraw_instruction,
+        hodl,
+        llx_decimals,
+        strategy_accounts,
+      } => {
+        msg!("Instruction: CreateVault");
+        Self::process_create_vault(
+          program_id,
+          accounts,
+          nonce,
+          hodl,
+          llx_decimals,
+          strategy_program_deposit_instruction,
+          strategy_program_withdraw_instruction,
+          strategy_accounts,
+        )
+      }
+      VaultInstruction::RegisterVault {
+        mint_registry_page,
+        strategy_registry_page,
+      } => {
+        msg!("Instruction: RegisterVault");
+        Self::process_register_vault(
+          program_id,
+          accounts,
+          mint_registry_page,
+          strategy_registry_page,
+        )
+      }
+      VaultInstruction::DeregisterVault => {
+        msg!("Instruction: DeregisterVault");
+        Self::process_deregister_vault(program_id, accounts)
+      }
+      VaultInstruction::CloseVault => {
+        msg!("Instruction: CloseVault");
+        Self::process_close_vault(program_id, accounts)
+      }
+      VaultInstruction::RollUp => {
+        msg!("Instruction: RollUp");
+        Self::process_roll_up(program_id, accounts)
+      }
+      VaultInstruction::SetFees {
+        management_fee_bps,
+        referral_fee_bps,
+        fee_recipients,
+      } => {
+        msg!("Instruction: SetFees");
+        Self::process_set_fees(
+          program_id,
+          accounts,
+          management_fee_bps,
+          referral_fee_bps,
+          fee_recipients,
+        )
+      }
+      VaultInstruction::CollectFees => {
+        msg!("Instruction: CollectFees");
+        Self::process_collect_fees(program_id, accounts)
+      }
+      VaultInstruction::CreateTrigger {
+        nonce,
+        condition,
+        action,
+        bounty_lamports,
+      } => {
+        msg!("Instruction: CreateTrigger");
+        Self::process_create_trigger(
+          program_id,
+          accounts,
+          nonce,
+          condition,
+          action,
+          bounty_lamports,
+        )
+      }
+      VaultInstruction::ExecuteTrigger => {
+        msg!("Instruction: ExecuteTrigger");
+        Self::process_execute_trigger(program_id, accounts)
+      }
+      VaultInstruction::SetPriceFeed { price_feed } => {
+        msg!("Instruction: SetPriceFeed");
+        Self::process_set_price_feed(program_id, accounts, price_feed)
+      }
+      VaultInstruction::SetMaxDrawdown { max_drawdown_bps } => {
+        msg!("Instruction: SetMaxDrawdown");
+        Self::process_set_max_drawdown(program_id, accounts, max_drawdown_bps)
+      }
+      VaultInstruction::EnableStrategy => {
+        msg!("Instruction: EnableStrategy");
+        Self::process_enable_strategy(program_id, accounts)
+      }
+      VaultInstruction::MigrateStrategy {
+        strategy_program_deposit_instruction,
+        strategy_program_withdraw_instruction,
+        strategy_accounts,
+      } => {
+        msg!("Instruction: MigrateStrategy");
+        Self::process_migrate_strategy(
+          program_id,
+          accounts,
+          strategy_program_deposit_instruction,
+          strategy_program_withdraw_instruction,
+          strategy_accounts,
+        )
+      }
+      VaultInstruction::Harvest { strategy } => {
+        msg!("Instruction: Harvest");
+        Self::process_harvest(program_id, accounts, strategy)
+      }
+      VaultInstruction::AddStrategy {
+        strategy_program_deposit_instruction,
+        strategy_program_withdraw_instruction,
+        debt_ratio_bps,
+        min_debt_per_harvest,
+        max_debt_per_harvest,
+        strategy_accounts,
+      } => {
+        msg!("Instruction: AddStrategy");
+        Self::process_add_strategy(
+          program_id,
+          accounts,
+          StrategyEntry {
+            strategy_program_id: Pubkey::default(),
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            strategy_accounts,
+            lx_token_account: Pubkey::default(),
+            debt_ratio_bps,
+            min_debt_per_harvest,
+            max_debt_per_harvest,
+            total_debt: 0,
+          },
+        )
+      }
+      VaultInstruction::UpdateStrategy {
+        strategy,
+        debt_ratio_bps,
+        min_debt_per_harvest,
+        max_debt_per_harvest,
+      } => {
+        msg!("Instruction: UpdateStrategy");
+        Self::process_update_strategy(
+          program_id,
+          accounts,
+          strategy,
+          debt_ratio_bps,
+          min_debt_per_harvest,
+          max_debt_per_harvest,
+        )
+      }
+      VaultInstruction::SetWithdrawalQueue { withdrawal_queue } => {
+        msg!("Instruction: SetWithdrawalQueue");
+        Self::process_set_withdrawal_queue(program_id, accounts, withdrawal_queue)
+      }
+    }
+  }
+
+  fn process_set_price_feed(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    price_feed: COption<PriceFeed>,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    if let COption::Some(price_feed) = price_feed {
+      // Catch oracles that aren't price accounts now, rather than on every roll-up.
+      for oracle in [price_feed.asset_oracle, price_feed.x_oracle].iter() {
+        let oracle_account = next_account_info(account_info_iter)?;
+        if oracle_account.key != oracle {
+          return Err(ProgramError::InvalidArgument);
+        }
+        oracle::Price::load(oracle_account)?;
+      }
+    }
+    storage_info.price_feed = price_feed;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_set_max_drawdown(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_drawdown_bps: u16,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    if max_drawdown_bps as u64 > math::MAX_BPS {
+      return Err(ProgramError::InvalidArgument);
+    }
+    // Like moving to hodl by trigger, breaching the limit needs an X token account.
+    if max_drawdown_bps != 0 && storage_info.x_token_account.is_none() {
+      let system_program = next_account_info(account_info_iter)?;
+      let rent_info = next_account_info(account_info_iter)?;
+      Self::create_x_token_account(
+        program_id,
+        governance,
+        storage_account,
+        &mut storage_info,
+        system_program,
+        rent_info,
+        account_info_iter,
+      )?;
+    }
+    storage_info.max_drawdown_bps = max_drawdown_bps;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_enable_strategy(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let vault_authority = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let x_token_account = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    // Only vaults moved to hodl have a strategy to go back to, and those lending X to strategies
+    // would have to take it all back first.
+    if !storage_info.hodl
+      || storage_info.strategy_accounts.is_empty()
+      || !storage_info.strategies.is_empty()
+    {
+      return Err(ProgramError::InvalidArgument);
+    }
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if !token::is_token_program(token_program.key)
+      || *strategy_program.key != storage_info.strategy_program_id
+    {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if *lx_token_account.key != storage_info.lx_token_account
+      || COption::Some(*x_token_account.key) != storage_info.x_token_account
+    {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &storage_info.strategy_accounts)?;
+
+    let x_held = token::unpack_account(x_token_account)?.amount;
+    msg!("Depositing {} X from hodl into strategy", x_held);
+    if x_held != 0 {
+      let authority_signature_seeds = [
+        pda::AUTHORITY_SEED,
+        storage_account.key.as_ref(),
+        &[bump_seed],
+      ];
+      Self::strategy_transfer(
+        strategy_program,
+        &storage_info.strategy_program_deposit_instruction,
+        x_held,
+        token_program,
+        x_token_account,
+        lx_token_account,
+        vault_authority,
+        storage_info.strategy_account_metas(),
+        &strategy_accounts,
+        &[&authority_signature_seeds],
+      )?;
+    }
+    storage_info.hodl = false;
+    // The share price is now quoted in lX again, so restart the high-water mark from the next
+    // roll-up rather than comparing against the price before the drawdown.
+    storage_info.high_water_share_price = 0;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_migrate_strategy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    strategy_program_deposit_instruction: InstructionTemplate,
+    strategy_program_withdraw_instruction: InstructionTemplate,
+    strategy_accounts: Vec<StrategyAccount>,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let registration_account = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    if *registration_account.key
+      != pda::find_registration_address(program_id, storage_account.key).0
+    {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    if !registration_account.data_is_empty() {
+      return Err(VaultError::VaultAlreadyRegistered.into());
+    }
+    if strategy_accounts.is_empty()
+      || !strategy_program_deposit_instruction.is_valid()
+      || !strategy_program_withdraw_instruction.is_valid()
+    {
+      return Err(ProgramError::InvalidInstructionData);
+    }
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if token::unpack_account(lx_token_account)?.owner != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if *lx_token_account.key == storage_info.lx_token_account
+      || COption::Some(*lx_token_account.key) == storage_info.x_token_account
+    {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let new_strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &strategy_accounts)?;
+    Self::check_strategy_accounts(
+      strategy_program,
+      &new_strategy_accounts[0],
+      &strategy_accounts,
+    )?;
+
+    // An invested vault moves everything to hodl, keeping the accounts to reinvest it with.
+    let invested = !storage_info.hodl;
+    let hodl_accounts = account_info_iter.as_slice();
+    if invested {
+      Self::move_to_hodl(
+        program_id,
+        account_info_iter,
+        storage_account.key,
+        &mut storage_info,
+      )?;
+    }
+    Self::check_investment_graph(
+      program_id,
+      storage_account.key,
+      strategy_program,
+      &new_strategy_accounts[0],
+      account_info_iter.as_slice(),
+    )?;
+
+    storage_info.strategy_program_id = *strategy_program.key;
+    storage_info.strategy_program_deposit_instruction = strategy_program_deposit_instruction;
+    storage_info.strategy_program_withdraw_instruction = strategy_program_withdraw_instruction;
+    storage_info.strategy_accounts = strategy_accounts;
+    storage_info.lx_token_account = *lx_token_account.key;
+    // The new strategy's holdings are valued afresh, so restart the high-water mark from the
+    // next roll-up and leave pricing them to governance.
+    storage_info.high_water_share_price = 0;
+    storage_info.total_assets_slot = 0;
+    storage_info.price_feed = COption::None;
+
+    if invested {
+      let (vault_authority, token_program, x_token_account) =
+        (&hodl_accounts[0], &hodl_accounts[1], &hodl_accounts[3]);
+      let x_held = token::unpack_account(x_token_account)?.amount;
+      msg!("Depositing {} X into the new strategy", x_held);
+      if x_held != 0 {
+        let authority_signature_seeds = [
+          pda::AUTHORITY_SEED,
+          storage_account.key.as_ref(),
+          &[bump_seed],
+        ];
+        Self::strategy_transfer(
+          strategy_program,
+          &storage_info.strategy_program_deposit_instruction,
+          x_held,
+          token_program,
+          x_token_account,
+          lx_token_account,
+          vault_authority,
+          storage_info.strategy_account_metas(),
+          &new_strategy_accounts,
+          &[&authority_signature_seeds],
+        )?;
+      }
+      storage_info.hodl = false;
+    }
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_create_trigger(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+    condition: TriggerCondition,
+    action: TriggerAction,
+    bounty_lamports: u64,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let trigger_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    // Harvests & moves to hodl act on the vault's strategy, and vaults investing through another
+    // vault have no harvest. Rebalances act on the strategies a hodl vault lends to.
+    let valid_action = match action {
+      TriggerAction::Harvest => {
+        !storage_info.hodl && storage_info.strategy_program_id != *program_id
+      }
+      TriggerAction::MoveToHodl => !storage_info.hodl,
+      TriggerAction::Rebalance => storage_info.hodl && !storage_info.strategies.is_empty(),
+    };
+    let valid_condition = match condition {
+      TriggerCondition::Drawdown { bps } => bps as u64 <= math::MAX_BPS,
+      _ => true,
+    };
+    if !valid_action || !valid_condition {
+      return Err(VaultError::InvalidTrigger.into());
+    }
+    let (address, bump_seed) = pda::find_trigger_address(program_id, storage_account.key, nonce);
+    if *trigger_account.key != address {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    Self::create_pda_account(
+      governance,
+      trigger_account,
+      system_program,
+      program_id,
+      Trigger::LEN,
+      rent,
+      &[
+        pda::TRIGGER_SEED,
+        storage_account.key.as_ref(),
+        &nonce.to_le_bytes(),
+        &[bump_seed],
+      ],
+    )?;
+
+    // Moving to hodl needs somewhere to hold X, which vaults created investing don't have.
+    if action == TriggerAction::MoveToHodl && storage_info.x_token_account.is_none() {
+      Self::create_x_token_account(
+        program_id,
+        governance,
+        storage_account,
+        &mut storage_info,
+        system_program,
+        rent_info,
+        account_info_iter,
+      )?;
+      Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+    }
+
+    let clock = Clock::get()?;
+    Trigger::pack(
+      Trigger {
+        is_initialized: true,
+        vault: *storage_account.key,
+        condition,
+        action,
+        bounty_lamports,
+        executed_slot: clock.slot,
+        executed_timestamp: clock.unix_timestamp,
+      },
+      &mut trigger_account.data.borrow_mut(),
+    )
+  }
+
+  fn process_execute_trigger(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let bounty_recipient = next_account_info(account_info_iter)?;
+    let trigger_account = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if trigger_account.owner != program_id || storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut trigger = Trigger::unpack(&trigger_account.data.borrow())?;
+    if trigger.vault != *storage_account.key {
+      return Err(VaultError::InvalidTrigger.into());
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    let clock = Clock::get()?;
+
+    let condition_met = match trigger.condition {
+      TriggerCondition::SlotsElapsed { slots } => {
+        clock.slot.saturating_sub(trigger.executed_slot) >= slots
+      }
+      TriggerCondition::TimeElapsed { seconds } => {
+        clock
+          .unix_timestamp
+          .saturating_sub(trigger.executed_timestamp)
+          .max(0) as u64
+          >= seconds
+      }
+      TriggerCondition::PriceBelow { oracle, price }
+      | TriggerCondition::PriceAbove { oracle, price } => {
+        let oracle_account = next_account_info(account_info_iter)?;
+        if *oracle_account.key != oracle {
+          return Err(VaultError::InvalidTrigger.into());
+        }
+        let current = oracle::Price::load_current(oracle_account, clock.slot)?.price;
+        msg!("Oracle price {} against {}", current, price);
+        match trigger.condition {
+          TriggerCondition::PriceBelow { .. } => current < price,
+          _ => current > price,
+        }
+      }
+      TriggerCondition::Drawdown { bps } => {
+        let llx_token_mint = next_account_info(account_info_iter)?;
+        Self::share_price_below_floor(&storage_info, llx_token_mint, clock.slot, bps)?
+      }
+    };
+    if !condition_met {
+      return Err(VaultError::TriggerConditionNotMet.into());
+    }
+
+    if trigger.action == TriggerAction::Rebalance {
+      if !storage_info.hodl {
+        return Err(VaultError::InvalidTrigger.into());
+      }
+    } else if storage_info.hodl {
+      // The roll-up a harvest is checked against may have just moved the vault to hodl, leaving
+      // nothing to harvest nor a bounty to pay.
+      if trigger.action == TriggerAction::Harvest
+        && storage_info.max_drawdown_bps != 0
+        && storage_info.total_assets_slot == clock.slot
+      {
+        msg!("Vault moved to hodl, skipping harvest");
+        return Ok(());
+      }
+      return Err(VaultError::InvalidTrigger.into());
+    }
+    match trigger.action {
+      TriggerAction::Harvest => Self::harvest(
+        program_id,
+        account_info_iter,
+        storage_account,
+        &mut storage_info,
+        clock.slot,
+      )?,
+      TriggerAction::MoveToHodl => {
+        Self::move_to_hodl(
+          program_id,
+          account_info_iter,
+          storage_account.key,
+          &mut storage_info,
+        )?;
+        Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+      }
+      TriggerAction::Rebalance => {
+        Self::rebalance(
+          program_id,
+          account_info_iter.as_slice(),
+          storage_account,
+          &mut storage_info,
+          clock.slot,
+        )?;
+        Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+      }
+    }
+
+    // The bounty is paid as far as the trigger holds lamports beyond its rent exemption.
+    let available = trigger_account
+      .lamports()
+      .saturating_sub(Rent::get()?.minimum_balance(Trigger::LEN));
+    let bounty = trigger.bounty_lamports.min(available);
+    if bounty != 0 {
+      msg!("Paying a bounty of {} lamports", bounty);
+      **trigger_account.lamports.borrow_mut() -= bounty;
+      **bounty_recipient.lamports.borrow_mut() = bounty_recipient
+        .lamports()
+        .checked_add(bounty)
+        .ok_or(VaultError::MathOverflow)?;
+    }
+    trigger.executed_slot = clock.slot;
+    trigger.executed_timestamp = clock.unix_timestamp;
+    Trigger::pack(trigger, &mut trigger_account.data.borrow_mut())
+  }
+
+  fn process_harvest(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    strategy: COption<u8>,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let storage_account = next_account_info(account_info_iter)?;
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    let slot = Clock::get()?.slot;
+    if let COption::Some(strategy) = strategy {
+      Self::harvest_debt(
+        program_id,
+        account_info_iter,
+        storage_account,
+        &mut storage_info,
+        strategy as usize,
+        slot,
+      )?;
+      return Vault::pack(storage_info, &mut storage_account.data.borrow_mut());
+    }
+    if storage_info.hodl {
+      // As for `ExecuteTrigger`, the roll-up a harvest is checked against may have just moved the
+      // vault to hodl.
+      if storage_info.max_drawdown_bps != 0 && storage_info.total_assets_slot == slot {
+        msg!("Vault moved to hodl, skipping harvest");
+        return Ok(());
+      }
+      return Err(ProgramError::InvalidArgument);
+    }
+    Self::harvest(
+      program_id,
+      account_info_iter,
+      storage_account,
+      &mut storage_info,
+      slot,
+    )
+  }
+
+  /// Marks the debt of the vault's `index`th strategy to the worth of its lX as peeked at in
+  /// `slot`, then lends the strategy X up to its debt ratio of the vault's total assets, or takes
+  /// back the X lent beyond it, taking the accounts `Harvest` expects for this.
+  fn harvest_debt<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    program_id: &Pubkey,
+    account_info_iter: &mut I,
+    storage_account: &AccountInfo<'a>,
+    storage_info: &mut Vault,
+    index: usize,
+    slot: u64,
+  ) -> ProgramResult
+  where
+    'a: 'b,
+  {
+    let vault_authority = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let x_token_account = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+
+    let mut strategy = storage_info
+      .strategies
+      .get(index)
+      .cloned()
+      .ok_or(ProgramError::InvalidArgument)?;
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if !token::is_token_program(token_program.key)
+      || *strategy_program.key != strategy.strategy_program_id
+    {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if COption::Some(*x_token_account.key) != storage_info.x_token_account
+      || *lx_token_account.key != strategy.lx_token_account
+    {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &strategy.strategy_accounts)?;
+    let peek_result_account = next_account_info(account_info_iter)?;
+    if peek_result_account.owner != strategy_program.key {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let peek_result = PeekResult::unpack(&peek_result_account.data.borrow())?;
+    if *strategy_accounts[0].key != peek_result.data_account
+      || peek_result.vault != *storage_account.key
+    {
+      return Err(VaultError::IncorrectStrategyAccount.into());
+    }
+    let lx_held = token::unpack_account(lx_token_account)?.amount;
+    if peek_result.slot != slot || peek_result.lx_amount != lx_held {
+      return Err(VaultError::StaleValuation.into());
+    }
+
+    msg!(
+      "Marking a debt of {} X to {} X",
+      strategy.total_debt,
+      peek_result.x_amount
+    );
+    strategy.total_debt = peek_result.x_amount;
+    storage_info.strategies[index].total_debt = strategy.total_debt;
+    let x_held = token::unpack_account(x_token_account)?.amount;
+    let total_assets = storage_info
+      .total_debt()
+      .and_then(|debt| debt.checked_add(x_held))
+      .ok_or(VaultError::MathOverflow)?;
+    let debt_limit =
+      math::bps_of(total_assets, strategy.debt_ratio_bps as u64).ok_or(VaultError::MathOverflow)?;
+    let authority_signature_seeds = [
+      pda::AUTHORITY_SEED,
+      storage_account.key.as_ref(),
+      &[bump_seed],
+    ];
+
+    if strategy.total_debt < debt_limit {
+      let credit = (debt_limit - strategy.total_debt)
+        .min(x_held)
+        .min(strategy.max_debt_per_harvest);
+      if credit != 0 && credit >= strategy.min_debt_per_harvest {
+        msg!("Lending {} X to the strategy", credit);
+        Self::strategy_transfer(
+          strategy_program,
+          &strategy.strategy_program_deposit_instruction,
+          credit,
+          token_program,
+          x_token_account,
+          lx_token_account,
+          vault_authority,
+          strategy.strategy_account_metas(),
+          &strategy_accounts,
+          &[&authority_signature_seeds],
+        )?;
+        strategy.total_debt = strategy
+          .total_debt
+          .checked_add(credit)
+          .ok_or(VaultError::MathOverflow)?;
+      }
+    } else if strategy.total_debt > debt_limit {
+      // Withdraw the share of the lX worth what's owed, writing off its share of the debt.
+      let outstanding = strategy.total_debt - debt_limit;
+      let lx_amount = math::assets_for_shares(outstanding, lx_held, strategy.total_debt)
+        .ok_or(VaultError::MathOverflow)?;
+      if lx_amount != 0 {
+        msg!("Taking back {} X lent for {} lX", outstanding, lx_amount);
+        Self::strategy_transfer(
+          strategy_program,
+          &strategy.strategy_program_withdraw_instruction,
+          lx_amount,
+          token_program,
+          lx_token_account,
+          x_token_account,
+          vault_authority,
+          strategy.strategy_account_metas(),
+          &strategy_accounts,
+          &[&authority_signature_seeds],
+        )?;
+        strategy.total_debt = math::assets_for_shares(lx_amount, strategy.total_debt, lx_held)
+          .and_then(|repaid| strategy.total_debt.checked_sub(repaid))
+          .ok_or(VaultError::MathOverflow)?;
       }
     }
+    storage_info.strategies[index].total_debt = strategy.total_debt;
+    // A hodl vault's roll-up is its X plus its debts, so keep one from this slot current.
+    if storage_info.total_assets_slot == slot {
+      storage_info.total_assets = storage_info
+        .total_debt()
+        .and_then(|debt| debt.checked_add(token::unpack_account(x_token_account).ok()?.amount))
+        .ok_or(VaultError::MathOverflow)?;
+    }
+    Ok(())
+  }
+
+  /// Harvests each strategy of a hodl vault toward its debt ratio, taking the accounts `Harvest`
+  /// expects for each strategy in turn. Strategies owed more than their ratio, as marked by their
+  /// peeks, are harvested first, so the X they repay can be lent to the others.
+  fn rebalance<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    storage_account: &AccountInfo<'a>,
+    storage_info: &mut Vault,
+    slot: u64,
+  ) -> ProgramResult {
+    let mut groups = Vec::with_capacity(storage_info.strategies.len());
+    let mut rest = accounts;
+    for strategy in storage_info.strategies.iter() {
+      // The vault authority, token program, X token account, strategy program & lX token
+      // account, then the strategy's accounts and its peek result.
+      let len = 5 + strategy.strategy_accounts.len() + 1;
+      if rest.len() < len {
+        return Err(ProgramError::NotEnoughAccountKeys);
+      }
+      let (group, remaining) = rest.split_at(len);
+      groups.push(group);
+      rest = remaining;
+    }
+
+    // Only the order depends on these marks, which each harvest checks before acting on them.
+    let marked_debts = groups
+      .iter()
+      .zip(storage_info.strategies.iter())
+      .map(|(group, strategy)| {
+        PeekResult::unpack(&group[group.len() - 1].data.borrow())
+          .map_or(strategy.total_debt, |peek_result| peek_result.x_amount)
+      })
+      .collect::<Vec<_>>();
+    let x_token_account = &groups.first().ok_or(VaultError::InvalidTrigger)?[2];
+    let x_held = token::unpack_account(x_token_account)?.amount;
+    let total_assets = marked_debts
+      .iter()
+      .try_fold(x_held, |total, debt| total.checked_add(*debt))
+      .ok_or(VaultError::MathOverflow)?;
+    let mut order = (0..groups.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| {
+      let debt_ratio_bps = storage_info.strategies[*index].debt_ratio_bps as u64;
+      marked_debts[*index] <= math::bps_of(total_assets, debt_ratio_bps).unwrap_or(u64::MAX)
+    });
+    for index in order {
+      Self::harvest_debt(
+        program_id,
+        &mut groups[index].iter(),
+        storage_account,
+        storage_info,
+        index,
+        slot,
+      )?;
+    }
+    Ok(())
+  }
+
+  fn process_add_strategy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mut strategy: StrategyEntry,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    // Only hodl vaults lend their X, which vaults below would have to be rolled up to value.
+    if !storage_info.hodl || storage_info.x_token_account.is_none() {
+      return Err(ProgramError::InvalidArgument);
+    }
+    if strategy_program.key == program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if storage_info.strategies.len() == MAX_STRATEGIES {
+      return Err(VaultError::TooManyStrategies.into());
+    }
+    if strategy.strategy_accounts.is_empty()
+      || !strategy.strategy_program_deposit_instruction.is_valid()
+      || !strategy.strategy_program_withdraw_instruction.is_valid()
+    {
+      return Err(ProgramError::InvalidInstructionData);
+    }
+    let (pda, _) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if token::unpack_account(lx_token_account)?.owner != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if *lx_token_account.key == storage_info.lx_token_account
+      || COption::Some(*lx_token_account.key) == storage_info.x_token_account
+      || storage_info
+        .strategies
+        .iter()
+        .any(|other| other.lx_token_account == *lx_token_account.key)
+    {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &strategy.strategy_accounts)?;
+    Self::check_strategy_accounts(
+      strategy_program,
+      &strategy_accounts[0],
+      &strategy.strategy_accounts,
+    )?;
+
+    strategy.strategy_program_id = *strategy_program.key;
+    strategy.lx_token_account = *lx_token_account.key;
+    strategy.total_debt = 0;
+    storage_info
+      .withdrawal_queue
+      .push(storage_info.strategies.len() as u8);
+    storage_info.strategies.push(strategy);
+    Self::check_debt_ratios(&storage_info)?;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_update_strategy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    strategy: u8,
+    debt_ratio_bps: u16,
+    min_debt_per_harvest: u64,
+    max_debt_per_harvest: u64,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    let entry = storage_info
+      .strategies
+      .get_mut(strategy as usize)
+      .ok_or(ProgramError::InvalidArgument)?;
+    entry.debt_ratio_bps = debt_ratio_bps;
+    entry.min_debt_per_harvest = min_debt_per_harvest;
+    entry.max_debt_per_harvest = max_debt_per_harvest;
+    Self::check_debt_ratios(&storage_info)?;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_set_withdrawal_queue(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    withdrawal_queue: Vec<u8>,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    for (position, &index) in withdrawal_queue.iter().enumerate() {
+      if index as usize >= storage_info.strategies.len()
+        || withdrawal_queue[..position].contains(&index)
+      {
+        return Err(ProgramError::InvalidArgument);
+      }
+    }
+    storage_info.withdrawal_queue = withdrawal_queue;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  /// Checks that a vault's strategies' debt ratios add up to at most the whole, each lending at
+  /// least as much per harvest as its minimum.
+  fn check_debt_ratios(storage_info: &Vault) -> ProgramResult {
+    let total_bps: u64 = storage_info
+      .strategies
+      .iter()
+      .map(|strategy| strategy.debt_ratio_bps as u64)
+      .sum();
+    if total_bps > math::MAX_BPS
+      || storage_info
+        .strategies
+        .iter()
+        .any(|strategy| strategy.min_debt_per_harvest > strategy.max_debt_per_harvest)
+    {
+      return Err(VaultError::InvalidDebtRatio.into());
+    }
+    Ok(())
+  }
+
+  fn process_set_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    management_fee_bps: u16,
+    referral_fee_bps: u16,
+    fee_recipients: Vec<FeeRecipient>,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    // Recipients must take the whole of the fees, and there must be some if there's a fee.
+    let split: u64 = fee_recipients.iter().map(|recipient| recipient.bps as u64).sum();
+    if management_fee_bps as u64 > math::MAX_BPS
+      || referral_fee_bps as u64 > math::MAX_BPS
+      || (!fee_recipients.is_empty() && split != math::MAX_BPS)
+      || (management_fee_bps != 0 && fee_recipients.is_empty())
+    {
+      return Err(VaultError::InvalidFeeConfig.into());
+    }
+    // Setting fees restarts their accrual, so what's accrued at the old rate must have been
+    // collected in the same slot.
+    let slot = Clock::get()?.slot;
+    if storage_info.management_fee_bps != 0 && storage_info.fees_collected_slot != slot {
+      return Err(VaultError::UncollectedFees.into());
+    }
+    // Fees are minted in llX, so each recipient must hold it.
+    for recipient in fee_recipients.iter() {
+      let recipient_account = next_account_info(account_info_iter)?;
+      if *recipient_account.key != recipient.token_account {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      if token::unpack_account(recipient_account)?.mint != storage_info.llx_token_mint_id {
+        return Err(VaultError::IncorrectMint.into());
+      }
+    }
+
+    storage_info.management_fee_bps = management_fee_bps;
+    storage_info.referral_fee_bps = referral_fee_bps;
+    storage_info.fee_recipients = fee_recipients;
+    storage_info.fees_collected_slot = slot;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_collect_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let storage_account = next_account_info(account_info_iter)?;
+    let vault_authority = next_account_info(account_info_iter)?;
+    let llx_token_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if *llx_token_mint.key != storage_info.llx_token_mint_id {
+      return Err(VaultError::IncorrectMint.into());
+    }
+    let token_programs = [token_program.clone()];
+    let llx_token_program = Self::token_program_for(llx_token_mint, &token_programs)?;
+
+    let slot = Clock::get()?.slot;
+    // Fees accrue from the first collection if they were never set.
+    let elapsed_slots = match storage_info.fees_collected_slot {
+      0 => 0,
+      collected_slot => slot.saturating_sub(collected_slot),
+    };
+    let share_supply = token::unpack_mint(llx_token_mint)?.supply;
+    let fee_shares =
+      math::management_fee_shares(share_supply, storage_info.management_fee_bps, elapsed_slots)
+        .ok_or(VaultError::MathOverflow)?;
+    msg!("Collecting {} llX in fees over {} slots", fee_shares, elapsed_slots);
+
+    let mut recipient_accounts = Vec::with_capacity(storage_info.fee_recipients.len());
+    for recipient in storage_info.fee_recipients.iter() {
+      let recipient_account = next_account_info(account_info_iter)?;
+      if *recipient_account.key != recipient.token_account {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      recipient_accounts.push(recipient_account);
+    }
+
+    // Settle the referrals passed first, so llX withdrawn or moved out of referred accounts since
+    // they were last settled no longer counts as referred in the split below.
+    let mut referrals = vec![];
+    let mut referrals_paid = vec![];
+    while let Ok(referral_account) = next_account_info(account_info_iter) {
+      let holder_account = next_account_info(account_info_iter)?;
+      let referrer_account = next_account_info(account_info_iter)?;
+      if referral_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+      }
+      let mut referral = Referral::unpack(&referral_account.data.borrow())?;
+      if referral.vault != *storage_account.key {
+        return Err(ProgramError::InvalidSeeds);
+      }
+      if referrals_paid.contains(referral_account.key) {
+        return Err(ProgramError::InvalidArgument);
+      }
+      referrals_paid.push(*referral_account.key);
+      let holder_token_account = token::unpack_account(holder_account)?;
+      if *holder_account.key != referral.llx_token_account
+        || holder_token_account.mint != storage_info.llx_token_mint_id
+      {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      let referrer_token_account = token::unpack_account(referrer_account)?;
+      if referrer_token_account.mint != storage_info.llx_token_mint_id
+        || referrer_token_account.owner != referral.referrer
+      {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      referral
+        .settle(&mut storage_info, holder_token_account.amount)
+        .ok_or(VaultError::MathOverflow)?;
+      referrals.push((
+        referral_account,
+        referrer_account,
+        referral,
+        holder_token_account.amount,
+      ));
+    }
+
+    // Referrers are owed their cut of the fees on the shares they referred first. It accrues to
+    // each referral through the vault's fee index, and is paid to the referrals passed.
+    let referral_shares = math::referral_fee_shares(
+      fee_shares,
+      storage_info.referred_shares,
+      share_supply,
+      storage_info.referral_fee_bps,
+    )
+    .ok_or(VaultError::MathOverflow)?
+    .min(fee_shares);
+    let recipients_shares = fee_shares - referral_shares;
+    storage_info.referral_fee_index =
+      math::fee_index_delta(referral_shares, storage_info.referred_shares)
+        .and_then(|delta| storage_info.referral_fee_index.checked_add(delta))
+        .ok_or(VaultError::MathOverflow)?;
+
+    let authority_signature_seeds = [
+      pda::AUTHORITY_SEED,
+      storage_account.key.as_ref(),
+      &[bump_seed],
+    ];
+    for (referral_account, referrer_account, mut referral, balance) in referrals {
+      referral
+        .settle(&mut storage_info, balance)
+        .ok_or(VaultError::MathOverflow)?;
+      if referral.accrued_fees != 0 {
+        msg!(
+          "Paying {} llX to referrer {}",
+          referral.accrued_fees,
+          referral.referrer
+        );
+        Self::token_mint_to(
+          llx_token_program,
+          llx_token_mint,
+          referrer_account,
+          vault_authority,
+          referral.accrued_fees,
+          storage_info.llx_decimals,
+          &[&authority_signature_seeds],
+        )?;
+        referral.accrued_fees = 0;
+      }
+      Referral::pack(referral, &mut referral_account.data.borrow_mut())?;
+    }
+
+    // Each recipient's cut rounds down, and the first takes the remainder.
+    let mut cuts = storage_info
+      .fee_recipients
+      .iter()
+      .map(|recipient| math::bps_of(recipients_shares, recipient.bps as u64))
+      .collect::<Option<Vec<_>>>()
+      .ok_or(VaultError::MathOverflow)?;
+    let remainder = recipients_shares - cuts.iter().sum::<u64>();
+    if let Some(first) = cuts.first_mut() {
+      *first += remainder;
+    }
+    for (recipient_account, cut) in recipient_accounts.into_iter().zip(cuts) {
+      if cut != 0 {
+        Self::token_mint_to(
+          llx_token_program,
+          llx_token_mint,
+          recipient_account,
+          vault_authority,
+          cut,
+          storage_info.llx_decimals,
+          &[&authority_signature_seeds],
+        )?;
+      }
+    }
+
+    storage_info.fees_collected_slot = slot;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  fn process_roll_up(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let slot = Clock::get()?.slot;
+
+    // Walk down the chain, then value it from the bottom up: each vault's lX is llX of the vault
+    // below, worth its share of that vault's total assets.
+    let mut chain = Vec::with_capacity(MAX_VAULT_DEPTH);
+    // The next vault down, and the mint of the lX the vault above holds, which must be its llX.
+    let mut expected_child = None;
+    let mut total_assets = loop {
+      let storage_account = next_account_info(account_info_iter)?;
+      let llx_token_mint = next_account_info(account_info_iter)?;
+      let assets_account = next_account_info(account_info_iter)?;
+      if storage_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+      }
+      if matches!(expected_child, Some((child, _)) if child != *storage_account.key) {
+        return Err(VaultError::IncorrectStrategyAccount.into());
+      }
+      if chain.len() == MAX_VAULT_DEPTH {
+        return Err(VaultError::InvestmentGraphTooDeep.into());
+      }
+      let storage_info = Vault::unpack(&storage_account.data.borrow())?;
+      if *llx_token_mint.key != storage_info.llx_token_mint_id
+        || matches!(expected_child, Some((_, lx_mint)) if lx_mint != *llx_token_mint.key)
+      {
+        return Err(VaultError::IncorrectMint.into());
+      }
+      let assets_account_id = if storage_info.hodl {
+        storage_info.x_token_account
+      } else {
+        COption::Some(storage_info.lx_token_account)
+      };
+      if COption::Some(*assets_account.key) != assets_account_id {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      let assets = token::unpack_account(assets_account)?;
+      let share_supply = token::unpack_mint(llx_token_mint)?.supply;
+      let child = storage_info.child_vault(program_id);
+      let hodl = storage_info.hodl;
+      // A hodl vault's X lent to strategies counts at the debt marked by their last harvest.
+      let hodl_assets = storage_info
+        .total_debt()
+        .and_then(|debt| debt.checked_add(assets.amount))
+        .ok_or(VaultError::MathOverflow)?;
+      let strategy_program_id = storage_info.strategy_program_id;
+      let data_account = storage_info
+        .strategy_accounts
+        .first()
+        .map(|account| account.pubkey);
+      chain.push((storage_account, storage_info, share_supply, assets.amount));
+
+      if hodl {
+        break hodl_assets;
+      }
+      match child {
+        Some(child) => expected_child = Some((child, assets.mint)),
+        None => {
+          let peek_result_account = next_account_info(account_info_iter)?;
+          if *peek_result_account.owner != strategy_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+          }
+          let peek_result = PeekResult::unpack(&peek_result_account.data.borrow())?;
+          // Only a valuation of this vault's own strategy instance counts.
+          if Some(peek_result.data_account) != data_account
+            || peek_result.vault != *storage_account.key
+          {
+            return Err(VaultError::IncorrectStrategyAccount.into());
+          }
+          if peek_result.slot != slot || peek_result.lx_amount != assets.amount {
+            return Err(VaultError::StaleValuation.into());
+          }
+          match chain.last().unwrap().1.price_feed {
+            COption::Some(price_feed) => {
+              let asset_oracle = next_account_info(account_info_iter)?;
+              let x_oracle = next_account_info(account_info_iter)?;
+              if *asset_oracle.key != price_feed.asset_oracle
+                || *x_oracle.key != price_feed.x_oracle
+              {
+                return Err(ProgramError::InvalidArgument);
+              }
+              let asset_price = oracle::Price::load_current(asset_oracle, slot)?;
+              let x_price = oracle::Price::load_current(x_oracle, slot)?;
+              let x_decimals = chain.last().unwrap().1.x_decimals;
+              msg!(
+                "Pricing {} of the strategy's asset at {}e{} against {}e{}",
+                peek_result.x_amount,
+                asset_price.price,
+                asset_price.expo,
+                x_price.price,
+                x_price.expo
+              );
+              break asset_price
+                .value_in(
+                  peek_result.x_amount,
+                  price_feed.asset_decimals,
+                  &x_price,
+                  x_decimals,
+                )
+                .ok_or(VaultError::MathOverflow)?;
+            }
+            COption::None => break peek_result.x_amount,
+          }
+        }
+      }
+    };
+
+    let mut child_supply = None;
+    for (depth, (storage_account, mut storage_info, share_supply, lx_held)) in
+      chain.into_iter().enumerate().rev()
+    {
+      if let Some(child_share_supply) = child_supply {
+        total_assets = math::assets_for_shares(lx_held, total_assets, child_share_supply)
+          .ok_or(VaultError::MathOverflow)?;
+      }
+      msg!("Rolled up {} X in {}", total_assets, storage_account.key);
+      storage_info.total_assets = total_assets;
+      storage_info.total_assets_slot = slot;
+      let share_price =
+        math::share_price(total_assets, share_supply).ok_or(VaultError::MathOverflow)?;
+      storage_info.high_water_share_price = storage_info.high_water_share_price.max(share_price);
+      // Only the vault rolled up for is derisked; those below it are by their own roll-ups.
+      if depth == 0 && !storage_info.hodl && storage_info.max_drawdown_bps != 0 {
+        let floor = storage_info
+          .share_price_floor(storage_info.max_drawdown_bps)
+          .ok_or(VaultError::MathOverflow)?;
+        if share_price < floor {
+          msg!("Share price {} breached a floor of {}", share_price, floor);
+          Self::move_to_hodl(
+            program_id,
+            account_info_iter,
+            storage_account.key,
+            &mut storage_info,
+          )?;
+        }
+      }
+      Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+      child_supply = Some(share_supply);
+    }
+    Ok(())
+  }
+
+  fn process_close_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let governance = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let vault_authority = next_account_info(account_info_iter)?;
+    let recipient = next_account_info(account_info_iter)?;
+    let llx_token_mint = next_account_info(account_info_iter)?;
+    let lx_token_mint = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+    let recipient_lx_token_account = next_account_info(account_info_iter)?;
+
+    if !governance.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    if *governance.key != storage_info.governance {
+      return Err(VaultError::InvalidGovernance.into());
+    }
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if *llx_token_mint.key != storage_info.llx_token_mint_id {
+      return Err(VaultError::IncorrectMint.into());
+    }
+    if token::unpack_mint(llx_token_mint)?.supply != 0
+      || storage_info.strategies.iter().any(|strategy| strategy.total_debt != 0)
+    {
+      return Err(VaultError::VaultNotEmpty.into());
+    }
+    if *lx_token_account.key != storage_info.lx_token_account {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let mut token_accounts = vec![(lx_token_account, lx_token_mint, recipient_lx_token_account)];
+    if let COption::Some(x_token_account_id) = storage_info.x_token_account {
+      let x_token_mint = next_account_info(account_info_iter)?;
+      let x_token_account = next_account_info(account_info_iter)?;
+      let recipient_x_token_account = next_account_info(account_info_iter)?;
+      if *x_token_mint.key != storage_info.x_token_mint_id {
+        return Err(VaultError::IncorrectMint.into());
+      }
+      if *x_token_account.key != x_token_account_id {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      token_accounts.push((x_token_account, x_token_mint, recipient_x_token_account));
+    }
+    let token_programs: Vec<AccountInfo> = account_info_iter.cloned().collect();
+
+    let authority_signature_seeds = [
+      pda::AUTHORITY_SEED,
+      storage_account.key.as_ref(),
+      &[bump_seed],
+    ];
+    for (token_account, mint, recipient_token_account) in token_accounts {
+      let token_program = Self::token_program_for(token_account, &token_programs)?;
+      let amount = token::unpack_account(token_account)?.amount;
+      // With no llX outstanding nobody has a claim on what's left, e.g. a donation, so it's
+      // swept to the recipient rather than keeping the vault open.
+      if amount != 0 {
+        msg!("Sweeping {} from {}", amount, token_account.key);
+        Self::token_transfer(
+          token_program,
+          token_account,
+          mint,
+          recipient_token_account,
+          vault_authority,
+          amount,
+          token::unpack_mint(mint)?.decimals,
+          &[&authority_signature_seeds],
+        )?;
+      }
+      msg!("Closing token account {}", token_account.key);
+      invoke_signed(
+        &token::close_account(token_program.key, token_account.key, recipient.key, &pda)?,
+        &[
+          token_account.clone(),
+          recipient.clone(),
+          vault_authority.clone(),
+          token_program.clone(),
+        ],
+        &[&authority_signature_seeds],
+      )?;
+    }
+
+    msg!("Closing vault storage");
+    storage_account.data.borrow_mut().fill(0);
+    let lamports = storage_account.lamports();
+    **storage_account.lamports.borrow_mut() = 0;
+    **recipient.lamports.borrow_mut() = recipient
+      .lamports()
+      .checked_add(lamports)
+      .ok_or(VaultError::MathOverflow)?;
+    Ok(())
+  }
+
+  fn process_register_vault(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint_registry_page: u32,
+    strategy_registry_page: u32,
+  ) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let mint_registry_account = next_account_info(account_info_iter)?;
+    let strategy_registry_account = next_account_info(account_info_iter)?;
+    let registration_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+    if !payer.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    // A vault's registration exists for as long as it's listed, on whichever pages.
+    let (registration, registration_bump_seed) =
+      pda::find_registration_address(program_id, storage_account.key);
+    if *registration_account.key != registration {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    if !registration_account.data_is_empty() {
+      return Err(VaultError::VaultAlreadyRegistered.into());
+    }
+
+    let registries = [
+      (
+        mint_registry_account,
+        RegistryKind::Mint,
+        storage_info.x_token_mint_id,
+        pda::MINT_REGISTRY_SEED,
+        mint_registry_page,
+      ),
+      (
+        strategy_registry_account,
+        RegistryKind::Strategy,
+        storage_info.strategy_program_id,
+        pda::STRATEGY_REGISTRY_SEED,
+        strategy_registry_page,
+      ),
+    ];
+    for (registry_account, kind, key, kind_seed, page) in registries.iter() {
+      let page_seed = page.to_le_bytes();
+      let (address, bump_seed) = Pubkey::find_program_address(
+        &[pda::REGISTRY_SEED, kind_seed, key.as_ref(), &page_seed],
+        program_id,
+      );
+      if *registry_account.key != address {
+        return Err(ProgramError::InvalidSeeds);
+      }
+      // Pages only take vaults once those before them are full, so filling one can't keep later
+      // vaults out of the registry.
+      if let Some(previous_page) = page.checked_sub(1) {
+        let previous_account = next_account_info(account_info_iter)?;
+        let (previous_address, _) = Pubkey::find_program_address(
+          &[
+            pda::REGISTRY_SEED,
+            kind_seed,
+            key.as_ref(),
+            &previous_page.to_le_bytes(),
+          ],
+          program_id,
+        );
+        if *previous_account.key != previous_address {
+          return Err(ProgramError::InvalidSeeds);
+        }
+        if previous_account.owner != program_id
+          || Registry::unpack(&previous_account.data.borrow())?
+            .vaults
+            .len()
+            < MAX_REGISTERED_VAULTS
+        {
+          return Err(ProgramError::InvalidArgument);
+        }
+      }
+      if registry_account.data_is_empty() {
+        msg!("Creating {:?} registry page {} for {}", kind, page, key);
+        Self::create_pda_account(
+          payer,
+          registry_account,
+          system_program,
+          program_id,
+          Registry::LEN,
+          rent,
+          &[
+            pda::REGISTRY_SEED,
+            kind_seed,
+            key.as_ref(),
+            &page_seed,
+            &[bump_seed],
+          ],
+        )?;
+      }
+      let mut registry = Registry::unpack_unchecked(&registry_account.data.borrow())?;
+      if !registry.is_initialized() {
+        registry = Registry {
+          is_initialized: true,
+          kind: *kind,
+          key: *key,
+          vaults: vec![],
+        };
+      }
+      if registry.vaults.contains(storage_account.key) {
+        return Err(VaultError::VaultAlreadyRegistered.into());
+      }
+      if registry.vaults.len() >= MAX_REGISTERED_VAULTS {
+        return Err(VaultError::RegistryFull.into());
+      }
+      registry.vaults.push(*storage_account.key);
+      Registry::pack(registry, &mut registry_account.data.borrow_mut())?;
+    }
+
+    Self::create_pda_account(
+      payer,
+      registration_account,
+      system_program,
+      program_id,
+      Registration::LEN,
+      rent,
+      &[
+        pda::REGISTRY_SEED,
+        pda::REGISTRATION_SEED,
+        storage_account.key.as_ref(),
+        &[registration_bump_seed],
+      ],
+    )?;
+    Registration::pack(
+      Registration {
+        is_initialized: true,
+        vault: *storage_account.key,
+        payer: *payer.key,
+        mint_registry_page,
+        strategy_registry_page,
+      },
+      &mut registration_account.data.borrow_mut(),
+    )
+  }
+
+  fn process_deregister_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let storage_account = next_account_info(account_info_iter)?;
+    let mint_registry_account = next_account_info(account_info_iter)?;
+    let strategy_registry_account = next_account_info(account_info_iter)?;
+    let registration_account = next_account_info(account_info_iter)?;
+    let registration_payer = next_account_info(account_info_iter)?;
+
+    // Only vaults which have been closed may be removed, so anyone can prune the registries.
+    if storage_account.owner == program_id
+      && Vault::unpack_unchecked(&storage_account.data.borrow())
+        .map(|vault| vault.is_initialized())
+        .unwrap_or(false)
+    {
+      return Err(VaultError::VaultStillActive.into());
+    }
+    if *registration_account.key
+      != pda::find_registration_address(program_id, storage_account.key).0
+    {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    if registration_account.owner != program_id {
+      return Err(VaultError::VaultNotRegistered.into());
+    }
+    let registration = Registration::unpack(&registration_account.data.borrow())?;
+    if *registration_payer.key != registration.payer {
+      return Err(ProgramError::InvalidArgument);
+    }
+    for (registry_account, kind, page) in [
+      (
+        mint_registry_account,
+        RegistryKind::Mint,
+        registration.mint_registry_page,
+      ),
+      (
+        strategy_registry_account,
+        RegistryKind::Strategy,
+        registration.strategy_registry_page,
+      ),
+    ]
+    .iter()
+    {
+      if registry_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+      }
+      let mut registry = Registry::unpack(&registry_account.data.borrow())?;
+      let (address, _) = match kind {
+        RegistryKind::Mint => pda::find_mint_registry_address(program_id, &registry.key, *page),
+        RegistryKind::Strategy => {
+          pda::find_strategy_registry_address(program_id, &registry.key, *page)
+        }
+      };
+      if registry.kind != *kind || *registry_account.key != address {
+        return Err(ProgramError::InvalidAccountData);
+      }
+      let position = registry
+        .vaults
+        .iter()
+        .position(|vault| vault == storage_account.key)
+        .ok_or(VaultError::VaultNotRegistered)?;
+      registry.vaults.remove(position);
+      Registry::pack(registry, &mut registry_account.data.borrow_mut())?;
+    }
+
+    msg!("Closing registration");
+    registration_account.data.borrow_mut().fill(0);
+    let lamports = registration_account.lamports();
+    **registration_account.lamports.borrow_mut() = 0;
+    **registration_payer.lamports.borrow_mut() = registration_payer
+      .lamports()
+      .checked_add(lamports)
+      .ok_or(VaultError::MathOverflow)?;
+    Ok(())
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn process_create_vault(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+    hodl: bool,
+    llx_decimals: u8,
+    strategy_program_deposit_instruction: InstructionTemplate,
+    strategy_program_withdraw_instruction: InstructionTemplate,
+    strategy_accounts: Vec<StrategyAccount>,
+  ) -> ProgramResult {
+    msg!("Creating vault");
+    let account_info_iter = &mut accounts.iter();
+
+    let payer = next_account_info(account_info_iter)?;
+    let storage_account = next_account_info(account_info_iter)?;
+    let llx_token_mint = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+    let lx_token_mint = next_account_info(account_info_iter)?;
+    let x_token_mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    if !payer.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let x_mint = token::unpack_mint(x_token_mint)?;
+    token::check_x_mint_extensions(x_token_mint)?;
+    token::unpack_mint(lx_token_mint)?;
+
+    let (vault, vault_bump_seed) = pda::find_vault_address(
+      program_id,
+      x_token_mint.key,
+      strategy_program.key,
+      nonce,
+    );
+    let (llx_mint, llx_mint_bump_seed) = pda::find_llx_mint_address(program_id, &vault);
+    let (lx_account, lx_account_bump_seed) = pda::find_lx_token_address(program_id, &vault);
+    if *storage_account.key != vault
+      || *llx_token_mint.key != llx_mint
+      || *lx_token_account.key != lx_account
+    {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    // Vaults investing through a strategy also route deposits through their X token account.
+    let x_token_account = next_account_info(account_info_iter)?;
+    let (x_account, x_account_bump_seed) = pda::find_x_token_address(program_id, &vault);
+    if *x_token_account.key != x_account {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    if !hodl {
+      let strategy_data_account = next_account_info(account_info_iter)?;
+      Self::check_strategy_accounts(strategy_program, strategy_data_account, &strategy_accounts)?;
+      Self::check_investment_graph(
+        program_id,
+        &vault,
+        strategy_program,
+        strategy_data_account,
+        account_info_iter.as_slice(),
+      )?;
+    }
+    if hodl && !strategy_accounts.is_empty() {
+      return Err(VaultError::IncorrectStrategyAccount.into());
+    }
+    // Any remaining accounts are token programs, for when X, lX & llX don't share one, or the
+    // vaults below a vault investing through another.
+    let mut token_programs = vec![token_program.clone()];
+    token_programs.extend(account_info_iter.cloned());
+    let (authority, _bump_seed) = pda::find_vault_authority_address(program_id, &vault);
+
+    msg!("Creating vault storage account");
+    let nonce_bytes = nonce.to_le_bytes();
+    Self::create_pda_account(
+      payer,
+      storage_account,
+      system_program,
+      program_id,
+      Vault::LEN,
+      rent,
+      &[
+        pda::VAULT_SEED,
+        x_token_mint.key.as_ref(),
+        strategy_program.key.as_ref(),
+        &nonce_bytes,
+        &[vault_bump_seed],
+      ],
+    )?;
+
+    msg!("Creating llX mint");
+    Self::create_pda_account(
+      payer,
+      llx_token_mint,
+      system_program,
+      token_program.key,
+      Mint::LEN,
+      rent,
+      &[pda::LLX_MINT_SEED, vault.as_ref(), &[llx_mint_bump_seed]],
+    )?;
+    invoke(
+      &token::initialize_mint(token_program.key, llx_token_mint.key, &authority, llx_decimals)?,
+      &[llx_token_mint.clone(), rent_info.clone(), token_program.clone()],
+    )?;
+
+    msg!("Creating lX token account");
+    Self::create_token_account(
+      payer,
+      lx_token_account,
+      lx_token_mint,
+      Self::token_program_for(lx_token_mint, &token_programs)?,
+      system_program,
+      rent_info,
+      &authority,
+      &[pda::LX_TOKEN_ACCOUNT_SEED, vault.as_ref(), &[lx_account_bump_seed]],
+    )?;
+
+    let mut storage_info = Vault::unpack_unchecked(&storage_account.data.borrow())?;
+    storage_info.is_initialized = true;
+    storage_info.hodl = hodl;
+    storage_info.llx_token_mint_id = llx_mint;
+    storage_info.llx_decimals = llx_decimals;
+    storage_info.lx_token_account = lx_account;
+    storage_info.x_token_mint_id = *x_token_mint.key;
+    storage_info.x_decimals = x_mint.decimals;
+    storage_info.governance = *payer.key;
+    storage_info.strategy_program_id = *strategy_program.key;
+    storage_info.strategy_accounts = strategy_accounts;
+    storage_info.strategy_program_deposit_instruction = strategy_program_deposit_instruction;
+    storage_info.strategy_program_withdraw_instruction = strategy_program_withdraw_instruction;
+    msg!("Creating X token account");
+    Self::create_token_account(
+      payer,
+      x_token_account,
+      x_token_mint,
+      Self::token_program_for(x_token_mint, &token_programs)?,
+      system_program,
+      rent_info,
+      &authority,
+      &[pda::X_TOKEN_ACCOUNT_SEED, vault.as_ref(), &[x_account_bump_seed]],
+    )?;
+    storage_info.x_token_account = COption::Some(x_account);
+
+    // Write the info to the actual account.
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+    Ok(())
   }
 
   fn process_initialize_vault(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     hodl: bool,
-    strategy_program_deposit_instruction_id: u8,
-    strategy_program_withdraw_instruction_id: u8,
+    strategy_program_deposit_instruction: InstructionTemplate,
+    strategy_program_withdraw_instruction: InstructionTemplate,
+    strategy_accounts: Vec<StrategyAccount>,
   ) -> ProgramResult {
     msg!("Initializing vault");
     let account_info_iter = &mut accounts.iter();
@@ -66,13 +1954,20 @@ impl Processor {
     let storage_account = next_account_info(account_info_iter)?;
     let lx_token_account = next_account_info(account_info_iter)?;
     let llx_token_mint_id = next_account_info(account_info_iter)?;
+    let x_token_mint_id = next_account_info(account_info_iter)?;
     let token_program = next_account_info(account_info_iter)?;
     let strategy_program = next_account_info(account_info_iter)?;
     let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-    if *lx_token_account.owner != spl_token::id() || *llx_token_mint_id.owner != spl_token::id() {
+    if !token::is_token_program(token_program.key) {
       return Err(ProgramError::IncorrectProgramId);
     }
+    // Authority changes have no checked variant, so make sure the lX account is a real token
+    // account before handing it over.
+    token::unpack_account(lx_token_account)?;
+    let x_mint = token::unpack_mint(x_token_mint_id)?;
+    token::check_x_mint_extensions(x_token_mint_id)?;
+    let llx_mint = token::unpack_mint(llx_token_mint_id)?;
 
     if !rent.is_exempt(storage_account.lamports(), storage_account.data_len()) {
       return Err(VaultError::NotRentExempt.into());
@@ -82,100 +1977,101 @@ impl Processor {
     if storage_info.is_initialized() {
       return Err(ProgramError::AccountAlreadyInitialized);
     }
+    let x_token_account = next_account_info(account_info_iter)?;
+    if !hodl {
+      let strategy_data_account = next_account_info(account_info_iter)?;
+      Self::check_strategy_accounts(strategy_program, strategy_data_account, &strategy_accounts)?;
+      Self::check_investment_graph(
+        program_id,
+        storage_account.key,
+        strategy_program,
+        strategy_data_account,
+        account_info_iter.as_slice(),
+      )?;
+    }
+    if hodl && !strategy_accounts.is_empty() {
+      return Err(VaultError::IncorrectStrategyAccount.into());
+    }
+    storage_info.strategy_accounts = strategy_accounts;
+    // Any remaining accounts are token programs, for when X, lX & llX don't share one, or the
+    // vaults below a vault investing through another.
+    let mut token_programs = vec![token_program.clone()];
+    token_programs.extend(account_info_iter.cloned());
 
     storage_info.is_initialized = true;
     storage_info.hodl = hodl;
     storage_info.llx_token_mint_id = *llx_token_mint_id.key;
+    storage_info.llx_decimals = llx_mint.decimals;
+    storage_info.lx_token_account = *lx_token_account.key;
+    storage_info.governance = *initializer.key;
+    storage_info.x_token_mint_id = *x_token_mint_id.key;
+    storage_info.x_decimals = x_mint.decimals;
+    // Transfer ownership of the vault's accounts to this program via a derived address.
+    let (pda, _bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
     msg!("Setting auth");
-    if hodl {
-      msg!("Transferring program X token ownership");
-      let x_token_account = next_account_info(account_info_iter)?;
-      storage_info.x_token_account = COption::Some(*x_token_account.key);
-      // Transfer ownership of the temp account to this program via a derived address.
-      let (pda, _bump_seed) = Pubkey::find_program_address(&[b"vault"], program_id);
-      let account_owner_change_ix = spl_token::instruction::set_authority(
-        token_program.key,
-        x_token_account.key,
-        Some(&pda),
-        spl_token::instruction::AuthorityType::AccountOwner,
-        initializer.key,
-        &[&initializer.key],
-      )?;
-      invoke(
-        &account_owner_change_ix,
-        &[
-          x_token_account.clone(),
-          initializer.clone(),
-          token_program.clone(),
-        ],
-      )?;
+    msg!("Transferring program X token ownership");
+    if token::unpack_account(x_token_account)?.mint != *x_token_mint_id.key {
+      return Err(VaultError::IncorrectMint.into());
     }
+    storage_info.x_token_account = COption::Some(*x_token_account.key);
+    Self::token_set_authority(
+      Self::token_program_for(x_token_account, &token_programs)?,
+      x_token_account,
+      initializer,
+      &pda,
+      AuthorityType::AccountOwner,
+    )?;
     storage_info.strategy_program_id = *strategy_program.key;
-    storage_info.strategy_program_deposit_instruction_id = strategy_program_deposit_instruction_id;
-    storage_info.strategy_program_withdraw_instruction_id =
-      strategy_program_withdraw_instruction_id;
+    storage_info.strategy_program_deposit_instruction = strategy_program_deposit_instruction;
+    storage_info.strategy_program_withdraw_instruction = strategy_program_withdraw_instruction;
 
     // Write the info to the actual account.
     Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
 
-    // Transfer ownership of the temp account to this program via a derived address.
-    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"vault"], program_id);
-    let account_owner_change_ix = spl_token::instruction::set_authority(
-      token_program.key,
-      lx_token_account.key,
-      Some(&pda),
-      spl_token::instruction::AuthorityType::AccountOwner,
-      initializer.key,
-      &[&initializer.key],
-    )?;
-
+    let lx_token_program = Self::token_program_for(lx_token_account, &token_programs)?;
     msg!("Calling the token program to transfer X vault token account ownership");
     msg!(
       "Token program: {}. Transferring ownership {} -> {}",
-      token_program.key,
+      lx_token_program.key,
       initializer.key,
       pda
     );
-    invoke(
-      &account_owner_change_ix,
-      &[
-        lx_token_account.clone(),
-        initializer.clone(),
-        token_program.clone(),
-      ],
-    )?;
-    let mint_owner_change_ix = spl_token::instruction::set_authority(
-      token_program.key,
-      llx_token_mint_id.key,
-      Some(&pda),
-      spl_token::instruction::AuthorityType::MintTokens,
-      initializer.key,
-      &[&initializer.key],
+    Self::token_set_authority(
+      lx_token_program,
+      lx_token_account,
+      initializer,
+      &pda,
+      AuthorityType::AccountOwner,
     )?;
 
+    let llx_token_program = Self::token_program_for(llx_token_mint_id, &token_programs)?;
     msg!("Calling the token program to transfer llX token mint authority");
     msg!(
       "Token program: {}. Transferring minting control {} -> {}",
-      token_program.key,
+      llx_token_program.key,
       initializer.key,
       pda
     );
-    invoke(
-      &mint_owner_change_ix,
-      &[
-        llx_token_mint_id.clone(),
-        initializer.clone(),
-        token_program.clone(),
-      ],
+    Self::token_set_authority(
+      llx_token_program,
+      llx_token_mint_id,
+      initializer,
+      &pda,
+      AuthorityType::MintTokens,
     )?;
     Ok(())
   }
 
+  /// Deposits or withdraws from a vault. When `native` is set, the vault's X token is wrapped SOL
+  /// and deposits take lamports while withdrawals unwrap back to lamports.
   fn process_transfer(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
     is_deposit: bool,
+    native: bool,
+    referrer: COption<Pubkey>,
+    max_loss_bps: COption<u16>,
   ) -> ProgramResult {
     msg!("Transferring");
     let account_info_iter = &mut accounts.iter();
@@ -187,77 +2083,1092 @@ impl Processor {
     // Additional account metas:
     let source_authority = next_account_info(account_info_iter)?;
     let storage_account = next_account_info(account_info_iter)?;
+    let vault_authority = next_account_info(account_info_iter)?;
+    let x_token_mint = next_account_info(account_info_iter)?;
+    let llx_token_mint = next_account_info(account_info_iter)?;
 
-    let storage_info = Vault::unpack_unchecked(&storage_account.data.borrow())?;
+    if !source_authority.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !token::is_token_program(token_program.key) || storage_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack_unchecked(&storage_account.data.borrow())?;
     if !storage_info.is_initialized() {
       msg!("Storage not configured!");
       return Err(VaultError::InvalidInstruction.into());
     }
-
-    // Charge fees
-    if is_deposit {
-      // TODO(001): implement.
-      msg!("Mint lX tokens to client account");
-    } else {
-      // TODO(002): implement.
-      msg!("Transfer & burn lX tokens from client");
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, storage_account.key);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if *x_token_mint.key != storage_info.x_token_mint_id
+      || *llx_token_mint.key != storage_info.llx_token_mint_id
+      || (native && *x_token_mint.key != native_mint::id())
+    {
+      return Err(VaultError::IncorrectMint.into());
     }
+    // Check a referral before moving anything, so a bad one fails for what it is.
+    let referral_account = match referrer {
+      COption::Some(referrer) => Some(Self::referral_account(
+        program_id,
+        storage_account,
+        target_token_account,
+        source_authority,
+        &referrer,
+        accounts,
+      )?),
+      COption::None => None,
+    };
 
-    // Check if this is a HODL Vault; if so, we deposit & withdraw from 
-    if storage_info.hodl {
+    // Check if this is a HODL Vault; if so, we deposit & withdraw from the vault's X account.
+    if !storage_info.hodl {
+      if native {
+        return Err(VaultError::NotImplemented.into());
+      }
+      let lx_token_account = next_account_info(account_info_iter)?;
       let x_token_account = next_account_info(account_info_iter)?;
-      msg!("Calling the token program to transfer tokens");
+      let strategy_program = next_account_info(account_info_iter)?;
+      if *lx_token_account.key != storage_info.lx_token_account
+        || COption::Some(*x_token_account.key) != storage_info.x_token_account
+      {
+        return Err(VaultError::IncorrectTokenAccount.into());
+      }
+      if *strategy_program.key != storage_info.strategy_program_id {
+        return Err(ProgramError::IncorrectProgramId);
+      }
+      let strategy_accounts =
+        Self::next_strategy_accounts(account_info_iter, &storage_info.strategy_accounts)?;
+      // The llX mint's token program may be among the strategy's accounts or follow them.
+      let mut token_programs = vec![token_program.clone()];
+      token_programs.extend(strategy_accounts.iter().cloned());
+      token_programs.extend(account_info_iter.cloned());
+      let x_token_program = Self::token_program_for(x_token_mint, &token_programs)?;
+      let llx_token_program = Self::token_program_for(llx_token_mint, &token_programs)?;
+
+      // Shares are priced in lX, which the strategy appreciates against X.
+      let lx_held = token::unpack_account(lx_token_account)?.amount;
+      let share_supply = token::unpack_mint(llx_token_mint)?.supply;
+      let rolled_up = storage_info.total_assets_slot == Clock::get()?.slot && share_supply != 0;
+      let authority_signature_seeds = [
+        pda::AUTHORITY_SEED,
+        storage_account.key.as_ref(),
+        &[bump_seed],
+      ];
+
       if is_deposit {
-        let transfer_to_vault_ix = spl_token::instruction::transfer(
-          token_program.key,
-          source_token_account.key,
-          x_token_account.key,
-          &source_authority.key,
-          &[&source_authority.key],
+        // Take exactly `amount` into the vault first, so the strategy is only ever signed for by
+        // the vault and can't move anything else of the depositor's.
+        let x_held = token::unpack_account(x_token_account)?.amount;
+        Self::token_transfer(
+          x_token_program,
+          source_token_account,
+          x_token_mint,
+          x_token_account,
+          source_authority,
+          amount,
+          storage_info.x_decimals,
+          &[],
+        )?;
+        let x_received = token::unpack_account(x_token_account)?
+          .amount
+          .checked_sub(x_held)
+          .ok_or(VaultError::MathOverflow)?;
+        msg!("Depositing into strategy");
+        Self::strategy_transfer(
+          strategy_program,
+          &storage_info.strategy_program_deposit_instruction,
+          x_received,
+          token_program,
+          x_token_account,
+          lx_token_account,
+          vault_authority,
+          storage_info.strategy_account_metas(),
+          &strategy_accounts,
+          &[&authority_signature_seeds],
+        )?;
+        let received = token::unpack_account(lx_token_account)?
+          .amount
+          .checked_sub(lx_held)
+          .ok_or(VaultError::MathOverflow)?;
+        // The first deposit is priced in X, like hodl vaults, since lX may trade at any rate.
+        let mut shares = if share_supply == 0 || lx_held == 0 {
+          math::normalize_amount(x_received, storage_info.x_decimals, storage_info.llx_decimals)
+        } else {
+          math::shares_for_value(received, lx_held, share_supply)
+        }
+        .ok_or(VaultError::MathOverflow)?;
+        // After a roll-up in the same slot, the X paid in is also priced against the vault's
+        // valuation, so a strategy issuing lX below its worth can't mint more than the X's share.
+        if rolled_up {
+          shares = math::shares_for_value(x_received, storage_info.total_assets, share_supply)
+            .ok_or(VaultError::MathOverflow)?
+            .min(shares);
+          storage_info.total_assets = math::assets_for_shares(
+            shares,
+            storage_info.total_assets,
+            share_supply,
+          )
+          .and_then(|value| storage_info.total_assets.checked_add(value))
+          .ok_or(VaultError::MathOverflow)?;
+          // Keeping the roll-up current for the transfers after this one.
+          Vault::pack(storage_info.clone(), &mut storage_account.data.borrow_mut())?;
+        }
+        msg!("Deposited {} X for {} lX and {} llX", x_received, received, shares);
+        Self::check_deposit_shares(shares)?;
+
+        Self::token_mint_to(
+          llx_token_program,
+          llx_token_mint,
+          target_token_account,
+          vault_authority,
+          shares,
+          storage_info.llx_decimals,
+          &[&authority_signature_seeds],
+        )?;
+        if let (COption::Some(referrer), Some(referral_account)) = (referrer, referral_account) {
+          Self::credit_referral(
+            program_id,
+            storage_account,
+            referral_account,
+            target_token_account,
+            source_authority,
+            &referrer,
+            accounts,
+            x_received,
+            shares,
+          )?;
+        }
+      } else {
+        let lx_amount = math::assets_for_shares(amount, lx_held, share_supply)
+          .ok_or(VaultError::MathOverflow)?;
+        msg!("Withdrawing {} lX from strategy for {} llX", lx_amount, amount);
+        let loss_check = Self::withdrawal_loss_check(
+          &storage_info,
+          max_loss_bps,
+          amount,
+          share_supply,
+          target_token_account,
+        )?;
+
+        Self::token_burn(
+          llx_token_program,
+          source_token_account,
+          llx_token_mint,
+          source_authority,
           amount,
+          storage_info.llx_decimals,
+          &[],
         )?;
-        msg!("Depositing to hodl account");
+        Self::strategy_transfer(
+          strategy_program,
+          &storage_info.strategy_program_withdraw_instruction,
+          lx_amount,
+          token_program,
+          lx_token_account,
+          target_token_account,
+          vault_authority,
+          storage_info.strategy_account_metas(),
+          &strategy_accounts,
+          &[&authority_signature_seeds],
+        )?;
+        Self::check_withdrawal_loss(loss_check, target_token_account)?;
+        if rolled_up {
+          storage_info.total_assets = math::assets_for_shares(
+            amount,
+            storage_info.total_assets,
+            share_supply,
+          )
+          .and_then(|value| storage_info.total_assets.checked_sub(value))
+          .ok_or(VaultError::MathOverflow)?;
+          Vault::pack(storage_info, &mut storage_account.data.borrow_mut())?;
+        }
+      }
+      return Ok(());
+    }
+    let x_token_account = next_account_info(account_info_iter)?;
+    if COption::Some(*x_token_account.key) != storage_info.x_token_account {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let system_program = if native && is_deposit {
+      Some(next_account_info(account_info_iter)?)
+    } else {
+      None
+    };
+    // Withdrawals may take back X lent to the strategies in the withdrawal queue.
+    let mut queue_accounts = Vec::new();
+    if !is_deposit {
+      for &index in storage_info.withdrawal_queue.iter() {
+        let strategy = &storage_info.strategies[index as usize];
+        let strategy_program = next_account_info(account_info_iter)?;
+        let lx_token_account = next_account_info(account_info_iter)?;
+        if *strategy_program.key != strategy.strategy_program_id {
+          return Err(ProgramError::IncorrectProgramId);
+        }
+        if *lx_token_account.key != strategy.lx_token_account {
+          return Err(VaultError::IncorrectTokenAccount.into());
+        }
+        let strategy_accounts =
+          Self::next_strategy_accounts(account_info_iter, &strategy.strategy_accounts)?;
+        queue_accounts.push((index, strategy_program, lx_token_account, strategy_accounts));
+      }
+    }
+    // Any remaining accounts are token programs, for when X & llX don't share one, which may
+    // also be among the strategies' accounts.
+    let mut token_programs = vec![token_program.clone()];
+    for (_, _, _, strategy_accounts) in queue_accounts.iter() {
+      token_programs.extend(strategy_accounts.iter().cloned());
+    }
+    token_programs.extend(account_info_iter.cloned());
+    let x_token_program = Self::token_program_for(x_token_mint, &token_programs)?;
+    let llx_token_program = Self::token_program_for(llx_token_mint, &token_programs)?;
+
+    // Shares are priced against the X held and the X lent to strategies.
+    let x_held = token::unpack_account(x_token_account)?.amount;
+    let total_assets = storage_info
+      .total_debt()
+      .and_then(|debt| debt.checked_add(x_held))
+      .ok_or(VaultError::MathOverflow)?;
+    let share_supply = token::unpack_mint(llx_token_mint)?.supply;
+    let authority_signature_seeds = [
+      pda::AUTHORITY_SEED,
+      storage_account.key.as_ref(),
+      &[bump_seed],
+    ];
+
+    if is_deposit {
+      if let Some(system_program) = system_program {
         invoke(
-          &transfer_to_vault_ix,
+          &system_instruction::transfer(source_token_account.key, x_token_account.key, amount),
           &[
             source_token_account.clone(),
             x_token_account.clone(),
-            source_authority.clone(),
-            token_program.clone(),
+            system_program.clone(),
           ],
         )?;
+        Self::token_sync_native(x_token_program, x_token_account)?;
       } else {
-        let (pda, bump_seed) = Pubkey::find_program_address(&[b"vault"], program_id);
-        let transfer_to_client_ix = spl_token::instruction::transfer(
-          token_program.key,
-          x_token_account.key,
-          target_token_account.key,
-          &pda,
-          &[&pda],
+        Self::token_transfer(
+          x_token_program,
+          source_token_account,
+          x_token_mint,
+          x_token_account,
+          source_authority,
           amount,
+          storage_info.x_decimals,
+          &[],
+        )?;
+      }
+      // Price shares against what actually arrived, since Token-2022 transfer fees may withhold
+      // part of the amount sent.
+      let received = token::unpack_account(x_token_account)?
+        .amount
+        .checked_sub(x_held)
+        .ok_or(VaultError::MathOverflow)?;
+      let shares = math::shares_for_deposit(
+        received,
+        total_assets,
+        share_supply,
+        storage_info.x_decimals,
+        storage_info.llx_decimals,
+      )
+      .ok_or(VaultError::MathOverflow)?;
+      msg!("Deposited {} X to hodl account for {} llX", received, shares);
+      Self::check_deposit_shares(shares)?;
+
+      msg!("Mint llX tokens to client account");
+      Self::token_mint_to(
+        llx_token_program,
+        llx_token_mint,
+        target_token_account,
+        vault_authority,
+        shares,
+        storage_info.llx_decimals,
+        &[&authority_signature_seeds],
+      )?;
+      if let (COption::Some(referrer), Some(referral_account)) = (referrer, referral_account) {
+        Self::credit_referral(
+          program_id,
+          storage_account,
+          referral_account,
+          target_token_account,
+          source_authority,
+          &referrer,
+          accounts,
+          received,
+          shares,
+        )?;
+      }
+    } else {
+      let mut x_amount = math::assets_for_shares(amount, total_assets, share_supply)
+        .ok_or(VaultError::MathOverflow)?;
+      msg!("Withdrawing {} X from hodl account for {} llX", x_amount, amount);
+      let loss_check = Self::withdrawal_loss_check(
+        &storage_info,
+        max_loss_bps,
+        amount,
+        share_supply,
+        target_token_account,
+      )?;
+
+      msg!("Burn llX tokens from client");
+      Self::token_burn(
+        llx_token_program,
+        source_token_account,
+        llx_token_mint,
+        source_authority,
+        amount,
+        storage_info.llx_decimals,
+        &[],
+      )?;
+
+      if x_amount > x_held && !queue_accounts.is_empty() {
+        let loss = Self::withdraw_from_queue(
+          &mut storage_info,
+          &queue_accounts,
+          token_program,
+          x_token_account,
+          vault_authority,
+          &[&authority_signature_seeds],
+          x_amount,
         )?;
-        msg!("Withdrawing from hodl account");
-        invoke_signed(
-          &transfer_to_client_ix,
+        x_amount = x_amount.saturating_sub(loss);
+        Vault::pack(storage_info.clone(), &mut storage_account.data.borrow_mut())?;
+      }
+      Self::token_transfer(
+        x_token_program,
+        x_token_account,
+        x_token_mint,
+        target_token_account,
+        vault_authority,
+        x_amount,
+        storage_info.x_decimals,
+        &[&authority_signature_seeds],
+      )?;
+      Self::check_withdrawal_loss(loss_check, target_token_account)?;
+
+      if native {
+        msg!("Unwrapping SOL to client");
+        let close_ix = token::close_account(
+          x_token_program.key,
+          target_token_account.key,
+          source_authority.key,
+          source_authority.key,
+        )?;
+        invoke(
+          &close_ix,
           &[
-            x_token_account.clone(),
             target_token_account.clone(),
             source_authority.clone(),
-            token_program.clone(),
+            x_token_program.clone(),
           ],
-          &[&[&b"vault"[..], &[bump_seed]]],
         )?;
       }
     }
-    else {
-      if is_deposit {
-        // TODO(003): implement.
-        msg!("Depositing into strategy");
+    Ok(())
+  }
+
+  /// Takes back X lent to the strategies in the vault's withdrawal queue, in order, until
+  /// `x_token_account` holds `x_amount` less the losses realised on the way, writing off the debt
+  /// repaid. Returns those losses, which come out of the withdrawal.
+  fn withdraw_from_queue<'a>(
+    storage_info: &mut Vault,
+    queue_accounts: &[(u8, &AccountInfo<'a>, &AccountInfo<'a>, Vec<AccountInfo<'a>>)],
+    token_program: &AccountInfo<'a>,
+    x_token_account: &AccountInfo<'a>,
+    vault_authority: &AccountInfo<'a>,
+    signers_seeds: &[&[&[u8]]],
+    mut x_amount: u64,
+  ) -> Result<u64, ProgramError> {
+    let mut total_loss = 0u64;
+    for (index, strategy_program, lx_token_account, strategy_accounts) in queue_accounts.iter() {
+      let x_before = token::unpack_account(x_token_account)?.amount;
+      if x_amount <= x_before {
+        break;
+      }
+      let strategy = &storage_info.strategies[*index as usize];
+      let needed = (x_amount - x_before).min(strategy.total_debt);
+      let lx_held = token::unpack_account(lx_token_account)?.amount;
+      if needed == 0 || lx_held == 0 {
+        continue;
+      }
+      // Withdraw the share of the lX booked at what's needed, writing off its share of the debt.
+      let lx_amount = math::mul_div_ceil(needed, lx_held, strategy.total_debt)
+        .ok_or(VaultError::MathOverflow)?
+        .min(lx_held);
+      let repaid = if lx_amount == lx_held {
+        strategy.total_debt
       } else {
-        // TODO(003): implement.
-        msg!("Withdrawing from strategy");
+        math::mul_div(lx_amount, strategy.total_debt, lx_held).ok_or(VaultError::MathOverflow)?
+      };
+      msg!("Taking back {} X lent for {} lX", repaid, lx_amount);
+      Self::strategy_transfer(
+        strategy_program,
+        &strategy.strategy_program_withdraw_instruction,
+        lx_amount,
+        token_program,
+        lx_token_account,
+        x_token_account,
+        vault_authority,
+        strategy.strategy_account_metas(),
+        strategy_accounts,
+        signers_seeds,
+      )?;
+      let received = token::unpack_account(x_token_account)?
+        .amount
+        .checked_sub(x_before)
+        .ok_or(VaultError::MathOverflow)?;
+      let loss = repaid.saturating_sub(received);
+      if loss != 0 {
+        msg!("Realised a loss of {} X", loss);
+        x_amount = x_amount.saturating_sub(loss);
+        total_loss = total_loss
+          .checked_add(loss)
+          .ok_or(VaultError::MathOverflow)?;
+      }
+      storage_info.strategies[*index as usize].total_debt -= repaid;
+    }
+    Ok(total_loss)
+  }
+
+  /// Rejects a deposit minting no llX, as rounding would leave one too small for a share.
+  fn check_deposit_shares(shares: u64) -> ProgramResult {
+    if shares == 0 {
+      return Err(VaultError::DepositTooSmall.into());
+    }
+    Ok(())
+  }
+
+  /// The least X to accept for `shares` llX under `max_loss_bps`, valued against the vault's
+  /// roll-up in the same slot before they're burnt, and what `target_token_account` held then.
+  fn withdrawal_loss_check(
+    storage_info: &Vault,
+    max_loss_bps: COption<u16>,
+    shares: u64,
+    share_supply: u64,
+    target_token_account: &AccountInfo,
+  ) -> Result<Option<(u64, u64)>, ProgramError> {
+    let max_loss_bps = match max_loss_bps {
+      COption::Some(max_loss_bps) => max_loss_bps,
+      COption::None => return Ok(None),
+    };
+    if storage_info.total_assets_slot != Clock::get()?.slot {
+      return Err(VaultError::StaleValuation.into());
+    }
+    let value = math::assets_for_shares(shares, storage_info.total_assets, share_supply)
+      .and_then(|value| math::bps_of(value, math::MAX_BPS.saturating_sub(max_loss_bps as u64)))
+      .ok_or(VaultError::MathOverflow)?;
+    Ok(Some((
+      value,
+      token::unpack_account(target_token_account)?.amount,
+    )))
+  }
+
+  /// Fails a withdrawal if `target_token_account` received less than its `loss_check` accepts.
+  fn check_withdrawal_loss(
+    loss_check: Option<(u64, u64)>,
+    target_token_account: &AccountInfo,
+  ) -> ProgramResult {
+    if let Some((min_x_amount, x_held)) = loss_check {
+      let received = token::unpack_account(target_token_account)?
+        .amount
+        .checked_sub(x_held)
+        .ok_or(VaultError::MathOverflow)?;
+      if received < min_x_amount {
+        msg!("Received {} X, short of at least {}", received, min_x_amount);
+        return Err(VaultError::WithdrawalLossExceeded.into());
+      }
+    }
+    Ok(())
+  }
+
+  /// Finds the `Referral` of deposits into `llx_token_account` among `accounts`, checking that
+  /// `referrer` isn't the depositor nor the owner of the llX.
+  fn referral_account<'a, 'b>(
+    program_id: &Pubkey,
+    storage_account: &AccountInfo<'a>,
+    llx_token_account: &AccountInfo<'a>,
+    depositor: &AccountInfo<'a>,
+    referrer: &Pubkey,
+    accounts: &'b [AccountInfo<'a>],
+  ) -> Result<&'b AccountInfo<'a>, ProgramError> {
+    let holder = token::unpack_account(llx_token_account)?.owner;
+    if *referrer == *depositor.key || *referrer == holder {
+      return Err(VaultError::SelfReferral.into());
+    }
+    let (address, _) =
+      pda::find_referral_address(program_id, storage_account.key, llx_token_account.key);
+    accounts
+      .iter()
+      .find(|account| *account.key == address)
+      .ok_or(ProgramError::InvalidSeeds)
+  }
+
+  /// Credits `referrer` with a deposit of `principal` X for `shares` llX into
+  /// `llx_token_account`, creating its `Referral`, paid for by `payer`, on its first referred
+  /// deposit, or else settling the fees accrued on it first. The system program is found among
+  /// `accounts`.
+  #[allow(clippy::too_many_arguments)]
+  fn credit_referral<'a>(
+    program_id: &Pubkey,
+    storage_account: &AccountInfo<'a>,
+    referral_account: &AccountInfo<'a>,
+    llx_token_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    referrer: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    principal: u64,
+    shares: u64,
+  ) -> ProgramResult {
+    let (_, bump_seed) =
+      pda::find_referral_address(program_id, storage_account.key, llx_token_account.key);
+    if referral_account.data_is_empty() {
+      msg!("Creating referral for {}", llx_token_account.key);
+      let system_program = accounts
+        .iter()
+        .find(|account| *account.key == system_program::id())
+        .ok_or(ProgramError::IncorrectProgramId)?;
+      Self::create_pda_account(
+        payer,
+        referral_account,
+        system_program,
+        program_id,
+        Referral::LEN,
+        &Rent::get()?,
+        &[
+          pda::REFERRAL_SEED,
+          storage_account.key.as_ref(),
+          llx_token_account.key.as_ref(),
+          &[bump_seed],
+        ],
+      )?;
+    }
+    if referral_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut storage_info = Vault::unpack(&storage_account.data.borrow())?;
+    let mut referral = Referral::unpack_unchecked(&referral_account.data.borrow())?;
+    if !referral.is_initialized() {
+      referral = Referral {
+        is_initialized: true,
+        vault: *storage_account.key,
+        referrer: *referrer,
+        llx_token_account: *llx_token_account.key,
+        principal: 0,
+        shares: 0,
+        fee_index: storage_info.referral_fee_index,
+        accrued_fees: 0,
+      };
+    } else if referral.referrer != *referrer {
+      return Err(ProgramError::InvalidArgument);
+    }
+    // Settle against what the account held before the shares were minted to it.
+    let balance = token::unpack_account(llx_token_account)?
+      .amount
+      .saturating_sub(shares);
+    referral
+      .settle(&mut storage_info, balance)
+      .ok_or(VaultError::MathOverflow)?;
+    referral.principal = referral
+      .principal
+      .checked_add(principal)
+      .ok_or(VaultError::MathOverflow)?;
+    referral.shares = referral
+      .shares
+      .checked_add(shares)
+      .ok_or(VaultError::MathOverflow)?;
+    storage_info.referred_shares = storage_info
+      .referred_shares
+      .checked_add(shares)
+      .ok_or(VaultError::MathOverflow)?;
+    msg!("Referred {} X in total by {}", referral.principal, referrer);
+    Referral::pack(referral, &mut referral_account.data.borrow_mut())?;
+    Vault::pack(storage_info, &mut storage_account.data.borrow_mut())
+  }
+
+  /// Takes the accounts passed through to the strategy, which must be exactly those the vault
+  /// stores.
+  fn next_strategy_accounts<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    account_info_iter: &mut I,
+    expected_accounts: &[StrategyAccount],
+  ) -> Result<Vec<AccountInfo<'a>>, ProgramError>
+  where
+    'a: 'b,
+  {
+    let mut strategy_accounts = Vec::with_capacity(expected_accounts.len());
+    for expected in expected_accounts.iter() {
+      let account = next_account_info(account_info_iter)?;
+      if *account.key != expected.pubkey || (expected.is_writable && !account.is_writable) {
+        return Err(VaultError::IncorrectStrategyAccount.into());
       }
+      strategy_accounts.push(account.clone());
+    }
+    Ok(strategy_accounts)
+  }
+
+  /// Whether the vault's share price, rolled up in `slot`, is more than `bps` below its high-water
+  /// mark.
+  fn share_price_below_floor(
+    storage_info: &Vault,
+    llx_token_mint: &AccountInfo,
+    slot: u64,
+    bps: u16,
+  ) -> Result<bool, ProgramError> {
+    if *llx_token_mint.key != storage_info.llx_token_mint_id {
+      return Err(VaultError::IncorrectMint.into());
+    }
+    if storage_info.total_assets_slot != slot {
+      return Err(VaultError::StaleValuation.into());
+    }
+    let share_price = math::share_price(
+      storage_info.total_assets,
+      token::unpack_mint(llx_token_mint)?.supply,
+    )
+    .ok_or(VaultError::MathOverflow)?;
+    let floor = storage_info
+      .share_price_floor(bps)
+      .ok_or(VaultError::MathOverflow)?;
+    msg!("Share price {} against a floor of {}", share_price, floor);
+    Ok(share_price < floor)
+  }
+
+  /// Harvests the vault's strategy, or moves it to hodl instead once its drawdown limit is
+  /// breached, taking the accounts `Harvest` expects after the Vault storage account.
+  fn harvest<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    program_id: &Pubkey,
+    account_info_iter: &mut I,
+    storage_account: &AccountInfo<'a>,
+    storage_info: &mut Vault,
+    slot: u64,
+  ) -> ProgramResult
+  where
+    'a: 'b,
+  {
+    let strategy_program = next_account_info(account_info_iter)?;
+    if *strategy_program.key != storage_info.strategy_program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &storage_info.strategy_accounts)?;
+    // Vaults with a drawdown limit are checked against it as by `RollUp`, and moved to hodl
+    // instead of harvested once it's breached.
+    let breached = storage_info.max_drawdown_bps != 0 && {
+      let llx_token_mint = next_account_info(account_info_iter)?;
+      Self::share_price_below_floor(
+        storage_info,
+        llx_token_mint,
+        slot,
+        storage_info.max_drawdown_bps,
+      )?
+    };
+    if breached {
+      Self::move_to_hodl(
+        program_id,
+        account_info_iter,
+        storage_account.key,
+        storage_info,
+      )?;
+      Vault::pack(storage_info.clone(), &mut storage_account.data.borrow_mut())?;
+    } else {
+      let strategy_account_metas = storage_info.strategy_account_metas();
+      let (data_account, strategy_account_metas) = strategy_account_metas
+        .split_first()
+        .ok_or(VaultError::IncorrectStrategyAccount)?;
+      msg!("Harvesting strategy");
+      strategy_accounts.push(strategy_program.clone());
+      invoke(
+        &StrategyInstruction::harvest(
+          strategy_program.key,
+          &data_account.pubkey,
+          strategy_account_metas.to_vec(),
+        )?,
+        &strategy_accounts,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Withdraws all the vault's lX from its strategy into its X token account and moves it to
+  /// hodl, taking the accounts `ExecuteTrigger` expects for this.
+  fn move_to_hodl<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    program_id: &Pubkey,
+    account_info_iter: &mut I,
+    vault: &Pubkey,
+    storage_info: &mut Vault,
+  ) -> ProgramResult
+  where
+    'a: 'b,
+  {
+    let vault_authority = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let lx_token_account = next_account_info(account_info_iter)?;
+    let x_token_account = next_account_info(account_info_iter)?;
+    let strategy_program = next_account_info(account_info_iter)?;
+    let (pda, bump_seed) = pda::find_vault_authority_address(program_id, vault);
+    if *vault_authority.key != pda {
+      return Err(VaultError::InvalidAuthority.into());
+    }
+    if !token::is_token_program(token_program.key)
+      || *strategy_program.key != storage_info.strategy_program_id
+    {
+      return Err(ProgramError::IncorrectProgramId);
     }
+    if *lx_token_account.key != storage_info.lx_token_account
+      || COption::Some(*x_token_account.key) != storage_info.x_token_account
+    {
+      return Err(VaultError::IncorrectTokenAccount.into());
+    }
+    let strategy_accounts =
+      Self::next_strategy_accounts(account_info_iter, &storage_info.strategy_accounts)?;
+
+    let lx_held = token::unpack_account(lx_token_account)?.amount;
+    msg!("Withdrawing {} lX from strategy to hodl", lx_held);
+    if lx_held != 0 {
+      let authority_signature_seeds = [pda::AUTHORITY_SEED, vault.as_ref(), &[bump_seed]];
+      Self::strategy_transfer(
+        strategy_program,
+        &storage_info.strategy_program_withdraw_instruction,
+        lx_held,
+        token_program,
+        lx_token_account,
+        x_token_account,
+        vault_authority,
+        storage_info.strategy_account_metas(),
+        &strategy_accounts,
+        &[&authority_signature_seeds],
+      )?;
+    }
+    storage_info.hodl = true;
+    Ok(())
+  }
+
+  /// Creates the vault's derived X token account, paid for by `payer`, taking the X mint, the
+  /// account & the mint's token program.
+  fn create_x_token_account<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    storage_account: &AccountInfo<'a>,
+    storage_info: &mut Vault,
+    system_program: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    account_info_iter: &mut I,
+  ) -> ProgramResult
+  where
+    'a: 'b,
+  {
+    let x_token_mint = next_account_info(account_info_iter)?;
+    let x_token_account = next_account_info(account_info_iter)?;
+    let x_token_program = next_account_info(account_info_iter)?;
+    if *x_token_mint.key != storage_info.x_token_mint_id {
+      return Err(VaultError::IncorrectMint.into());
+    }
+    let (x_account, x_account_bump_seed) =
+      pda::find_x_token_address(program_id, storage_account.key);
+    if *x_token_account.key != x_account {
+      return Err(ProgramError::InvalidSeeds);
+    }
+    msg!("Creating X token account");
+    Self::create_token_account(
+      payer,
+      x_token_account,
+      x_token_mint,
+      Self::token_program_for(x_token_mint, std::slice::from_ref(x_token_program))?,
+      system_program,
+      rent_info,
+      &pda::find_vault_authority_address(program_id, storage_account.key).0,
+      &[
+        pda::X_TOKEN_ACCOUNT_SEED,
+        storage_account.key.as_ref(),
+        &[x_account_bump_seed],
+      ],
+    )?;
+    storage_info.x_token_account = COption::Some(x_account);
     Ok(())
   }
+
+  /// Invokes a strategy's deposit or withdraw instruction, moving `amount` from `source` to
+  /// `target` (see `StrategyInstruction`), with instruction data built from `instruction`.
+  #[allow(clippy::too_many_arguments)]
+  fn strategy_transfer<'a>(
+    strategy_program: &AccountInfo<'a>,
+    instruction: &InstructionTemplate,
+    amount: u64,
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    target: &AccountInfo<'a>,
+    source_authority: &AccountInfo<'a>,
+    strategy_account_metas: Vec<AccountMeta>,
+    strategy_accounts: &[AccountInfo<'a>],
+    signers_seeds: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let data = instruction.encode(amount);
+    let mut account_metas = vec![
+      AccountMeta::new_readonly(*token_program.key, false),
+      AccountMeta::new(*source.key, false),
+      AccountMeta::new(*target.key, false),
+      AccountMeta::new_readonly(*source_authority.key, true),
+    ];
+    account_metas.extend(strategy_account_metas);
+    let mut account_infos = vec![
+      token_program.clone(),
+      source.clone(),
+      target.clone(),
+      source_authority.clone(),
+    ];
+    account_infos.extend(strategy_accounts.iter().cloned());
+    account_infos.push(strategy_program.clone());
+    invoke_signed(
+      &Instruction {
+        program_id: *strategy_program.key,
+        accounts: account_metas,
+        data,
+      },
+      &account_infos,
+      signers_seeds,
+    )
+  }
+
+  /// Checks the accounts a vault will pass to its strategy, which start with the strategy
+  /// instance data account.
+  fn check_strategy_accounts(
+    strategy_program: &AccountInfo,
+    strategy_data_account: &AccountInfo,
+    strategy_accounts: &[StrategyAccount],
+  ) -> ProgramResult {
+    match strategy_accounts.first() {
+      Some(first)
+        if first.pubkey == *strategy_data_account.key
+          && first.is_writable
+          && strategy_data_account.owner == strategy_program.key =>
+      {
+        Ok(())
+      }
+      _ => Err(VaultError::IncorrectStrategyAccount.into()),
+    }
+  }
+
+  /// Checks that `vault` investing through `strategy_data_account` keeps the investment graph
+  /// acyclic and within `MAX_VAULT_DEPTH`. When the strategy is another vault of this program, the
+  /// chain of vaults below it must be among `accounts`, so it can be walked to its end.
+  fn check_investment_graph<'a, 'b>(
+    program_id: &Pubkey,
+    vault: &Pubkey,
+    strategy_program: &AccountInfo<'a>,
+    strategy_data_account: &'b AccountInfo<'a>,
+    accounts: &'b [AccountInfo<'a>],
+  ) -> ProgramResult {
+    if strategy_program.key != program_id {
+      return Ok(());
+    }
+    let mut chain = vec![*vault];
+    let mut child = strategy_data_account;
+    loop {
+      if chain.contains(child.key) {
+        return Err(VaultError::InvestmentCycle.into());
+      }
+      if chain.len() == MAX_VAULT_DEPTH {
+        return Err(VaultError::InvestmentGraphTooDeep.into());
+      }
+      chain.push(*child.key);
+      let grandchild = match Vault::unpack(&child.data.borrow())?.child_vault(program_id) {
+        Some(grandchild) => grandchild,
+        None => return Ok(()),
+      };
+      child = accounts
+        .iter()
+        .find(|account| *account.key == grandchild && account.owner == program_id)
+        .ok_or(VaultError::IncorrectStrategyAccount)?;
+    }
+  }
+
+  /// Creates & funds an account at the program derived address signed for by `signer_seeds`.
+  fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    owner: &Pubkey,
+    space: usize,
+    rent: &Rent,
+    signer_seeds: &[&[u8]],
+  ) -> ProgramResult {
+    invoke_signed(
+      &system_instruction::create_account(
+        payer.key,
+        new_account.key,
+        rent.minimum_balance(space),
+        space as u64,
+        owner,
+      ),
+      &[payer.clone(), new_account.clone(), system_program.clone()],
+      &[signer_seeds],
+    )
+  }
+
+  /// Creates a token account for `mint` owned by `owner` at the program derived address signed
+  /// for by `signer_seeds`, sized for the account extensions the mint requires.
+  #[allow(clippy::too_many_arguments)]
+  fn create_token_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    owner: &Pubkey,
+    signer_seeds: &[&[u8]],
+  ) -> ProgramResult {
+    Self::create_pda_account(
+      payer,
+      new_account,
+      system_program,
+      token_program.key,
+      token::account_len(&mint.data.borrow())?,
+      &Rent::from_account_info(rent_info)?,
+      signer_seeds,
+    )?;
+    invoke(
+      &token::initialize_account(token_program.key, new_account.key, mint.key, owner)?,
+      &[
+        new_account.clone(),
+        mint.clone(),
+        rent_info.clone(),
+        token_program.clone(),
+      ],
+    )
+  }
+
+  /// Finds the token program owning `account` among `token_programs`.
+  fn token_program_for<'a, 'b>(
+    account: &AccountInfo<'a>,
+    token_programs: &'b [AccountInfo<'a>],
+  ) -> Result<&'b AccountInfo<'a>, ProgramError> {
+    token_programs
+      .iter()
+      .find(|program| program.key == account.owner && token::is_token_program(program.key))
+      .ok_or(ProgramError::IncorrectProgramId)
+  }
+
+  /// Issues a token `SetAuthority` instruction signed by the current `owner`.
+  fn token_set_authority<'a>(
+    token_program: &AccountInfo<'a>,
+    owned: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    new_authority: &Pubkey,
+    authority_type: AuthorityType,
+  ) -> ProgramResult {
+    let ix = token::set_authority(
+      token_program.key,
+      owned.key,
+      Some(new_authority),
+      authority_type,
+      owner.key,
+    )?;
+    invoke(
+      &ix,
+      &[owned.clone(), owner.clone(), token_program.clone()],
+    )
+  }
+
+  /// Issues a token `TransferChecked` instruction.
+  #[allow(clippy::too_many_arguments)]
+  fn token_transfer<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    decimals: u8,
+    signers_seeds: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let ix = token::transfer_checked(
+      token_program.key,
+      source.key,
+      mint.key,
+      destination.key,
+      authority.key,
+      amount,
+      decimals,
+    )?;
+    invoke_signed(
+      &ix,
+      &[
+        source.clone(),
+        mint.clone(),
+        destination.clone(),
+        authority.clone(),
+        token_program.clone(),
+      ],
+      signers_seeds,
+    )
+  }
+
+  /// Issues a token `SyncNative` instruction.
+  fn token_sync_native<'a>(
+    token_program: &AccountInfo<'a>,
+    native_account: &AccountInfo<'a>,
+  ) -> ProgramResult {
+    let ix = token::sync_native(token_program.key, native_account.key)?;
+    invoke(&ix, &[native_account.clone(), token_program.clone()])
+  }
+
+  /// Issues a token `MintToChecked` instruction.
+  fn token_mint_to<'a>(
+    token_program: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    decimals: u8,
+    signers_seeds: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let ix = token::mint_to_checked(
+      token_program.key,
+      mint.key,
+      destination.key,
+      authority.key,
+      amount,
+      decimals,
+    )?;
+    invoke_signed(
+      &ix,
+      &[
+        mint.clone(),
+        destination.clone(),
+        authority.clone(),
+        token_program.clone(),
+      ],
+      signers_seeds,
+    )
+  }
+
+  /// Issues a token `BurnChecked` instruction.
+  fn token_burn<'a>(
+    token_program: &AccountInfo<'a>,
+    burn_account: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    amount: u64,
+    decimals: u8,
+    signers_seeds: &[&[&[u8]]],
+  ) -> ProgramResult {
+    let ix = token::burn_checked(
+      token_program.key,
+      burn_account.key,
+      mint.key,
+      authority.key,
+      amount,
+      decimals,
+    )?;
+    invoke_signed(
+      &ix,
+      &[
+        burn_account.clone(),
+        mint.clone(),
+        authority.clone(),
+        token_program.clone(),
+      ],
+      signers_seeds,
+    )
+  }
 }
@@ -0,0 +1,278 @@
+//! Off-chain simulation of vault accounting, for tuning fees before deploying.
+//!
+//! A [Scenario](struct.Scenario.html) is read from JSON and replayed against a model of a vault's
+//! X reserves & llX supply, priced with the same [math](../math/index.html) as the processor.
+//! Strategy returns & losses move the reserves directly. As on chain, the only fee is a yearly
+//! management fee, paid by minting llX to the fee recipients as `CollectFees` would at the end
+//! of each period, diluting holders rather than taking X out of the reserves. The program has no
+//! deposit, withdrawal or performance fees, so scenarios giving them are rejected.
+//!
+//! ```json
+//! {
+//!   "x_decimals": 6,
+//!   "llx_decimals": 6,
+//!   "period_slots": 216000,
+//!   "fees": { "management_bps": 200 },
+//!   "steps": [
+//!     { "deposit": { "user": "alice", "amount": 1000000 } },
+//!     { "yield_curve": { "bps": [10, 20, 30] } },
+//!     { "loss": { "bps": 500 } },
+//!     { "withdraw": { "user": "alice" } }
+//!   ]
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::math;
+
+#[derive(Error, Debug)]
+pub enum SimError {
+    #[error("Invalid scenario: {0}")]
+    InvalidScenario(#[from] serde_json::Error),
+    #[error("Fees may not exceed {} bps", math::MAX_BPS)]
+    InvalidFees,
+    #[error("Step {step}: {user} holds {held} llX, not {shares}")]
+    InsufficientShares {
+        step: usize,
+        user: String,
+        held: u64,
+        shares: u64,
+    },
+    #[error("Step {0}: loss exceeds the reserves")]
+    LossTooLarge(usize),
+    #[error("Step {0}: math overflow")]
+    MathOverflow(usize),
+}
+
+/// Fees charged by the simulated vault, as set by `SetFees`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeConfig {
+    /// The yearly management fee in basis points, minted in llX on the share supply.
+    pub management_bps: u16,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub x_decimals: u8,
+    pub llx_decimals: u8,
+    /// Slots in each period, a day's by default.
+    #[serde(default = "default_period_slots")]
+    pub period_slots: u64,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    pub steps: Vec<Step>,
+}
+
+fn default_period_slots() -> u64 {
+    math::SLOTS_PER_YEAR / 365
+}
+
+/// A scenario step. Yields & losses each last one period, after which management fees are
+/// collected.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// `user` deposits `amount` X.
+    Deposit { user: String, amount: u64 },
+    /// `user` redeems `shares` llX, or all of their shares if omitted.
+    Withdraw {
+        user: String,
+        #[serde(default)]
+        shares: Option<u64>,
+    },
+    /// The strategy returns `bps` of the reserves.
+    Yield { bps: u64 },
+    /// The strategy returns each of `bps` in turn, one period each.
+    YieldCurve { bps: Vec<u64> },
+    /// The strategy loses `bps` of the reserves.
+    Loss { bps: u64 },
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, SimError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// The vault after a step, or after each period of a yield curve.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepReport {
+    /// Index of the scenario step.
+    pub step: usize,
+    pub total_assets: u64,
+    pub share_supply: u64,
+    /// X per llX in whole tokens, or `None` while no shares are outstanding.
+    pub share_price: Option<f64>,
+    /// llX minted to the fee recipients during the step.
+    pub fee_shares: u64,
+    /// The X value of all the llX minted to the fee recipients so far.
+    pub fee_value: u64,
+    /// Each user's profit in X so far: the value of their shares, plus what they've withdrawn,
+    /// less what they've deposited.
+    pub user_pnl: BTreeMap<String, i128>,
+}
+
+#[derive(Default)]
+struct Holder {
+    shares: u64,
+    deposited: u64,
+    withdrawn: u64,
+}
+
+/// Replays `scenario`, reporting the vault after each step.
+pub fn run(scenario: &Scenario) -> Result<Vec<StepReport>, SimError> {
+    if scenario.fees.management_bps as u64 > math::MAX_BPS {
+        return Err(SimError::InvalidFees);
+    }
+    let mut simulation = Simulation {
+        scenario,
+        total_assets: 0,
+        share_supply: 0,
+        fee_shares: 0,
+        holders: BTreeMap::new(),
+    };
+    let mut reports = Vec::with_capacity(scenario.steps.len());
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let overflow = || SimError::MathOverflow(index);
+        match step {
+            Step::Deposit { user, amount } => {
+                simulation.deposit(user, *amount).ok_or_else(overflow)?;
+                reports.push(simulation.report(index, 0)?);
+            }
+            Step::Withdraw { user, shares } => {
+                let holder = simulation.holders.get(user);
+                let held = holder.map_or(0, |holder| holder.shares);
+                let shares = shares.unwrap_or(held);
+                if holder.is_none() || shares > held {
+                    return Err(SimError::InsufficientShares {
+                        step: index,
+                        user: user.clone(),
+                        held,
+                        shares,
+                    });
+                }
+                simulation.withdraw(user, shares).ok_or_else(overflow)?;
+                reports.push(simulation.report(index, 0)?);
+            }
+            Step::Yield { bps } => {
+                let fee_shares = simulation.accrue(*bps).ok_or_else(overflow)?;
+                reports.push(simulation.report(index, fee_shares)?);
+            }
+            Step::YieldCurve { bps } => {
+                for bps in bps {
+                    let fee_shares = simulation.accrue(*bps).ok_or_else(overflow)?;
+                    reports.push(simulation.report(index, fee_shares)?);
+                }
+            }
+            Step::Loss { bps } => {
+                if *bps > math::MAX_BPS {
+                    return Err(SimError::LossTooLarge(index));
+                }
+                let loss = math::bps_of(simulation.total_assets, *bps).ok_or_else(overflow)?;
+                simulation.total_assets -= loss;
+                let fee_shares = simulation.collect_fees().ok_or_else(overflow)?;
+                reports.push(simulation.report(index, fee_shares)?);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+struct Simulation<'a> {
+    scenario: &'a Scenario,
+    total_assets: u64,
+    share_supply: u64,
+    /// llX held by the fee recipients.
+    fee_shares: u64,
+    holders: BTreeMap<String, Holder>,
+}
+
+impl Simulation<'_> {
+    /// Deposits `amount` into the reserves.
+    fn deposit(&mut self, user: &str, amount: u64) -> Option<()> {
+        let shares = math::shares_for_deposit(
+            amount,
+            self.total_assets,
+            self.share_supply,
+            self.scenario.x_decimals,
+            self.scenario.llx_decimals,
+        )?;
+        self.total_assets = self.total_assets.checked_add(amount)?;
+        self.share_supply = self.share_supply.checked_add(shares)?;
+        let holder = self.holders.entry(user.to_string()).or_default();
+        holder.shares = holder.shares.checked_add(shares)?;
+        holder.deposited = holder.deposited.checked_add(amount)?;
+        Some(())
+    }
+
+    /// Redeems `shares` of `user`'s.
+    fn withdraw(&mut self, user: &str, shares: u64) -> Option<()> {
+        let x_amount = math::assets_for_shares(shares, self.total_assets, self.share_supply)?;
+        self.total_assets = self.total_assets.checked_sub(x_amount)?;
+        self.share_supply = self.share_supply.checked_sub(shares)?;
+        let holder = self.holders.get_mut(user)?;
+        holder.shares -= shares;
+        holder.withdrawn = holder.withdrawn.checked_add(x_amount)?;
+        Some(())
+    }
+
+    /// Grows the reserves by `bps` over a period, returning the llX minted in fees.
+    fn accrue(&mut self, bps: u64) -> Option<u64> {
+        let returns = math::bps_of(self.total_assets, bps)?;
+        self.total_assets = self.total_assets.checked_add(returns)?;
+        self.collect_fees()
+    }
+
+    /// Mints the management fee accrued over a period to the fee recipients, as `CollectFees`
+    /// does, returning the llX minted.
+    fn collect_fees(&mut self) -> Option<u64> {
+        let fee_shares = math::management_fee_shares(
+            self.share_supply,
+            self.scenario.fees.management_bps,
+            self.scenario.period_slots,
+        )?;
+        self.share_supply = self.share_supply.checked_add(fee_shares)?;
+        self.fee_shares = self.fee_shares.checked_add(fee_shares)?;
+        Some(fee_shares)
+    }
+
+    fn report(&self, step: usize, fee_shares: u64) -> Result<StepReport, SimError> {
+        let mut user_pnl = BTreeMap::new();
+        for (user, holder) in self.holders.iter() {
+            let value = math::assets_for_shares(holder.shares, self.total_assets, self.share_supply)
+                .ok_or(SimError::MathOverflow(step))?;
+            user_pnl.insert(
+                user.clone(),
+                value as i128 + holder.withdrawn as i128 - holder.deposited as i128,
+            );
+        }
+        let share_price = if self.share_supply == 0 {
+            None
+        } else {
+            Some(
+                (self.total_assets as f64 / 10f64.powi(self.scenario.x_decimals as i32))
+                    / (self.share_supply as f64 / 10f64.powi(self.scenario.llx_decimals as i32)),
+            )
+        };
+        Ok(StepReport {
+            step,
+            total_assets: self.total_assets,
+            share_supply: self.share_supply,
+            share_price,
+            fee_shares,
+            fee_value: math::assets_for_shares(
+                self.fee_shares,
+                self.total_assets,
+                self.share_supply,
+            )
+            .ok_or(SimError::MathOverflow(step))?,
+            user_pnl,
+        })
+    }
+}
@@ -1,12 +1,407 @@
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_enum::TryFromPrimitive;
 use solana_program::{
+    hash::hash,
+    instruction::AccountMeta,
     program_error::ProgramError,
     program_option::COption,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use std::convert::TryInto;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+use crate::math;
+
+/// Maximum number of accounts a vault passes through to its strategy.
+pub const MAX_STRATEGY_ACCOUNTS: usize = 16;
+
+/// An account a vault passes to its strategy on every deposit & withdrawal.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StrategyAccount {
+    pub pubkey: Pubkey,
+    pub is_writable: bool,
+}
+
+impl StrategyAccount {
+    pub fn new(pubkey: Pubkey, is_writable: bool) -> Self {
+        StrategyAccount {
+            pubkey,
+            is_writable,
+        }
+    }
+
+    pub fn account_meta(&self) -> AccountMeta {
+        AccountMeta {
+            pubkey: self.pubkey,
+            is_signer: false,
+            is_writable: self.is_writable,
+        }
+    }
+}
+
+impl From<AccountMeta> for StrategyAccount {
+    fn from(meta: AccountMeta) -> Self {
+        StrategyAccount::new(meta.pubkey, meta.is_writable)
+    }
+}
+
+impl Sealed for StrategyAccount {}
+
+impl Pack for StrategyAccount {
+    const LEN: usize = 32 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, StrategyAccount::LEN];
+        let (pubkey, is_writable) = array_refs![src, 32, 1];
+        let is_writable = match is_writable {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(StrategyAccount::new(Pubkey::new_from_array(*pubkey), is_writable))
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, StrategyAccount::LEN];
+        let (pubkey_dst, is_writable_dst) = mut_array_refs![dst, 32, 1];
+        pubkey_dst.copy_from_slice(self.pubkey.as_ref());
+        is_writable_dst[0] = self.is_writable as u8;
+    }
+}
+
+/// Maximum length of the fixed instruction data in an `InstructionTemplate`.
+pub const MAX_INSTRUCTION_PREFIX_LEN: usize = 16;
+
+/// How an `InstructionTemplate` encodes the amount transferred.
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum AmountEncoding {
+    /// Little-endian u64, as used by `StrategyInstruction`, SPL programs & Anchor (Borsh).
+    U64Le,
+    /// Big-endian u64.
+    U64Be,
+}
+
+/// Instruction data a vault sends to its strategy program to deposit or withdraw an amount,
+/// letting vaults call third-party programs directly instead of through a wrapper strategy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionTemplate {
+    /// Fixed instruction data, e.g. a one-byte tag or an Anchor discriminator, with any constant
+    /// arguments following the amount at its end.
+    pub prefix: Vec<u8>,
+    /// Where in `prefix` the encoded amount is inserted.
+    pub amount_offset: u8,
+    pub amount_encoding: AmountEncoding,
+}
+
+impl InstructionTemplate {
+    /// A one-byte tag followed by the amount, as for `StrategyInstruction`.
+    pub fn tag(tag: u8) -> Self {
+        InstructionTemplate {
+            prefix: vec![tag],
+            amount_offset: 1,
+            amount_encoding: AmountEncoding::U64Le,
+        }
+    }
+
+    /// An Anchor instruction named `name`, in snake case, taking the amount as its only argument.
+    pub fn anchor(name: &str) -> Self {
+        let discriminator = hash(format!("global:{}", name).as_bytes()).to_bytes();
+        InstructionTemplate {
+            prefix: discriminator[..8].to_vec(),
+            amount_offset: 8,
+            amount_encoding: AmountEncoding::U64Le,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.prefix.len() <= MAX_INSTRUCTION_PREFIX_LEN
+            && self.amount_offset as usize <= self.prefix.len()
+    }
+
+    /// Returns the instruction data transferring `amount`.
+    pub fn encode(&self, amount: u64) -> Vec<u8> {
+        let amount = match self.amount_encoding {
+            AmountEncoding::U64Le => amount.to_le_bytes(),
+            AmountEncoding::U64Be => amount.to_be_bytes(),
+        };
+        let (head, tail) = self.prefix.split_at(self.amount_offset as usize);
+        [head, &amount, tail].concat()
+    }
+}
+
+impl Default for InstructionTemplate {
+    fn default() -> Self {
+        InstructionTemplate {
+            prefix: vec![],
+            amount_offset: 0,
+            amount_encoding: AmountEncoding::U64Le,
+        }
+    }
+}
+
+impl Sealed for InstructionTemplate {}
+
+impl Pack for InstructionTemplate {
+    const LEN: usize = 1 + MAX_INSTRUCTION_PREFIX_LEN + 1 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, InstructionTemplate::LEN];
+        let (prefix_len, prefix, amount_offset, amount_encoding) =
+            array_refs![src, 1, MAX_INSTRUCTION_PREFIX_LEN, 1, 1];
+        let prefix_len = prefix_len[0] as usize;
+        if prefix_len > MAX_INSTRUCTION_PREFIX_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let template = InstructionTemplate {
+            prefix: prefix[..prefix_len].to_vec(),
+            amount_offset: amount_offset[0],
+            amount_encoding: AmountEncoding::try_from_primitive(amount_encoding[0])
+                .map_err(|_| ProgramError::InvalidAccountData)?,
+        };
+        if !template.is_valid() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(template)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, InstructionTemplate::LEN];
+        let (prefix_len_dst, prefix_dst, amount_offset_dst, amount_encoding_dst) =
+            mut_array_refs![dst, 1, MAX_INSTRUCTION_PREFIX_LEN, 1, 1];
+        prefix_len_dst[0] = self.prefix.len() as u8;
+        prefix_dst.fill(0);
+        prefix_dst[..self.prefix.len()].copy_from_slice(&self.prefix);
+        amount_offset_dst[0] = self.amount_offset;
+        amount_encoding_dst[0] = self.amount_encoding as u8;
+    }
+}
+
+/// Maximum number of accounts a vault's fees are split between.
+pub const MAX_FEE_RECIPIENTS: usize = 4;
+
+/// A token account receiving `bps` of a vault's fees, in llX. It may be another vault's X token
+/// account, when that vault holds this vault's llX, so the fees compound for its holders.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeRecipient {
+    pub token_account: Pubkey,
+    pub bps: u16,
+}
+
+impl FeeRecipient {
+    pub fn new(token_account: Pubkey, bps: u16) -> Self {
+        FeeRecipient { token_account, bps }
+    }
+}
+
+impl Sealed for FeeRecipient {}
+
+impl Pack for FeeRecipient {
+    const LEN: usize = 32 + 2;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, FeeRecipient::LEN];
+        let (token_account, bps) = array_refs![src, 32, 2];
+        Ok(FeeRecipient::new(
+            Pubkey::new_from_array(*token_account),
+            u16::from_le_bytes(*bps),
+        ))
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, FeeRecipient::LEN];
+        let (token_account_dst, bps_dst) = mut_array_refs![dst, 32, 2];
+        token_account_dst.copy_from_slice(self.token_account.as_ref());
+        *bps_dst = self.bps.to_le_bytes();
+    }
+}
+
+/// Oracles valuing what a strategy holds other than X, whose `PeekResult` then reports an amount
+/// of that asset rather than of X. Both oracles must price their token in the same currency.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceFeed {
+    /// Price account of the strategy's asset.
+    pub asset_oracle: Pubkey,
+    /// Price account of X.
+    pub x_oracle: Pubkey,
+    /// Decimals of the amounts of the asset reported.
+    pub asset_decimals: u8,
+}
+
+impl Sealed for PriceFeed {}
+
+impl Pack for PriceFeed {
+    const LEN: usize = 32 + 32 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PriceFeed::LEN];
+        let (asset_oracle, x_oracle, asset_decimals) = array_refs![src, 32, 32, 1];
+        Ok(PriceFeed {
+            asset_oracle: Pubkey::new_from_array(*asset_oracle),
+            x_oracle: Pubkey::new_from_array(*x_oracle),
+            asset_decimals: asset_decimals[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PriceFeed::LEN];
+        let (asset_oracle_dst, x_oracle_dst, asset_decimals_dst) = mut_array_refs![dst, 32, 32, 1];
+        asset_oracle_dst.copy_from_slice(self.asset_oracle.as_ref());
+        x_oracle_dst.copy_from_slice(self.x_oracle.as_ref());
+        asset_decimals_dst[0] = self.asset_decimals;
+    }
+}
+
+/// Maximum number of strategies a hodl vault lends its X to.
+pub const MAX_STRATEGIES: usize = 4;
+
+/// A strategy a hodl vault lends part of its X to, Yearn-v2 style: each `Harvest` of it moves X
+/// towards `debt_ratio_bps` of the vault's total assets.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StrategyEntry {
+    pub strategy_program_id: Pubkey,
+    pub strategy_program_deposit_instruction: InstructionTemplate,
+    pub strategy_program_withdraw_instruction: InstructionTemplate,
+    /// Accounts passed to the strategy after the source authority, starting with the strategy
+    /// instance data account, as for the vault's own strategy.
+    pub strategy_accounts: Vec<StrategyAccount>,
+    /// The vault's token account holding the strategy's lX, owned by the Vault's derived
+    /// authority.
+    pub lx_token_account: Pubkey,
+    /// Share of the vault's total assets to lend the strategy, in bps.
+    pub debt_ratio_bps: u16,
+    /// The least X a harvest lends the strategy at once, below which it lends none.
+    pub min_debt_per_harvest: u64,
+    /// The most X a harvest lends the strategy at once.
+    pub max_debt_per_harvest: u64,
+    /// The X lent to the strategy, valued at its lX's worth as of its last harvest.
+    pub total_debt: u64,
+}
+
+impl StrategyEntry {
+    /// Metas for the accounts passed through to the strategy.
+    pub fn strategy_account_metas(&self) -> Vec<AccountMeta> {
+        self.strategy_accounts
+            .iter()
+            .map(StrategyAccount::account_meta)
+            .collect()
+    }
+}
+
+impl Sealed for StrategyEntry {}
+
+impl Pack for StrategyEntry {
+    const LEN: usize = 32
+        + InstructionTemplate::LEN
+        + InstructionTemplate::LEN
+        + 1
+        + StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS
+        + 32
+        + 2
+        + 8
+        + 8
+        + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, StrategyEntry::LEN];
+        let (
+            strategy_program_id,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            strategy_account_count,
+            strategy_accounts,
+            lx_token_account,
+            debt_ratio_bps,
+            min_debt_per_harvest,
+            max_debt_per_harvest,
+            total_debt,
+        ) = array_refs![
+            src,
+            32,
+            InstructionTemplate::LEN,
+            InstructionTemplate::LEN,
+            1,
+            StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS,
+            32,
+            2,
+            8,
+            8,
+            8
+        ];
+        let strategy_account_count = strategy_account_count[0] as usize;
+        if strategy_account_count > MAX_STRATEGY_ACCOUNTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(StrategyEntry {
+            strategy_program_id: Pubkey::new_from_array(*strategy_program_id),
+            strategy_program_deposit_instruction: InstructionTemplate::unpack_from_slice(
+                strategy_program_deposit_instruction,
+            )?,
+            strategy_program_withdraw_instruction: InstructionTemplate::unpack_from_slice(
+                strategy_program_withdraw_instruction,
+            )?,
+            strategy_accounts: strategy_accounts
+                .chunks(StrategyAccount::LEN)
+                .take(strategy_account_count)
+                .map(StrategyAccount::unpack_from_slice)
+                .collect::<Result<Vec<_>, _>>()?,
+            lx_token_account: Pubkey::new_from_array(*lx_token_account),
+            debt_ratio_bps: u16::from_le_bytes(*debt_ratio_bps),
+            min_debt_per_harvest: u64::from_le_bytes(*min_debt_per_harvest),
+            max_debt_per_harvest: u64::from_le_bytes(*max_debt_per_harvest),
+            total_debt: u64::from_le_bytes(*total_debt),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, StrategyEntry::LEN];
+        let (
+            strategy_program_id_dst,
+            strategy_program_deposit_instruction_dst,
+            strategy_program_withdraw_instruction_dst,
+            strategy_account_count_dst,
+            strategy_accounts_dst,
+            lx_token_account_dst,
+            debt_ratio_bps_dst,
+            min_debt_per_harvest_dst,
+            max_debt_per_harvest_dst,
+            total_debt_dst,
+        ) = mut_array_refs![
+            dst,
+            32,
+            InstructionTemplate::LEN,
+            InstructionTemplate::LEN,
+            1,
+            StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS,
+            32,
+            2,
+            8,
+            8,
+            8
+        ];
+        strategy_program_id_dst.copy_from_slice(self.strategy_program_id.as_ref());
+        self.strategy_program_deposit_instruction
+            .pack_into_slice(strategy_program_deposit_instruction_dst);
+        self.strategy_program_withdraw_instruction
+            .pack_into_slice(strategy_program_withdraw_instruction_dst);
+        strategy_account_count_dst[0] = self.strategy_accounts.len() as u8;
+        strategy_accounts_dst.fill(0);
+        for (account, dst) in self
+            .strategy_accounts
+            .iter()
+            .zip(strategy_accounts_dst.chunks_mut(StrategyAccount::LEN))
+        {
+            account.pack_into_slice(dst);
+        }
+        lx_token_account_dst.copy_from_slice(self.lx_token_account.as_ref());
+        *debt_ratio_bps_dst = self.debt_ratio_bps.to_le_bytes();
+        *min_debt_per_harvest_dst = self.min_debt_per_harvest.to_le_bytes();
+        *max_debt_per_harvest_dst = self.max_debt_per_harvest.to_le_bytes();
+        *total_debt_dst = self.total_debt.to_le_bytes();
+    }
+}
+
+/// Maximum number of vaults in a chain of vaults investing through one another, counting the top.
+/// A deposit at the top invokes each vault below it and then the token program, which the
+/// runtime's cross-program invocation depth of 4 bounds.
+pub const MAX_VAULT_DEPTH: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Vault {
     pub is_initialized: bool,
     pub hodl: bool,
@@ -14,15 +409,83 @@ pub struct Vault {
     pub lx_token_account: Pubkey,
     pub x_token_account: COption<Pubkey>,
     pub strategy_program_id: Pubkey,
-    pub strategy_program_deposit_instruction_id: u8,
-    pub strategy_program_withdraw_instruction_id: u8,
-    pub strategy_data_account: COption<Pubkey>,
+    pub strategy_program_deposit_instruction: InstructionTemplate,
+    pub strategy_program_withdraw_instruction: InstructionTemplate,
+    /// Accounts passed to the strategy after the source authority, starting with the strategy
+    /// instance data account. Callers must pass exactly these, in order.
+    pub strategy_accounts: Vec<StrategyAccount>,
+    pub x_token_mint_id: Pubkey,
+    pub x_decimals: u8,
+    pub llx_decimals: u8,
+    pub governance: Pubkey,
+    /// The vault's holdings valued in X by the last `RollUp`, through every vault below it.
+    pub total_assets: u64,
+    /// The slot `total_assets` was rolled up in, or 0 if it never has been.
+    pub total_assets_slot: u64,
+    /// Yearly fee on the vault's holdings, taken by minting llX to `fee_recipients`.
+    pub management_fee_bps: u16,
+    /// Accounts the fees are split between, whose `bps` add up to the whole.
+    pub fee_recipients: Vec<FeeRecipient>,
+    /// The slot fees were last collected in, or 0 if they never have been.
+    pub fees_collected_slot: u64,
+    /// Share of the fees on referred deposits paid to their referrer (see `Referral`), before the
+    /// rest is split between `fee_recipients`.
+    pub referral_fee_bps: u16,
+    /// The highest share price rolled up, in X per llX scaled by `math::SHARE_PRICE_SCALE`.
+    pub high_water_share_price: u64,
+    /// Oracles `RollUp` values the strategy's holdings with, if it holds something other than X.
+    pub price_feed: COption<PriceFeed>,
+    /// How far the share price may fall below `high_water_share_price`, in bps, before `RollUp`
+    /// moves the vault to hodl, or 0 for no limit.
+    pub max_drawdown_bps: u16,
+    /// The llX held by referred token accounts as of their referral's last settlement, which
+    /// referrers are owed `referral_fee_bps` of the fees on (see `Referral`).
+    pub referred_shares: u64,
+    /// The llX owed to referrers per referred llX since the vault was created, scaled by
+    /// `math::FEE_INDEX_SCALE`.
+    pub referral_fee_index: u128,
+    /// Strategies a hodl vault lends its X to, which count towards its total assets at their
+    /// `total_debt`.
+    pub strategies: Vec<StrategyEntry>,
+    /// Indices into `strategies`, in the order withdrawals take back X lent to them once the
+    /// vault's own X runs short.
+    pub withdrawal_queue: Vec<u8>,
 }
 
 impl Sealed for Vault {}
 
 impl Pack for Vault {
-    const LEN: usize = 1 + 1 + 32 + 32 + 36 + 32 + 1 + 1 + 36;
+    const LEN: usize = 1
+        + 1
+        + 32
+        + 32
+        + 36
+        + 32
+        + InstructionTemplate::LEN
+        + InstructionTemplate::LEN
+        + 1
+        + StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS
+        + 32
+        + 1
+        + 1
+        + 32
+        + 8
+        + 8
+        + 2
+        + 1
+        + FeeRecipient::LEN * MAX_FEE_RECIPIENTS
+        + 8
+        + 2
+        + 8
+        + 4
+        + PriceFeed::LEN
+        + 2
+        + 8
+        + 16
+        + 1
+        + StrategyEntry::LEN * MAX_STRATEGIES
+        + 1
+        + MAX_STRATEGIES;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Vault::LEN];
         let (
@@ -32,10 +495,63 @@ impl Pack for Vault {
             lx_token_account,
             x_token_account,
             strategy_program_id,
-            strategy_program_deposit_instruction_id,
-            strategy_program_withdraw_instruction_id,
-            strategy_data_account,
-        ) = array_refs![src, 1, 1, 32, 32, 36, 32, 1, 1, 36];
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            strategy_account_count,
+            strategy_accounts,
+            x_token_mint_id,
+            x_decimals,
+            llx_decimals,
+            governance,
+            total_assets,
+            total_assets_slot,
+            management_fee_bps,
+            fee_recipient_count,
+            fee_recipients,
+            fees_collected_slot,
+            referral_fee_bps,
+            high_water_share_price,
+            price_feed,
+            max_drawdown_bps,
+            referred_shares,
+            referral_fee_index,
+            strategy_count,
+            strategies,
+            withdrawal_queue_len,
+            withdrawal_queue,
+        ) = array_refs![
+            src,
+            1,
+            1,
+            32,
+            32,
+            36,
+            32,
+            InstructionTemplate::LEN,
+            InstructionTemplate::LEN,
+            1,
+            StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS,
+            32,
+            1,
+            1,
+            32,
+            8,
+            8,
+            2,
+            1,
+            FeeRecipient::LEN * MAX_FEE_RECIPIENTS,
+            8,
+            2,
+            8,
+            4 + PriceFeed::LEN,
+            2,
+            8,
+            16,
+            1,
+            StrategyEntry::LEN * MAX_STRATEGIES,
+            1,
+            MAX_STRATEGIES
+        ];
 
         let hodl = match hodl {
             [0] => false,
@@ -48,8 +564,45 @@ impl Pack for Vault {
             _ => return Err(ProgramError::InvalidAccountData),
         };
 
-        let strategy_data_account = unpack_coption_key(strategy_data_account)?;
+        let strategy_account_count = strategy_account_count[0] as usize;
+        if strategy_account_count > MAX_STRATEGY_ACCOUNTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let strategy_accounts = strategy_accounts
+            .chunks(StrategyAccount::LEN)
+            .take(strategy_account_count)
+            .map(StrategyAccount::unpack_from_slice)
+            .collect::<Result<Vec<_>, _>>()?;
+        let fee_recipient_count = fee_recipient_count[0] as usize;
+        if fee_recipient_count > MAX_FEE_RECIPIENTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let fee_recipients = fee_recipients
+            .chunks(FeeRecipient::LEN)
+            .take(fee_recipient_count)
+            .map(FeeRecipient::unpack_from_slice)
+            .collect::<Result<Vec<_>, _>>()?;
         let x_token_account = unpack_coption_key(x_token_account)?;
+        let (price_feed_tag, price_feed) = array_refs![price_feed, 4, PriceFeed::LEN];
+        let price_feed = match *price_feed_tag {
+            [0, 0, 0, 0] => COption::None,
+            [1, 0, 0, 0] => COption::Some(PriceFeed::unpack_from_slice(price_feed)?),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let strategy_count = strategy_count[0] as usize;
+        if strategy_count > MAX_STRATEGIES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let strategies = strategies
+            .chunks(StrategyEntry::LEN)
+            .take(strategy_count)
+            .map(StrategyEntry::unpack_from_slice)
+            .collect::<Result<Vec<_>, _>>()?;
+        let withdrawal_queue_len = withdrawal_queue_len[0] as usize;
+        if withdrawal_queue_len > MAX_STRATEGIES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let withdrawal_queue = withdrawal_queue[..withdrawal_queue_len].to_vec();
         Ok(Vault {
             is_initialized,
             hodl,
@@ -57,9 +610,30 @@ impl Pack for Vault {
             lx_token_account: Pubkey::new_from_array(*lx_token_account),
             x_token_account,
             strategy_program_id: Pubkey::new_from_array(*strategy_program_id),
-            strategy_program_deposit_instruction_id: strategy_program_deposit_instruction_id[0],
-            strategy_program_withdraw_instruction_id: strategy_program_withdraw_instruction_id[0],
-            strategy_data_account,
+            strategy_program_deposit_instruction: InstructionTemplate::unpack_from_slice(
+                strategy_program_deposit_instruction,
+            )?,
+            strategy_program_withdraw_instruction: InstructionTemplate::unpack_from_slice(
+                strategy_program_withdraw_instruction,
+            )?,
+            strategy_accounts,
+            x_token_mint_id: Pubkey::new_from_array(*x_token_mint_id),
+            x_decimals: x_decimals[0],
+            llx_decimals: llx_decimals[0],
+            governance: Pubkey::new_from_array(*governance),
+            total_assets: u64::from_le_bytes(*total_assets),
+            total_assets_slot: u64::from_le_bytes(*total_assets_slot),
+            management_fee_bps: u16::from_le_bytes(*management_fee_bps),
+            fee_recipients,
+            fees_collected_slot: u64::from_le_bytes(*fees_collected_slot),
+            referral_fee_bps: u16::from_le_bytes(*referral_fee_bps),
+            high_water_share_price: u64::from_le_bytes(*high_water_share_price),
+            price_feed,
+            max_drawdown_bps: u16::from_le_bytes(*max_drawdown_bps),
+            referred_shares: u64::from_le_bytes(*referred_shares),
+            referral_fee_index: u128::from_le_bytes(*referral_fee_index),
+            strategies,
+            withdrawal_queue,
         })
     }
 
@@ -72,10 +646,63 @@ impl Pack for Vault {
             lx_token_account_dst,
             x_token_account_dst,
             strategy_program_id_dst,
-            strategy_program_deposit_instruction_id_dst,
-            strategy_program_withdraw_instruction_id_dst,
-            strategy_data_account_dst,
-        ) = mut_array_refs![dst, 1, 1, 32, 32, 36, 32, 1, 1, 36];
+            strategy_program_deposit_instruction_dst,
+            strategy_program_withdraw_instruction_dst,
+            strategy_account_count_dst,
+            strategy_accounts_dst,
+            x_token_mint_id_dst,
+            x_decimals_dst,
+            llx_decimals_dst,
+            governance_dst,
+            total_assets_dst,
+            total_assets_slot_dst,
+            management_fee_bps_dst,
+            fee_recipient_count_dst,
+            fee_recipients_dst,
+            fees_collected_slot_dst,
+            referral_fee_bps_dst,
+            high_water_share_price_dst,
+            price_feed_dst,
+            max_drawdown_bps_dst,
+            referred_shares_dst,
+            referral_fee_index_dst,
+            strategy_count_dst,
+            strategies_dst,
+            withdrawal_queue_len_dst,
+            withdrawal_queue_dst,
+        ) = mut_array_refs![
+            dst,
+            1,
+            1,
+            32,
+            32,
+            36,
+            32,
+            InstructionTemplate::LEN,
+            InstructionTemplate::LEN,
+            1,
+            StrategyAccount::LEN * MAX_STRATEGY_ACCOUNTS,
+            32,
+            1,
+            1,
+            32,
+            8,
+            8,
+            2,
+            1,
+            FeeRecipient::LEN * MAX_FEE_RECIPIENTS,
+            8,
+            2,
+            8,
+            4 + PriceFeed::LEN,
+            2,
+            8,
+            16,
+            1,
+            StrategyEntry::LEN * MAX_STRATEGIES,
+            1,
+            MAX_STRATEGIES
+        ];
 
         let Vault {
             is_initialized,
@@ -84,9 +711,26 @@ impl Pack for Vault {
             lx_token_account,
             x_token_account,
             strategy_program_id,
-            strategy_program_deposit_instruction_id,
-            strategy_program_withdraw_instruction_id,
-            strategy_data_account,
+            strategy_program_deposit_instruction,
+            strategy_program_withdraw_instruction,
+            strategy_accounts,
+            x_token_mint_id,
+            x_decimals,
+            llx_decimals,
+            governance,
+            total_assets,
+            total_assets_slot,
+            management_fee_bps,
+            fee_recipients,
+            fees_collected_slot,
+            referral_fee_bps,
+            high_water_share_price,
+            price_feed,
+            max_drawdown_bps,
+            referred_shares,
+            referral_fee_index,
+            strategies,
+            withdrawal_queue,
         } = self;
 
         is_initialized_dst[0] = *is_initialized as u8;
@@ -95,9 +739,109 @@ impl Pack for Vault {
         lx_token_account_dst.copy_from_slice(lx_token_account.as_ref());
         pack_coption_key(x_token_account, x_token_account_dst);
         strategy_program_id_dst.copy_from_slice(strategy_program_id.as_ref());
-        strategy_program_deposit_instruction_id_dst[0] = *strategy_program_deposit_instruction_id;
-        strategy_program_withdraw_instruction_id_dst[0] = *strategy_program_withdraw_instruction_id;
-        pack_coption_key(strategy_data_account, strategy_data_account_dst);
+        strategy_program_deposit_instruction.pack_into_slice(strategy_program_deposit_instruction_dst);
+        strategy_program_withdraw_instruction
+            .pack_into_slice(strategy_program_withdraw_instruction_dst);
+        strategy_account_count_dst[0] = strategy_accounts.len() as u8;
+        strategy_accounts_dst.fill(0);
+        for (account, dst) in strategy_accounts
+            .iter()
+            .zip(strategy_accounts_dst.chunks_mut(StrategyAccount::LEN))
+        {
+            account.pack_into_slice(dst);
+        }
+        x_token_mint_id_dst.copy_from_slice(x_token_mint_id.as_ref());
+        x_decimals_dst[0] = *x_decimals;
+        llx_decimals_dst[0] = *llx_decimals;
+        governance_dst.copy_from_slice(governance.as_ref());
+        *total_assets_dst = total_assets.to_le_bytes();
+        *total_assets_slot_dst = total_assets_slot.to_le_bytes();
+        *management_fee_bps_dst = management_fee_bps.to_le_bytes();
+        fee_recipient_count_dst[0] = fee_recipients.len() as u8;
+        fee_recipients_dst.fill(0);
+        for (recipient, dst) in fee_recipients
+            .iter()
+            .zip(fee_recipients_dst.chunks_mut(FeeRecipient::LEN))
+        {
+            recipient.pack_into_slice(dst);
+        }
+        *fees_collected_slot_dst = fees_collected_slot.to_le_bytes();
+        *referral_fee_bps_dst = referral_fee_bps.to_le_bytes();
+        *high_water_share_price_dst = high_water_share_price.to_le_bytes();
+        let (price_feed_tag_dst, price_feed_dst) =
+            mut_array_refs![price_feed_dst, 4, PriceFeed::LEN];
+        match price_feed {
+            COption::Some(price_feed) => {
+                *price_feed_tag_dst = [1, 0, 0, 0];
+                price_feed.pack_into_slice(price_feed_dst);
+            }
+            COption::None => {
+                *price_feed_tag_dst = [0; 4];
+                price_feed_dst.fill(0);
+            }
+        }
+        *max_drawdown_bps_dst = max_drawdown_bps.to_le_bytes();
+        *referred_shares_dst = referred_shares.to_le_bytes();
+        *referral_fee_index_dst = referral_fee_index.to_le_bytes();
+        strategy_count_dst[0] = strategies.len() as u8;
+        strategies_dst.fill(0);
+        for (strategy, dst) in strategies
+            .iter()
+            .zip(strategies_dst.chunks_mut(StrategyEntry::LEN))
+        {
+            strategy.pack_into_slice(dst);
+        }
+        withdrawal_queue_len_dst[0] = withdrawal_queue.len() as u8;
+        withdrawal_queue_dst.fill(0);
+        withdrawal_queue_dst[..withdrawal_queue.len()].copy_from_slice(withdrawal_queue);
+    }
+}
+
+impl Vault {
+    /// Metas for the accounts passed through to the strategy, as expected by `Deposit` &
+    /// `Withdraw` after the strategy program.
+    pub fn strategy_account_metas(&self) -> Vec<AccountMeta> {
+        self.strategy_accounts
+            .iter()
+            .map(StrategyAccount::account_meta)
+            .collect()
+    }
+
+    /// Metas for the accounts `RollUp` expects for this vault, stored at `address`.
+    pub fn roll_up_account_metas(&self, address: &Pubkey) -> Vec<AccountMeta> {
+        let assets_account = match self.x_token_account {
+            COption::Some(x_token_account) if self.hodl => x_token_account,
+            _ => self.lx_token_account,
+        };
+        vec![
+            AccountMeta::new(*address, false),
+            AccountMeta::new_readonly(self.llx_token_mint_id, false),
+            AccountMeta::new_readonly(assets_account, false),
+        ]
+    }
+
+    /// The share price `drawdown_bps` below the high-water mark, under which a drawdown limit of
+    /// that many bps is breached.
+    pub fn share_price_floor(&self, drawdown_bps: u16) -> Option<u64> {
+        math::bps_of(
+            self.high_water_share_price,
+            math::MAX_BPS.checked_sub(drawdown_bps as u64)?,
+        )
+    }
+
+    /// The X lent to the vault's strategies, as of their last harvests.
+    pub fn total_debt(&self) -> Option<u64> {
+        self.strategies
+            .iter()
+            .try_fold(0u64, |total, strategy| total.checked_add(strategy.total_debt))
+    }
+
+    /// The vault this one invests through, when its strategy is another vault of `program_id`.
+    pub fn child_vault(&self, program_id: &Pubkey) -> Option<Pubkey> {
+        if self.hodl || self.strategy_program_id != *program_id {
+            return None;
+        }
+        self.strategy_accounts.first().map(|account| account.pubkey)
     }
 }
 
@@ -129,3 +873,492 @@ fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
         _ => Err(ProgramError::InvalidAccountData),
     }
 }
+
+/// Maximum number of vaults a single registry account can index.
+pub const MAX_REGISTERED_VAULTS: usize = 64;
+
+/// What a registry indexes vaults by.
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum RegistryKind {
+    /// Vaults sharing an underlying X mint.
+    Mint,
+    /// Vaults investing through a strategy program.
+    Strategy,
+}
+
+/// Index of vaults sharing an X mint or strategy program, stored at a program derived address
+/// (see `pda::find_mint_registry_address` & `pda::find_strategy_registry_address`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Registry {
+    pub is_initialized: bool,
+    pub kind: RegistryKind,
+    /// The X mint or strategy program this registry indexes.
+    pub key: Pubkey,
+    pub vaults: Vec<Pubkey>,
+}
+
+impl Sealed for Registry {}
+
+impl Pack for Registry {
+    const LEN: usize = 1 + 1 + 32 + 1 + 32 * MAX_REGISTERED_VAULTS;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Registry::LEN];
+        let (is_initialized, kind, key, vault_count, vaults) =
+            array_refs![src, 1, 1, 32, 1, 32 * MAX_REGISTERED_VAULTS];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let kind =
+            RegistryKind::try_from_primitive(kind[0]).map_err(|_| ProgramError::InvalidAccountData)?;
+        let vault_count = vault_count[0] as usize;
+        if vault_count > MAX_REGISTERED_VAULTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let vaults = vaults
+            .chunks(32)
+            .take(vault_count)
+            .map(Pubkey::new)
+            .collect();
+        Ok(Registry {
+            is_initialized,
+            kind,
+            key: Pubkey::new_from_array(*key),
+            vaults,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Registry::LEN];
+        let (is_initialized_dst, kind_dst, key_dst, vault_count_dst, vaults_dst) =
+            mut_array_refs![dst, 1, 1, 32, 1, 32 * MAX_REGISTERED_VAULTS];
+
+        let Registry {
+            is_initialized,
+            kind,
+            key,
+            vaults,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        kind_dst[0] = *kind as u8;
+        key_dst.copy_from_slice(key.as_ref());
+        vault_count_dst[0] = vaults.len() as u8;
+        for (vault_dst, vault) in vaults_dst.chunks_mut(32).zip(vaults.iter()) {
+            vault_dst.copy_from_slice(vault.as_ref());
+        }
+        for vault_dst in vaults_dst.chunks_mut(32).skip(vaults.len()) {
+            vault_dst.copy_from_slice(&[0; 32]);
+        }
+    }
+}
+
+impl IsInitialized for Registry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// Where a vault is listed in the registries, stored at a program derived address (see
+/// `pda::find_registration_address`) while it's registered, so that a vault is only ever listed
+/// once whichever page it's registered in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Registration {
+    pub is_initialized: bool,
+    pub vault: Pubkey,
+    /// Paid for this account, and is refunded its lamports when the vault is deregistered.
+    pub payer: Pubkey,
+    pub mint_registry_page: u32,
+    pub strategy_registry_page: u32,
+}
+
+impl Sealed for Registration {}
+
+impl Pack for Registration {
+    const LEN: usize = 1 + 32 + 32 + 4 + 4;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Registration::LEN];
+        let (is_initialized, vault, payer, mint_registry_page, strategy_registry_page) =
+            array_refs![src, 1, 32, 32, 4, 4];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Registration {
+            is_initialized,
+            vault: Pubkey::new_from_array(*vault),
+            payer: Pubkey::new_from_array(*payer),
+            mint_registry_page: u32::from_le_bytes(*mint_registry_page),
+            strategy_registry_page: u32::from_le_bytes(*strategy_registry_page),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Registration::LEN];
+        let (
+            is_initialized_dst,
+            vault_dst,
+            payer_dst,
+            mint_registry_page_dst,
+            strategy_registry_page_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 4, 4];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        vault_dst.copy_from_slice(self.vault.as_ref());
+        payer_dst.copy_from_slice(self.payer.as_ref());
+        *mint_registry_page_dst = self.mint_registry_page.to_le_bytes();
+        *strategy_registry_page_dst = self.strategy_registry_page.to_le_bytes();
+    }
+}
+
+impl IsInitialized for Registration {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// Result of a strategy's `Peek`, written into an account owned by the strategy program since
+/// programs can't return data to their caller.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PeekResult {
+    pub is_initialized: bool,
+    /// The strategy instance data account peeked at.
+    pub data_account: Pubkey,
+    /// The vault the valuation was made for.
+    pub vault: Pubkey,
+    /// The # of lX tokens valued.
+    pub lx_amount: u64,
+    /// The # of X tokens they can currently be withdrawn for.
+    pub x_amount: u64,
+    /// The slot the valuation was made in.
+    pub slot: u64,
+}
+
+impl Sealed for PeekResult {}
+
+impl Pack for PeekResult {
+    const LEN: usize = 1 + 32 + 32 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PeekResult::LEN];
+        let (is_initialized, data_account, vault, lx_amount, x_amount, slot) =
+            array_refs![src, 1, 32, 32, 8, 8, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(PeekResult {
+            is_initialized,
+            data_account: Pubkey::new_from_array(*data_account),
+            vault: Pubkey::new_from_array(*vault),
+            lx_amount: u64::from_le_bytes(*lx_amount),
+            x_amount: u64::from_le_bytes(*x_amount),
+            slot: u64::from_le_bytes(*slot),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PeekResult::LEN];
+        let (
+            is_initialized_dst,
+            data_account_dst,
+            vault_dst,
+            lx_amount_dst,
+            x_amount_dst,
+            slot_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        data_account_dst.copy_from_slice(self.data_account.as_ref());
+        vault_dst.copy_from_slice(self.vault.as_ref());
+        *lx_amount_dst = self.lx_amount.to_le_bytes();
+        *x_amount_dst = self.x_amount.to_le_bytes();
+        *slot_dst = self.slot.to_le_bytes();
+    }
+}
+
+impl IsInitialized for PeekResult {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// Deposits into a vault credited to whoever referred the llX token account they minted into,
+/// stored at a program derived address (see `pda::find_referral_address`). The referrer is fixed
+/// by the account's first referred deposit.
+///
+/// Withdrawals can't be traced back to the deposits they redeem, so instead the referred llX is
+/// cut to what the token account still holds whenever the referral is settled, which only ever
+/// lowers it until the next referred deposit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Referral {
+    pub is_initialized: bool,
+    pub vault: Pubkey,
+    /// Owner of the llX token accounts the referrer's fees may be paid to.
+    pub referrer: Pubkey,
+    /// The llX token account the referred deposits were minted into.
+    pub llx_token_account: Pubkey,
+    /// The # of X tokens deposited through the referral.
+    pub principal: u64,
+    /// The # of llX tokens minted for them still held, as of the last settlement, which fees are
+    /// attributed by.
+    pub shares: u64,
+    /// The vault's `referral_fee_index` as of the last settlement.
+    pub fee_index: u128,
+    /// The llX owed to the referrer as of the last settlement, paid when they're next passed to
+    /// `CollectFees`.
+    pub accrued_fees: u64,
+}
+
+impl Referral {
+    /// Accrues the referrer's fees up to `vault`'s `referral_fee_index`, after cutting the
+    /// referred llX to the `balance` the token account holds.
+    pub fn settle(&mut self, vault: &mut Vault, balance: u64) -> Option<()> {
+        let shares = self.shares.min(balance);
+        vault.referred_shares = vault.referred_shares.checked_sub(self.shares - shares)?;
+        self.shares = shares;
+        let fees = math::fees_for_index(
+            shares,
+            vault.referral_fee_index.checked_sub(self.fee_index)?,
+        )?;
+        self.accrued_fees = self.accrued_fees.checked_add(fees)?;
+        self.fee_index = vault.referral_fee_index;
+        Some(())
+    }
+}
+
+impl Sealed for Referral {}
+
+impl Pack for Referral {
+    const LEN: usize = 1 + 32 + 32 + 32 + 8 + 8 + 16 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Referral::LEN];
+        let (
+            is_initialized,
+            vault,
+            referrer,
+            llx_token_account,
+            principal,
+            shares,
+            fee_index,
+            accrued_fees,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 16, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Referral {
+            is_initialized,
+            vault: Pubkey::new_from_array(*vault),
+            referrer: Pubkey::new_from_array(*referrer),
+            llx_token_account: Pubkey::new_from_array(*llx_token_account),
+            principal: u64::from_le_bytes(*principal),
+            shares: u64::from_le_bytes(*shares),
+            fee_index: u128::from_le_bytes(*fee_index),
+            accrued_fees: u64::from_le_bytes(*accrued_fees),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Referral::LEN];
+        let (
+            is_initialized_dst,
+            vault_dst,
+            referrer_dst,
+            llx_token_account_dst,
+            principal_dst,
+            shares_dst,
+            fee_index_dst,
+            accrued_fees_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 16, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        vault_dst.copy_from_slice(self.vault.as_ref());
+        referrer_dst.copy_from_slice(self.referrer.as_ref());
+        llx_token_account_dst.copy_from_slice(self.llx_token_account.as_ref());
+        *principal_dst = self.principal.to_le_bytes();
+        *shares_dst = self.shares.to_le_bytes();
+        *fee_index_dst = self.fee_index.to_le_bytes();
+        *accrued_fees_dst = self.accrued_fees.to_le_bytes();
+    }
+}
+
+impl IsInitialized for Referral {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// When a trigger may be executed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerCondition {
+    /// At least `slots` after the trigger was created or last executed.
+    SlotsElapsed { slots: u64 },
+    /// At least `seconds` after the trigger was created or last executed.
+    TimeElapsed { seconds: u64 },
+    /// While the `oracle`'s price, in its own exponent, is below `price`.
+    PriceBelow { oracle: Pubkey, price: i64 },
+    /// While the `oracle`'s price, in its own exponent, is above `price`.
+    PriceAbove { oracle: Pubkey, price: i64 },
+    /// While the vault's share price, rolled up in the same slot, is more than `bps` below its
+    /// high-water mark.
+    Drawdown { bps: u16 },
+}
+
+impl Default for TriggerCondition {
+    fn default() -> Self {
+        TriggerCondition::SlotsElapsed { slots: 0 }
+    }
+}
+
+impl Sealed for TriggerCondition {}
+
+impl Pack for TriggerCondition {
+    const LEN: usize = 1 + 32 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, TriggerCondition::LEN];
+        let (tag, oracle, value) = array_refs![src, 1, 32, 8];
+        let oracle = Pubkey::new_from_array(*oracle);
+        Ok(match tag[0] {
+            0 => TriggerCondition::SlotsElapsed {
+                slots: u64::from_le_bytes(*value),
+            },
+            1 => TriggerCondition::TimeElapsed {
+                seconds: u64::from_le_bytes(*value),
+            },
+            2 => TriggerCondition::PriceBelow {
+                oracle,
+                price: i64::from_le_bytes(*value),
+            },
+            3 => TriggerCondition::PriceAbove {
+                oracle,
+                price: i64::from_le_bytes(*value),
+            },
+            4 => TriggerCondition::Drawdown {
+                bps: u64::from_le_bytes(*value)
+                    .try_into()
+                    .map_err(|_| ProgramError::InvalidAccountData)?,
+            },
+            _ => return Err(ProgramError::InvalidAccountData),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, TriggerCondition::LEN];
+        let (tag_dst, oracle_dst, value_dst) = mut_array_refs![dst, 1, 32, 8];
+        let (tag, oracle, value) = match *self {
+            TriggerCondition::SlotsElapsed { slots } => (0, Pubkey::default(), slots.to_le_bytes()),
+            TriggerCondition::TimeElapsed { seconds } => {
+                (1, Pubkey::default(), seconds.to_le_bytes())
+            }
+            TriggerCondition::PriceBelow { oracle, price } => (2, oracle, price.to_le_bytes()),
+            TriggerCondition::PriceAbove { oracle, price } => (3, oracle, price.to_le_bytes()),
+            TriggerCondition::Drawdown { bps } => {
+                (4, Pubkey::default(), (bps as u64).to_le_bytes())
+            }
+        };
+        tag_dst[0] = tag;
+        oracle_dst.copy_from_slice(oracle.as_ref());
+        *value_dst = value;
+    }
+}
+
+/// What a trigger does when executed.
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum TriggerAction {
+    /// Harvests the vault's strategy, or moves it to hodl instead if its `max_drawdown_bps` is
+    /// breached.
+    Harvest,
+    /// Withdraws everything from the vault's strategy into its X token account and switches it to
+    /// hodling.
+    MoveToHodl,
+    /// Harvests each strategy a hodl vault lends to toward its debt ratio, those owed more than
+    /// it first.
+    Rebalance,
+}
+
+/// A condition under which anyone may perform an action on a vault, for a bounty paid out of the
+/// lamports the trigger holds beyond its rent exemption. Stored at a program derived address (see
+/// `pda::find_trigger_address`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trigger {
+    pub is_initialized: bool,
+    pub vault: Pubkey,
+    pub condition: TriggerCondition,
+    pub action: TriggerAction,
+    /// Lamports paid to whoever executes the trigger, as far as the trigger holds them.
+    pub bounty_lamports: u64,
+    /// The slot & time the trigger was created or last executed in.
+    pub executed_slot: u64,
+    pub executed_timestamp: i64,
+}
+
+impl Sealed for Trigger {}
+
+impl Pack for Trigger {
+    const LEN: usize = 1 + 32 + TriggerCondition::LEN + 1 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Trigger::LEN];
+        let (
+            is_initialized,
+            vault,
+            condition,
+            action,
+            bounty_lamports,
+            executed_slot,
+            executed_timestamp,
+        ) = array_refs![src, 1, 32, TriggerCondition::LEN, 1, 8, 8, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Trigger {
+            is_initialized,
+            vault: Pubkey::new_from_array(*vault),
+            condition: TriggerCondition::unpack_from_slice(condition)?,
+            action: TriggerAction::try_from_primitive(action[0])
+                .map_err(|_| ProgramError::InvalidAccountData)?,
+            bounty_lamports: u64::from_le_bytes(*bounty_lamports),
+            executed_slot: u64::from_le_bytes(*executed_slot),
+            executed_timestamp: i64::from_le_bytes(*executed_timestamp),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Trigger::LEN];
+        let (
+            is_initialized_dst,
+            vault_dst,
+            condition_dst,
+            action_dst,
+            bounty_lamports_dst,
+            executed_slot_dst,
+            executed_timestamp_dst,
+        ) = mut_array_refs![dst, 1, 32, TriggerCondition::LEN, 1, 8, 8, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        vault_dst.copy_from_slice(self.vault.as_ref());
+        self.condition.pack_into_slice(condition_dst);
+        action_dst[0] = self.action as u8;
+        *bounty_lamports_dst = self.bounty_lamports.to_le_bytes();
+        *executed_slot_dst = self.executed_slot.to_le_bytes();
+        *executed_timestamp_dst = self.executed_timestamp.to_le_bytes();
+    }
+}
+
+impl IsInitialized for Trigger {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
@@ -0,0 +1,208 @@
+//! SDK for writing strategy programs.
+//!
+//! A strategy program implements [StrategyProcessor](trait.StrategyProcessor.html) and forwards
+//! its entrypoint to [process_strategy_instruction](fn.process_strategy_instruction.html), which
+//! unpacks the [StrategyInstruction](../instruction/enum.StrategyInstruction.html), checks
+//! signers and loads & stores the strategy's instance data account.
+//!
+//! Crates depending on this one for the SDK should enable the `no-entrypoint` feature.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+use std::slice::Iter;
+
+use crate::{error::VaultError, instruction::StrategyInstruction, state::PeekResult};
+
+/// Token accounts moved between by `Deposit` & `Withdraw`.
+pub struct TransferAccounts<'a, 'b> {
+    pub token_program: &'a AccountInfo<'b>,
+    pub source: &'a AccountInfo<'b>,
+    pub target: &'a AccountInfo<'b>,
+    /// Authority of `source`, which has signed the instruction.
+    pub source_authority: &'a AccountInfo<'b>,
+}
+
+/// A strategy instance's data, along with the strategy-specific accounts passed after it.
+pub struct Context<'a, 'b, D> {
+    pub program_id: &'a Pubkey,
+    pub data_account: &'a AccountInfo<'b>,
+    pub data: D,
+    pub remaining_accounts: &'a [AccountInfo<'b>],
+}
+
+pub trait StrategyProcessor {
+    /// Strategy instance data, stored in an account owned by the strategy program.
+    type Data: Pack + IsInitialized;
+
+    /// Takes `amount` X tokens from `accounts.source`, crediting lX tokens to `accounts.target`.
+    fn deposit<'a, 'b>(
+        ctx: &mut Context<'a, 'b, Self::Data>,
+        accounts: &TransferAccounts<'a, 'b>,
+        amount: u64,
+    ) -> ProgramResult;
+
+    /// Takes `amount` lX tokens from `accounts.source`, returning X tokens to `accounts.target`.
+    fn withdraw<'a, 'b>(
+        ctx: &mut Context<'a, 'b, Self::Data>,
+        accounts: &TransferAccounts<'a, 'b>,
+        amount: u64,
+    ) -> ProgramResult;
+
+    /// Returns the # of X tokens `amount` lX tokens can currently be withdrawn for.
+    fn peek(ctx: &Context<Self::Data>, amount: u64) -> Result<u64, ProgramError>;
+
+    /// Realizes any pending rewards.
+    fn harvest(ctx: &mut Context<Self::Data>) -> ProgramResult;
+
+    /// Unwinds the strategy's positions. `authority` has signed, but it's up to the strategy to
+    /// check it's allowed to exit.
+    fn emergency_exit<'a, 'b>(
+        ctx: &mut Context<'a, 'b, Self::Data>,
+        authority: &'a AccountInfo<'b>,
+    ) -> ProgramResult;
+
+    /// Handles instructions outside the strategy interface, such as initializing an instance. Tags
+    /// from 128 up are left for these.
+    fn process_custom_instruction(
+        _program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+        _instruction_data: &[u8],
+    ) -> ProgramResult {
+        Err(VaultError::InvalidInstruction.into())
+    }
+}
+
+/// Dispatches a strategy program's instruction to `S`.
+pub fn process_strategy_instruction<S: StrategyProcessor>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = match StrategyInstruction::unpack(instruction_data) {
+        Ok(instruction) => instruction,
+        Err(_) => return S::process_custom_instruction(program_id, accounts, instruction_data),
+    };
+    let account_info_iter = &mut accounts.iter();
+
+    match instruction {
+        StrategyInstruction::Deposit { amount } => {
+            msg!("Instruction: Deposit");
+            let transfer_accounts = next_transfer_accounts(account_info_iter)?;
+            let mut ctx = load_context::<S::Data>(program_id, account_info_iter, true)?;
+            S::deposit(&mut ctx, &transfer_accounts, amount)?;
+            store_context(&ctx)
+        }
+        StrategyInstruction::Withdraw { amount } => {
+            msg!("Instruction: Withdraw");
+            let transfer_accounts = next_transfer_accounts(account_info_iter)?;
+            let mut ctx = load_context::<S::Data>(program_id, account_info_iter, true)?;
+            S::withdraw(&mut ctx, &transfer_accounts, amount)?;
+            store_context(&ctx)
+        }
+        StrategyInstruction::Peek { amount, vault } => {
+            msg!("Instruction: Peek");
+            let result_account = next_account_info(account_info_iter)?;
+            if result_account.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if !result_account.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            // The result account must be a dedicated, PeekResult-sized account, so a Peek can't
+            // overwrite the instance data or another account of the strategy.
+            PeekResult::unpack_unchecked(&result_account.data.borrow())?;
+            let ctx = load_context::<S::Data>(program_id, account_info_iter, false)?;
+            if result_account.key == ctx.data_account.key
+                || ctx
+                    .remaining_accounts
+                    .iter()
+                    .any(|account| account.key == result_account.key)
+            {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let x_amount = S::peek(&ctx, amount)?;
+            PeekResult::pack(
+                PeekResult {
+                    is_initialized: true,
+                    data_account: *ctx.data_account.key,
+                    vault,
+                    lx_amount: amount,
+                    x_amount,
+                    slot: Clock::get()?.slot,
+                },
+                &mut result_account.data.borrow_mut(),
+            )
+        }
+        StrategyInstruction::Harvest => {
+            msg!("Instruction: Harvest");
+            let mut ctx = load_context::<S::Data>(program_id, account_info_iter, true)?;
+            S::harvest(&mut ctx)?;
+            store_context(&ctx)
+        }
+        StrategyInstruction::EmergencyExit => {
+            msg!("Instruction: EmergencyExit");
+            let authority = next_account_info(account_info_iter)?;
+            if !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut ctx = load_context::<S::Data>(program_id, account_info_iter, true)?;
+            S::emergency_exit(&mut ctx, authority)?;
+            store_context(&ctx)
+        }
+    }
+}
+
+fn next_transfer_accounts<'a, 'b>(
+    account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+) -> Result<TransferAccounts<'a, 'b>, ProgramError> {
+    let token_program = next_account_info(account_info_iter)?;
+    let source = next_account_info(account_info_iter)?;
+    let target = next_account_info(account_info_iter)?;
+    let source_authority = next_account_info(account_info_iter)?;
+    if !source_authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(TransferAccounts {
+        token_program,
+        source,
+        target,
+        source_authority,
+    })
+}
+
+fn load_context<'a, 'b, D: Pack + IsInitialized>(
+    program_id: &'a Pubkey,
+    account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+    writable: bool,
+) -> Result<Context<'a, 'b, D>, ProgramError> {
+    let data_account = next_account_info(account_info_iter)?;
+    if data_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if writable && !data_account.is_writable {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = D::unpack(&data_account.data.borrow())?;
+    Ok(Context {
+        program_id,
+        data_account,
+        data,
+        remaining_accounts: account_info_iter.as_slice(),
+    })
+}
+
+fn store_context<D: Pack + IsInitialized>(ctx: &Context<D>) -> ProgramResult {
+    let mut dst = ctx.data_account.data.borrow_mut();
+    if dst.len() != D::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    ctx.data.pack_into_slice(&mut dst);
+    Ok(())
+}
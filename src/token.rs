@@ -0,0 +1,343 @@
+//! Token program interface shared by the legacy SPL Token program and Token-2022.
+//!
+//! Both programs use the same wire format for the instructions and base account layouts used by
+//! vaults, but the spl-token builders reject any program ID other than the legacy one, so
+//! instructions are encoded here against whichever token program owns the relevant account.
+
+use arrayref::array_ref;
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar,
+};
+use spl_token::{
+    instruction::{AuthorityType, TokenInstruction},
+    state::{Account, Mint},
+};
+use std::convert::TryInto;
+
+use crate::error::VaultError;
+
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// `TokenInstruction::SyncNative` tag, which spl-token 3.1 predates.
+const SYNC_NATIVE_INSTRUCTION: u8 = 17;
+
+/// Offset of the account type byte in Token-2022 accounts carrying extensions. Mints are padded
+/// to the base account length so both share the same TLV layout.
+const ACCOUNT_TYPE_OFFSET: usize = Account::LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// Token-2022 extension types vaults care about.
+pub const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+pub const EXTENSION_TRANSFER_FEE_AMOUNT: u16 = 2;
+pub const EXTENSION_NON_TRANSFERABLE: u16 = 9;
+pub const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+pub const EXTENSION_NON_TRANSFERABLE_ACCOUNT: u16 = 13;
+pub const EXTENSION_TRANSFER_HOOK: u16 = 14;
+pub const EXTENSION_TRANSFER_HOOK_ACCOUNT: u16 = 15;
+pub const EXTENSION_CONFIDENTIAL_TRANSFER_FEE_CONFIG: u16 = 16;
+pub const EXTENSION_CONFIDENTIAL_TRANSFER_FEE_AMOUNT: u16 = 17;
+
+/// Length of an extension's type & length header.
+const EXTENSION_HEADER_LEN: usize = 4;
+
+/// Account extensions Token-2022's `InitializeAccount` adds to every account of a mint carrying
+/// the mint extension, and their lengths, which the account must have room for.
+const REQUIRED_ACCOUNT_EXTENSIONS: [(u16, u16, usize); 4] = [
+    (
+        EXTENSION_TRANSFER_FEE_CONFIG,
+        EXTENSION_TRANSFER_FEE_AMOUNT,
+        8,
+    ),
+    (
+        EXTENSION_NON_TRANSFERABLE,
+        EXTENSION_NON_TRANSFERABLE_ACCOUNT,
+        0,
+    ),
+    (EXTENSION_TRANSFER_HOOK, EXTENSION_TRANSFER_HOOK_ACCOUNT, 1),
+    (
+        EXTENSION_CONFIDENTIAL_TRANSFER_FEE_CONFIG,
+        EXTENSION_CONFIDENTIAL_TRANSFER_FEE_AMOUNT,
+        64,
+    ),
+];
+
+/// Extensions which make a mint unusable as a vault's X token: non-transferable tokens can't be
+/// deposited, a permanent delegate can drain the vault's holdings and transfer hooks require
+/// accounts the vault doesn't pass.
+const INCOMPATIBLE_X_EXTENSIONS: [u16; 3] = [
+    EXTENSION_NON_TRANSFERABLE,
+    EXTENSION_PERMANENT_DELEGATE,
+    EXTENSION_TRANSFER_HOOK,
+];
+
+/// Returns whether `program_id` is a supported token program.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+}
+
+/// Unpacks a mint owned by either token program, ignoring any Token-2022 extensions.
+pub fn unpack_mint(account: &AccountInfo) -> Result<Mint, ProgramError> {
+    if !is_token_program(account.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    unpack_base(&account.data.borrow(), ACCOUNT_TYPE_MINT)
+}
+
+/// Unpacks a token account owned by either token program, ignoring any Token-2022 extensions.
+pub fn unpack_account(account: &AccountInfo) -> Result<Account, ProgramError> {
+    if !is_token_program(account.owner) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    unpack_base(&account.data.borrow(), ACCOUNT_TYPE_ACCOUNT)
+}
+
+/// Unpacks mint data fetched off-chain, ignoring any Token-2022 extensions.
+pub fn unpack_mint_data(data: &[u8]) -> Result<Mint, ProgramError> {
+    unpack_base(data, ACCOUNT_TYPE_MINT)
+}
+
+/// Unpacks token account data fetched off-chain, ignoring any Token-2022 extensions.
+pub fn unpack_account_data(data: &[u8]) -> Result<Account, ProgramError> {
+    unpack_base(data, ACCOUNT_TYPE_ACCOUNT)
+}
+
+fn unpack_base<T: Pack + IsInitialized>(data: &[u8], account_type: u8) -> Result<T, ProgramError> {
+    if data.len() == T::LEN {
+        return T::unpack(data);
+    }
+    if data.get(ACCOUNT_TYPE_OFFSET) != Some(&account_type) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let base = T::unpack_from_slice(&data[..T::LEN])?;
+    if !base.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(base)
+}
+
+/// Returns the extension types present on a Token-2022 mint or account.
+pub fn extension_types(data: &[u8]) -> Result<Vec<u16>, ProgramError> {
+    let mut types = Vec::new();
+    if data.len() <= ACCOUNT_TYPE_OFFSET {
+        return Ok(types);
+    }
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + EXTENSION_HEADER_LEN <= data.len() {
+        let header = array_ref![data, offset, 4];
+        let extension_type = u16::from_le_bytes(header[..2].try_into().unwrap());
+        let length = u16::from_le_bytes(header[2..].try_into().unwrap()) as usize;
+        // Uninitialized space marks the end of the extensions.
+        if extension_type == 0 {
+            break;
+        }
+        types.push(extension_type);
+        offset += EXTENSION_HEADER_LEN + length;
+    }
+    if offset > data.len() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(types)
+}
+
+/// Fails if the X mint carries an extension vaults can't safely hold.
+pub fn check_x_mint_extensions(account: &AccountInfo) -> Result<(), ProgramError> {
+    let types = extension_types(&account.data.borrow())?;
+    if types
+        .iter()
+        .any(|extension_type| INCOMPATIBLE_X_EXTENSIONS.contains(extension_type))
+    {
+        return Err(VaultError::UnsupportedMintExtension.into());
+    }
+    Ok(())
+}
+
+/// Length of a token account for the mint with `mint_data`, with room for the account extensions
+/// Token-2022 requires of the mint's extensions. Accounts needing none have the base length.
+pub fn account_len(mint_data: &[u8]) -> Result<usize, ProgramError> {
+    let mint_types = extension_types(mint_data)?;
+    let extensions_len: usize = REQUIRED_ACCOUNT_EXTENSIONS
+        .iter()
+        .filter(|(mint_type, _, _)| mint_types.contains(mint_type))
+        .map(|(_, _, len)| EXTENSION_HEADER_LEN + len)
+        .sum();
+    if extensions_len == 0 {
+        return Ok(Account::LEN);
+    }
+    // The account type byte separates the base account from its extensions.
+    Ok(ACCOUNT_TYPE_OFFSET + 1 + extensions_len)
+}
+
+fn check_program_account(token_program_id: &Pubkey) -> Result<(), ProgramError> {
+    if !is_token_program(token_program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source_pubkey, false),
+            AccountMeta::new_readonly(*mint_pubkey, false),
+            AccountMeta::new(*destination_pubkey, false),
+            AccountMeta::new_readonly(*authority_pubkey, true),
+        ],
+        data: TokenInstruction::TransferChecked { amount, decimals }.pack(),
+    })
+}
+
+pub fn mint_to_checked(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*mint_pubkey, false),
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new_readonly(*owner_pubkey, true),
+        ],
+        data: TokenInstruction::MintToChecked { amount, decimals }.pack(),
+    })
+}
+
+pub fn burn_checked(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new(*mint_pubkey, false),
+            AccountMeta::new_readonly(*authority_pubkey, true),
+        ],
+        data: TokenInstruction::BurnChecked { amount, decimals }.pack(),
+    })
+}
+
+pub fn set_authority(
+    token_program_id: &Pubkey,
+    owned_pubkey: &Pubkey,
+    new_authority_pubkey: Option<&Pubkey>,
+    authority_type: AuthorityType,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*owned_pubkey, false),
+            AccountMeta::new_readonly(*owner_pubkey, true),
+        ],
+        data: TokenInstruction::SetAuthority {
+            authority_type,
+            new_authority: new_authority_pubkey.cloned().into(),
+        }
+        .pack(),
+    })
+}
+
+pub fn close_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new(*destination_pubkey, false),
+            AccountMeta::new_readonly(*owner_pubkey, true),
+        ],
+        data: TokenInstruction::CloseAccount.pack(),
+    })
+}
+
+pub fn sync_native(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![AccountMeta::new(*account_pubkey, false)],
+        data: vec![SYNC_NATIVE_INSTRUCTION],
+    })
+}
+
+pub fn initialize_mint(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*mint_pubkey, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: TokenInstruction::InitializeMint {
+            mint_authority: *mint_authority_pubkey,
+            freeze_authority: COption::None,
+            decimals,
+        }
+        .pack(),
+    })
+}
+
+/// Initializes a token account with `InitializeAccount2`, which takes the owner as data so a
+/// vault needn't pass its derived authority's account to create its token accounts.
+pub fn initialize_account(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_account(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account_pubkey, false),
+            AccountMeta::new_readonly(*mint_pubkey, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: TokenInstruction::InitializeAccount2 {
+            owner: *owner_pubkey,
+        }
+        .pack(),
+    })
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+use solana_program::program_error::ProgramError;
+
+#[derive(Error, Debug, Copy, Clone)]
+pub enum AmmLpError {
+    #[error("Invalid Instruction")]
+    InvalidInstruction,
+    #[error("Not Rent Exempt")]
+    NotRentExempt,
+    #[error("Math Overflow")]
+    MathOverflow,
+    #[error("Incorrect Swap Account")]
+    IncorrectSwapAccount,
+    #[error("Invalid Strategy Authority")]
+    InvalidAuthority,
+    #[error("Strategy Exited")]
+    StrategyExited,
+    #[error("Unsupported Swap Curve")]
+    UnsupportedCurve,
+    #[error("Pool Price Deviates From Oracles")]
+    PoolPriceDeviation,
+    #[error("Incorrect Oracle Account")]
+    IncorrectOracle,
+}
+
+impl From<AmmLpError> for ProgramError {
+    fn from(e: AmmLpError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,175 @@
+//! Instructions specific to the AMM LP strategy. The strategy interface itself is
+//! `Vault::instruction::StrategyInstruction`, with the following accounts expected after the
+//! strategy account:
+//!
+//! Deposit & Withdraw:
+//! 1. `[]` The token-swap account.
+//! 2. `[]` The swap authority.
+//! 3. `[writeable]` The swap's token A reserve.
+//! 4. `[writeable]` The swap's token B reserve.
+//! 5. `[writeable]` The pool mint, i.e. the lX Token ID.
+//! 6. `[writeable]` The pool fee account.
+//! 7. `[writeable]` The strategy's holding account for the pool's other token.
+//! 8. `[]` The strategy authority.
+//! 9. `[]` The token-swap program.
+//! 10. `[]` The X price account.
+//! 11. `[]` The other token's price account.
+//!
+//! Peek:
+//! 1. `[]` The swap's token A reserve.
+//! 2. `[]` The swap's token B reserve.
+//! 3. `[]` The pool mint.
+//! 4. `[]` The X price account.
+//! 5. `[]` The other token's price account.
+//!
+//! Harvest expects none and EmergencyExit must be signed by the strategy's admin.
+//!
+//! Deposits, withdrawals & peeks fail while the pool's price is more than the strategy's
+//! `max_slippage_bps` from the oracles', so that a pool pushed off its price within a transaction
+//! can be neither traded against nor valued at its spot reserves. Deposits & withdrawals then
+//! require at least the amounts the spot reserves imply, less `max_slippage_bps`, and swap the
+//! other token into no less X than the oracles value it at, less `max_slippage_bps`.
+
+use crate::error::AmmLpError::InvalidInstruction;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
+use std::{convert::TryInto, mem::size_of};
+
+/// Tag of the first AMM LP instruction, above those of the strategy interface.
+const INSTRUCTION_TAG_OFFSET: u8 = 128;
+
+pub enum AmmLpInstruction {
+    /// Initializes a strategy providing liquidity to a constant product token-swap pool.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` The rent exempt strategy account, owned by this program.
+    /// 2. `[signer]` The strategy's admin.
+    /// 3. `[]` The token-swap account, owned by the token-swap program.
+    /// 4. `[]` The X Token ID, either of the pool's tokens.
+    /// 5. `[]` Token account for the pool's other token, owned by the strategy authority.
+    /// 6. `[]` The Token ID of the pool's other token.
+    /// 7. `[]` Price account of X, in the layout of Pyth's.
+    /// 8. `[]` Price account of the other token, priced in the same currency as X.
+    /// 9. `[]` The rent sysvar
+    InitializeStrategy {
+        /// See `LpStrategy::max_slippage_bps`.
+        max_slippage_bps: u16,
+    },
+}
+
+impl AmmLpInstruction {
+    /// Unpacks a byte buffer into an [AmmLpInstruction](enum.AmmLpInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+
+        let tag = tag.checked_sub(INSTRUCTION_TAG_OFFSET);
+        Ok(match (tag, rest.len()) {
+            (Some(0), 2) => Self::InitializeStrategy {
+                max_slippage_bps: u16::from_le_bytes(rest.try_into().unwrap()),
+            },
+            _ => return Err(InvalidInstruction.into()),
+        })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::InitializeStrategy { max_slippage_bps } => {
+                buf.push(INSTRUCTION_TAG_OFFSET);
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_strategy(
+        program_id: &Pubkey,
+        strategy: &Pubkey,
+        admin: &Pubkey,
+        swap: &Pubkey,
+        x_token_mint_id: &Pubkey,
+        holding_account: &Pubkey,
+        other_token_mint_id: &Pubkey,
+        x_oracle: &Pubkey,
+        other_oracle: &Pubkey,
+        max_slippage_bps: u16,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(*strategy, false),
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new_readonly(*swap, false),
+                AccountMeta::new_readonly(*x_token_mint_id, false),
+                AccountMeta::new_readonly(*holding_account, false),
+                AccountMeta::new_readonly(*other_token_mint_id, false),
+                AccountMeta::new_readonly(*x_oracle, false),
+                AccountMeta::new_readonly(*other_oracle, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+            data: Self::InitializeStrategy { max_slippage_bps }.pack(),
+        })
+    }
+}
+
+/// Authority owning a strategy's holding account.
+pub fn find_strategy_authority_address(program_id: &Pubkey, strategy: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[strategy.as_ref()], program_id)
+}
+
+/// The token-swap program's authority over `swap`.
+pub fn find_swap_authority_address(swap_program_id: &Pubkey, swap: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[swap.as_ref()], swap_program_id)
+}
+
+/// Accounts following the strategy account in `Deposit` & `Withdraw`.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_account_metas(
+    program_id: &Pubkey,
+    strategy: &Pubkey,
+    swap_program_id: &Pubkey,
+    swap: &Pubkey,
+    swap_token_a: &Pubkey,
+    swap_token_b: &Pubkey,
+    pool_mint: &Pubkey,
+    pool_fee_account: &Pubkey,
+    holding_account: &Pubkey,
+    x_oracle: &Pubkey,
+    other_oracle: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*swap, false),
+        AccountMeta::new_readonly(find_swap_authority_address(swap_program_id, swap).0, false),
+        AccountMeta::new(*swap_token_a, false),
+        AccountMeta::new(*swap_token_b, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*pool_fee_account, false),
+        AccountMeta::new(*holding_account, false),
+        AccountMeta::new_readonly(find_strategy_authority_address(program_id, strategy).0, false),
+        AccountMeta::new_readonly(*swap_program_id, false),
+        AccountMeta::new_readonly(*x_oracle, false),
+        AccountMeta::new_readonly(*other_oracle, false),
+    ]
+}
+
+/// Accounts following the strategy account in `Peek`.
+pub fn peek_account_metas(
+    swap_token_a: &Pubkey,
+    swap_token_b: &Pubkey,
+    pool_mint: &Pubkey,
+    x_oracle: &Pubkey,
+    other_oracle: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*swap_token_a, false),
+        AccountMeta::new_readonly(*swap_token_b, false),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new_readonly(*x_oracle, false),
+        AccountMeta::new_readonly(*other_oracle, false),
+    ]
+}
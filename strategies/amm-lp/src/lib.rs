@@ -0,0 +1,14 @@
+//! Reference strategy providing X as liquidity to an spl-token-swap constant product pool.
+//!
+//! X is deposited single-sided, which the pool prices as swapping half of it for the other token
+//! and adding both as liquidity. The pool's LP tokens are the strategy's lX. Withdrawals remove
+//! both sides of the liquidity and swap the other token back into X.
+
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+
+solana_program::declare_id!("6xEqt5L2VUAhKQ1qfNvEVqLKvSpGPEBWeJxi6djVDqdp");
@@ -0,0 +1,507 @@
+use solana_program::{
+  account_info::{next_account_info, AccountInfo},
+  entrypoint::ProgramResult,
+  msg,
+  program::{invoke, invoke_signed},
+  program_error::ProgramError,
+  program_pack::{IsInitialized, Pack},
+  pubkey::Pubkey,
+  sysvar::{clock::Clock, rent::Rent, Sysvar},
+};
+
+use spl_token::state::{Account as TokenAccount, Mint};
+use spl_token_swap::{
+  curve::base::CurveType,
+  instruction::{DepositSingleTokenTypeExactAmountIn, Swap, WithdrawAllTokenTypes},
+  state::SwapVersion,
+};
+
+use Vault::{
+  math,
+  oracle::Price,
+  strategy::{self, Context, StrategyProcessor, TransferAccounts},
+};
+
+use crate::{
+  error::AmmLpError,
+  instruction::{find_strategy_authority_address, AmmLpInstruction},
+  state::LpStrategy,
+};
+
+/// Accounts following the strategy account in `Deposit` & `Withdraw`.
+struct SwapAccounts<'a, 'b> {
+  swap: &'a AccountInfo<'b>,
+  swap_authority: &'a AccountInfo<'b>,
+  swap_token_a: &'a AccountInfo<'b>,
+  swap_token_b: &'a AccountInfo<'b>,
+  pool_mint: &'a AccountInfo<'b>,
+  pool_fee_account: &'a AccountInfo<'b>,
+  holding_account: &'a AccountInfo<'b>,
+  strategy_authority: &'a AccountInfo<'b>,
+  swap_program: &'a AccountInfo<'b>,
+  x_oracle: &'a AccountInfo<'b>,
+  other_oracle: &'a AccountInfo<'b>,
+}
+
+pub struct Processor;
+impl Processor {
+  pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+  ) -> ProgramResult {
+    strategy::process_strategy_instruction::<Self>(program_id, accounts, instruction_data)
+  }
+
+  fn process_initialize_strategy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_slippage_bps: u16,
+  ) -> ProgramResult {
+    msg!("Initializing strategy");
+    let account_info_iter = &mut accounts.iter();
+
+    let strategy_account = next_account_info(account_info_iter)?;
+    let admin = next_account_info(account_info_iter)?;
+    let swap = next_account_info(account_info_iter)?;
+    let x_token_mint = next_account_info(account_info_iter)?;
+    let holding_account = next_account_info(account_info_iter)?;
+    let other_token_mint = next_account_info(account_info_iter)?;
+    let x_oracle = next_account_info(account_info_iter)?;
+    let other_oracle = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+    if !admin.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if strategy_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if !rent.is_exempt(strategy_account.lamports(), strategy_account.data_len()) {
+      return Err(AmmLpError::NotRentExempt.into());
+    }
+    let mut strategy = LpStrategy::unpack_unchecked(&strategy_account.data.borrow())?;
+    if strategy.is_initialized() {
+      return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let swap_info = SwapVersion::unpack(&swap.data.borrow())?;
+    if !swap_info.is_initialized() || *swap_info.token_program_id() != spl_token::id() {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    }
+    // Peek values positions with the constant product formula.
+    if swap_info.swap_curve().curve_type != CurveType::ConstantProduct {
+      return Err(AmmLpError::UnsupportedCurve.into());
+    }
+    let (x_is_token_a, other_mint) = if swap_info.token_a_mint() == x_token_mint.key {
+      (true, swap_info.token_b_mint())
+    } else if swap_info.token_b_mint() == x_token_mint.key {
+      (false, swap_info.token_a_mint())
+    } else {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    };
+    let (authority, bump_seed) = find_strategy_authority_address(program_id, strategy_account.key);
+    if holding_account.owner != &spl_token::id() {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    let holding = TokenAccount::unpack(&holding_account.data.borrow())?;
+    if holding.mint != *other_mint {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    }
+    if holding.owner != authority {
+      return Err(AmmLpError::InvalidAuthority.into());
+    }
+    if other_token_mint.key != other_mint {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    }
+    if max_slippage_bps as u64 > math::MAX_BPS {
+      return Err(ProgramError::InvalidArgument);
+    }
+    // Both must be price accounts, though they needn't be current yet.
+    Price::load(x_oracle)?;
+    Price::load(other_oracle)?;
+
+    strategy.is_initialized = true;
+    strategy.bump_seed = bump_seed;
+    strategy.admin = *admin.key;
+    strategy.swap_program_id = *swap.owner;
+    strategy.swap = *swap.key;
+    strategy.swap_token_a = *swap_info.token_a_account();
+    strategy.swap_token_b = *swap_info.token_b_account();
+    strategy.x_is_token_a = x_is_token_a;
+    strategy.pool_mint = *swap_info.pool_mint();
+    strategy.pool_fee_account = *swap_info.pool_fee_account();
+    strategy.holding_account = *holding_account.key;
+    strategy.exited = false;
+    strategy.x_oracle = *x_oracle.key;
+    strategy.other_oracle = *other_oracle.key;
+    strategy.x_decimals = Mint::unpack(&x_token_mint.data.borrow())?.decimals;
+    strategy.other_decimals = Mint::unpack(&other_token_mint.data.borrow())?.decimals;
+    strategy.max_slippage_bps = max_slippage_bps;
+    LpStrategy::pack(strategy, &mut strategy_account.data.borrow_mut())
+  }
+
+  /// Checks the accounts following the strategy account in `Deposit` & `Withdraw`.
+  fn swap_accounts<'a, 'b>(
+    ctx: &Context<'a, 'b, LpStrategy>,
+    accounts: &TransferAccounts<'a, 'b>,
+  ) -> Result<SwapAccounts<'a, 'b>, ProgramError> {
+    let account_info_iter = &mut ctx.remaining_accounts.iter();
+    let swap_accounts = SwapAccounts {
+      swap: next_account_info(account_info_iter)?,
+      swap_authority: next_account_info(account_info_iter)?,
+      swap_token_a: next_account_info(account_info_iter)?,
+      swap_token_b: next_account_info(account_info_iter)?,
+      pool_mint: next_account_info(account_info_iter)?,
+      pool_fee_account: next_account_info(account_info_iter)?,
+      holding_account: next_account_info(account_info_iter)?,
+      strategy_authority: next_account_info(account_info_iter)?,
+      swap_program: next_account_info(account_info_iter)?,
+      x_oracle: next_account_info(account_info_iter)?,
+      other_oracle: next_account_info(account_info_iter)?,
+    };
+
+    if *accounts.token_program.key != spl_token::id()
+      || *swap_accounts.swap_program.key != ctx.data.swap_program_id
+    {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    // The token-swap program checks its own accounts against the swap.
+    if *swap_accounts.swap.key != ctx.data.swap
+      || *swap_accounts.holding_account.key != ctx.data.holding_account
+      || *swap_accounts.swap_token_a.key != ctx.data.swap_token_a
+      || *swap_accounts.swap_token_b.key != ctx.data.swap_token_b
+      || *swap_accounts.pool_mint.key != ctx.data.pool_mint
+    {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    }
+    if *swap_accounts.x_oracle.key != ctx.data.x_oracle
+      || *swap_accounts.other_oracle.key != ctx.data.other_oracle
+    {
+      return Err(AmmLpError::IncorrectOracle.into());
+    }
+    let authority = Pubkey::create_program_address(
+      &[ctx.data_account.key.as_ref(), &[ctx.data.bump_seed]],
+      ctx.program_id,
+    )?;
+    if *swap_accounts.strategy_authority.key != authority {
+      return Err(AmmLpError::InvalidAuthority.into());
+    }
+    Ok(swap_accounts)
+  }
+
+  /// Reads the pool's X & other token reserves and LP supply, checking its price against the
+  /// oracles' so that a pool pushed off its price, e.g. earlier in the same transaction, is
+  /// neither traded against nor valued.
+  fn load_pool(
+    data: &LpStrategy,
+    swap_token_a: &AccountInfo,
+    swap_token_b: &AccountInfo,
+    pool_mint: &AccountInfo,
+    x_oracle: &AccountInfo,
+    other_oracle: &AccountInfo,
+  ) -> Result<Pool, ProgramError> {
+    let token_a_amount = TokenAccount::unpack(&swap_token_a.data.borrow())?.amount;
+    let token_b_amount = TokenAccount::unpack(&swap_token_b.data.borrow())?.amount;
+    let (x_reserve, other_reserve) = if data.x_is_token_a {
+      (token_a_amount, token_b_amount)
+    } else {
+      (token_b_amount, token_a_amount)
+    };
+    let pool_supply = Mint::unpack(&pool_mint.data.borrow())?.supply;
+
+    let slot = Clock::get()?.slot;
+    let pool = Pool {
+      x_reserve,
+      other_reserve,
+      pool_supply,
+      x_price: Price::load_current(x_oracle, slot)?,
+      other_price: Price::load_current(other_oracle, slot)?,
+    };
+    // The X the oracles value the other token's reserve at, which the pool prices at its X reserve.
+    let fair_x_reserve = pool
+      .other_in_x(data, other_reserve)
+      .ok_or(AmmLpError::MathOverflow)?;
+    let max_deviation =
+      math::bps_of(fair_x_reserve, data.max_slippage_bps as u64).ok_or(AmmLpError::MathOverflow)?;
+    let deviation = x_reserve.max(fair_x_reserve) - x_reserve.min(fair_x_reserve);
+    if deviation > max_deviation {
+      msg!(
+        "Pool values its other reserve at {} X, oracles at {} X",
+        x_reserve,
+        fair_x_reserve
+      );
+      return Err(AmmLpError::PoolPriceDeviation.into());
+    }
+    Ok(pool)
+  }
+}
+
+/// A pool's state, as of a `Processor::load_pool`.
+struct Pool {
+  x_reserve: u64,
+  other_reserve: u64,
+  pool_supply: u64,
+  x_price: Price,
+  other_price: Price,
+}
+
+impl Pool {
+  /// Values `amount` of the other token in X at the oracles' prices.
+  fn other_in_x(&self, data: &LpStrategy, amount: u64) -> Option<u64> {
+    self
+      .other_price
+      .value_in(amount, data.other_decimals, &self.x_price, data.x_decimals)
+  }
+}
+
+impl StrategyProcessor for Processor {
+  type Data = LpStrategy;
+
+  fn deposit<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LpStrategy>,
+    accounts: &TransferAccounts<'a, 'b>,
+    amount: u64,
+  ) -> ProgramResult {
+    if ctx.data.exited {
+      return Err(AmmLpError::StrategyExited.into());
+    }
+    let swap_accounts = Self::swap_accounts(ctx, accounts)?;
+    let pool = Self::load_pool(
+      &ctx.data,
+      swap_accounts.swap_token_a,
+      swap_accounts.swap_token_b,
+      swap_accounts.pool_mint,
+      swap_accounts.x_oracle,
+      swap_accounts.other_oracle,
+    )?;
+    let minimum_pool_token_amount = LpStrategy::lp_for_x(amount, pool.pool_supply, pool.x_reserve)
+      .and_then(|lp_amount| ctx.data.less_slippage(lp_amount))
+      .ok_or(AmmLpError::MathOverflow)?;
+    msg!(
+      "Depositing {} X single-sided for at least {} LP tokens",
+      amount,
+      minimum_pool_token_amount
+    );
+
+    invoke(
+      &spl_token_swap::instruction::deposit_single_token_type_exact_amount_in(
+        swap_accounts.swap_program.key,
+        accounts.token_program.key,
+        swap_accounts.swap.key,
+        swap_accounts.swap_authority.key,
+        accounts.source_authority.key,
+        accounts.source.key,
+        swap_accounts.swap_token_a.key,
+        swap_accounts.swap_token_b.key,
+        swap_accounts.pool_mint.key,
+        accounts.target.key,
+        DepositSingleTokenTypeExactAmountIn {
+          source_token_amount: amount,
+          minimum_pool_token_amount,
+        },
+      )?,
+      &[
+        swap_accounts.swap.clone(),
+        swap_accounts.swap_authority.clone(),
+        accounts.source_authority.clone(),
+        accounts.source.clone(),
+        swap_accounts.swap_token_a.clone(),
+        swap_accounts.swap_token_b.clone(),
+        swap_accounts.pool_mint.clone(),
+        accounts.target.clone(),
+        accounts.token_program.clone(),
+        swap_accounts.swap_program.clone(),
+      ],
+    )
+  }
+
+  fn withdraw<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LpStrategy>,
+    accounts: &TransferAccounts<'a, 'b>,
+    amount: u64,
+  ) -> ProgramResult {
+    let swap_accounts = Self::swap_accounts(ctx, accounts)?;
+    let pool = Self::load_pool(
+      &ctx.data,
+      swap_accounts.swap_token_a,
+      swap_accounts.swap_token_b,
+      swap_accounts.pool_mint,
+      swap_accounts.x_oracle,
+      swap_accounts.other_oracle,
+    )?;
+    let (x_destination, other_destination) = if ctx.data.x_is_token_a {
+      (accounts.target, swap_accounts.holding_account)
+    } else {
+      (swap_accounts.holding_account, accounts.target)
+    };
+    // The pool pays out its reserves in proportion to the LP tokens burnt.
+    let minimum_out = |reserve| {
+      math::mul_div(amount, reserve, pool.pool_supply)
+        .and_then(|out| ctx.data.less_slippage(out))
+        .ok_or(AmmLpError::MathOverflow)
+    };
+    let minimum_x_amount = minimum_out(pool.x_reserve)?;
+    let minimum_other_amount = minimum_out(pool.other_reserve)?;
+    let (minimum_token_a_amount, minimum_token_b_amount) = if ctx.data.x_is_token_a {
+      (minimum_x_amount, minimum_other_amount)
+    } else {
+      (minimum_other_amount, minimum_x_amount)
+    };
+    msg!("Withdrawing {} LP tokens", amount);
+
+    invoke(
+      &spl_token_swap::instruction::withdraw_all_token_types(
+        swap_accounts.swap_program.key,
+        accounts.token_program.key,
+        swap_accounts.swap.key,
+        swap_accounts.swap_authority.key,
+        accounts.source_authority.key,
+        swap_accounts.pool_mint.key,
+        swap_accounts.pool_fee_account.key,
+        accounts.source.key,
+        swap_accounts.swap_token_a.key,
+        swap_accounts.swap_token_b.key,
+        x_destination.key,
+        other_destination.key,
+        WithdrawAllTokenTypes {
+          pool_token_amount: amount,
+          minimum_token_a_amount,
+          minimum_token_b_amount,
+        },
+      )?,
+      &[
+        swap_accounts.swap.clone(),
+        swap_accounts.swap_authority.clone(),
+        accounts.source_authority.clone(),
+        swap_accounts.pool_mint.clone(),
+        accounts.source.clone(),
+        swap_accounts.swap_token_a.clone(),
+        swap_accounts.swap_token_b.clone(),
+        x_destination.clone(),
+        other_destination.clone(),
+        swap_accounts.pool_fee_account.clone(),
+        accounts.token_program.clone(),
+        swap_accounts.swap_program.clone(),
+      ],
+    )?;
+
+    // Swap everything held of the other token, including any dust from earlier withdrawals.
+    let other_amount = TokenAccount::unpack(&swap_accounts.holding_account.data.borrow())?.amount;
+    if other_amount == 0 {
+      return Ok(());
+    }
+    let (swap_source, swap_destination) = if ctx.data.x_is_token_a {
+      (swap_accounts.swap_token_b, swap_accounts.swap_token_a)
+    } else {
+      (swap_accounts.swap_token_a, swap_accounts.swap_token_b)
+    };
+    // The oracles, rather than the pool, bound what the swap must return.
+    let minimum_amount_out = pool
+      .other_in_x(&ctx.data, other_amount)
+      .and_then(|x_amount| ctx.data.less_slippage(x_amount))
+      .ok_or(AmmLpError::MathOverflow)?;
+    msg!(
+      "Swapping {} of the other token into at least {} X",
+      other_amount,
+      minimum_amount_out
+    );
+    invoke_signed(
+      &spl_token_swap::instruction::swap(
+        swap_accounts.swap_program.key,
+        accounts.token_program.key,
+        swap_accounts.swap.key,
+        swap_accounts.swap_authority.key,
+        swap_accounts.strategy_authority.key,
+        swap_accounts.holding_account.key,
+        swap_source.key,
+        swap_destination.key,
+        accounts.target.key,
+        swap_accounts.pool_mint.key,
+        swap_accounts.pool_fee_account.key,
+        None,
+        Swap {
+          amount_in: other_amount,
+          minimum_amount_out,
+        },
+      )?,
+      &[
+        swap_accounts.swap.clone(),
+        swap_accounts.swap_authority.clone(),
+        swap_accounts.strategy_authority.clone(),
+        swap_accounts.holding_account.clone(),
+        swap_source.clone(),
+        swap_destination.clone(),
+        accounts.target.clone(),
+        swap_accounts.pool_mint.clone(),
+        swap_accounts.pool_fee_account.clone(),
+        accounts.token_program.clone(),
+        swap_accounts.swap_program.clone(),
+      ],
+      &[&[ctx.data_account.key.as_ref(), &[ctx.data.bump_seed]]],
+    )
+  }
+
+  /// Values the LP tokens at the pool's spot reserves, which `load_pool` only accepts while the
+  /// pool's price is within `max_slippage_bps` of the oracles'.
+  fn peek(ctx: &Context<LpStrategy>, amount: u64) -> Result<u64, ProgramError> {
+    let account_info_iter = &mut ctx.remaining_accounts.iter();
+    let swap_token_a = next_account_info(account_info_iter)?;
+    let swap_token_b = next_account_info(account_info_iter)?;
+    let pool_mint = next_account_info(account_info_iter)?;
+    let x_oracle = next_account_info(account_info_iter)?;
+    let other_oracle = next_account_info(account_info_iter)?;
+    if *swap_token_a.key != ctx.data.swap_token_a
+      || *swap_token_b.key != ctx.data.swap_token_b
+      || *pool_mint.key != ctx.data.pool_mint
+    {
+      return Err(AmmLpError::IncorrectSwapAccount.into());
+    }
+    if *x_oracle.key != ctx.data.x_oracle || *other_oracle.key != ctx.data.other_oracle {
+      return Err(AmmLpError::IncorrectOracle.into());
+    }
+    let pool = Self::load_pool(
+      &ctx.data,
+      swap_token_a,
+      swap_token_b,
+      pool_mint,
+      x_oracle,
+      other_oracle,
+    )?;
+    Ok(
+      LpStrategy::x_for_lp(amount, pool.pool_supply, pool.x_reserve, pool.other_reserve)
+        .ok_or(AmmLpError::MathOverflow)?,
+    )
+  }
+
+  fn harvest(_ctx: &mut Context<LpStrategy>) -> ProgramResult {
+    // Trading fees accrue to the pool's reserves, and so to the LP tokens, without harvesting.
+    msg!("Nothing to harvest");
+    Ok(())
+  }
+
+  fn emergency_exit<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LpStrategy>,
+    authority: &'a AccountInfo<'b>,
+  ) -> ProgramResult {
+    if *authority.key != ctx.data.admin {
+      return Err(AmmLpError::InvalidAuthority.into());
+    }
+    // LP tokens are held by depositors, who can still withdraw; only new deposits are stopped.
+    ctx.data.exited = true;
+    Ok(())
+  }
+
+  fn process_custom_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+  ) -> ProgramResult {
+    match AmmLpInstruction::unpack(instruction_data)? {
+      AmmLpInstruction::InitializeStrategy { max_slippage_bps } => {
+        msg!("Instruction: InitializeStrategy");
+        Self::process_initialize_strategy(program_id, accounts, max_slippage_bps)
+      }
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+use Vault::math;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LpStrategy {
+    pub is_initialized: bool,
+    /// Bump seed of the strategy authority, derived from the strategy's address.
+    pub bump_seed: u8,
+    /// May trigger an emergency exit.
+    pub admin: Pubkey,
+    /// The token-swap program owning `swap`.
+    pub swap_program_id: Pubkey,
+    /// The token-swap account of the pool.
+    pub swap: Pubkey,
+    pub swap_token_a: Pubkey,
+    pub swap_token_b: Pubkey,
+    /// Whether X is the pool's token A, rather than B.
+    pub x_is_token_a: bool,
+    /// The pool's LP token, used as lX.
+    pub pool_mint: Pubkey,
+    pub pool_fee_account: Pubkey,
+    /// Account owned by the strategy authority, holding the pool's other token while unwinding.
+    pub holding_account: Pubkey,
+    /// Set by an emergency exit, after which deposits are refused.
+    pub exited: bool,
+    /// Price account of X, in the layout of Pyth's.
+    pub x_oracle: Pubkey,
+    /// Price account of the pool's other token, priced in the same currency as X.
+    pub other_oracle: Pubkey,
+    pub x_decimals: u8,
+    pub other_decimals: u8,
+    /// How far, in bps, the pool's price may stray from the oracles' before the strategy refuses
+    /// to trade or value positions, and how much deposits & withdrawals may lose to fees & price
+    /// impact.
+    pub max_slippage_bps: u16,
+}
+
+impl LpStrategy {
+    /// Returns the X an LP position of `amount` out of `pool_supply` would unwind into, given the
+    /// pool's X & other token reserves, ignoring fees: the X side of the withdrawal plus the
+    /// other side swapped into the remaining constant product pool.
+    pub fn x_for_lp(amount: u64, pool_supply: u64, x_reserve: u64, other_reserve: u64) -> Option<u64> {
+        if pool_supply == 0 || other_reserve == 0 {
+            return Some(0);
+        }
+        let x_out = (amount as u128)
+            .checked_mul(x_reserve as u128)?
+            .checked_div(pool_supply as u128)?;
+        let other_out = (amount as u128)
+            .checked_mul(other_reserve as u128)?
+            .checked_div(pool_supply as u128)?;
+        let swapped = (x_reserve as u128)
+            .checked_sub(x_out)?
+            .checked_mul(other_out)?
+            .checked_div(other_reserve as u128)?;
+        x_out.checked_add(swapped)?.try_into().ok()
+    }
+
+    /// Returns the LP tokens out of `pool_supply` a single-sided deposit of `amount` X mints,
+    /// given the pool's X reserve, ignoring fees: the constant product curve mints
+    /// `pool_supply * (sqrt(1 + amount / x_reserve) - 1)`.
+    pub fn lp_for_x(amount: u64, pool_supply: u64, x_reserve: u64) -> Option<u64> {
+        if x_reserve == 0 {
+            return Some(0);
+        }
+        let x_reserve = x_reserve as u128;
+        let root = isqrt(
+            x_reserve
+                .checked_add(amount as u128)?
+                .checked_mul(x_reserve)?,
+        );
+        (pool_supply as u128)
+            .checked_mul(root.checked_sub(x_reserve)?)?
+            .checked_div(x_reserve)?
+            .try_into()
+            .ok()
+    }
+
+    /// Returns `amount` less `max_slippage_bps` of it, the least a trade expected to yield
+    /// `amount` may yield.
+    pub fn less_slippage(&self, amount: u64) -> Option<u64> {
+        let slippage = math::bps_of(amount, self.max_slippage_bps as u64)?;
+        amount.checked_sub(slippage)
+    }
+}
+
+/// Integer square root, rounded down.
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = (x + n / x) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+impl Sealed for LpStrategy {}
+
+impl Pack for LpStrategy {
+    const LEN: usize = 1 + 1 + 32 + 32 + 32 + 32 + 32 + 1 + 32 + 32 + 32 + 1 + 32 + 32 + 1 + 1 + 2;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, LpStrategy::LEN];
+        let (
+            is_initialized,
+            bump_seed,
+            admin,
+            swap_program_id,
+            swap,
+            swap_token_a,
+            swap_token_b,
+            x_is_token_a,
+            pool_mint,
+            pool_fee_account,
+            holding_account,
+            exited,
+            x_oracle,
+            other_oracle,
+            x_decimals,
+            other_decimals,
+            max_slippage_bps,
+        ) = array_refs![src, 1, 1, 32, 32, 32, 32, 32, 1, 32, 32, 32, 1, 32, 32, 1, 1, 2];
+
+        let unpack_bool = |src: &[u8; 1]| match src {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+        Ok(LpStrategy {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            admin: Pubkey::new_from_array(*admin),
+            swap_program_id: Pubkey::new_from_array(*swap_program_id),
+            swap: Pubkey::new_from_array(*swap),
+            swap_token_a: Pubkey::new_from_array(*swap_token_a),
+            swap_token_b: Pubkey::new_from_array(*swap_token_b),
+            x_is_token_a: unpack_bool(x_is_token_a)?,
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            holding_account: Pubkey::new_from_array(*holding_account),
+            exited: unpack_bool(exited)?,
+            x_oracle: Pubkey::new_from_array(*x_oracle),
+            other_oracle: Pubkey::new_from_array(*other_oracle),
+            x_decimals: x_decimals[0],
+            other_decimals: other_decimals[0],
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, LpStrategy::LEN];
+        let (
+            is_initialized_dst,
+            bump_seed_dst,
+            admin_dst,
+            swap_program_id_dst,
+            swap_dst,
+            swap_token_a_dst,
+            swap_token_b_dst,
+            x_is_token_a_dst,
+            pool_mint_dst,
+            pool_fee_account_dst,
+            holding_account_dst,
+            exited_dst,
+            x_oracle_dst,
+            other_oracle_dst,
+            x_decimals_dst,
+            other_decimals_dst,
+            max_slippage_bps_dst,
+        ) = mut_array_refs![dst, 1, 1, 32, 32, 32, 32, 32, 1, 32, 32, 32, 1, 32, 32, 1, 1, 2];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        bump_seed_dst[0] = self.bump_seed;
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        swap_program_id_dst.copy_from_slice(self.swap_program_id.as_ref());
+        swap_dst.copy_from_slice(self.swap.as_ref());
+        swap_token_a_dst.copy_from_slice(self.swap_token_a.as_ref());
+        swap_token_b_dst.copy_from_slice(self.swap_token_b.as_ref());
+        x_is_token_a_dst[0] = self.x_is_token_a as u8;
+        pool_mint_dst.copy_from_slice(self.pool_mint.as_ref());
+        pool_fee_account_dst.copy_from_slice(self.pool_fee_account.as_ref());
+        holding_account_dst.copy_from_slice(self.holding_account.as_ref());
+        exited_dst[0] = self.exited as u8;
+        x_oracle_dst.copy_from_slice(self.x_oracle.as_ref());
+        other_oracle_dst.copy_from_slice(self.other_oracle.as_ref());
+        x_decimals_dst[0] = self.x_decimals;
+        other_decimals_dst[0] = self.other_decimals;
+        *max_slippage_bps_dst = self.max_slippage_bps.to_le_bytes();
+    }
+}
+
+impl IsInitialized for LpStrategy {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
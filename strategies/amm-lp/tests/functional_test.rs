@@ -0,0 +1,681 @@
+#![cfg(feature = "test-bpf")]
+
+use {
+  ::Vault::{instruction::StrategyInstruction, oracle, state::PeekResult},
+  amm_lp_strategy::{
+    instruction::{
+      find_strategy_authority_address, find_swap_authority_address, peek_account_metas,
+      transfer_account_metas, AmmLpInstruction,
+    },
+    processor::Processor,
+    state::LpStrategy,
+  },
+  assert_matches::*,
+  mock_price::instruction::MockPriceInstruction,
+  solana_program::{
+    program_pack::Pack, pubkey::Pubkey, rent::Rent, system_instruction,
+  },
+  solana_program_test::{processor, ProgramTest, ProgramTestContext},
+  solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+  },
+  spl_token_swap::{
+    curve::{
+      base::{CurveType, SwapCurve},
+      constant_product::ConstantProductCurve,
+      fees::Fees,
+    },
+    instruction::Swap,
+    state::SwapVersion,
+  },
+};
+
+/// Initial reserves of each of the pool's tokens.
+const RESERVE: u64 = 1_000_000;
+/// How far the pool's price may be from the oracles', and how much trades may lose.
+const MAX_SLIPPAGE_BPS: u16 = 100;
+
+struct Pool {
+  swap: Keypair,
+  x_mint: Keypair,
+  other_mint: Keypair,
+  x_reserve: Keypair,
+  other_reserve: Keypair,
+  pool_mint: Keypair,
+  pool_fee_account: Keypair,
+}
+
+struct Strategy {
+  strategy: Keypair,
+  holding_account: Keypair,
+  x_oracle: Keypair,
+  other_oracle: Keypair,
+}
+
+/// Tests depositing X single-sided into a pool, peeking at its value & unwinding it.
+#[tokio::test]
+async fn test_amm_lp_strategy() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context).await;
+  let strategy = create_strategy(&mut program_test_context, &pool).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.pool_mint.pubkey(), &payer)
+    .await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+
+  process(
+    &mut program_test_context,
+    StrategyInstruction::deposit(
+      &amm_lp_strategy::id(),
+      &spl_token::id(),
+      &client_x.pubkey(),
+      &client_lx.pubkey(),
+      &payer,
+      &strategy.strategy.pubkey(),
+      swap_account_metas(&pool, &strategy),
+      1000,
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let lx_amount = token_balance(&mut program_test_context, &client_lx.pubkey()).await;
+  assert!(lx_amount > 0);
+  assert_eq!(token_balance(&mut program_test_context, &client_x.pubkey()).await, 0);
+
+  // Peek writes the X-equivalent value of the LP tokens into a result account.
+  let result_account = Keypair::new();
+  let rent = Rent::default().minimum_balance(PeekResult::LEN);
+  let vault = Pubkey::new_unique();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &result_account.pubkey(),
+        rent,
+        PeekResult::LEN as u64,
+        &amm_lp_strategy::id(),
+      ),
+      StrategyInstruction::peek(
+        &amm_lp_strategy::id(),
+        &result_account.pubkey(),
+        &strategy.strategy.pubkey(),
+        peek_account_metas(
+          &pool.x_reserve.pubkey(),
+          &pool.other_reserve.pubkey(),
+          &pool.pool_mint.pubkey(),
+          &strategy.x_oracle.pubkey(),
+          &strategy.other_oracle.pubkey(),
+        ),
+        lx_amount,
+        &vault,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &result_account],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let result = PeekResult::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(result_account.pubkey())
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap();
+  assert_eq!(result.data_account, strategy.strategy.pubkey());
+  assert_eq!(result.vault, vault);
+  assert_eq!(result.lx_amount, lx_amount);
+  // Swapping half of the deposit costs trading fees & price impact.
+  assert!(result.x_amount > 990 && result.x_amount <= 1000);
+
+  process(
+    &mut program_test_context,
+    StrategyInstruction::withdraw(
+      &amm_lp_strategy::id(),
+      &spl_token::id(),
+      &client_lx.pubkey(),
+      &client_x.pubkey(),
+      &payer,
+      &strategy.strategy.pubkey(),
+      swap_account_metas(&pool, &strategy),
+      lx_amount,
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+  assert_eq!(token_balance(&mut program_test_context, &client_lx.pubkey()).await, 0);
+  assert_eq!(
+    token_balance(&mut program_test_context, &strategy.holding_account.pubkey()).await,
+    0
+  );
+  let x_amount = token_balance(&mut program_test_context, &client_x.pubkey()).await;
+  assert!(x_amount > 980 && x_amount <= result.x_amount);
+}
+
+/// Tests that only the admin may exit a strategy, after which deposits are refused.
+#[tokio::test]
+async fn test_amm_lp_strategy_emergency_exit() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context).await;
+  let strategy = create_strategy(&mut program_test_context, &pool).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let impostor = Keypair::new();
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::emergency_exit(
+        &amm_lp_strategy::id(),
+        &impostor.pubkey(),
+        &strategy.strategy.pubkey(),
+        vec![],
+      )
+      .unwrap(),
+      &[&impostor],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(4) // AmmLpError::InvalidAuthority
+    )))
+  );
+  process(
+    &mut program_test_context,
+    StrategyInstruction::emergency_exit(
+      &amm_lp_strategy::id(),
+      &payer,
+      &strategy.strategy.pubkey(),
+      vec![],
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.pool_mint.pubkey(), &payer)
+    .await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::deposit(
+        &amm_lp_strategy::id(),
+        &spl_token::id(),
+        &client_x.pubkey(),
+        &client_lx.pubkey(),
+        &payer,
+        &strategy.strategy.pubkey(),
+        swap_account_metas(&pool, &strategy),
+        1000,
+      )
+      .unwrap(),
+      &[],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(5) // AmmLpError::StrategyExited
+    )))
+  );
+}
+
+/// Tests that the strategy neither trades against nor values a pool pushed off the oracles' price.
+#[tokio::test]
+async fn test_amm_lp_strategy_rejects_manipulated_pool() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context).await;
+  let strategy = create_strategy(&mut program_test_context, &pool).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  let client_other = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.pool_mint.pubkey(), &payer)
+    .await;
+  create_token_account(
+    &mut program_test_context,
+    &client_other,
+    &pool.other_mint.pubkey(),
+    &payer,
+  )
+  .await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+  mint_to(
+    &mut program_test_context,
+    &pool.other_mint.pubkey(),
+    &client_other.pubkey(),
+    RESERVE / 10,
+  )
+  .await;
+
+  // Dumping the other token into the pool makes X some 20% dearer than the oracles price it.
+  process(
+    &mut program_test_context,
+    spl_token_swap::instruction::swap(
+      &spl_token_swap::id(),
+      &spl_token::id(),
+      &pool.swap.pubkey(),
+      &find_swap_authority_address(&spl_token_swap::id(), &pool.swap.pubkey()).0,
+      &payer,
+      &client_other.pubkey(),
+      &pool.other_reserve.pubkey(),
+      &pool.x_reserve.pubkey(),
+      &client_x.pubkey(),
+      &pool.pool_mint.pubkey(),
+      &pool.pool_fee_account.pubkey(),
+      None,
+      Swap {
+        amount_in: RESERVE / 10,
+        minimum_amount_out: 0,
+      },
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::deposit(
+        &amm_lp_strategy::id(),
+        &spl_token::id(),
+        &client_x.pubkey(),
+        &client_lx.pubkey(),
+        &payer,
+        &strategy.strategy.pubkey(),
+        swap_account_metas(&pool, &strategy),
+        1000,
+      )
+      .unwrap(),
+      &[],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(7) // AmmLpError::PoolPriceDeviation
+    )))
+  );
+}
+
+fn program_test() -> ProgramTest {
+  let mut program_test = ProgramTest::new(
+    "amm_lp_strategy",
+    amm_lp_strategy::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "token_test",
+    spl_token::id(),
+    processor!(spl_token::processor::Processor::process),
+  );
+  program_test.add_program(
+    "spl_token_swap",
+    spl_token_swap::id(),
+    processor!(spl_token_swap::processor::Processor::process),
+  );
+  program_test.add_program(
+    "mock_price",
+    mock_price::id(),
+    processor!(mock_price::processor::Processor::process),
+  );
+  program_test
+}
+
+fn swap_account_metas(
+  pool: &Pool,
+  strategy: &Strategy,
+) -> Vec<solana_program::instruction::AccountMeta> {
+  transfer_account_metas(
+    &amm_lp_strategy::id(),
+    &strategy.strategy.pubkey(),
+    &spl_token_swap::id(),
+    &pool.swap.pubkey(),
+    &pool.x_reserve.pubkey(),
+    &pool.other_reserve.pubkey(),
+    &pool.pool_mint.pubkey(),
+    &pool.pool_fee_account.pubkey(),
+    &strategy.holding_account.pubkey(),
+    &strategy.x_oracle.pubkey(),
+    &strategy.other_oracle.pubkey(),
+  )
+}
+
+/// Creates a constant product pool with X as token A, each reserve holding `RESERVE`.
+async fn create_pool(program_test_context: &mut ProgramTestContext) -> Pool {
+  let pool = Pool {
+    swap: Keypair::new(),
+    x_mint: Keypair::new(),
+    other_mint: Keypair::new(),
+    x_reserve: Keypair::new(),
+    other_reserve: Keypair::new(),
+    pool_mint: Keypair::new(),
+    pool_fee_account: Keypair::new(),
+  };
+  let payer = program_test_context.payer.pubkey();
+  let (authority, nonce) = find_swap_authority_address(&spl_token_swap::id(), &pool.swap.pubkey());
+  create_mint(program_test_context, &pool.x_mint, &payer).await;
+  create_mint(program_test_context, &pool.other_mint, &payer).await;
+  create_mint(program_test_context, &pool.pool_mint, &authority).await;
+  create_token_account(program_test_context, &pool.x_reserve, &pool.x_mint.pubkey(), &authority)
+    .await;
+  create_token_account(
+    program_test_context,
+    &pool.other_reserve,
+    &pool.other_mint.pubkey(),
+    &authority,
+  )
+  .await;
+  create_token_account(
+    program_test_context,
+    &pool.pool_fee_account,
+    &pool.pool_mint.pubkey(),
+    &payer,
+  )
+  .await;
+  // The initial liquidity goes to the payer.
+  let initial_lp = Keypair::new();
+  create_token_account(program_test_context, &initial_lp, &pool.pool_mint.pubkey(), &payer).await;
+  mint_to(program_test_context, &pool.x_mint.pubkey(), &pool.x_reserve.pubkey(), RESERVE).await;
+  mint_to(
+    program_test_context,
+    &pool.other_mint.pubkey(),
+    &pool.other_reserve.pubkey(),
+    RESERVE,
+  )
+  .await;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &pool.swap.pubkey(),
+        Rent::default().minimum_balance(SwapVersion::LATEST_LEN),
+        SwapVersion::LATEST_LEN as u64,
+        &spl_token_swap::id(),
+      ),
+      spl_token_swap::instruction::initialize(
+        &spl_token_swap::id(),
+        &spl_token::id(),
+        &pool.swap.pubkey(),
+        &authority,
+        &pool.x_reserve.pubkey(),
+        &pool.other_reserve.pubkey(),
+        &pool.pool_mint.pubkey(),
+        &pool.pool_fee_account.pubkey(),
+        &initial_lp.pubkey(),
+        nonce,
+        Fees {
+          trade_fee_numerator: 25,
+          trade_fee_denominator: 10000,
+          ..Fees::default()
+        },
+        SwapCurve {
+          curve_type: CurveType::ConstantProduct,
+          calculator: Box::new(ConstantProductCurve),
+        },
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &pool.swap],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  pool
+}
+
+/// Creates a strategy over `pool` with X as its token A, administered by the payer, whose oracles
+/// price both of the pool's tokens at 1.
+async fn create_strategy(program_test_context: &mut ProgramTestContext, pool: &Pool) -> Strategy {
+  let strategy = Strategy {
+    strategy: Keypair::new(),
+    holding_account: Keypair::new(),
+    x_oracle: Keypair::new(),
+    other_oracle: Keypair::new(),
+  };
+  let payer = program_test_context.payer.pubkey();
+  let (authority, _) =
+    find_strategy_authority_address(&amm_lp_strategy::id(), &strategy.strategy.pubkey());
+  create_token_account(
+    program_test_context,
+    &strategy.holding_account,
+    &pool.other_mint.pubkey(),
+    &authority,
+  )
+  .await;
+
+  let mut instructions = vec![];
+  for price_account in [&strategy.x_oracle, &strategy.other_oracle].iter() {
+    instructions.push(system_instruction::create_account(
+      &payer,
+      &price_account.pubkey(),
+      Rent::default().minimum_balance(mock_price::PRICE_ACCOUNT_LEN),
+      mock_price::PRICE_ACCOUNT_LEN as u64,
+      &mock_price::id(),
+    ));
+    instructions.push(
+      MockPriceInstruction::set_price(
+        &mock_price::id(),
+        &price_account.pubkey(),
+        &payer,
+        100_000_000,
+        100_000,
+        -8,
+        oracle::STATUS_TRADING,
+      )
+      .unwrap(),
+    );
+  }
+  instructions.push(system_instruction::create_account(
+    &payer,
+    &strategy.strategy.pubkey(),
+    Rent::default().minimum_balance(LpStrategy::LEN),
+    LpStrategy::LEN as u64,
+    &amm_lp_strategy::id(),
+  ));
+  instructions.push(
+    AmmLpInstruction::initialize_strategy(
+      &amm_lp_strategy::id(),
+      &strategy.strategy.pubkey(),
+      &payer,
+      &pool.swap.pubkey(),
+      &pool.x_mint.pubkey(),
+      &strategy.holding_account.pubkey(),
+      &pool.other_mint.pubkey(),
+      &strategy.x_oracle.pubkey(),
+      &strategy.other_oracle.pubkey(),
+      MAX_SLIPPAGE_BPS,
+    )
+    .unwrap(),
+  );
+  let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer));
+  transaction.sign(
+    &[
+      &program_test_context.payer,
+      &strategy.x_oracle,
+      &strategy.other_oracle,
+      &strategy.strategy,
+    ],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  strategy
+}
+
+async fn create_mint(
+  program_test_context: &mut ProgramTestContext,
+  mint: &Keypair,
+  mint_authority: &Pubkey,
+) {
+  let payer = program_test_context.payer.pubkey();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_mint(
+        &spl_token::id(),
+        &mint.pubkey(),
+        mint_authority,
+        None, // Freeze authority
+        6,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, mint],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+}
+
+async fn create_token_account(
+  program_test_context: &mut ProgramTestContext,
+  token_account: &Keypair,
+  mint: &Pubkey,
+  owner: &Pubkey,
+) {
+  let payer = program_test_context.payer.pubkey();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &token_account.pubkey(),
+        Rent::default().minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &token_account.pubkey(),
+        mint,
+        owner,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, token_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+}
+
+/// Mints tokens whose mint authority is the payer.
+async fn mint_to(
+  program_test_context: &mut ProgramTestContext,
+  mint: &Pubkey,
+  account: &Pubkey,
+  amount: u64,
+) {
+  let payer = program_test_context.payer.pubkey();
+  process(
+    program_test_context,
+    spl_token::instruction::mint_to(&spl_token::id(), mint, account, &payer, &[], amount).unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+}
+
+/// Processes `instruction`, paid for & signed by the payer and `signers`.
+async fn process(
+  program_test_context: &mut ProgramTestContext,
+  instruction: solana_program::instruction::Instruction,
+  signers: &[&Keypair],
+) -> Result<(), TransportError> {
+  let recent_blockhash = program_test_context
+    .banks_client
+    .get_recent_blockhash()
+    .await
+    .unwrap();
+  let mut transaction =
+    Transaction::new_with_payer(&[instruction], Some(&program_test_context.payer.pubkey()));
+  let mut all_signers = vec![&program_test_context.payer];
+  all_signers.extend(signers);
+  transaction.sign(&all_signers, recent_blockhash);
+  program_test_context
+    .banks_client
+    .process_transaction(transaction)
+    .await
+}
+
+async fn token_balance(program_test_context: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+  spl_token::state::Account::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(*account)
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap()
+  .amount
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+use solana_program::program_error::ProgramError;
+
+#[derive(Error, Debug, Copy, Clone)]
+pub enum LendingError {
+    #[error("Invalid Instruction")]
+    InvalidInstruction,
+    #[error("Not Rent Exempt")]
+    NotRentExempt,
+    #[error("Math Overflow")]
+    MathOverflow,
+    #[error("Incorrect Pool Account")]
+    IncorrectPoolAccount,
+    #[error("Invalid Pool Authority")]
+    InvalidAuthority,
+    #[error("Pool Exited")]
+    PoolExited,
+}
+
+impl From<LendingError> for ProgramError {
+    fn from(e: LendingError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,117 @@
+//! Instructions specific to the lending strategy. The strategy interface itself is
+//! `Vault::instruction::StrategyInstruction`, with the following accounts expected after the
+//! pool account:
+//!
+//! Deposit & Withdraw:
+//! 1. `[writeable]` The pool's X reserve.
+//! 2. `[writeable]` The lX Token ID.
+//! 3. `[]` The pool authority.
+//!
+//! Peek & Harvest:
+//! 1. `[]` The pool's X reserve.
+//! 2. `[]` The lX Token ID.
+//!
+//! Interest is only paid as far as it's been repaid into the X reserve, which caps the exchange
+//! rate. EmergencyExit must be signed by the pool's admin.
+
+use crate::error::LendingError::InvalidInstruction;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
+use std::convert::TryInto;
+use std::mem::size_of;
+
+/// Tag of the first lending instruction, above those of the strategy interface.
+const INSTRUCTION_TAG_OFFSET: u8 = 128;
+
+pub enum LendingInstruction {
+    /// Initializes a lending pool.
+    ///
+    /// Accounts expected:
+    /// 1. `[writeable]` The rent exempt pool account, owned by this program.
+    /// 2. `[signer]` The pool's admin.
+    /// 3. `[]` The X Token ID.
+    /// 4. `[]` The lX Token ID, whose mint authority is the pool authority.
+    /// 5. `[]` The X reserve token account, owned by the pool authority.
+    /// 6. `[]` The rent sysvar
+    InitializePool {
+        /// Interest accrued per slot, scaled by `state::RATE_SCALE`.
+        rate_per_slot: u64,
+    },
+}
+
+impl LendingInstruction {
+    /// Unpacks a byte buffer into a [LendingInstruction](enum.LendingInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+
+        Ok(match tag.checked_sub(INSTRUCTION_TAG_OFFSET) {
+            Some(0) => {
+                let rate_per_slot = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::InitializePool { rate_per_slot }
+            }
+            _ => return Err(InvalidInstruction.into()),
+        })
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            &Self::InitializePool { rate_per_slot } => {
+                buf.push(INSTRUCTION_TAG_OFFSET);
+                buf.extend_from_slice(&rate_per_slot.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_pool(
+        program_id: &Pubkey,
+        pool: &Pubkey,
+        admin: &Pubkey,
+        x_token_mint_id: &Pubkey,
+        lx_token_mint_id: &Pubkey,
+        x_reserve: &Pubkey,
+        rate_per_slot: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(*pool, false),
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new_readonly(*x_token_mint_id, false),
+                AccountMeta::new_readonly(*lx_token_mint_id, false),
+                AccountMeta::new_readonly(*x_reserve, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+            data: Self::InitializePool { rate_per_slot }.pack(),
+        })
+    }
+}
+
+/// Authority owning a pool's X reserve & lX mint.
+pub fn find_pool_authority_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref()], program_id)
+}
+
+/// Accounts following the pool account in `Deposit` & `Withdraw`.
+pub fn transfer_account_metas(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    x_reserve: &Pubkey,
+    lx_token_mint_id: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*x_reserve, false),
+        AccountMeta::new(*lx_token_mint_id, false),
+        AccountMeta::new_readonly(find_pool_authority_address(program_id, pool).0, false),
+    ]
+}
@@ -0,0 +1,15 @@
+//! Reference strategy lending X out of a pool whose lX:X exchange rate accrues interest every slot
+//! at a configurable rate.
+//!
+//! The pool doesn't lend to anyone; interest is paid out of its X reserve, which has to be topped
+//! up by whoever plays the borrowers. It exists to exercise vaults against a strategy whose lX
+//! appreciates, e.g. under `solana-program-test`.
+
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+
+solana_program::declare_id!("2LN6P3Zwv9utphuMN9DJa1mRV8nwXcYdyT2zBg3YzwKf");
@@ -0,0 +1,285 @@
+use solana_program::{
+  account_info::{next_account_info, AccountInfo},
+  entrypoint::ProgramResult,
+  msg,
+  program::{invoke, invoke_signed},
+  program_error::ProgramError,
+  program_option::COption,
+  program_pack::{IsInitialized, Pack},
+  pubkey::Pubkey,
+  sysvar::{clock::Clock, rent::Rent, Sysvar},
+};
+
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use Vault::strategy::{self, Context, StrategyProcessor, TransferAccounts};
+
+use crate::{
+  error::LendingError,
+  instruction::{find_pool_authority_address, LendingInstruction},
+  state::{LendingPool, RATE_SCALE},
+};
+
+pub struct Processor;
+impl Processor {
+  pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+  ) -> ProgramResult {
+    strategy::process_strategy_instruction::<Self>(program_id, accounts, instruction_data)
+  }
+
+  fn process_initialize_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    rate_per_slot: u64,
+  ) -> ProgramResult {
+    msg!("Initializing pool");
+    let account_info_iter = &mut accounts.iter();
+
+    let pool_account = next_account_info(account_info_iter)?;
+    let admin = next_account_info(account_info_iter)?;
+    let x_token_mint = next_account_info(account_info_iter)?;
+    let lx_token_mint = next_account_info(account_info_iter)?;
+    let x_reserve = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+    if !admin.is_signer {
+      return Err(ProgramError::MissingRequiredSignature);
+    }
+    if pool_account.owner != program_id {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if !rent.is_exempt(pool_account.lamports(), pool_account.data_len()) {
+      return Err(LendingError::NotRentExempt.into());
+    }
+    let mut pool = LendingPool::unpack_unchecked(&pool_account.data.borrow())?;
+    if pool.is_initialized() {
+      return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let (authority, bump_seed) = find_pool_authority_address(program_id, pool_account.key);
+    if x_token_mint.owner != &spl_token::id()
+      || lx_token_mint.owner != &spl_token::id()
+      || x_reserve.owner != &spl_token::id()
+    {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    Mint::unpack(&x_token_mint.data.borrow())?;
+    if Mint::unpack(&lx_token_mint.data.borrow())?.mint_authority != COption::Some(authority) {
+      return Err(LendingError::InvalidAuthority.into());
+    }
+    let reserve = TokenAccount::unpack(&x_reserve.data.borrow())?;
+    if reserve.mint != *x_token_mint.key {
+      return Err(LendingError::IncorrectPoolAccount.into());
+    }
+    if reserve.owner != authority {
+      return Err(LendingError::InvalidAuthority.into());
+    }
+
+    pool.is_initialized = true;
+    pool.bump_seed = bump_seed;
+    pool.admin = *admin.key;
+    pool.x_token_mint_id = *x_token_mint.key;
+    pool.lx_token_mint_id = *lx_token_mint.key;
+    pool.x_reserve = *x_reserve.key;
+    pool.rate_per_slot = rate_per_slot;
+    pool.exchange_rate = RATE_SCALE;
+    pool.last_update_slot = Clock::get()?.slot;
+    pool.exited = false;
+    LendingPool::pack(pool, &mut pool_account.data.borrow_mut())
+  }
+
+  /// Checks the accounts following the pool account in `Deposit` & `Withdraw`, returning the X
+  /// reserve, lX mint and pool authority.
+  fn pool_transfer_accounts<'a, 'b>(
+    ctx: &Context<'a, 'b, LendingPool>,
+    accounts: &TransferAccounts<'a, 'b>,
+  ) -> Result<[&'a AccountInfo<'b>; 3], ProgramError> {
+    let account_info_iter = &mut ctx.remaining_accounts.iter();
+    let x_reserve = next_account_info(account_info_iter)?;
+    let lx_token_mint = next_account_info(account_info_iter)?;
+    let pool_authority = next_account_info(account_info_iter)?;
+
+    if *accounts.token_program.key != spl_token::id() {
+      return Err(ProgramError::IncorrectProgramId);
+    }
+    if *x_reserve.key != ctx.data.x_reserve || *lx_token_mint.key != ctx.data.lx_token_mint_id {
+      return Err(LendingError::IncorrectPoolAccount.into());
+    }
+    let authority = Pubkey::create_program_address(
+      &[ctx.data_account.key.as_ref(), &[ctx.data.bump_seed]],
+      ctx.program_id,
+    )?;
+    if *pool_authority.key != authority {
+      return Err(LendingError::InvalidAuthority.into());
+    }
+    Ok([x_reserve, lx_token_mint, pool_authority])
+  }
+
+  /// Accrues the pool's interest up to the current slot, as far as the X reserve & lX mint
+  /// following the pool account back it.
+  fn accrue(ctx: &mut Context<LendingPool>) -> ProgramResult {
+    let slot = Clock::get()?.slot;
+    let (reserve, lx_supply) = Self::pool_backing(ctx)?;
+    ctx
+      .data
+      .accrue(slot, reserve, lx_supply)
+      .ok_or_else(|| LendingError::MathOverflow.into())
+  }
+
+  /// The X held by the reserve & the supply of lX, from the X reserve & lX mint following the
+  /// pool account.
+  fn pool_backing(ctx: &Context<LendingPool>) -> Result<(u64, u64), ProgramError> {
+    let account_info_iter = &mut ctx.remaining_accounts.iter();
+    let x_reserve = next_account_info(account_info_iter)?;
+    let lx_token_mint = next_account_info(account_info_iter)?;
+    if *x_reserve.key != ctx.data.x_reserve || *lx_token_mint.key != ctx.data.lx_token_mint_id {
+      return Err(LendingError::IncorrectPoolAccount.into());
+    }
+    Ok((
+      TokenAccount::unpack(&x_reserve.data.borrow())?.amount,
+      Mint::unpack(&lx_token_mint.data.borrow())?.supply,
+    ))
+  }
+}
+
+impl StrategyProcessor for Processor {
+  type Data = LendingPool;
+
+  fn deposit<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LendingPool>,
+    accounts: &TransferAccounts<'a, 'b>,
+    amount: u64,
+  ) -> ProgramResult {
+    if ctx.data.exited {
+      return Err(LendingError::PoolExited.into());
+    }
+    let [x_reserve, lx_token_mint, pool_authority] = Self::pool_transfer_accounts(ctx, accounts)?;
+    Self::accrue(ctx)?;
+    let lx_amount =
+      LendingPool::lx_for_x(amount, ctx.data.exchange_rate).ok_or(LendingError::MathOverflow)?;
+    msg!("Depositing {} X for {} lX", amount, lx_amount);
+
+    invoke(
+      &spl_token::instruction::transfer(
+        accounts.token_program.key,
+        accounts.source.key,
+        x_reserve.key,
+        accounts.source_authority.key,
+        &[],
+        amount,
+      )?,
+      &[
+        accounts.source.clone(),
+        x_reserve.clone(),
+        accounts.source_authority.clone(),
+        accounts.token_program.clone(),
+      ],
+    )?;
+    invoke_signed(
+      &spl_token::instruction::mint_to(
+        accounts.token_program.key,
+        lx_token_mint.key,
+        accounts.target.key,
+        pool_authority.key,
+        &[],
+        lx_amount,
+      )?,
+      &[
+        lx_token_mint.clone(),
+        accounts.target.clone(),
+        pool_authority.clone(),
+        accounts.token_program.clone(),
+      ],
+      &[&[ctx.data_account.key.as_ref(), &[ctx.data.bump_seed]]],
+    )
+  }
+
+  fn withdraw<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LendingPool>,
+    accounts: &TransferAccounts<'a, 'b>,
+    amount: u64,
+  ) -> ProgramResult {
+    let [x_reserve, lx_token_mint, pool_authority] = Self::pool_transfer_accounts(ctx, accounts)?;
+    Self::accrue(ctx)?;
+    let x_amount =
+      LendingPool::x_for_lx(amount, ctx.data.exchange_rate).ok_or(LendingError::MathOverflow)?;
+    msg!("Withdrawing {} X for {} lX", x_amount, amount);
+
+    invoke(
+      &spl_token::instruction::burn(
+        accounts.token_program.key,
+        accounts.source.key,
+        lx_token_mint.key,
+        accounts.source_authority.key,
+        &[],
+        amount,
+      )?,
+      &[
+        accounts.source.clone(),
+        lx_token_mint.clone(),
+        accounts.source_authority.clone(),
+        accounts.token_program.clone(),
+      ],
+    )?;
+    invoke_signed(
+      &spl_token::instruction::transfer(
+        accounts.token_program.key,
+        x_reserve.key,
+        accounts.target.key,
+        pool_authority.key,
+        &[],
+        x_amount,
+      )?,
+      &[
+        x_reserve.clone(),
+        accounts.target.clone(),
+        pool_authority.clone(),
+        accounts.token_program.clone(),
+      ],
+      &[&[ctx.data_account.key.as_ref(), &[ctx.data.bump_seed]]],
+    )
+  }
+
+  fn peek(ctx: &Context<LendingPool>, amount: u64) -> Result<u64, ProgramError> {
+    let (reserve, lx_supply) = Self::pool_backing(ctx)?;
+    let exchange_rate = ctx
+      .data
+      .exchange_rate_at(Clock::get()?.slot, reserve, lx_supply)
+      .ok_or(LendingError::MathOverflow)?;
+    Ok(LendingPool::x_for_lx(amount, exchange_rate).ok_or(LendingError::MathOverflow)?)
+  }
+
+  fn harvest(ctx: &mut Context<LendingPool>) -> ProgramResult {
+    Self::accrue(ctx)?;
+    msg!("Exchange rate is now {}", ctx.data.exchange_rate);
+    Ok(())
+  }
+
+  fn emergency_exit<'a, 'b>(
+    ctx: &mut Context<'a, 'b, LendingPool>,
+    authority: &'a AccountInfo<'b>,
+  ) -> ProgramResult {
+    if *authority.key != ctx.data.admin {
+      return Err(LendingError::InvalidAuthority.into());
+    }
+    // Nothing is lent out, so exiting just stops new deposits; withdrawals carry on.
+    ctx.data.exited = true;
+    Ok(())
+  }
+
+  fn process_custom_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+  ) -> ProgramResult {
+    match LendingInstruction::unpack(instruction_data)? {
+      LendingInstruction::InitializePool { rate_per_slot } => {
+        msg!("Instruction: InitializePool");
+        Self::process_initialize_pool(program_id, accounts, rate_per_slot)
+      }
+    }
+  }
+}
@@ -0,0 +1,146 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryInto;
+
+/// Fixed point scale of `LendingPool::rate_per_slot` & `LendingPool::exchange_rate`.
+pub const RATE_SCALE: u128 = 1_000_000_000_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LendingPool {
+    pub is_initialized: bool,
+    /// Bump seed of the pool authority, derived from the pool's address.
+    pub bump_seed: u8,
+    /// May trigger an emergency exit.
+    pub admin: Pubkey,
+    pub x_token_mint_id: Pubkey,
+    pub lx_token_mint_id: Pubkey,
+    /// X token account owned by the pool authority, holding deposits & the interest repaid into
+    /// it, e.g. by borrowers.
+    pub x_reserve: Pubkey,
+    /// Interest accrued per slot, scaled by `RATE_SCALE`.
+    pub rate_per_slot: u64,
+    /// # of X tokens each lX token withdraws, scaled by `RATE_SCALE`.
+    pub exchange_rate: u128,
+    pub last_update_slot: u64,
+    /// Set by an emergency exit, after which deposits are refused.
+    pub exited: bool,
+}
+
+impl LendingPool {
+    /// Returns the exchange rate with interest accrued up to `slot`, capped at what the `reserve`
+    /// X tokens held pay out for the `lx_supply` lX tokens, since interest is only paid once it's
+    /// been funded. Every lX token can then be withdrawn in full.
+    pub fn exchange_rate_at(&self, slot: u64, reserve: u64, lx_supply: u64) -> Option<u128> {
+        let elapsed = slot.saturating_sub(self.last_update_slot) as u128;
+        let growth = RATE_SCALE.checked_add((self.rate_per_slot as u128).checked_mul(elapsed)?)?;
+        let exchange_rate = self.exchange_rate.checked_mul(growth)?.checked_div(RATE_SCALE)?;
+        if lx_supply == 0 {
+            return Some(exchange_rate);
+        }
+        let backed = (reserve as u128)
+            .checked_mul(RATE_SCALE)?
+            .checked_div(lx_supply as u128)?;
+        Some(exchange_rate.min(backed))
+    }
+
+    /// Accrues interest up to `slot`, as far as the reserve funds it (see `exchange_rate_at`).
+    pub fn accrue(&mut self, slot: u64, reserve: u64, lx_supply: u64) -> Option<()> {
+        self.exchange_rate = self.exchange_rate_at(slot, reserve, lx_supply)?;
+        self.last_update_slot = self.last_update_slot.max(slot);
+        Some(())
+    }
+
+    /// # of lX tokens minted for `amount` X tokens at `exchange_rate`, rounding down.
+    pub fn lx_for_x(amount: u64, exchange_rate: u128) -> Option<u64> {
+        (amount as u128)
+            .checked_mul(RATE_SCALE)?
+            .checked_div(exchange_rate)?
+            .try_into()
+            .ok()
+    }
+
+    /// # of X tokens returned for `amount` lX tokens at `exchange_rate`, rounding down.
+    pub fn x_for_lx(amount: u64, exchange_rate: u128) -> Option<u64> {
+        (amount as u128)
+            .checked_mul(exchange_rate)?
+            .checked_div(RATE_SCALE)?
+            .try_into()
+            .ok()
+    }
+}
+
+impl Sealed for LendingPool {}
+
+impl Pack for LendingPool {
+    const LEN: usize = 1 + 1 + 32 + 32 + 32 + 32 + 8 + 16 + 8 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, LendingPool::LEN];
+        let (
+            is_initialized,
+            bump_seed,
+            admin,
+            x_token_mint_id,
+            lx_token_mint_id,
+            x_reserve,
+            rate_per_slot,
+            exchange_rate,
+            last_update_slot,
+            exited,
+        ) = array_refs![src, 1, 1, 32, 32, 32, 32, 8, 16, 8, 1];
+
+        let unpack_bool = |src: &[u8; 1]| match src {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+        Ok(LendingPool {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            admin: Pubkey::new_from_array(*admin),
+            x_token_mint_id: Pubkey::new_from_array(*x_token_mint_id),
+            lx_token_mint_id: Pubkey::new_from_array(*lx_token_mint_id),
+            x_reserve: Pubkey::new_from_array(*x_reserve),
+            rate_per_slot: u64::from_le_bytes(*rate_per_slot),
+            exchange_rate: u128::from_le_bytes(*exchange_rate),
+            last_update_slot: u64::from_le_bytes(*last_update_slot),
+            exited: unpack_bool(exited)?,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, LendingPool::LEN];
+        let (
+            is_initialized_dst,
+            bump_seed_dst,
+            admin_dst,
+            x_token_mint_id_dst,
+            lx_token_mint_id_dst,
+            x_reserve_dst,
+            rate_per_slot_dst,
+            exchange_rate_dst,
+            last_update_slot_dst,
+            exited_dst,
+        ) = mut_array_refs![dst, 1, 1, 32, 32, 32, 32, 8, 16, 8, 1];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        bump_seed_dst[0] = self.bump_seed;
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        x_token_mint_id_dst.copy_from_slice(self.x_token_mint_id.as_ref());
+        lx_token_mint_id_dst.copy_from_slice(self.lx_token_mint_id.as_ref());
+        x_reserve_dst.copy_from_slice(self.x_reserve.as_ref());
+        *rate_per_slot_dst = self.rate_per_slot.to_le_bytes();
+        *exchange_rate_dst = self.exchange_rate.to_le_bytes();
+        *last_update_slot_dst = self.last_update_slot.to_le_bytes();
+        exited_dst[0] = self.exited as u8;
+    }
+}
+
+impl IsInitialized for LendingPool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
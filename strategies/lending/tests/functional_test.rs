@@ -0,0 +1,509 @@
+#![cfg(feature = "test-bpf")]
+
+use {
+  ::Vault::{instruction::StrategyInstruction, state::PeekResult},
+  assert_matches::*,
+  lending_strategy::{
+    instruction::{find_pool_authority_address, transfer_account_metas, LendingInstruction},
+    processor::Processor,
+    state::{LendingPool, RATE_SCALE},
+  },
+  solana_program::{
+    instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+    system_instruction,
+  },
+  solana_program_test::{processor, ProgramTest, ProgramTestContext},
+  solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+  },
+};
+
+/// Interest of 0.1% per slot.
+const RATE_PER_SLOT: u64 = (RATE_SCALE / 1000) as u64;
+
+struct Pool {
+  pool: Keypair,
+  x_mint: Keypair,
+  lx_mint: Keypair,
+  x_reserve: Keypair,
+}
+
+impl Pool {
+  /// Accounts following the pool account, which Peek & Harvest take the first two of.
+  fn transfer_account_metas(&self) -> Vec<AccountMeta> {
+    transfer_account_metas(
+      &lending_strategy::id(),
+      &self.pool.pubkey(),
+      &self.x_reserve.pubkey(),
+      &self.lx_mint.pubkey(),
+    )
+  }
+}
+
+/// Tests depositing into & withdrawing from a pool as interest accrues.
+#[tokio::test]
+async fn test_lending_pool() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context, RATE_PER_SLOT).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.lx_mint.pubkey(), &payer).await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+  // Fund the interest paid out on withdrawal.
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &pool.x_reserve.pubkey(), 1000).await;
+
+  process(
+    &mut program_test_context,
+    StrategyInstruction::deposit(
+      &lending_strategy::id(),
+      &spl_token::id(),
+      &client_x.pubkey(),
+      &client_lx.pubkey(),
+      &payer,
+      &pool.pool.pubkey(),
+      pool.transfer_account_metas(),
+      1000,
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let lx_amount = token_balance(&mut program_test_context, &client_lx.pubkey()).await;
+  assert!(lx_amount > 0 && lx_amount <= 1000);
+  assert_eq!(token_balance(&mut program_test_context, &client_x.pubkey()).await, 0);
+
+  let slot = program_test_context.banks_client.get_root_slot().await.unwrap();
+  program_test_context.warp_to_slot(slot + 100).unwrap();
+
+  // Peek writes the current value of the lX into a result account owned by the strategy.
+  let result_account = Keypair::new();
+  let rent = Rent::default().minimum_balance(PeekResult::LEN);
+  let vault = Pubkey::new_unique();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &result_account.pubkey(),
+        rent,
+        PeekResult::LEN as u64,
+        &lending_strategy::id(),
+      ),
+      StrategyInstruction::peek(
+        &lending_strategy::id(),
+        &result_account.pubkey(),
+        &pool.pool.pubkey(),
+        pool.transfer_account_metas(),
+        lx_amount,
+        &vault,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &result_account],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let result = PeekResult::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(result_account.pubkey())
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap();
+  assert_eq!(result.data_account, pool.pool.pubkey());
+  assert_eq!(result.vault, vault);
+  assert_eq!(result.lx_amount, lx_amount);
+  assert!(result.x_amount > 1000);
+
+  // Peek won't write its result over the pool itself.
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::peek(
+        &lending_strategy::id(),
+        &pool.pool.pubkey(),
+        &pool.pool.pubkey(),
+        pool.transfer_account_metas(),
+        lx_amount,
+        &vault,
+      )
+      .unwrap(),
+      &[],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::InvalidAccountData
+    )))
+  );
+
+  process(
+    &mut program_test_context,
+    StrategyInstruction::withdraw(
+      &lending_strategy::id(),
+      &spl_token::id(),
+      &client_lx.pubkey(),
+      &client_x.pubkey(),
+      &payer,
+      &pool.pool.pubkey(),
+      pool.transfer_account_metas(),
+      lx_amount,
+    )
+    .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+  assert_eq!(token_balance(&mut program_test_context, &client_lx.pubkey()).await, 0);
+  assert!(token_balance(&mut program_test_context, &client_x.pubkey()).await >= result.x_amount);
+}
+
+/// Tests that interest accrues no further than it's been repaid into the reserve, so every lX can
+/// be withdrawn.
+#[tokio::test]
+async fn test_lending_pool_pays_funded_interest() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context, RATE_PER_SLOT).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.lx_mint.pubkey(), &payer).await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+  let transfer = |amount, deposit: bool| {
+    let (source, target) = if deposit {
+      (client_x.pubkey(), client_lx.pubkey())
+    } else {
+      (client_lx.pubkey(), client_x.pubkey())
+    };
+    let build = if deposit {
+      StrategyInstruction::deposit
+    } else {
+      StrategyInstruction::withdraw
+    };
+    build(
+      &lending_strategy::id(),
+      &spl_token::id(),
+      &source,
+      &target,
+      &payer,
+      &pool.pool.pubkey(),
+      pool.transfer_account_metas(),
+      amount,
+    )
+    .unwrap()
+  };
+  process(&mut program_test_context, transfer(1000, true), &[])
+    .await
+    .unwrap();
+  let lx_amount = token_balance(&mut program_test_context, &client_lx.pubkey()).await;
+  assert_eq!(lx_amount, 1000);
+
+  // Nothing has been repaid, so however long interest accrues, the lX only withdraws the X
+  // deposited for it.
+  let slot = program_test_context.banks_client.get_root_slot().await.unwrap();
+  program_test_context.warp_to_slot(slot + 100).unwrap();
+  process(&mut program_test_context, transfer(lx_amount / 2, false), &[])
+    .await
+    .unwrap();
+  assert_eq!(token_balance(&mut program_test_context, &client_x.pubkey()).await, 500);
+
+  // Once interest is repaid into the reserve, it accrues to the lX still held.
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &pool.x_reserve.pubkey(), 1000).await;
+  let slot = program_test_context.banks_client.get_root_slot().await.unwrap();
+  program_test_context.warp_to_slot(slot + 100).unwrap();
+  process(&mut program_test_context, transfer(lx_amount / 2, false), &[])
+    .await
+    .unwrap();
+  let x_amount = token_balance(&mut program_test_context, &client_x.pubkey()).await;
+  assert!(x_amount > 1000 && x_amount < 2000);
+  assert_eq!(token_balance(&mut program_test_context, &client_lx.pubkey()).await, 0);
+}
+
+/// Tests that only the admin may exit a pool, after which deposits are refused.
+#[tokio::test]
+async fn test_lending_pool_emergency_exit() {
+  let mut program_test_context = program_test().start_with_context().await;
+  let pool = create_pool(&mut program_test_context, RATE_PER_SLOT).await;
+  let payer = program_test_context.payer.pubkey();
+
+  let impostor = Keypair::new();
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::emergency_exit(
+        &lending_strategy::id(),
+        &impostor.pubkey(),
+        &pool.pool.pubkey(),
+        vec![],
+      )
+      .unwrap(),
+      &[&impostor],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(4) // LendingError::InvalidAuthority
+    )))
+  );
+  process(
+    &mut program_test_context,
+    StrategyInstruction::emergency_exit(&lending_strategy::id(), &payer, &pool.pool.pubkey(), vec![])
+      .unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+
+  let client_x = Keypair::new();
+  let client_lx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_x, &pool.x_mint.pubkey(), &payer).await;
+  create_token_account(&mut program_test_context, &client_lx, &pool.lx_mint.pubkey(), &payer).await;
+  mint_to(&mut program_test_context, &pool.x_mint.pubkey(), &client_x.pubkey(), 1000).await;
+  assert_matches!(
+    process(
+      &mut program_test_context,
+      StrategyInstruction::deposit(
+        &lending_strategy::id(),
+        &spl_token::id(),
+        &client_x.pubkey(),
+        &client_lx.pubkey(),
+        &payer,
+        &pool.pool.pubkey(),
+        pool.transfer_account_metas(),
+        1000,
+      )
+      .unwrap(),
+      &[],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(5) // LendingError::PoolExited
+    )))
+  );
+}
+
+fn program_test() -> ProgramTest {
+  let mut program_test = ProgramTest::new(
+    "lending_strategy",
+    lending_strategy::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "token_test",
+    spl_token::id(),
+    processor!(spl_token::processor::Processor::process),
+  );
+  program_test
+}
+
+/// Creates a pool over a new 6-decimal X mint, administered by the payer.
+async fn create_pool(program_test_context: &mut ProgramTestContext, rate_per_slot: u64) -> Pool {
+  let pool = Pool {
+    pool: Keypair::new(),
+    x_mint: Keypair::new(),
+    lx_mint: Keypair::new(),
+    x_reserve: Keypair::new(),
+  };
+  let payer = program_test_context.payer.pubkey();
+  let (authority, _) = find_pool_authority_address(&lending_strategy::id(), &pool.pool.pubkey());
+  create_mint(program_test_context, &pool.x_mint, &payer).await;
+  create_mint(program_test_context, &pool.lx_mint, &authority).await;
+  create_token_account(
+    program_test_context,
+    &pool.x_reserve,
+    &pool.x_mint.pubkey(),
+    &authority,
+  )
+  .await;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &pool.pool.pubkey(),
+        Rent::default().minimum_balance(LendingPool::LEN),
+        LendingPool::LEN as u64,
+        &lending_strategy::id(),
+      ),
+      LendingInstruction::initialize_pool(
+        &lending_strategy::id(),
+        &pool.pool.pubkey(),
+        &payer,
+        &pool.x_mint.pubkey(),
+        &pool.lx_mint.pubkey(),
+        &pool.x_reserve.pubkey(),
+        rate_per_slot,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &pool.pool],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  pool
+}
+
+async fn create_mint(
+  program_test_context: &mut ProgramTestContext,
+  mint: &Keypair,
+  mint_authority: &Pubkey,
+) {
+  let payer = program_test_context.payer.pubkey();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_mint(
+        &spl_token::id(),
+        &mint.pubkey(),
+        mint_authority,
+        None, // Freeze authority
+        6,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, mint],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+}
+
+async fn create_token_account(
+  program_test_context: &mut ProgramTestContext,
+  token_account: &Keypair,
+  mint: &Pubkey,
+  owner: &Pubkey,
+) {
+  let payer = program_test_context.payer.pubkey();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &token_account.pubkey(),
+        Rent::default().minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &token_account.pubkey(),
+        mint,
+        owner,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, token_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+}
+
+/// Mints X tokens, whose mint authority is the payer.
+async fn mint_to(
+  program_test_context: &mut ProgramTestContext,
+  mint: &Pubkey,
+  account: &Pubkey,
+  amount: u64,
+) {
+  let payer = program_test_context.payer.pubkey();
+  process(
+    program_test_context,
+    spl_token::instruction::mint_to(&spl_token::id(), mint, account, &payer, &[], amount).unwrap(),
+    &[],
+  )
+  .await
+  .unwrap();
+}
+
+/// Processes `instruction`, paid for & signed by the payer and `signers`.
+async fn process(
+  program_test_context: &mut ProgramTestContext,
+  instruction: solana_program::instruction::Instruction,
+  signers: &[&Keypair],
+) -> Result<(), TransportError> {
+  let recent_blockhash = program_test_context
+    .banks_client
+    .get_recent_blockhash()
+    .await
+    .unwrap();
+  let mut transaction =
+    Transaction::new_with_payer(&[instruction], Some(&program_test_context.payer.pubkey()));
+  let mut all_signers = vec![&program_test_context.payer];
+  all_signers.extend(signers);
+  transaction.sign(&all_signers, recent_blockhash);
+  program_test_context
+    .banks_client
+    .process_transaction(transaction)
+    .await
+}
+
+async fn token_balance(program_test_context: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+  spl_token::state::Account::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(*account)
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap()
+  .amount
+}
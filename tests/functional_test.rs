@@ -1,13 +1,26 @@
 #![cfg(feature = "test-bpf")]
 
 use {
-  ::Vault::{entrypoint::process_instruction, id, instruction::VaultInstruction, state::Vault},
+  ::Vault::{
+    client::{self, Position, SharePrice, VaultClient},
+    id,
+    instruction::{StrategyInstruction, VaultInstruction},
+    math, oracle, pda,
+    state::{
+      AmountEncoding, FeeRecipient, InstructionTemplate, PeekResult, PriceFeed, Referral,
+      StrategyAccount, TriggerAction, TriggerCondition, Vault, MAX_INSTRUCTION_PREFIX_LEN,
+    },
+  },
+  mock_price::instruction::MockPriceInstruction,
   assert_matches::*,
   solana_program::{
+    account_info::AccountInfo,
     borsh::get_packed_len,
+    entrypoint::ProgramResult,
     hash::Hash,
     instruction::{AccountMeta, Instruction},
     msg,
+    program_error::ProgramError,
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
@@ -17,34 +30,5040 @@ use {
   },
   solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext},
   solana_sdk::signature::Keypair,
-  solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+  solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+  },
   spl_token::{processor::Processor, state::AccountState},
   std::str::FromStr,
 };
 
-/// Tests a simple hodl vault
-/// Based on Record functional test: https://github.com/solana-labs/solana-program-library/blob/2b3f71ead5b81f4ea4a2fd3e4fe9583a6e39b6a4/record/program/tests/functional.rs
-#[tokio::test]
-async fn test_hodl_vault() {
-  // Start the test client
-  let mut program_test = ProgramTest::new(
-    "token_test",
-    spl_token::id(),
-    processor!(Processor::process),
+/// Tests a simple hodl vault
+/// Based on Record functional test: https://github.com/solana-labs/solana-program-library/blob/2b3f71ead5b81f4ea4a2fd3e4fe9583a6e39b6a4/record/program/tests/functional.rs
+#[tokio::test]
+async fn test_hodl_vault() {
+  // Start the test client
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  // A basic Vault has 3 relevant tokens: X (underlying asset), lX (strategy derivative), llX (vault
+  // derivative). We roughly need a client-managed & vault-managed SPL token account per-token.
+  // For succintnesss, we set all of these up together:
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+
+  // Create Vault account
+  let hodl_vault_storage_account =
+    initialize_hodl_vault(&mut program_test_context, &mint_client_vault_accounts).await;
+
+  // Transact with hodl vault.
+  let (pda, _bump_seed) =
+    pda::find_vault_authority_address(&::Vault::id(), &hodl_vault_storage_account.pubkey());
+  deposit_into_hodl_vault(
+    &mut program_test_context,
+    &mint_client_vault_accounts,
+    &hodl_vault_storage_account.pubkey(),
+    1000,
+    100,
+  )
+  .await;
+  // Ensure accounts have correct balances.
+  // Due to Rust semantics limitations around borrowing, we don't pass an expected owner.
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][1].pubkey(),
+    &COption::None,
+    900,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][2].pubkey(),
+    &COption::Some(pda),
+    100,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[1][2].pubkey(),
+    &COption::Some(pda),
+    0,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[2][1].pubkey(),
+    &COption::None,
+    100,
+  )
+  .await;
+
+  withdraw_from_hodl_vault(
+    &mut program_test_context,
+    &mint_client_vault_accounts,
+    &hodl_vault_storage_account.pubkey(),
+    100,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][1].pubkey(),
+    &COption::None,
+    1000,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][2].pubkey(),
+    &COption::Some(pda),
+    0,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[2][1].pubkey(),
+    &COption::None,
+    0,
+  )
+  .await;
+
+  // Create wrapper vault which uses the hodl vault as a Strategy.
+  // TODO(004): Uncomment below.
+  // let wrapper_vault_storage_account = Keypair::new();
+  // let mut transaction = Transaction::new_with_payer(
+  //   &[
+  //     // Create Vault storage acccount.
+  //     system_instruction::create_account(
+  //       &program_test_context.payer.pubkey(),
+  //       &wrapper_vault_storage_account.pubkey(),
+  //       1.max(Rent::default().minimum_balance(::Vault::state::Vault::LEN)),
+  //       ::Vault::state::Vault::LEN as u64,
+  //       &::Vault::id(),
+  //     ),
+  //     // Initialize the vault & setup its storage account.
+  //     VaultInstruction::initialize_vault(
+  //       &::Vault::id(),
+  //       &program_test_context.payer.pubkey(),
+  //       &wrapper_vault_storage_account.pubkey(),
+  //       &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
+  //       &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
+  //       &mint_client_vault_accounts[0][0].pubkey(), // x mint account
+  //       &spl_token::id(),
+  //       &::Vault::id(), // Strategy program ID
+  //       false,          // hodl
+  //       COption::None,  // Unused vault_x_token account
+  //       vec![StrategyAccount::new(hodl_vault_storage_account.pubkey(), true)],
+  //       InstructionTemplate::tag(2), // deposit instruction
+  //       InstructionTemplate::tag(3), // withdraw instruction
+  //     )
+  //     .unwrap(),
+  //   ],
+  //   Some(&program_test_context.payer.pubkey()),
+  // );
+  // transaction.sign(
+  //   &[&program_test_context.payer, &wrapper_vault_storage_account],
+  //   program_test_context.last_blockhash,
+  // );
+  // assert_matches!(
+  //   program_test_context
+  //     .banks_client
+  //     .process_transaction(transaction)
+  //     .await,
+  //   Ok(())
+  // );
+}
+
+/// Tests a hodl vault wrapping a 9-decimal X token with 6-decimal llX shares.
+#[tokio::test]
+async fn test_hodl_vault_mixed_decimals() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[9, 6, 6], 3).await;
+  let hodl_vault_storage_account =
+    initialize_hodl_vault(&mut program_test_context, &mint_client_vault_accounts).await;
+
+  let storage_account = program_test_context
+    .banks_client
+    .get_account(hodl_vault_storage_account.pubkey())
+    .await
+    .unwrap()
+    .expect("Account unretrievable");
+  let vault = Vault::unpack(&storage_account.data).unwrap();
+  assert_eq!(vault.x_decimals, 9);
+  assert_eq!(vault.llx_decimals, 6);
+
+  // 2 X at 9 decimals should be worth 2 llX at 6 decimals.
+  deposit_into_hodl_vault(
+    &mut program_test_context,
+    &mint_client_vault_accounts,
+    &hodl_vault_storage_account.pubkey(),
+    2_000_000_000,
+    2_000_000_000,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[2][1].pubkey(),
+    &COption::None,
+    2_000_000,
+  )
+  .await;
+
+  withdraw_from_hodl_vault(
+    &mut program_test_context,
+    &mint_client_vault_accounts,
+    &hodl_vault_storage_account.pubkey(),
+    500_000,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][1].pubkey(),
+    &COption::None,
+    500_000_000,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[2][1].pubkey(),
+    &COption::None,
+    1_500_000,
+  )
+  .await;
+
+  // Less than a thousandth of an X rounds down to no llX, which the vault refuses to mint.
+  let (pda, _bump_seed) =
+    pda::find_vault_authority_address(&::Vault::id(), &hodl_vault_storage_account.pubkey());
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::deposit(
+      &::Vault::id(),
+      &spl_token::id(),
+      &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
+      &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
+      vec![
+        AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+        AccountMeta::new_readonly(hodl_vault_storage_account.pubkey(), false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(mint_client_vault_accounts[0][0].pubkey(), false), // X mint
+        AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+        AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
+      ],
+      999,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(24) // VaultError::DepositTooSmall
+    )))
+  );
+}
+
+/// Tests that a deposit naming the wrong X mint, or another vault's authority, fails instead of
+/// moving another asset.
+#[tokio::test]
+async fn test_hodl_vault_rejects_wrong_mint() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let hodl_vault_storage_account =
+    initialize_hodl_vault(&mut program_test_context, &mint_client_vault_accounts).await;
+
+  let (pda, _bump_seed) =
+    pda::find_vault_authority_address(&::Vault::id(), &hodl_vault_storage_account.pubkey());
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::deposit(
+      &::Vault::id(),
+      &spl_token::id(),
+      &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
+      &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
+      vec![
+        AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+        AccountMeta::new_readonly(hodl_vault_storage_account.pubkey(), false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(mint_client_vault_accounts[1][0].pubkey(), false), // lX mint
+        AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+        AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
+      ],
+      100,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(4) // VaultError::IncorrectMint
+    )))
+  );
+
+  // Nor will the vault sign with the authority of another vault's accounts.
+  let (other_authority, _bump_seed) =
+    pda::find_vault_authority_address(&::Vault::id(), &Pubkey::new_unique());
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::deposit(
+      &::Vault::id(),
+      &spl_token::id(),
+      &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
+      &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
+      vec![
+        AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+        AccountMeta::new_readonly(hodl_vault_storage_account.pubkey(), false),
+        AccountMeta::new_readonly(other_authority, false),
+        AccountMeta::new_readonly(mint_client_vault_accounts[0][0].pubkey(), false), // X mint
+        AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+        AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
+      ],
+      100,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(6) // VaultError::InvalidAuthority
+    )))
+  );
+}
+
+/// Tests a hodl vault wrapping native SOL.
+#[tokio::test]
+async fn test_wrapped_sol_vault() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut native_mint_data = vec![0; spl_token::state::Mint::LEN];
+  spl_token::state::Mint {
+    mint_authority: COption::None,
+    supply: 0,
+    decimals: spl_token::native_mint::DECIMALS,
+    is_initialized: true,
+    freeze_authority: COption::None,
+  }
+  .pack_into_slice(&mut native_mint_data);
+  program_test.add_account(
+    spl_token::native_mint::id(),
+    Account {
+      lamports: Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+      data: native_mint_data,
+      owner: spl_token::id(),
+      executable: false,
+      rent_epoch: 0,
+    },
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  // Row 0 is replaced by the native mint below; lX & llX share its 9 decimals.
+  let mut mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[9, 9, 9], 3).await;
+  let native_accounts = vec![Keypair::new(), Keypair::new(), Keypair::new()];
+  for native_account in native_accounts[1..].iter() {
+    create_token_account(
+      &mut program_test_context,
+      native_account,
+      &spl_token::native_mint::id(),
+    )
+    .await;
+  }
+  mint_client_vault_accounts[0] = native_accounts;
+  let native_mint = spl_token::native_mint::id();
+
+  let storage_account = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &program_test_context.payer.pubkey(),
+        &storage_account.pubkey(),
+        1.max(Rent::default().minimum_balance(::Vault::state::Vault::LEN)),
+        ::Vault::state::Vault::LEN as u64,
+        &::Vault::id(),
+      ),
+      VaultInstruction::initialize_vault(
+        &::Vault::id(),
+        &program_test_context.payer.pubkey(),
+        &storage_account.pubkey(),
+        &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
+        &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
+        &native_mint,
+        &spl_token::id(),
+        &::Vault::id(), // Strategy program ID
+        true,           // hodl
+        &mint_client_vault_accounts[0][2].pubkey(), // vault_x_token account
+        vec![],        // No strategy accounts
+        InstructionTemplate::default(), // unused deposit instruction
+        InstructionTemplate::default(), // unused withdraw instruction
+      )
+      .unwrap(),
+    ],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &storage_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  let (pda, _bump_seed) =
+    pda::find_vault_authority_address(&::Vault::id(), &storage_account.pubkey());
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::deposit_native(
+      &::Vault::id(),
+      &spl_token::id(),
+      &program_test_context.payer.pubkey(), // lamport source
+      &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
+      vec![
+        AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+        AccountMeta::new_readonly(storage_account.pubkey(), false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(native_mint, false),
+        AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+        AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+      ],
+      1_000_000_000,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][2].pubkey(),
+    &COption::Some(pda),
+    1_000_000_000,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[2][1].pubkey(),
+    &COption::None,
+    1_000_000_000,
+  )
+  .await;
+
+  // Unwrap part of the position through the client's temporary native account.
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::withdraw_native(
+      &::Vault::id(),
+      &spl_token::id(),
+      &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
+      &mint_client_vault_accounts[0][1].pubkey(), // temporary native account
+      vec![
+        AccountMeta::new(program_test_context.payer.pubkey(), true), // source authority
+        AccountMeta::new_readonly(storage_account.pubkey(), false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(native_mint, false),
+        AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+        AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl source.
+      ],
+      400_000_000,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][2].pubkey(),
+    &COption::Some(pda),
+    600_000_000,
+  )
+  .await;
+  assert_eq!(
+    program_test_context
+      .banks_client
+      .get_account(mint_client_vault_accounts[0][1].pubkey())
+      .await
+      .unwrap(),
+    None
+  );
+}
+
+/// Tests that a Token-2022 X mint which is non-transferable is rejected at initialization.
+#[tokio::test]
+async fn test_vault_rejects_non_transferable_token_2022_mint() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  // Base mint, padded to the account length, followed by the mint account type & a
+  // NonTransferable extension with an empty body.
+  let mut x_mint_data = vec![0; spl_token::state::Account::LEN];
+  spl_token::state::Mint {
+    mint_authority: COption::None,
+    supply: 0,
+    decimals: 6,
+    is_initialized: true,
+    freeze_authority: COption::None,
+  }
+  .pack_into_slice(&mut x_mint_data[..spl_token::state::Mint::LEN]);
+  x_mint_data.push(1);
+  x_mint_data.extend_from_slice(&::Vault::token::EXTENSION_NON_TRANSFERABLE.to_le_bytes());
+  x_mint_data.extend_from_slice(&0u16.to_le_bytes());
+  let x_mint = Pubkey::new_unique();
+  program_test.add_account(
+    x_mint,
+    Account {
+      lamports: Rent::default().minimum_balance(x_mint_data.len()),
+      data: x_mint_data,
+      owner: ::Vault::token::spl_token_2022::id(),
+      executable: false,
+      rent_epoch: 0,
+    },
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+
+  let storage_account = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &program_test_context.payer.pubkey(),
+        &storage_account.pubkey(),
+        1.max(Rent::default().minimum_balance(::Vault::state::Vault::LEN)),
+        ::Vault::state::Vault::LEN as u64,
+        &::Vault::id(),
+      ),
+      VaultInstruction::initialize_vault(
+        &::Vault::id(),
+        &program_test_context.payer.pubkey(),
+        &storage_account.pubkey(),
+        &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
+        &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
+        &x_mint,
+        &spl_token::id(),
+        &::Vault::id(), // Strategy program ID
+        false,          // hodl
+        &mint_client_vault_accounts[0][2].pubkey(), // vault_x_token account
+        vec![StrategyAccount::new(Pubkey::new_unique(), true)], // Never read
+        InstructionTemplate::default(), // unused deposit instruction
+        InstructionTemplate::default(), // unused withdraw instruction
+      )
+      .unwrap(),
+    ],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &storage_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(7) // VaultError::UnsupportedMintExtension
+    )))
+  );
+}
+
+/// Tests creating a hodl vault whose accounts all live at program derived addresses.
+#[tokio::test]
+async fn test_create_vault_at_derived_addresses() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let nonce = 7;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::create_vault(
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      &x_mint,
+      &mint_client_vault_accounts[1][0].pubkey(), // lX mint
+      &spl_token::id(),
+      &::Vault::id(), // Strategy program ID
+      nonce,
+      true, // hodl
+      vec![],       // No strategy accounts
+      6,    // llX decimals
+      InstructionTemplate::default(), // unused deposit instruction
+      InstructionTemplate::default(), // unused withdraw instruction
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  let (vault_address, _) = pda::find_vault_address(&::Vault::id(), &x_mint, &::Vault::id(), nonce);
+  let (llx_mint, _) = pda::find_llx_mint_address(&::Vault::id(), &vault_address);
+  let (x_token_account, _) = pda::find_x_token_address(&::Vault::id(), &vault_address);
+  let (authority, _) = pda::find_vault_authority_address(&::Vault::id(), &vault_address);
+  let storage_account = program_test_context
+    .banks_client
+    .get_account(vault_address)
+    .await
+    .unwrap()
+    .expect("Account unretrievable");
+  let vault = Vault::unpack(&storage_account.data).unwrap();
+  assert!(vault.hodl);
+  assert_eq!(vault.llx_token_mint_id, llx_mint);
+  assert_eq!(vault.x_token_mint_id, x_mint);
+  assert_eq!(vault.x_token_account, COption::Some(x_token_account));
+  assert_eq!(
+    vault.lx_token_account,
+    pda::find_lx_token_address(&::Vault::id(), &vault_address).0
+  );
+
+  // Deposit through the derived accounts.
+  let client_llx_account = Keypair::new();
+  create_token_account(&mut program_test_context, &client_llx_account, &llx_mint).await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &mint_client_vault_accounts[0][1].pubkey(),
+        &program_test_context.payer.pubkey(),
+        &[&program_test_context.payer.pubkey()],
+        100,
+      )
+      .unwrap(),
+      VaultInstruction::deposit(
+        &::Vault::id(),
+        &spl_token::id(),
+        &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
+        &client_llx_account.pubkey(),
+        vec![
+          AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+          AccountMeta::new_readonly(vault_address, false),
+          AccountMeta::new_readonly(authority, false),
+          AccountMeta::new_readonly(x_mint, false),
+          AccountMeta::new(llx_mint, false),
+          AccountMeta::new(x_token_account, false), // hodl destination.
+        ],
+        100,
+      )
+      .unwrap(),
+    ],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &x_token_account,
+    &COption::Some(authority),
+    100,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &client_llx_account.pubkey(),
+    &COption::None,
+    100,
+  )
+  .await;
+}
+
+/// Stands in for Token-2022's `InitializeAccount2`, which fails unless the account has room for
+/// the `TransferFeeAmount` extension every account of a transfer-fee mint carries.
+fn process_token_2022_initialize_account(
+  _program_id: &Pubkey,
+  accounts: &[AccountInfo],
+  instruction_data: &[u8],
+) -> ProgramResult {
+  if instruction_data.len() != 33 || instruction_data[0] != 16 {
+    return Err(ProgramError::InvalidInstructionData);
+  }
+  let (account, mint) = (&accounts[0], &accounts[1]);
+  let owner = Pubkey::new(&instruction_data[1..]);
+  let base_len = spl_token::state::Account::LEN;
+  let mut data = account.data.borrow_mut();
+  if data.len() != base_len + 1 + 4 + 8 {
+    return Err(ProgramError::InvalidAccountData);
+  }
+  spl_token::state::Account {
+    mint: *mint.key,
+    owner,
+    state: AccountState::Initialized,
+    ..spl_token::state::Account::default()
+  }
+  .pack_into_slice(&mut data[..base_len]);
+  data[base_len] = 2; // Account type
+  data[base_len + 1..base_len + 3]
+    .copy_from_slice(&::Vault::token::EXTENSION_TRANSFER_FEE_AMOUNT.to_le_bytes());
+  data[base_len + 3..base_len + 5].copy_from_slice(&8u16.to_le_bytes());
+  Ok(())
+}
+
+/// Tests that `CreateVault` makes room in the vault's X token account for the extension a
+/// Token-2022 transfer-fee X mint requires.
+#[tokio::test]
+async fn test_create_vault_with_transfer_fee_mint() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "token_2022_test",
+    ::Vault::token::spl_token_2022::id(),
+    processor!(process_token_2022_initialize_account),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  // Base mint, padded to the account length, followed by the mint account type & a
+  // TransferFeeConfig extension with no authorities & no fees.
+  let mut x_mint_data = vec![0; spl_token::state::Account::LEN];
+  spl_token::state::Mint {
+    mint_authority: COption::None,
+    supply: 0,
+    decimals: 6,
+    is_initialized: true,
+    freeze_authority: COption::None,
+  }
+  .pack_into_slice(&mut x_mint_data[..spl_token::state::Mint::LEN]);
+  x_mint_data.push(1);
+  x_mint_data.extend_from_slice(&::Vault::token::EXTENSION_TRANSFER_FEE_CONFIG.to_le_bytes());
+  x_mint_data.extend_from_slice(&108u16.to_le_bytes());
+  x_mint_data.extend_from_slice(&[0; 108]);
+  let x_mint = Pubkey::new_unique();
+  program_test.add_account(
+    x_mint,
+    Account {
+      lamports: Rent::default().minimum_balance(x_mint_data.len()),
+      data: x_mint_data,
+      owner: ::Vault::token::spl_token_2022::id(),
+      executable: false,
+      rent_epoch: 0,
+    },
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6], 1).await;
+  let nonce = 0;
+
+  let mut create_vault = VaultInstruction::create_vault(
+    &::Vault::id(),
+    &program_test_context.payer.pubkey(),
+    &x_mint,
+    &mint_client_vault_accounts[0][0].pubkey(), // lX mint
+    &spl_token::id(),
+    &::Vault::id(), // Strategy program ID
+    nonce,
+    true,                           // hodl
+    vec![],                         // No strategy accounts
+    6,                              // llX decimals
+    InstructionTemplate::default(), // unused deposit instruction
+    InstructionTemplate::default(), // unused withdraw instruction
+  )
+  .unwrap();
+  // X's token program, after the one shared by lX & llX.
+  create_vault.accounts.push(AccountMeta::new_readonly(
+    ::Vault::token::spl_token_2022::id(),
+    false,
+  ));
+  let mut transaction =
+    Transaction::new_with_payer(&[create_vault], Some(&program_test_context.payer.pubkey()));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  let (vault_address, _) = pda::find_vault_address(&::Vault::id(), &x_mint, &::Vault::id(), nonce);
+  let (x_token_account, _) = pda::find_x_token_address(&::Vault::id(), &vault_address);
+  let x_token_account = program_test_context
+    .banks_client
+    .get_account(x_token_account)
+    .await
+    .unwrap()
+    .expect("Account unretrievable");
+  assert_eq!(x_token_account.owner, ::Vault::token::spl_token_2022::id());
+  assert_eq!(
+    x_token_account.data.len(),
+    spl_token::state::Account::LEN + 13
+  );
+  let x_token_account = ::Vault::token::unpack_account_data(&x_token_account.data).unwrap();
+  assert_eq!(x_token_account.mint, x_mint);
+  assert_eq!(
+    x_token_account.owner,
+    pda::find_vault_authority_address(&::Vault::id(), &vault_address).0
+  );
+}
+
+/// Tests depositing into & withdrawing from a vault through `VaultClient`, and its share price &
+/// position queries.
+#[tokio::test]
+async fn test_vault_client() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let vault_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.x_token_program, spl_token::id());
+  assert_eq!(vault.llx_token_program, spl_token::id());
+  assert_eq!(
+    vault
+      .share_price(&mut program_test_context.banks_client)
+      .await
+      .unwrap(),
+    SharePrice {
+      total_assets: 0,
+      share_supply: 0
+    }
+  );
+  assert!(vault.harvest().is_err());
+
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &vault.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        100,
+      )
+      .unwrap(),
+      vault
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+      vault
+        .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 40)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  assert_eq!(
+    vault
+      .share_price(&mut program_test_context.banks_client)
+      .await
+      .unwrap(),
+    SharePrice {
+      total_assets: 60,
+      share_supply: 60
+    }
+  );
+  assert_eq!(
+    vault
+      .position(
+        &mut program_test_context.banks_client,
+        &client_llx_account.pubkey()
+      )
+      .await
+      .unwrap(),
+    Position {
+      shares: 60,
+      assets: 60
+    }
+  );
+  // Positions are only held in llX.
+  assert!(vault
+    .position(&mut program_test_context.banks_client, &client_x_account)
+    .await
+    .is_err());
+}
+
+/// Tests a vault investing through another vault, and that the investment graph is kept acyclic
+/// & at most `MAX_VAULT_DEPTH` vaults deep.
+#[tokio::test]
+async fn test_nested_vaults() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let child_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+  let child = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &child_address,
+  )
+  .await
+  .unwrap();
+
+  // The parent holds the child's llX as its lX.
+  let parent_address = create_nested_vault(
+    &mut program_test_context,
+    &x_mint,
+    &child.vault.llx_token_mint_id,
+    1,
+    child.as_strategy_accounts(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &parent.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        100,
+      )
+      .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+      parent
+        .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 40)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &child.vault.x_token_account.unwrap(),
+    &COption::Some(child.authority),
+    60,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &parent.vault.lx_token_account,
+    &COption::Some(parent.authority),
+    60,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &client_llx_account.pubkey(),
+    &COption::None,
+    60,
+  )
+  .await;
+  check_token_account(&mut program_test_context, &client_x_account, &COption::None, 40).await;
+
+  // After the child yields 30 X & sells 20 llX for 30 more, rolling up values the parent's 60 of
+  // the child's 80 llX at 90 X.
+  let client_child_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_child_llx_account,
+    &child.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &child.vault.x_token_account.unwrap(),
+        &payer,
+        &[&payer],
+        30,
+      )
+      .unwrap(),
+      child
+        .deposit(&client_x_account, &client_child_llx_account.pubkey(), &payer, 30)
+        .unwrap(),
+      parent
+        .roll_up(&mut program_test_context.banks_client, None)
+        .await
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &client_child_llx_account.pubkey(),
+    &COption::None,
+    20,
+  )
+  .await;
+  let rolled_up_child = client::get_vault(&mut program_test_context.banks_client, &child_address)
+    .await
+    .unwrap();
+  let rolled_up_parent = client::get_vault(&mut program_test_context.banks_client, &parent_address)
+    .await
+    .unwrap();
+  assert_eq!(rolled_up_child.total_assets, 120);
+  assert_eq!(rolled_up_parent.total_assets, 90);
+  assert_eq!(
+    rolled_up_parent.total_assets_slot,
+    rolled_up_child.total_assets_slot
+  );
+  // The whole chain must be passed, from the top down.
+  let mut roll_up = parent
+    .roll_up(&mut program_test_context.banks_client, None)
+    .await
+    .unwrap();
+  roll_up.accounts.truncate(3);
+  let mut transaction = Transaction::new_with_payer(&[roll_up], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::NotEnoughAccountKeys
+    )))
+  );
+
+  // Vaults investing through the parent must pass the child, so the chain can be walked.
+  assert_matches!(
+    create_nested_vault(
+      &mut program_test_context,
+      &x_mint,
+      &parent.vault.llx_token_mint_id,
+      2,
+      parent.as_strategy_accounts(),
+      &[],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(14) // VaultError::IncorrectStrategyAccount
+    )))
+  );
+  let grandparent = create_nested_vault(
+    &mut program_test_context,
+    &x_mint,
+    &parent.vault.llx_token_mint_id,
+    2,
+    parent.as_strategy_accounts(),
+    &[child_address],
+  )
+  .await
+  .unwrap();
+  assert_matches!(
+    create_nested_vault(
+      &mut program_test_context,
+      &x_mint,
+      &mint_client_vault_accounts[1][0].pubkey(),
+      3,
+      vec![StrategyAccount::new(grandparent, true)],
+      &[parent_address, child_address],
+    )
+    .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(16) // VaultError::InvestmentGraphTooDeep
+    )))
+  );
+
+  // A vault can't invest through itself.
+  let storage_account = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &storage_account.pubkey(),
+        1.max(Rent::default().minimum_balance(Vault::LEN)),
+        Vault::LEN as u64,
+        &::Vault::id(),
+      ),
+      VaultInstruction::initialize_vault(
+        &::Vault::id(),
+        &payer,
+        &storage_account.pubkey(),
+        &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
+        &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
+        &x_mint,
+        &spl_token::id(),
+        &::Vault::id(), // Strategy program ID
+        false,          // hodl
+        &mint_client_vault_accounts[0][2].pubkey(), // vault_x_token account
+        vec![StrategyAccount::new(storage_account.pubkey(), true)],
+        InstructionTemplate::tag(1),
+        InstructionTemplate::tag(2),
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &storage_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(15) // VaultError::InvestmentCycle
+    )))
+  );
+}
+
+/// Tests that deposits & withdrawals after a roll-up in the same slot keep its valuation current,
+/// so later transfers in the transaction are priced against it too.
+#[tokio::test]
+async fn test_transfers_after_roll_up() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let child_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+  let child = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &child_address,
+  )
+  .await
+  .unwrap();
+  let parent_address = create_nested_vault(
+    &mut program_test_context,
+    &x_mint,
+    &child.vault.llx_token_mint_id,
+    1,
+    child.as_strategy_accounts(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &parent.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        300,
+      )
+      .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // The child doubles in value, so 100 more X is worth half the parent's shares.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &child.vault.x_token_account.unwrap(),
+        &payer,
+        &[&payer],
+        100,
+      )
+      .unwrap(),
+      parent
+        .roll_up(&mut program_test_context.banks_client, None)
+        .await
+        .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+      parent
+        .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 30)
+        .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 60)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  // 100 llX + 50 for 100 X - 30 for 60 X + 30 for 60 X.
+  check_token_account(
+    &mut program_test_context,
+    &client_llx_account.pubkey(),
+    &COption::None,
+    150,
+  )
+  .await;
+  let rolled_up_parent = client::get_vault(&mut program_test_context.banks_client, &parent_address)
+    .await
+    .unwrap();
+  assert_eq!(rolled_up_parent.total_assets, 300);
+
+  // Transfers in later transactions of the roll-up's slot keep it current too.
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 50)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let rolled_up_parent = client::get_vault(&mut program_test_context.banks_client, &parent_address)
+    .await
+    .unwrap();
+  assert_eq!(rolled_up_parent.total_assets, 200);
+}
+
+/// Tests that governance migrates a vault between strategies, moving its holdings, unless the
+/// new strategy would invest through the vault itself or the vault is registered.
+#[tokio::test]
+async fn test_migrate_strategy() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let lx_mint = mint_client_vault_accounts[1][0].pubkey();
+  let child_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lx_mint, 0).await;
+  let target_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lx_mint, 1).await;
+  let child = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &child_address,
+  )
+  .await
+  .unwrap();
+  let target = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &target_address,
+  )
+  .await
+  .unwrap();
+  let parent_address = create_nested_vault(
+    &mut program_test_context,
+    &x_mint,
+    &child.vault.llx_token_mint_id,
+    2,
+    child.as_strategy_accounts(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &parent.vault.llx_token_mint_id,
+  )
+  .await;
+  // The parent's lX in the target is its llX, held by the parent's authority.
+  let parent_target_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &parent_target_llx_account,
+    &target.vault.llx_token_mint_id,
+  )
+  .await;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        100,
+      )
+      .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+      spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &parent_target_llx_account.pubkey(),
+        Some(&parent.authority),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        &payer,
+        &[&payer],
+      )
+      .unwrap(),
+      parent
+        .migrate_strategy(
+          &::Vault::id(),
+          &parent_target_llx_account.pubkey(),
+          target.as_strategy_accounts(),
+          InstructionTemplate::tag(1), // VaultInstruction::Deposit
+          InstructionTemplate::tag(2), // VaultInstruction::Withdraw
+          vec![],
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  // Everything the parent held in the child is now held in the target.
+  check_token_account(
+    &mut program_test_context,
+    &child.vault.x_token_account.unwrap(),
+    &COption::Some(child.authority),
+    0,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &parent.vault.lx_token_account,
+    &COption::Some(parent.authority),
+    0,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &target.vault.x_token_account.unwrap(),
+    &COption::Some(target.authority),
+    100,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &parent_target_llx_account.pubkey(),
+    &COption::Some(parent.authority),
+    100,
+  )
+  .await;
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  assert!(!parent.vault.hodl);
+  assert_eq!(parent.vault.lx_token_account, parent_target_llx_account.pubkey());
+  assert_eq!(parent.vault.child_vault(&::Vault::id()), Some(target_address));
+
+  // Withdrawals now come out of the target.
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 40)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &target.vault.x_token_account.unwrap(),
+    &COption::Some(target.authority),
+    60,
+  )
+  .await;
+
+  // The target can't invest through the parent, which invests through it.
+  let target_parent_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &target_parent_llx_account,
+    &parent.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &target_parent_llx_account.pubkey(),
+        Some(&target.authority),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        &payer,
+        &[&payer],
+      )
+      .unwrap(),
+      target
+        .migrate_strategy(
+          &::Vault::id(),
+          &target_parent_llx_account.pubkey(),
+          parent.as_strategy_accounts(),
+          InstructionTemplate::tag(1), // VaultInstruction::Deposit
+          InstructionTemplate::tag(2), // VaultInstruction::Withdraw
+          vec![AccountMeta::new_readonly(target_address, false)],
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(15) // VaultError::InvestmentCycle
+    )))
+  );
+
+  // Nor can it migrate once registered, as it's listed by its strategy.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      client::register_vault(
+        &mut program_test_context.banks_client,
+        &::Vault::id(),
+        &payer,
+        &child_address,
+        &x_mint,
+        &::Vault::id(),
+      )
+      .await
+      .unwrap(),
+      spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &target_parent_llx_account.pubkey(),
+        Some(&child.authority),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        &payer,
+        &[&payer],
+      )
+      .unwrap(),
+      child
+        .migrate_strategy(
+          &::Vault::id(),
+          &target_parent_llx_account.pubkey(),
+          target.as_strategy_accounts(),
+          InstructionTemplate::tag(1), // VaultInstruction::Deposit
+          InstructionTemplate::tag(2), // VaultInstruction::Withdraw
+          vec![],
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      2,
+      InstructionError::Custom(9) // VaultError::VaultAlreadyRegistered
+    )))
+  );
+}
+
+/// Tests that governance sets a vault's fees, which accrue in llX & are split between recipients,
+/// including another vault holding the llX.
+#[tokio::test]
+async fn test_vault_fees() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let vault_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let llx_mint = vault.vault.llx_token_mint_id;
+  // A vault hodling the llX, to which fees paid compound.
+  let compounding_vault_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &llx_mint,
+    &mint_client_vault_accounts[2][0].pubkey(),
+    0,
+  )
+  .await;
+  let compounding_vault = client::get_vault(
+    &mut program_test_context.banks_client,
+    &compounding_vault_address,
+  )
+  .await
+  .unwrap();
+  let compounding_x_account = compounding_vault.x_token_account.unwrap();
+  let treasury = Keypair::new();
+  create_token_account(&mut program_test_context, &treasury, &llx_mint).await;
+  let client_llx_account = Keypair::new();
+  create_token_account(&mut program_test_context, &client_llx_account, &llx_mint).await;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        1000,
+      )
+      .unwrap(),
+      vault
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 1000)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Only governance sets fees, and the recipients must take the whole of them in llX.
+  let fee_recipients = vec![
+    FeeRecipient::new(treasury.pubkey(), 7000),
+    FeeRecipient::new(compounding_x_account, 3000),
+  ];
+  let impostor = Keypair::new();
+  let mut short_recipients = fee_recipients.clone();
+  short_recipients[1].bps = 2000;
+  for (governance, recipients, error) in [
+    (&impostor, fee_recipients.clone(), 12), // VaultError::InvalidGovernance
+    (&program_test_context.payer, short_recipients, 18), // VaultError::InvalidFeeConfig
+    (&program_test_context.payer, vec![], 18),
+    (
+      &program_test_context.payer,
+      vec![FeeRecipient::new(client_x_account, 10_000)],
+      4, // VaultError::IncorrectMint
+    ),
+  ]
+  .iter()
+  {
+    let mut transaction = Transaction::new_with_payer(
+      &[VaultInstruction::set_fees(
+        &::Vault::id(),
+        &governance.pubkey(),
+        &vault_address,
+        1000,
+        0,
+        recipients.clone(),
+      )
+      .unwrap()],
+      Some(&payer),
+    );
+    let mut signers = vec![&program_test_context.payer];
+    if governance.pubkey() != payer {
+      signers.push(*governance);
+    }
+    transaction.sign(&signers, program_test_context.last_blockhash);
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Err(TransportError::TransactionError(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(code)
+      ))) if code == *error
+    );
+  }
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::set_fees(
+      &::Vault::id(),
+      &payer,
+      &vault_address,
+      1000, // 10% a year
+      0,
+      fee_recipients.clone(),
+    )
+    .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // A tenth of a year later, a tenth of the fee is minted as llX, 7:3. The fee rounds down, so
+  // the extra slots, past however many the fees were set after, don't add up to more.
+  let slot = program_test_context
+    .banks_client
+    .get_root_slot()
+    .await
+    .unwrap();
+  program_test_context
+    .warp_to_slot(slot + math::SLOTS_PER_YEAR / 10 + 1000)
+    .unwrap();
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.fee_recipients, fee_recipients);
+  let mut transaction =
+    Transaction::new_with_payer(&[vault.collect_fees(&[]).unwrap()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &treasury.pubkey(),
+    &COption::None,
+    7,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &compounding_x_account,
+    &COption::Some(pda::find_vault_authority_address(&::Vault::id(), &compounding_vault_address).0),
+    3,
+  )
+  .await;
+  assert_eq!(
+    vault
+      .share_price(&mut program_test_context.banks_client)
+      .await
+      .unwrap(),
+    SharePrice {
+      total_assets: 1000,
+      share_supply: 1010
+    }
+  );
+  let collected = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+    .await
+    .unwrap();
+  assert!(collected.fees_collected_slot >= slot + math::SLOTS_PER_YEAR / 10);
+
+  // Fees are only set after collecting those accrued at the old rate in the same slot.
+  program_test_context
+    .warp_to_slot(collected.fees_collected_slot + 1000)
+    .unwrap();
+  let set_fees = VaultInstruction::set_fees(
+    &::Vault::id(),
+    &payer,
+    &vault_address,
+    500,
+    0,
+    fee_recipients,
+  )
+  .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[set_fees.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(23) // VaultError::UncollectedFees
+    )))
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[vault.collect_fees(&[]).unwrap(), set_fees],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.management_fee_bps, 500);
+}
+
+/// Tests that deposits credit their referrer, who accrues a share of the fees on what they referred
+/// while the depositor holds it, paid whenever their referral is passed to a collection.
+#[tokio::test]
+async fn test_referral_fees() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6], 2).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let vault_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let llx_mint = vault.vault.llx_token_mint_id;
+  let client_llx_account = Keypair::new();
+  create_token_account(&mut program_test_context, &client_llx_account, &llx_mint).await;
+  let treasury = Keypair::new();
+  create_token_account(&mut program_test_context, &treasury, &llx_mint).await;
+  // The referrer needn't sign anything, and is paid into an llX account of their own.
+  let referrer = Keypair::new();
+  let referrer_llx_account = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &referrer_llx_account.pubkey(),
+        Rent::default().minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &referrer_llx_account.pubkey(),
+        &llx_mint,
+        &referrer.pubkey(),
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &referrer_llx_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Referred deposits must pass the derived referral account of the llX account minted into.
+  let mut misreferred = vault
+    .deposit_referred(
+      &client_x_account,
+      &client_llx_account.pubkey(),
+      &payer,
+      1,
+      &referrer.pubkey(),
+    )
+    .unwrap();
+  let referral_index = misreferred.accounts.len() - 2;
+  misreferred.accounts[referral_index].pubkey = Pubkey::new_unique();
+  let mut transaction = Transaction::new_with_payer(&[misreferred], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(
+      TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
+    ))
+  );
+
+  // Nor can depositors refer themselves.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .deposit_referred(&client_x_account, &client_llx_account.pubkey(), &payer, 1, &payer)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(25) // VaultError::SelfReferral
+    )))
+  );
+
+  // 600 of the 1000 X deposited are referred, over two deposits.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        1001,
+      )
+      .unwrap(),
+      vault
+        .deposit_referred(
+          &client_x_account,
+          &client_llx_account.pubkey(),
+          &payer,
+          500,
+          &referrer.pubkey(),
+        )
+        .unwrap(),
+      vault
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 400)
+        .unwrap(),
+      vault
+        .deposit_referred(
+          &client_x_account,
+          &client_llx_account.pubkey(),
+          &payer,
+          100,
+          &referrer.pubkey(),
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  assert_eq!(
+    vault
+      .get_referral(
+        &mut program_test_context.banks_client,
+        &client_llx_account.pubkey()
+      )
+      .await
+      .unwrap(),
+    Some(Referral {
+      is_initialized: true,
+      vault: vault_address,
+      referrer: referrer.pubkey(),
+      llx_token_account: client_llx_account.pubkey(),
+      principal: 600,
+      shares: 600,
+      fee_index: 0,
+      accrued_fees: 0,
+    })
+  );
+
+  // The first referred deposit into an account fixes its referrer.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .deposit_referred(
+        &client_x_account,
+        &client_llx_account.pubkey(),
+        &payer,
+        1,
+        &Pubkey::new_unique(),
+      )
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(
+      TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+    ))
+  );
+
+  // Referrers take half the fees on what they referred, before the treasury takes the rest.
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::set_fees(
+      &::Vault::id(),
+      &payer,
+      &vault_address,
+      1000, // 10% a year
+      5000,
+      vec![FeeRecipient::new(treasury.pubkey(), 10_000)],
+    )
+    .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let slot = program_test_context
+    .banks_client
+    .get_root_slot()
+    .await
+    .unwrap();
+  program_test_context
+    .warp_to_slot(slot + math::SLOTS_PER_YEAR / 10 + 1000)
+    .unwrap();
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let recent_blockhash = program_test_context
+    .banks_client
+    .get_recent_blockhash()
+    .await
+    .unwrap();
+
+  // Referrers are paid into accounts they own, once per collection.
+  for (referrals, error) in [
+    (
+      vec![(client_llx_account.pubkey(), treasury.pubkey())],
+      InstructionError::Custom(5), // VaultError::IncorrectTokenAccount
+    ),
+    (
+      vec![
+        (client_llx_account.pubkey(), referrer_llx_account.pubkey()),
+        (client_llx_account.pubkey(), referrer_llx_account.pubkey()),
+      ],
+      InstructionError::InvalidArgument,
+    ),
+  ]
+  .iter()
+  {
+    let mut transaction =
+      Transaction::new_with_payer(&[vault.collect_fees(referrals).unwrap()], Some(&payer));
+    transaction.sign(&[&program_test_context.payer], recent_blockhash);
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Err(TransportError::TransactionError(
+        TransactionError::InstructionError(0, ref e)
+      )) if e == error
+    );
+  }
+
+  // 10 llX in fees, 6 of them on referred shares, of which the referrer is owed half. Collecting
+  // without their referral pays the treasury its share, leaving theirs accrued on the vault.
+  let mut transaction =
+    Transaction::new_with_payer(&[vault.collect_fees(&[]).unwrap()], Some(&payer));
+  transaction.sign(&[&program_test_context.payer], recent_blockhash);
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &treasury.pubkey(),
+    &COption::None,
+    7,
+  )
+  .await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.referred_shares, 600);
+  assert_eq!(
+    vault.vault.referral_fee_index,
+    3 * math::FEE_INDEX_SCALE / 600
+  );
+
+  // Passing the referral later pays what it accrued.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .collect_fees(&[(client_llx_account.pubkey(), referrer_llx_account.pubkey())])
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(&[&program_test_context.payer], recent_blockhash);
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &referrer_llx_account.pubkey(),
+    &COption::Some(referrer.pubkey()),
+    3,
+  )
+  .await;
+
+  // Withdrawing 700 of the depositor's 1000 llX leaves 300 of the referred llX, which the fees
+  // are attributed by from then on: the collection settles the referral first, so of 31 llX in
+  // fees over a year on the 310 llX left, the referrer is owed half of those on 300, 15.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 700)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(&[&program_test_context.payer], recent_blockhash);
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let slot = program_test_context
+    .banks_client
+    .get_root_slot()
+    .await
+    .unwrap();
+  program_test_context
+    .warp_to_slot(slot + math::SLOTS_PER_YEAR + 1000)
+    .unwrap();
+  let recent_blockhash = program_test_context
+    .banks_client
+    .get_recent_blockhash()
+    .await
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .collect_fees(&[(client_llx_account.pubkey(), referrer_llx_account.pubkey())])
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(&[&program_test_context.payer], recent_blockhash);
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &referrer_llx_account.pubkey(),
+    &COption::Some(referrer.pubkey()),
+    18,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &treasury.pubkey(),
+    &COption::None,
+    23,
+  )
+  .await;
+  let fee_index = 3 * math::FEE_INDEX_SCALE / 600 + 15 * math::FEE_INDEX_SCALE / 300;
+  assert_eq!(
+    vault
+      .get_referral(
+        &mut program_test_context.banks_client,
+        &client_llx_account.pubkey()
+      )
+      .await
+      .unwrap(),
+    Some(Referral {
+      is_initialized: true,
+      vault: vault_address,
+      referrer: referrer.pubkey(),
+      llx_token_account: client_llx_account.pubkey(),
+      principal: 600,
+      shares: 300,
+      fee_index,
+      accrued_fees: 0,
+    })
+  );
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.referred_shares, 300);
+  assert_eq!(vault.vault.referral_fee_index, fee_index);
+}
+
+/// Tests that keepers execute a vault's trigger once its condition holds, moving the vault out
+/// of its strategy to hodl & collecting the bounty.
+#[tokio::test]
+async fn test_vault_triggers() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6], 2).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let client_x_account = mint_client_vault_accounts[0][1].pubkey();
+  let payer = program_test_context.payer.pubkey();
+  let child_address = create_derived_hodl_vault(
+    &mut program_test_context,
+    &x_mint,
+    &mint_client_vault_accounts[1][0].pubkey(),
+    0,
+  )
+  .await;
+  let child = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &child_address,
+  )
+  .await
+  .unwrap();
+  let parent_address = create_nested_vault(
+    &mut program_test_context,
+    &x_mint,
+    &child.vault.llx_token_mint_id,
+    1,
+    child.as_strategy_accounts(),
+    &[],
+  )
+  .await
+  .unwrap();
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  // Every vault is created with an X token account, which deposits pass through.
+  assert_eq!(
+    parent.vault.x_token_account,
+    COption::Some(pda::find_x_token_address(&::Vault::id(), &parent_address).0)
+  );
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &parent.vault.llx_token_mint_id,
+  )
+  .await;
+
+  // Vaults investing through another vault have no strategy to harvest.
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .create_trigger(
+        0,
+        TriggerCondition::SlotsElapsed { slots: 50 },
+        TriggerAction::Harvest,
+        0,
+      )
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(20) // VaultError::InvalidTrigger
+    )))
+  );
+
+  // A trigger moves the parent to hodl in its X token account.
+  let bounty = 1_000_000;
+  let (trigger_address, _) = pda::find_trigger_address(&::Vault::id(), &parent_address, 0);
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &client_x_account,
+        &payer,
+        &[&payer],
+        100,
+      )
+      .unwrap(),
+      parent
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 100)
+        .unwrap(),
+      parent
+        .create_trigger(
+          0,
+          TriggerCondition::SlotsElapsed { slots: 50 },
+          TriggerAction::MoveToHodl,
+          bounty,
+        )
+        .unwrap(),
+      system_instruction::transfer(&payer, &trigger_address, 2 * bounty),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  let x_token_account = pda::find_x_token_address(&::Vault::id(), &parent_address).0;
+  let trigger = parent
+    .get_trigger(&mut program_test_context.banks_client, 0)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(trigger.vault, parent_address);
+  assert_eq!(trigger.action, TriggerAction::MoveToHodl);
+
+  // Until the slots have passed, the trigger can't be executed.
+  let keeper = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .execute_trigger(&trigger_address, &trigger, &keeper.pubkey(), &[])
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(19) // VaultError::TriggerConditionNotMet
+    )))
+  );
+
+  let slot = program_test_context
+    .banks_client
+    .get_root_slot()
+    .await
+    .unwrap();
+  program_test_context.warp_to_slot(slot + 100).unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .execute_trigger(&trigger_address, &trigger, &keeper.pubkey(), &[])
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // The parent withdrew all of the child's llX for X, which it now hodls.
+  let parent = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &parent_address,
+  )
+  .await
+  .unwrap();
+  assert!(parent.vault.hodl);
+  check_token_account(
+    &mut program_test_context,
+    &parent.vault.lx_token_account,
+    &COption::Some(parent.authority),
+    0,
+  )
+  .await;
+  check_token_account(
+    &mut program_test_context,
+    &x_token_account,
+    &COption::Some(parent.authority),
+    100,
+  )
+  .await;
+  assert_eq!(
+    program_test_context
+      .banks_client
+      .get_balance(keeper.pubkey())
+      .await
+      .unwrap(),
+    bounty
+  );
+
+  // Depositors withdraw from the hodl account.
+  let mut transaction = Transaction::new_with_payer(
+    &[parent
+      .withdraw(&client_llx_account.pubkey(), &client_x_account, &payer, 40)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &x_token_account,
+    &COption::Some(parent.authority),
+    60,
+  )
+  .await;
+  check_token_account(&mut program_test_context, &client_x_account, &COption::None, 40).await;
+}
+
+/// Tests that vaults store the instruction templates used to call their strategy, and reject
+/// templates placing the amount outside their prefix.
+#[tokio::test]
+async fn test_vault_instruction_templates() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let payer = program_test_context.payer.pubkey();
+
+  // An Anchor deposit, and a withdrawal taking a trailing constant argument after the amount.
+  let deposit_instruction = InstructionTemplate::anchor("deposit");
+  let withdraw_instruction = InstructionTemplate {
+    prefix: vec![7, 1, 0],
+    amount_offset: 1,
+    amount_encoding: AmountEncoding::U64Be,
+  };
+  assert_eq!(deposit_instruction.encode(5)[8..], 5u64.to_le_bytes());
+  assert_eq!(
+    withdraw_instruction.encode(5),
+    vec![7, 0, 0, 0, 0, 0, 0, 0, 5, 1, 0]
+  );
+
+  let create_vault = |nonce| {
+    VaultInstruction::create_vault(
+      &::Vault::id(),
+      &payer,
+      &x_mint,
+      &mint_client_vault_accounts[1][0].pubkey(), // lX mint
+      &spl_token::id(),
+      &::Vault::id(), // Strategy program ID
+      nonce,
+      true,   // hodl
+      vec![], // No strategy accounts
+      6,      // llX decimals
+      deposit_instruction.clone(),
+      withdraw_instruction.clone(),
+    )
+    .unwrap()
+  };
+  let mut transaction = Transaction::new_with_payer(&[create_vault(0)], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let vault_address = pda::find_vault_address(&::Vault::id(), &x_mint, &::Vault::id(), 0).0;
+  let vault = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+    .await
+    .unwrap();
+  assert_eq!(vault.strategy_program_deposit_instruction, deposit_instruction);
+  assert_eq!(vault.strategy_program_withdraw_instruction, withdraw_instruction);
+
+  // Move the deposit amount past the end of its prefix: the tag, nonce & hodl flag are followed
+  // by the prefix length, the prefix & then the amount offset.
+  let mut instruction = create_vault(1);
+  instruction.data[1 + 8 + 1 + 1 + MAX_INSTRUCTION_PREFIX_LEN] = 9;
+  let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(0) // VaultError::InvalidInstruction
+    )))
+  );
+}
+
+/// Tests registering vaults & listing them by X mint and strategy program.
+#[tokio::test]
+async fn test_vault_registry() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let lx_mint = mint_client_vault_accounts[1][0].pubkey();
+
+  let mut vaults = vec![];
+  for nonce in 0..2 {
+    let vault = create_derived_hodl_vault(&mut program_test_context, &x_mint, &lx_mint, nonce).await;
+    let mut transaction = Transaction::new_with_payer(
+      &[VaultInstruction::register_vault(
+        &::Vault::id(),
+        &program_test_context.payer.pubkey(),
+        &vault,
+        &x_mint,
+        &::Vault::id(), // Strategy program ID
+        0,
+        0,
+      )
+      .unwrap()],
+      Some(&program_test_context.payer.pubkey()),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context.last_blockhash,
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+    vaults.push(vault);
+  }
+
+  let listed = client::list_vaults_for_mint(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &x_mint,
+  )
+  .await
+  .unwrap();
+  assert_eq!(
+    listed.iter().map(|(vault, _)| *vault).collect::<Vec<_>>(),
+    vaults
+  );
+  assert!(listed.iter().all(|(_, vault)| vault.x_token_mint_id == x_mint));
+  let listed = client::list_vaults_for_strategy(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &::Vault::id(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(listed.len(), 2);
+  assert!(client::list_vaults_for_mint(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &lx_mint,
+  )
+  .await
+  .unwrap()
+  .is_empty());
+
+  // Registering twice is rejected, even in another page.
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::register_vault(
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      &vaults[0],
+      &x_mint,
+      &::Vault::id(),
+      1,
+      1,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(9) // VaultError::VaultAlreadyRegistered
+    )))
+  );
+
+  // A vault can't skip ahead to the next page while the first has room.
+  let vault = create_derived_hodl_vault(&mut program_test_context, &x_mint, &lx_mint, 2).await;
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::register_vault(
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      &vault,
+      &x_mint,
+      &::Vault::id(),
+      1,
+      0,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::InvalidArgument
+    )))
+  );
+  assert_eq!(
+    client::register_vault(
+      &mut program_test_context.banks_client,
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      &vault,
+      &x_mint,
+      &::Vault::id(),
+    )
+    .await
+    .unwrap()
+    .data,
+    VaultInstruction::RegisterVault {
+      mint_registry_page: 0,
+      strategy_registry_page: 0,
+    }
+    .pack()
+  );
+}
+
+#[tokio::test]
+async fn test_close_vault() {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 3).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let lx_mint = mint_client_vault_accounts[1][0].pubkey();
+  let vault = create_derived_hodl_vault(&mut program_test_context, &x_mint, &lx_mint, 0).await;
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::register_vault(
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      &vault,
+      &x_mint,
+      &::Vault::id(),
+      0,
+      0,
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  let storage = client::get_vault(&mut program_test_context.banks_client, &vault)
+    .await
+    .unwrap();
+  assert_eq!(storage.governance, program_test_context.payer.pubkey());
+  let recipient = Keypair::new();
+  let recipient_x_token_account = mint_client_vault_accounts[0][1].pubkey();
+  let recipient_lx_token_account = mint_client_vault_accounts[1][1].pubkey();
+  let close_vault = |governance: &Pubkey| {
+    VaultInstruction::close_vault(
+      &::Vault::id(),
+      governance,
+      &vault,
+      &recipient.pubkey(),
+      &storage.llx_token_mint_id,
+      &lx_mint,
+      &storage.lx_token_account,
+      &recipient_lx_token_account,
+      storage
+        .x_token_account
+        .map(|x_token_account| (x_mint, x_token_account, recipient_x_token_account)),
+      &[spl_token::id()],
+    )
+    .unwrap()
+  };
+
+  // Tokens donated to a vault with no llX outstanding don't keep it from closing.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &storage.x_token_account.unwrap(),
+        &program_test_context.payer.pubkey(),
+        &[],
+        1,
+      )
+      .unwrap(),
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &lx_mint,
+        &storage.lx_token_account,
+        &program_test_context.payer.pubkey(),
+        &[],
+        2,
+      )
+      .unwrap(),
+    ],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Only governance may close the vault.
+  let impostor = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[close_vault(&impostor.pubkey())],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &impostor],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(12) // VaultError::InvalidGovernance
+    )))
+  );
+
+  let mut transaction = Transaction::new_with_payer(
+    &[close_vault(&program_test_context.payer.pubkey())],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  for closed in [vault, storage.lx_token_account].iter() {
+    assert_eq!(
+      program_test_context
+        .banks_client
+        .get_account(*closed)
+        .await
+        .unwrap(),
+      None
+    );
+  }
+  assert!(
+    program_test_context
+      .banks_client
+      .get_balance(recipient.pubkey())
+      .await
+      .unwrap()
+      > 0
+  );
+  for (recipient_token_account, swept) in [
+    (recipient_x_token_account, 1),
+    (recipient_lx_token_account, 2),
+  ]
+  .iter()
+  {
+    check_token_account(
+      &mut program_test_context,
+      recipient_token_account,
+      &COption::None,
+      *swept,
+    )
+    .await;
+  }
+  // Until it's deregistered, the closed vault is left out of listings.
+  assert!(client::list_vaults_for_mint(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &x_mint,
+  )
+  .await
+  .unwrap()
+  .is_empty());
+
+  // The closed vault can now be deregistered.
+  let mut transaction = Transaction::new_with_payer(
+    &[client::deregister_vault(
+      &mut program_test_context.banks_client,
+      &::Vault::id(),
+      &vault,
+      &x_mint,
+      &::Vault::id(),
+    )
+    .await
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  assert!(client::list_vaults_for_mint(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &x_mint,
+  )
+  .await
+  .unwrap()
+  .is_empty());
+}
+
+/// Tests a vault investing through the lending strategy, whose lX appreciates every slot.
+#[tokio::test]
+async fn test_lending_strategy_vault() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let (vault, lx_account, client_x, client_llx, pool, x_reserve, peek_result) = (
+    lending.vault,
+    lending.lx_account,
+    lending.client_x,
+    lending.client_llx,
+    lending.pool,
+    lending.x_reserve,
+    lending.peek_result,
+  );
+  let strategy_account_metas = &lending.strategy_account_metas;
+  let peek = |lx_amount| lending.peek(lx_amount);
+
+  // After some slots, the llX withdraws more X than was deposited.
+  let slot = program_test_context.banks_client.get_root_slot().await.unwrap();
+  program_test_context.warp_to_slot(slot + 100).unwrap();
+
+  // Rolling up values the vault's lX with a peek at the pool in the same slot, of exactly the lX
+  // the vault holds.
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let roll_up = vault_client
+    .roll_up(&mut program_test_context.banks_client, Some(&peek_result))
+    .await
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[peek(1000), roll_up.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let rolled_up = client::get_vault(&mut program_test_context.banks_client, &vault)
+    .await
+    .unwrap();
+  assert!(rolled_up.total_assets > 1000);
+  assert!(rolled_up.total_assets_slot >= slot + 100);
+  let mut transaction = Transaction::new_with_payer(&[peek(999), roll_up.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(17) // VaultError::StaleValuation
+    )))
+  );
+  // Nor does one made for another vault holding as much lX.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      lending.peek_for(1000, &Pubkey::new_unique()),
+      roll_up.clone(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(14) // VaultError::IncorrectStrategyAccount
+    )))
+  );
+
+  // Without a loss limit, withdrawals take whatever the pool pays out for the lX.
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::withdraw(
+      &::Vault::id(),
+      &spl_token::id(),
+      &client_llx,
+      &client_x,
+      strategy_account_metas.clone(),
+      1000,
+    )
+    .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &client_llx, &COption::None, 0).await;
+  check_token_account(&mut program_test_context, &lx_account, &COption::None, 0).await;
+  let client_x_held = spl_token::state::Account::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(client_x)
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap()
+  .amount;
+  assert!(client_x_held > 1000);
+
+  // Exactly the vault's strategy accounts must follow its own, whether it's the pool or any of
+  // the accounts after it which are swapped out.
+  for replaced in [pool, x_reserve].iter() {
+    let mut transaction = Transaction::new_with_payer(
+      &[VaultInstruction::deposit(
+        &::Vault::id(),
+        &spl_token::id(),
+        &client_x,
+        &client_llx,
+        strategy_account_metas
+          .iter()
+          .map(|meta| {
+            if meta.pubkey == *replaced {
+              AccountMeta::new(Pubkey::new_unique(), false)
+            } else {
+              meta.clone()
+            }
+          })
+          .collect(),
+        1,
+      )
+      .unwrap()],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap(),
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Err(TransportError::TransactionError(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(14) // VaultError::IncorrectStrategyAccount
+      )))
+    );
+  }
+}
+
+/// Tests valuing a vault's lX through oracle price accounts, which must be current & known
+/// precisely enough.
+#[tokio::test]
+async fn test_lending_vault_price_feed() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let (vault, peek_result) = (lending.vault, lending.peek_result);
+  let peek = |lx_amount| lending.peek(lx_amount);
+
+  // With a price feed, the pool's peek is taken as an amount of another asset, which the oracles
+  // value at 2 X each.
+  let (_, x_oracle) = set_lending_price_feed(&mut program_test_context, &lending).await;
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let roll_up = vault_client
+    .roll_up(&mut program_test_context.banks_client, Some(&peek_result))
+    .await
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[peek(1000), roll_up.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let peeked = PeekResult::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(peek_result)
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap();
+  let rolled_up = client::get_vault(&mut program_test_context.banks_client, &vault)
+    .await
+    .unwrap();
+  assert_eq!(rolled_up.total_assets, 2 * peeked.x_amount);
+
+  // Prices known less precisely than `oracle::MAX_CONFIDENCE_BPS` are rejected, as are those
+  // published too long ago.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      set_mock_price(&x_oracle, &payer, 100_000_000, 10_000_000, -8),
+      peek(1000),
+      roll_up.clone(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      2,
+      InstructionError::Custom(21) // VaultError::StalePrice
+    )))
+  );
+  let slot = program_test_context.banks_client.get_root_slot().await.unwrap();
+  program_test_context
+    .warp_to_slot(slot + oracle::MAX_PRICE_AGE_SLOTS + 10)
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[peek(1000), roll_up], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(21) // VaultError::StalePrice
+    )))
+  );
+}
+
+/// Tests a drawdown limit moving a vault investing through the lending strategy to hodl, on
+/// roll-ups & harvests.
+#[tokio::test]
+async fn test_lending_vault_drawdown() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let vault = lending.vault;
+  let (asset_oracle, x_oracle) = set_lending_price_feed(&mut program_test_context, &lending).await;
+
+  // With a drawdown limit, a roll-up finding the share price that far below its high-water mark
+  // withdraws everything from the pool to hodl, until governance enables the strategy again.
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[vault_client.set_max_drawdown(1000).unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let x_token_account = pda::find_x_token_address(&::Vault::id(), &vault).0;
+  assert_eq!(vault_client.vault.max_drawdown_bps, 1000);
+  assert_eq!(
+    vault_client.vault.x_token_account,
+    COption::Some(x_token_account)
+  );
+
+  // Harvests check the limit too, against a roll-up in the same slot.
+  let harvest = vault_client.harvest().unwrap();
+  let mut transaction = Transaction::new_with_payer(&[harvest.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(17) // VaultError::StaleValuation
+    )))
+  );
+  let roll_up = vault_client
+    .roll_up(
+      &mut program_test_context.banks_client,
+      Some(&lending.peek_result),
+    )
+    .await
+    .unwrap();
+  for (asset_price, hodl) in [(200, false), (190, false), (150, true)].iter() {
+    let mut transaction = Transaction::new_with_payer(
+      &[
+        set_mock_price(&asset_oracle, &payer, *asset_price, 1, -2),
+        set_mock_price(&x_oracle, &payer, 100_000_000, 100_000, -8),
+        lending.peek(1000),
+        roll_up.clone(),
+        harvest.clone(),
+      ],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap(),
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+    let rolled_up = client::get_vault(&mut program_test_context.banks_client, &vault)
+      .await
+      .unwrap();
+    assert_eq!(
+      rolled_up.hodl, *hodl,
+      "at an asset price of {}",
+      asset_price
+    );
+  }
+  check_token_account(&mut program_test_context, &lending.lx_account, &COption::None, 0).await;
+  let x_held = spl_token::state::Account::unpack(
+    &program_test_context
+      .banks_client
+      .get_account(x_token_account)
+      .await
+      .unwrap()
+      .unwrap()
+      .data,
+  )
+  .unwrap()
+  .amount;
+  // No slots have passed for the pool to accrue interest in.
+  assert_eq!(x_held, 1000);
+
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  assert!(vault_client.harvest().is_err());
+  let mut transaction =
+    Transaction::new_with_payer(&[vault_client.enable_strategy().unwrap()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let enabled = client::get_vault(&mut program_test_context.banks_client, &vault)
+    .await
+    .unwrap();
+  assert!(!enabled.hodl);
+  assert_eq!(enabled.high_water_share_price, 0);
+  check_token_account(
+    &mut program_test_context,
+    &x_token_account,
+    &COption::None,
+    0,
+  )
+  .await;
+}
+
+/// Tests harvest triggers checking a vault's drawdown limit against a roll-up in the same slot,
+/// moving it to hodl instead of harvesting once it's breached.
+#[tokio::test]
+async fn test_lending_vault_harvest_trigger() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let vault = lending.vault;
+  let (asset_oracle, x_oracle) = set_lending_price_feed(&mut program_test_context, &lending).await;
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      vault_client.set_max_drawdown(1000).unwrap(),
+      vault_client
+        .create_trigger(
+          0,
+          TriggerCondition::SlotsElapsed { slots: 0 },
+          TriggerAction::Harvest,
+          0,
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // The first roll-up records the high-water mark, which the asset falling 15% breaches.
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault,
+  )
+  .await
+  .unwrap();
+  let roll_up = vault_client
+    .roll_up(
+      &mut program_test_context.banks_client,
+      Some(&lending.peek_result),
+    )
+    .await
+    .unwrap();
+  let (trigger_address, _) = pda::find_trigger_address(&::Vault::id(), &vault, 0);
+  let trigger = vault_client
+    .get_trigger(&mut program_test_context.banks_client, 0)
+    .await
+    .unwrap()
+    .unwrap();
+  let harvest = vault_client
+    .execute_trigger(&trigger_address, &trigger, &payer, &[])
+    .unwrap();
+  for (asset_price, hodl) in [(200, false), (170, true)].iter() {
+    let mut transaction = Transaction::new_with_payer(
+      &[
+        set_mock_price(&asset_oracle, &payer, *asset_price, 1, -2),
+        set_mock_price(&x_oracle, &payer, 100_000_000, 100_000, -8),
+        lending.peek(1000),
+        roll_up.clone(),
+        harvest.clone(),
+      ],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap(),
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+    let rolled_up = client::get_vault(&mut program_test_context.banks_client, &vault)
+      .await
+      .unwrap();
+    assert_eq!(
+      rolled_up.hodl, *hodl,
+      "at an asset price of {}",
+      asset_price
+    );
+  }
+  check_token_account(&mut program_test_context, &lending.lx_account, &COption::None, 0).await;
+  check_token_account(
+    &mut program_test_context,
+    &pda::find_x_token_address(&::Vault::id(), &vault).0,
+    &COption::None,
+    1000,
+  )
+  .await;
+}
+
+/// Tests a hodl vault lending its X to a strategy up to its debt ratio, marking its debt to peeks
+/// at its lX when harvested.
+#[tokio::test]
+async fn test_vault_strategy_debt() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let x_mint = client::get_vault(&mut program_test_context.banks_client, &lending.vault)
+    .await
+    .unwrap()
+    .x_token_mint_id;
+
+  // A hodl vault holding 1000 X lends it to the pool the lending vault invests through.
+  let vault_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lending.lx_mint, 1).await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let x_token_account = vault.vault.x_token_account.unwrap();
+  let client_llx = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx,
+    &vault.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(&spl_token::id(), &x_mint, &lending.client_x, &payer, &[], 1000)
+        .unwrap(),
+      vault
+        .deposit(&lending.client_x, &client_llx.pubkey(), &payer, 1000)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let lx_account =
+    add_lending_strategy(&mut program_test_context, &lending, &vault_address, 5000, 300).await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.strategies.len(), 1);
+  assert_eq!(vault.vault.strategies[0].lx_token_account, lx_account);
+  assert_eq!(vault.vault.strategies[0].total_debt, 0);
+
+  // Strategies may be owed no more than the vault's assets between them.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault.update_strategy(0, 10001, 0, 300).unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(27) // VaultError::InvalidDebtRatio
+    )))
+  );
+
+  // Harvests mark the debt against a peek of the strategy's lX in the same slot.
+  let harvest = vault.harvest_strategy(0, &lending.peek_result).unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[lending.peek_for(1, &vault_address), harvest.clone()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      1,
+      InstructionError::Custom(17) // VaultError::StaleValuation
+    )))
+  );
+
+  // The strategy is lent up to half the vault's assets, at most 300 X per harvest, and the vault's
+  // shares stay priced against its X held and lent.
+  for (lx_held, debt) in [(0, 300), (300, 500), (500, 500)].iter() {
+    let mut transaction = Transaction::new_with_payer(
+      &[lending.peek_for(*lx_held, &vault_address), harvest.clone()],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context.last_blockhash,
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+    let harvested = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+      .await
+      .unwrap();
+    assert_eq!(harvested.strategies[0].total_debt, *debt);
+    check_token_account(&mut program_test_context, &lx_account, &COption::None, *debt)
+      .await;
+    check_token_account(
+      &mut program_test_context,
+      &x_token_account,
+      &COption::None,
+      1000 - debt,
+    )
+    .await;
+  }
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(
+    vault
+      .share_price(&mut program_test_context.banks_client)
+      .await
+      .unwrap(),
+    SharePrice {
+      total_assets: 1000,
+      share_supply: 1000
+    }
+  );
+
+  // Lowering the ratio takes back what's lent beyond it on the next harvest.
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      vault.update_strategy(0, 2000, 0, 300).unwrap(),
+      lending.peek_for(500, &vault_address),
+      harvest.clone(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let harvested = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+    .await
+    .unwrap();
+  assert_eq!(harvested.strategies[0].debt_ratio_bps, 2000);
+  assert_eq!(harvested.strategies[0].total_debt, 200);
+  check_token_account(&mut program_test_context, &lx_account, &COption::None, 200).await;
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 800).await;
+
+  // Withdrawals are paid from the X held, at a share price counting the debt.
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .withdraw(&client_llx.pubkey(), &lending.client_x, &payer, 500)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &lending.client_x, &COption::None, 500).await;
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 300).await;
+}
+
+/// Tests withdrawals from a hodl vault taking back the X lent to its strategies, in the order of
+/// its withdrawal queue, once its own X runs short.
+#[tokio::test]
+async fn test_vault_withdrawal_queue() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let x_mint = client::get_vault(&mut program_test_context.banks_client, &lending.vault)
+    .await
+    .unwrap()
+    .x_token_mint_id;
+  let vault_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lending.lx_mint, 1).await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let x_token_account = vault.vault.x_token_account.unwrap();
+  let client_llx = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx,
+    &vault.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(&spl_token::id(), &x_mint, &lending.client_x, &payer, &[], 1000)
+        .unwrap(),
+      vault
+        .deposit(&lending.client_x, &client_llx.pubkey(), &payer, 1000)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Two strategies, lending to the same pool through their own lX accounts, are each lent 300 X.
+  let lx_accounts = [
+    add_lending_strategy(&mut program_test_context, &lending, &vault_address, 3000, 300).await,
+    add_lending_strategy(&mut program_test_context, &lending, &vault_address, 3000, 300).await,
+  ];
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(vault.vault.withdrawal_queue, vec![0, 1]);
+  for index in 0..2 {
+    let mut transaction = Transaction::new_with_payer(
+      &[
+        lending.peek_for(0, &vault_address),
+        vault.harvest_strategy(index, &lending.peek_result).unwrap(),
+      ],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context.last_blockhash,
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+  }
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 400).await;
+
+  // The queue may only order distinct strategies of the vault.
+  for withdrawal_queue in [vec![0, 0], vec![2]].iter() {
+    let mut transaction = Transaction::new_with_payer(
+      &[vault.set_withdrawal_queue(withdrawal_queue.clone()).unwrap()],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context.last_blockhash,
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Err(TransportError::TransactionError(TransactionError::InstructionError(
+        0,
+        InstructionError::InvalidArgument
+      )))
+    );
+  }
+  let mut transaction = Transaction::new_with_payer(
+    &[vault.set_withdrawal_queue(vec![1, 0]).unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Withdrawing 800 X takes back everything lent to the second strategy, then 100 X from the
+  // first, without loss.
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      vault
+        .roll_up(&mut program_test_context.banks_client, None)
+        .await
+        .unwrap(),
+      vault
+        .withdraw_with_max_loss(&client_llx.pubkey(), &lending.client_x, &payer, 800, 0)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &lending.client_x, &COption::None, 800).await;
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 0).await;
+  check_token_account(&mut program_test_context, &lx_accounts[0], &COption::None, 200).await;
+  check_token_account(&mut program_test_context, &lx_accounts[1], &COption::None, 0).await;
+  let withdrawn = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+    .await
+    .unwrap();
+  assert_eq!(withdrawn.strategies[0].total_debt, 200);
+  assert_eq!(withdrawn.strategies[1].total_debt, 0);
+
+  // What's left is still lent out, so the queue has to cover all of it.
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  assert_eq!(
+    vault
+      .position(&mut program_test_context.banks_client, &client_llx.pubkey())
+      .await
+      .unwrap(),
+    Position {
+      shares: 200,
+      assets: 200
+    }
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[vault
+      .withdraw(&client_llx.pubkey(), &lending.client_x, &payer, 200)
+      .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &lending.client_x, &COption::None, 1000).await;
+  check_token_account(&mut program_test_context, &lx_accounts[0], &COption::None, 0).await;
+}
+
+/// Tests rebalance triggers harvesting a hodl vault's strategies toward their debt ratios, taking
+/// back X from those owed more than their ratio before lending to the others.
+#[tokio::test]
+async fn test_vault_rebalance_trigger() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let x_mint = client::get_vault(&mut program_test_context.banks_client, &lending.vault)
+    .await
+    .unwrap()
+    .x_token_mint_id;
+  let vault_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lending.lx_mint, 1).await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let x_token_account = vault.vault.x_token_account.unwrap();
+  let client_llx = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx,
+    &vault.vault.llx_token_mint_id,
+  )
+  .await;
+
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(&spl_token::id(), &x_mint, &lending.client_x, &payer, &[], 1000)
+        .unwrap(),
+      vault
+        .deposit(&lending.client_x, &client_llx.pubkey(), &payer, 1000)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // There's nothing to rebalance until the vault lends to strategies.
+  let rebalance_trigger = vault
+    .create_trigger(
+      0,
+      TriggerCondition::SlotsElapsed { slots: 0 },
+      TriggerAction::Rebalance,
+      0,
+    )
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[rebalance_trigger.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(20) // VaultError::InvalidTrigger
+    )))
+  );
+
+  // The strategies are lent 300 & 500 X of the vault's 1000 X, before their ratios change to 80%
+  // & 10%.
+  let lx_accounts = [
+    add_lending_strategy(&mut program_test_context, &lending, &vault_address, 3000, 1000).await,
+    add_lending_strategy(&mut program_test_context, &lending, &vault_address, 5000, 1000).await,
+  ];
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  for index in 0..2 {
+    let mut transaction = Transaction::new_with_payer(
+      &[
+        lending.peek_for(0, &vault_address),
+        vault.harvest_strategy(index, &lending.peek_result).unwrap(),
+      ],
+      Some(&payer),
+    );
+    transaction.sign(
+      &[&program_test_context.payer],
+      program_test_context.last_blockhash,
+    );
+    assert_matches!(
+      program_test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await,
+      Ok(())
+    );
+  }
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      vault.update_strategy(1, 1000, 0, 1000).unwrap(),
+      vault.update_strategy(0, 8000, 0, 1000).unwrap(),
+      rebalance_trigger,
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 200).await;
+
+  // Taking back 400 X from the second strategy first leaves enough X to lend the first up to its
+  // ratio.
+  let peek_results = [lending.peek_result, create_peek_result(&mut program_test_context).await];
+  let (trigger_address, _) = pda::find_trigger_address(&::Vault::id(), &vault_address, 0);
+  let trigger = vault
+    .get_trigger(&mut program_test_context.banks_client, 0)
+    .await
+    .unwrap()
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      lending.peek_into(&peek_results[0], 300, &vault_address),
+      lending.peek_into(&peek_results[1], 500, &vault_address),
+      vault
+        .execute_trigger(&trigger_address, &trigger, &payer, &peek_results)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let rebalanced = client::get_vault(&mut program_test_context.banks_client, &vault_address)
+    .await
+    .unwrap();
+  assert_eq!(rebalanced.strategies[0].total_debt, 800);
+  assert_eq!(rebalanced.strategies[1].total_debt, 100);
+  check_token_account(&mut program_test_context, &lx_accounts[0], &COption::None, 800).await;
+  check_token_account(&mut program_test_context, &lx_accounts[1], &COption::None, 100).await;
+  check_token_account(&mut program_test_context, &x_token_account, &COption::None, 100).await;
+}
+
+/// Tests withdrawals capping the loss they accept against a roll-up in the same slot, from a hodl
+/// vault and from a vault investing through a lending pool valued by oracles.
+#[tokio::test]
+async fn test_withdrawal_max_loss() {
+  let (mut program_test_context, lending) = setup_lending_vault().await;
+  let payer = program_test_context.payer.pubkey();
+  let x_mint = client::get_vault(&mut program_test_context.banks_client, &lending.vault)
+    .await
+    .unwrap()
+    .x_token_mint_id;
+  let vault_address =
+    create_derived_hodl_vault(&mut program_test_context, &x_mint, &lending.lx_mint, 1).await;
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &vault_address,
+  )
+  .await
+  .unwrap();
+  let client_x_account = lending.client_x;
+  let client_llx_account = Keypair::new();
+  create_token_account(
+    &mut program_test_context,
+    &client_llx_account,
+    &vault.vault.llx_token_mint_id,
+  )
+  .await;
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(&spl_token::id(), &x_mint, &client_x_account, &payer, &[], 60)
+        .unwrap(),
+      vault
+        .deposit(&client_x_account, &client_llx_account.pubkey(), &payer, 60)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // Hodl withdrawals may cap their loss, against a roll-up in the same slot.
+  let withdraw = vault
+    .withdraw_with_max_loss(
+      &client_llx_account.pubkey(),
+      &client_x_account,
+      &payer,
+      10,
+      0,
+    )
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[withdraw.clone()], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      0,
+      InstructionError::Custom(17) // VaultError::StaleValuation
+    )))
+  );
+  let roll_up = vault
+    .roll_up(&mut program_test_context.banks_client, None)
+    .await
+    .unwrap();
+  let mut transaction = Transaction::new_with_payer(&[roll_up, withdraw], Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  assert_eq!(
+    vault
+      .position(
+        &mut program_test_context.banks_client,
+        &client_llx_account.pubkey()
+      )
+      .await
+      .unwrap(),
+    Position {
+      shares: 50,
+      assets: 50
+    }
+  );
+
+  // The pool pays out 1 X for each lX, short of the 1.5 X the oracles value it at, which
+  // withdrawals refuse unless they accept losing a third of the llX's value.
+  let (asset_oracle, x_oracle) = set_lending_price_feed(&mut program_test_context, &lending).await;
+  let vault_client = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    &lending.vault,
+  )
+  .await
+  .unwrap();
+  let roll_up = vault_client
+    .roll_up(&mut program_test_context.banks_client, Some(&lending.peek_result))
+    .await
+    .unwrap();
+  let withdraw = |max_loss_bps| {
+    VaultInstruction::withdraw_with_max_loss(
+      &::Vault::id(),
+      &spl_token::id(),
+      &lending.client_llx,
+      &lending.client_x,
+      lending.strategy_account_metas.clone(),
+      1000,
+      max_loss_bps,
+    )
+    .unwrap()
+  };
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      set_mock_price(&asset_oracle, &payer, 150, 1, -2),
+      set_mock_price(&x_oracle, &payer, 100_000_000, 100_000, -8),
+      lending.peek(1000),
+      roll_up.clone(),
+      withdraw(100),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Err(TransportError::TransactionError(TransactionError::InstructionError(
+      4,
+      InstructionError::Custom(22) // VaultError::WithdrawalLossExceeded
+    )))
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      set_mock_price(&asset_oracle, &payer, 150, 1, -2),
+      set_mock_price(&x_oracle, &payer, 100_000_000, 100_000, -8),
+      lending.peek(1000),
+      roll_up,
+      withdraw(3400),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(&mut program_test_context, &lending.client_llx, &COption::None, 0).await;
+  check_token_account(&mut program_test_context, &lending.client_x, &COption::None, 1010).await;
+}
+
+/// A vault investing through a lending pool accruing 0.1% per slot, into which the client has
+/// deposited 1000 X.
+struct LendingVault {
+  x_reserve: Pubkey,
+  lx_mint: Pubkey,
+  pool: Pubkey,
+  vault: Pubkey,
+  lx_account: Pubkey,
+  client_x: Pubkey,
+  client_llx: Pubkey,
+  /// The accounts transfers between the client & the vault pass after the client's.
+  strategy_account_metas: Vec<AccountMeta>,
+  /// The account the pool's peeks write their result to.
+  peek_result: Pubkey,
+}
+
+impl LendingVault {
+  /// Peeks at what `lx_amount` of the pool's lX held by `vault` is worth.
+  fn peek_for(&self, lx_amount: u64, vault: &Pubkey) -> Instruction {
+    self.peek_into(&self.peek_result, lx_amount, vault)
+  }
+
+  /// Peeks as `peek_for` does, writing the result to `peek_result`.
+  fn peek_into(&self, peek_result: &Pubkey, lx_amount: u64, vault: &Pubkey) -> Instruction {
+    StrategyInstruction::peek(
+      &lending_strategy::id(),
+      peek_result,
+      &self.pool,
+      // The reserve & lX mint back the pool's exchange rate.
+      vec![
+        AccountMeta::new_readonly(self.x_reserve, false),
+        AccountMeta::new_readonly(self.lx_mint, false),
+      ],
+      lx_amount,
+      vault,
+    )
+    .unwrap()
+  }
+
+  fn peek(&self, lx_amount: u64) -> Instruction {
+    self.peek_for(lx_amount, &self.vault)
+  }
+}
+
+async fn setup_lending_vault() -> (ProgramTestContext, LendingVault) {
+  let mut program_test = ProgramTest::new(
+    "token_test",
+    spl_token::id(),
+    processor!(Processor::process),
+  );
+  program_test.add_program(
+    "vault_test",
+    ::Vault::id(),
+    processor!(::Vault::processor::Processor::process),
+  );
+  program_test.add_program(
+    "lending_strategy",
+    lending_strategy::id(),
+    processor!(lending_strategy::processor::Processor::process),
+  );
+  program_test.add_program(
+    "mock_price",
+    mock_price::id(),
+    processor!(mock_price::processor::Processor::process),
+  );
+  let mut program_test_context = program_test.start_with_context().await;
+  // Token 0 is X, 1 the pool's lX and 2 unused, since the vault creates its own llX mint.
+  let mint_client_vault_accounts =
+    create_tokens_and_accounts(&mut program_test_context, &[6, 6, 6], 2).await;
+  let x_mint = mint_client_vault_accounts[0][0].pubkey();
+  let lx_mint = mint_client_vault_accounts[1][0].pubkey();
+  let x_reserve = mint_client_vault_accounts[0][2].pubkey();
+  let payer = program_test_context.payer.pubkey();
+
+  // Hand the lX mint & X reserve over to a new lending pool, accruing 0.1% per slot.
+  let pool = Keypair::new();
+  let (pool_authority, _) =
+    lending_strategy::instruction::find_pool_authority_address(&lending_strategy::id(), &pool.pubkey());
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &lx_mint,
+        Some(&pool_authority),
+        spl_token::instruction::AuthorityType::MintTokens,
+        &payer,
+        &[],
+      )
+      .unwrap(),
+      spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &x_reserve,
+        Some(&pool_authority),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        &payer,
+        &[],
+      )
+      .unwrap(),
+      system_instruction::create_account(
+        &payer,
+        &pool.pubkey(),
+        1.max(Rent::default().minimum_balance(lending_strategy::state::LendingPool::LEN)),
+        lending_strategy::state::LendingPool::LEN as u64,
+        &lending_strategy::id(),
+      ),
+      lending_strategy::instruction::LendingInstruction::initialize_pool(
+        &lending_strategy::id(),
+        &pool.pubkey(),
+        &payer,
+        &x_mint,
+        &lx_mint,
+        &x_reserve,
+        (lending_strategy::state::RATE_SCALE / 1000) as u64,
+      )
+      .unwrap(),
+      // Fund the interest paid out on withdrawal.
+      spl_token::instruction::mint_to(&spl_token::id(), &x_mint, &x_reserve, &payer, &[], 1000)
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &pool],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+
+  // The vault passes the pool, followed by the accounts the pool needs, to the strategy.
+  let mut strategy_accounts = vec![StrategyAccount::new(pool.pubkey(), true)];
+  strategy_accounts.extend(
+    lending_strategy::instruction::transfer_account_metas(
+      &lending_strategy::id(),
+      &pool.pubkey(),
+      &x_reserve,
+      &lx_mint,
+    )
+    .into_iter()
+    .map(StrategyAccount::from),
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::create_vault(
+      &::Vault::id(),
+      &payer,
+      &x_mint,
+      &lx_mint,
+      &spl_token::id(),
+      &lending_strategy::id(),
+      0,     // nonce
+      false, // hodl
+      strategy_accounts.clone(),
+      6, // llX decimals
+      InstructionTemplate::tag(0), // StrategyInstruction::Deposit
+      InstructionTemplate::tag(1), // StrategyInstruction::Withdraw
+    )
+    .unwrap()],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  let vault = pda::find_vault_address(&::Vault::id(), &x_mint, &lending_strategy::id(), 0).0;
+  let llx_mint = pda::find_llx_mint_address(&::Vault::id(), &vault).0;
+  let lx_account = pda::find_lx_token_address(&::Vault::id(), &vault).0;
+  let client_llx = Keypair::new();
+  create_token_account(&mut program_test_context, &client_llx, &llx_mint).await;
+
+  let vault_account_metas = vec![
+    AccountMeta::new_readonly(payer, true), // source authority
+    AccountMeta::new(vault, false),
+    AccountMeta::new_readonly(
+      pda::find_vault_authority_address(&::Vault::id(), &vault).0,
+      false,
+    ),
+    AccountMeta::new_readonly(x_mint, false),
+    AccountMeta::new(llx_mint, false),
+    AccountMeta::new(lx_account, false),
+    AccountMeta::new(pda::find_x_token_address(&::Vault::id(), &vault).0, false),
+    AccountMeta::new_readonly(lending_strategy::id(), false),
+  ];
+  let mut strategy_account_metas = vault_account_metas;
+  strategy_account_metas.extend(
+    client::get_vault(&mut program_test_context.banks_client, &vault)
+      .await
+      .unwrap()
+      .strategy_account_metas(),
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &x_mint,
+        &mint_client_vault_accounts[0][1].pubkey(),
+        &payer,
+        &[],
+        1000,
+      )
+      .unwrap(),
+      VaultInstruction::deposit(
+        &::Vault::id(),
+        &spl_token::id(),
+        &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
+        &client_llx.pubkey(),
+        strategy_account_metas.clone(),
+        1000,
+      )
+      .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  check_token_account(
+    &mut program_test_context,
+    &mint_client_vault_accounts[0][1].pubkey(),
+    &COption::None,
+    0,
+  )
+  .await;
+  check_token_account(&mut program_test_context, &client_llx.pubkey(), &COption::None, 1000).await;
+  check_token_account(&mut program_test_context, &lx_account, &COption::None, 1000).await;
+
+
+  let peek_result = create_peek_result(&mut program_test_context).await;
+  (
+    program_test_context,
+    LendingVault {
+      x_reserve,
+      lx_mint,
+      pool: pool.pubkey(),
+      vault,
+      lx_account,
+      client_x: mint_client_vault_accounts[0][1].pubkey(),
+      client_llx: client_llx.pubkey(),
+      strategy_account_metas,
+      peek_result,
+    },
+  )
+}
+
+/// Values the pool's peeks for `lending`'s vault as amounts of another asset, which mock oracles
+/// price at 2 X each, returning the asset's & X's price accounts.
+/// Creates an account for the lending pool's peeks to write their result to.
+async fn create_peek_result(program_test_context: &mut ProgramTestContext) -> Pubkey {
+  let payer = program_test_context.payer.pubkey();
+  let peek_result = Keypair::new();
+  let mut transaction = Transaction::new_with_payer(
+    &[system_instruction::create_account(
+      &payer,
+      &peek_result.pubkey(),
+      1.max(Rent::default().minimum_balance(PeekResult::LEN)),
+      PeekResult::LEN as u64,
+      &lending_strategy::id(),
+    )],
+    Some(&payer),
   );
-  program_test.add_program(
-    "vault_test",
-    ::Vault::id(),
-    processor!(::Vault::processor::Processor::process),
+  transaction.sign(
+    &[&program_test_context.payer, &peek_result],
+    program_test_context.last_blockhash,
   );
-  let mut program_test_context = program_test.start_with_context().await;
-  // A basic Vault has 3 relevant tokens: X (underlying asset), lX (strategy derivative), llX (vault
-  // derivative). We roughly need a client-managed & vault-managed SPL token account per-token.
-  // For succintnesss, we set all of these up together:
-  let mint_client_vault_accounts =
-    create_tokens_and_accounts(&mut program_test_context, 3, 3).await;
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  peek_result.pubkey()
+}
 
-  // Create Vault account
+/// Adds a strategy lending the hodl vault at `vault_address`'s X to the pool, through a new lX
+/// account, and returns that account.
+async fn add_lending_strategy(
+  program_test_context: &mut ProgramTestContext,
+  lending: &LendingVault,
+  vault_address: &Pubkey,
+  debt_ratio_bps: u16,
+  max_debt_per_harvest: u64,
+) -> Pubkey {
+  let payer = program_test_context.payer.pubkey();
+  let vault = VaultClient::load(
+    &mut program_test_context.banks_client,
+    &::Vault::id(),
+    vault_address,
+  )
+  .await
+  .unwrap();
+  let lx_account = Keypair::new();
+  let mut strategy_accounts = vec![StrategyAccount::new(lending.pool, true)];
+  strategy_accounts.extend(
+    lending_strategy::instruction::transfer_account_metas(
+      &lending_strategy::id(),
+      &lending.pool,
+      &lending.x_reserve,
+      &lending.lx_mint,
+    )
+    .into_iter()
+    .map(StrategyAccount::from),
+  );
+  let mut transaction = Transaction::new_with_payer(
+    &[
+      system_instruction::create_account(
+        &payer,
+        &lx_account.pubkey(),
+        1.max(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
+        spl_token::state::Account::LEN as u64,
+        &spl_token::id(),
+      ),
+      spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &lx_account.pubkey(),
+        &lending.lx_mint,
+        &vault.authority,
+      )
+      .unwrap(),
+      vault
+        .add_strategy(
+          &lending_strategy::id(),
+          &lx_account.pubkey(),
+          strategy_accounts,
+          InstructionTemplate::tag(0), // StrategyInstruction::Deposit
+          InstructionTemplate::tag(1), // StrategyInstruction::Withdraw
+          debt_ratio_bps,
+          0,
+          max_debt_per_harvest,
+        )
+        .unwrap(),
+    ],
+    Some(&payer),
+  );
+  transaction.sign(
+    &[&program_test_context.payer, &lx_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  lx_account.pubkey()
+}
+
+async fn set_lending_price_feed(
+  program_test_context: &mut ProgramTestContext,
+  lending: &LendingVault,
+) -> (Pubkey, Pubkey) {
+  let payer = program_test_context.payer.pubkey();
+  let (asset_oracle, x_oracle) = (Keypair::new(), Keypair::new());
+  let mut instructions = vec![];
+  for price_account in [&asset_oracle, &x_oracle].iter() {
+    instructions.push(system_instruction::create_account(
+      &payer,
+      &price_account.pubkey(),
+      Rent::default().minimum_balance(mock_price::PRICE_ACCOUNT_LEN),
+      mock_price::PRICE_ACCOUNT_LEN as u64,
+      &mock_price::id(),
+    ));
+  }
+  instructions.push(set_mock_price(&asset_oracle.pubkey(), &payer, 200, 1, -2));
+  instructions.push(set_mock_price(&x_oracle.pubkey(), &payer, 100_000_000, 100_000, -8));
+  instructions.push(
+    VaultInstruction::set_price_feed(
+      &::Vault::id(),
+      &payer,
+      &lending.vault,
+      COption::Some(PriceFeed {
+        asset_oracle: asset_oracle.pubkey(),
+        x_oracle: x_oracle.pubkey(),
+        asset_decimals: 6,
+      }),
+    )
+    .unwrap(),
+  );
+  let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer));
+  transaction.sign(
+    &[&program_test_context.payer, &asset_oracle, &x_oracle],
+    program_test_context
+      .banks_client
+      .get_recent_blockhash()
+      .await
+      .unwrap(),
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  (asset_oracle.pubkey(), x_oracle.pubkey())
+}
+
+fn set_mock_price(
+  price_account: &Pubkey,
+  authority: &Pubkey,
+  price: i64,
+  conf: u64,
+  expo: i32,
+) -> Instruction {
+  MockPriceInstruction::set_price(
+    &mock_price::id(),
+    price_account,
+    authority,
+    price,
+    conf,
+    expo,
+    oracle::STATUS_TRADING,
+  )
+  .unwrap()
+}
+
+/// Creates a hodl vault over `x_mint` at its derived address, returning that address.
+async fn create_derived_hodl_vault(
+  program_test_context: &mut ProgramTestContext,
+  x_mint: &Pubkey,
+  lx_mint: &Pubkey,
+  nonce: u64,
+) -> Pubkey {
+  let mut transaction = Transaction::new_with_payer(
+    &[VaultInstruction::create_vault(
+      &::Vault::id(),
+      &program_test_context.payer.pubkey(),
+      x_mint,
+      lx_mint,
+      &spl_token::id(),
+      &::Vault::id(), // Strategy program ID
+      nonce,
+      true, // hodl
+      vec![],       // No strategy accounts
+      6,    // llX decimals
+      InstructionTemplate::default(), // unused deposit instruction
+      InstructionTemplate::default(), // unused withdraw instruction
+    )
+    .unwrap()],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+  pda::find_vault_address(&::Vault::id(), x_mint, &::Vault::id(), nonce).0
+}
+
+/// Creates a vault at `nonce` investing through another vault with `strategy_accounts`, passing
+/// `below`, the vaults further down the chain, returning the new vault's address.
+async fn create_nested_vault(
+  program_test_context: &mut ProgramTestContext,
+  x_mint: &Pubkey,
+  lx_mint: &Pubkey,
+  nonce: u64,
+  strategy_accounts: Vec<StrategyAccount>,
+  below: &[Pubkey],
+) -> Result<Pubkey, TransportError> {
+  let mut instruction = VaultInstruction::create_vault(
+    &::Vault::id(),
+    &program_test_context.payer.pubkey(),
+    x_mint,
+    lx_mint,
+    &spl_token::id(),
+    &::Vault::id(), // Strategy program ID
+    nonce,
+    false, // hodl
+    strategy_accounts,
+    6, // llX decimals
+    InstructionTemplate::tag(1), // VaultInstruction::Deposit
+    InstructionTemplate::tag(2), // VaultInstruction::Withdraw
+  )
+  .unwrap();
+  instruction
+    .accounts
+    .extend(below.iter().map(|vault| AccountMeta::new_readonly(*vault, false)));
+  let mut transaction = Transaction::new_with_payer(
+    &[instruction],
+    Some(&program_test_context.payer.pubkey()),
+  );
+  transaction.sign(
+    &[&program_test_context.payer],
+    program_test_context.last_blockhash,
+  );
+  program_test_context
+    .banks_client
+    .process_transaction(transaction)
+    .await?;
+  Ok(pda::find_vault_address(&::Vault::id(), x_mint, &::Vault::id(), nonce).0)
+}
+
+/// Creates & initializes a hodl vault over the X, lX & llX tokens from
+/// `create_tokens_and_accounts`, returning its storage account.
+async fn initialize_hodl_vault(
+  program_test_context: &mut ProgramTestContext,
+  mint_client_vault_accounts: &[Vec<Keypair>],
+) -> Keypair {
   let hodl_vault_storage_account = Keypair::new();
   let mut transaction = Transaction::new_with_payer(
     &[
@@ -63,12 +5082,14 @@ async fn test_hodl_vault() {
         &hodl_vault_storage_account.pubkey(),
         &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
         &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
+        &mint_client_vault_accounts[0][0].pubkey(), // x mint account
         &spl_token::id(),
         &::Vault::id(), // Strategy program ID
         true,           // hodl
-        COption::Some(mint_client_vault_accounts[0][2].pubkey()), // vault_lx_token account
-        99,             // unused deposit inst. ID
-        99,             // unused withdraw inst. ID
+        &mint_client_vault_accounts[0][2].pubkey(), // vault_x_token account
+        vec![],        // No strategy accounts
+        InstructionTemplate::default(), // unused deposit instruction
+        InstructionTemplate::default(), // unused withdraw instruction
       )
       .unwrap(),
     ],
@@ -85,9 +5106,18 @@ async fn test_hodl_vault() {
       .await,
     Ok(())
   );
+  hodl_vault_storage_account
+}
 
-  // Transact with hodl vault.
-  let (pda, bump_seed) = Pubkey::find_program_address(&[b"vault"], &::Vault::id());
+/// Mints `mint_amount` X tokens to the client and deposits `amount` of them into the hodl vault.
+async fn deposit_into_hodl_vault(
+  program_test_context: &mut ProgramTestContext,
+  mint_client_vault_accounts: &[Vec<Keypair>],
+  storage_account: &Pubkey,
+  mint_amount: u64,
+  amount: u64,
+) {
+  let (pda, _bump_seed) = pda::find_vault_authority_address(&::Vault::id(), storage_account);
   let mut transaction = Transaction::new_with_payer(
     &[
       // Generate a bunch of X tokens and send them to the appropriate client-managed token acct.
@@ -97,7 +5127,7 @@ async fn test_hodl_vault() {
         &mint_client_vault_accounts[0][1].pubkey(),
         &program_test_context.payer.pubkey(),
         &[&program_test_context.payer.pubkey()],
-        1000,
+        mint_amount,
       )
       .unwrap(),
       // Deposit X tokens from client account into Vault in exchange for llX tokens.
@@ -105,13 +5135,16 @@ async fn test_hodl_vault() {
         &::Vault::id(),
         &spl_token::id(),
         &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
-        &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
+        &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
         vec![
           AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
-          AccountMeta::new_readonly(hodl_vault_storage_account.pubkey(), false),
+          AccountMeta::new_readonly(*storage_account, false),
+          AccountMeta::new_readonly(pda, false),
+          AccountMeta::new_readonly(mint_client_vault_accounts[0][0].pubkey(), false), // X mint
+          AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
           AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
         ],
-        100,
+        amount,
       )
       .unwrap(),
     ],
@@ -128,44 +5161,33 @@ async fn test_hodl_vault() {
       .await,
     Ok(())
   );
-  // Ensure accounts have correct balances.
-  // Due to Rust semantics limitations around borrowing, we don't pass an expected owner.
-  check_token_account(
-    &mut program_test_context,
-    &mint_client_vault_accounts[0][1].pubkey(),
-    &COption::None,
-    900,
-  )
-  .await;
-  check_token_account(
-    &mut program_test_context,
-    &mint_client_vault_accounts[0][2].pubkey(),
-    &COption::Some(pda),
-    100,
-  )
-  .await;
-  check_token_account(
-    &mut program_test_context,
-    &mint_client_vault_accounts[1][2].pubkey(),
-    &COption::Some(pda),
-    0,
-  )
-  .await;
+}
 
+/// Redeems `amount` llX tokens from the hodl vault into the client's X account.
+async fn withdraw_from_hodl_vault(
+  program_test_context: &mut ProgramTestContext,
+  mint_client_vault_accounts: &[Vec<Keypair>],
+  storage_account: &Pubkey,
+  amount: u64,
+) {
+  let (pda, _bump_seed) = pda::find_vault_authority_address(&::Vault::id(), storage_account);
   let mut transaction = Transaction::new_with_payer(
     &[
       // Withdraw X tokens from vault into client account in exchange for llX tokens.
       VaultInstruction::withdraw(
         &::Vault::id(),
         &spl_token::id(),
-        &mint_client_vault_accounts[0][2].pubkey(), // vault_x_token account
+        &mint_client_vault_accounts[2][1].pubkey(), // client_llx_token account
         &mint_client_vault_accounts[0][1].pubkey(), // client_x_token account
         vec![
+          AccountMeta::new_readonly(program_test_context.payer.pubkey(), true), // source authority
+          AccountMeta::new_readonly(*storage_account, false),
           AccountMeta::new_readonly(pda, false),
-          AccountMeta::new_readonly(hodl_vault_storage_account.pubkey(), false),
-          AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl destination.
+          AccountMeta::new_readonly(mint_client_vault_accounts[0][0].pubkey(), false), // X mint
+          AccountMeta::new(mint_client_vault_accounts[2][0].pubkey(), false), // llX mint
+          AccountMeta::new(mint_client_vault_accounts[0][2].pubkey(), false), // hodl source.
         ],
-        100,
+        amount,
       )
       .unwrap(),
     ],
@@ -182,63 +5204,6 @@ async fn test_hodl_vault() {
       .await,
     Ok(())
   );
-  check_token_account(
-    &mut program_test_context,
-    &mint_client_vault_accounts[0][1].pubkey(),
-    &COption::None,
-    1000,
-  )
-  .await;
-  check_token_account(
-    &mut program_test_context,
-    &mint_client_vault_accounts[0][2].pubkey(),
-    &COption::Some(pda),
-    0,
-  )
-  .await;
-
-  // Create wrapper vault which uses the hodl vault as a Strategy.
-  // TODO(004): Uncomment below.
-  // let wrapper_vault_storage_account = Keypair::new();
-  // let mut transaction = Transaction::new_with_payer(
-  //   &[
-  //     // Create Vault storage acccount.
-  //     system_instruction::create_account(
-  //       &program_test_context.payer.pubkey(),
-  //       &wrapper_vault_storage_account.pubkey(),
-  //       1.max(Rent::default().minimum_balance(::Vault::state::Vault::LEN)),
-  //       ::Vault::state::Vault::LEN as u64,
-  //       &::Vault::id(),
-  //     ),
-  //     // Initialize the vault & setup its storage account.
-  //     VaultInstruction::initialize_vault(
-  //       &::Vault::id(),
-  //       &program_test_context.payer.pubkey(),
-  //       &wrapper_vault_storage_account.pubkey(),
-  //       &mint_client_vault_accounts[1][2].pubkey(), // vault_lx_token account
-  //       &mint_client_vault_accounts[2][0].pubkey(), // llx mint account
-  //       &spl_token::id(),
-  //       &::Vault::id(), // Strategy program ID
-  //       false,          // hodl
-  //       COption::None,  // Unused vault_lx_token account
-  //       2,              // deposit inst. ID
-  //       3,              // withdraw inst. ID
-  //     )
-  //     .unwrap(),
-  //   ],
-  //   Some(&program_test_context.payer.pubkey()),
-  // );
-  // transaction.sign(
-  //   &[&program_test_context.payer, &wrapper_vault_storage_account],
-  //   program_test_context.last_blockhash,
-  // );
-  // assert_matches!(
-  //   program_test_context
-  //     .banks_client
-  //     .process_transaction(transaction)
-  //     .await,
-  //   Ok(())
-  // );
 }
 
 /// Checks for expected values on a token account.
@@ -262,16 +5227,18 @@ async fn check_token_account(
   assert_eq!(internal_account.amount, expected_amount);
 }
 
-/// Generates tokens & token-accounts to hold them in the specified numbers.
+/// Generates tokens & token-accounts to hold them in the specified numbers. One token is created
+/// per entry in `decimals`, using that entry as the mint's decimals.
 ///
 /// Returns a Vec matrix in which each row corresponds to a single token, the first value in the
 /// row is the mint account, and the remaining values are token accounts.
 async fn create_tokens_and_accounts(
   program_test_context: &mut ProgramTestContext,
-  num_tokens: u64,
+  decimals: &[u8],
   num_accounts: u64,
 ) -> Vec<Vec<Keypair>> {
-  let mint_client_vault_accounts = (1..(num_tokens + 1))
+  let mint_client_vault_accounts = decimals
+    .iter()
     .map(|_| {
       (1..(num_accounts + 2))
         .map(|_| Keypair::new())
@@ -280,7 +5247,7 @@ async fn create_tokens_and_accounts(
     .collect::<Vec<Vec<Keypair>>>();
 
   // Mint our various tokens & setup accounts.
-  for accounts in mint_client_vault_accounts.iter() {
+  for (accounts, decimals) in mint_client_vault_accounts.iter().zip(decimals) {
     let mut instructions = Vec::with_capacity(2);
     let mint = &accounts[0]; // First account is always mint
     instructions.push(system_instruction::create_account(
@@ -296,7 +5263,7 @@ async fn create_tokens_and_accounts(
         &mint.pubkey(),
         &program_test_context.payer.pubkey(),
         None, // Freeze authority
-        6,    // decimals
+        *decimals,
       )
       .unwrap(),
     );
@@ -315,39 +5282,48 @@ async fn create_tokens_and_accounts(
     );
 
     for token_account in accounts[1..].iter() {
-      let mut instructions = Vec::with_capacity(2);
-      instructions.push(system_instruction::create_account(
-        &program_test_context.payer.pubkey(),
-        &token_account.pubkey(),
-        1.max(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
-        spl_token::state::Account::LEN as u64,
-        &spl_token::id(),
-      ));
-      instructions.push(
-        spl_token::instruction::initialize_account(
-          &spl_token::id(),
-          &token_account.pubkey(),
-          &mint.pubkey(),
-          &program_test_context.payer.pubkey(),
-        )
-        .unwrap(),
-      );
-      // Note: We can only sign with so many signatures at once, so we need to split transactions
-      // up quite a
-      let mut transaction =
-        Transaction::new_with_payer(&instructions, Some(&program_test_context.payer.pubkey()));
-      transaction.sign(
-        &[&program_test_context.payer, &token_account],
-        program_test_context.last_blockhash,
-      );
-      assert_matches!(
-        program_test_context
-          .banks_client
-          .process_transaction(transaction)
-          .await,
-        Ok(())
-      );
+      create_token_account(program_test_context, token_account, &mint.pubkey()).await;
     }
   }
   return mint_client_vault_accounts;
 }
+
+/// Creates a token account for `mint` owned by the payer.
+async fn create_token_account(
+  program_test_context: &mut ProgramTestContext,
+  token_account: &Keypair,
+  mint: &Pubkey,
+) {
+  let mut instructions = Vec::with_capacity(2);
+  instructions.push(system_instruction::create_account(
+    &program_test_context.payer.pubkey(),
+    &token_account.pubkey(),
+    1.max(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
+    spl_token::state::Account::LEN as u64,
+    &spl_token::id(),
+  ));
+  instructions.push(
+    spl_token::instruction::initialize_account(
+      &spl_token::id(),
+      &token_account.pubkey(),
+      mint,
+      &program_test_context.payer.pubkey(),
+    )
+    .unwrap(),
+  );
+  // Note: We can only sign with so many signatures at once, so we need to split transactions
+  // up quite a
+  let mut transaction =
+    Transaction::new_with_payer(&instructions, Some(&program_test_context.payer.pubkey()));
+  transaction.sign(
+    &[&program_test_context.payer, token_account],
+    program_test_context.last_blockhash,
+  );
+  assert_matches!(
+    program_test_context
+      .banks_client
+      .process_transaction(transaction)
+      .await,
+    Ok(())
+  );
+}
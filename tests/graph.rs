@@ -0,0 +1,190 @@
+#![cfg(feature = "client")]
+
+use {
+  ::Vault::{
+    client::{AccountFetcher, ClientError, FetchedAccount},
+    graph::{child_vault_metas, InvestmentGraph},
+    state::{InstructionTemplate, StrategyAccount, Vault},
+  },
+  async_trait::async_trait,
+  solana_program::{
+    instruction::AccountMeta, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+  },
+  std::collections::HashMap,
+};
+
+/// Tests that vaults render as boxes with an edge to the vault or strategy they invest through.
+#[test]
+fn test_investment_graph_to_dot() {
+  let program_id = ::Vault::id();
+  let strategy_program = Pubkey::new_unique();
+  let (hodl, parent, leaf, strategy) = (
+    Pubkey::new_unique(),
+    Pubkey::new_unique(),
+    Pubkey::new_unique(),
+    Pubkey::new_unique(),
+  );
+  let x_mint = Pubkey::new_unique();
+  let graph = InvestmentGraph::new(
+    &program_id,
+    vec![
+      (hodl, vault(true, program_id, vec![], x_mint)),
+      (parent, vault(false, program_id, vec![hodl], x_mint)),
+      (leaf, vault(false, strategy_program, vec![strategy], x_mint)),
+    ],
+  );
+  let dot = graph.to_dot();
+  assert!(dot.starts_with("digraph vaults {\n"));
+  assert!(dot.ends_with("}\n"));
+  assert!(dot.contains(&format!(
+    "\"{}\" [shape=box, label=\"vault {}\\nX {}\\nhodl\"];",
+    hodl, hodl, x_mint
+  )));
+  assert!(dot.contains(&format!("\"{}\" -> \"{}\";", parent, hodl)));
+  assert!(dot.contains(&format!("\"{}\" -> \"{}\";", leaf, strategy)));
+  assert!(dot.contains(&format!(
+    "\"{}\" [shape=ellipse, label=\"strategy {}\\n{}\"];",
+    strategy, strategy_program, strategy
+  )));
+  // Vaults invested through aren't also drawn as strategies.
+  assert!(!dot.contains(&format!("\"{}\" [shape=ellipse", hodl)));
+}
+
+/// Tests loading a graph from its roots, & the child vault metas passed on creating a vault.
+#[tokio::test]
+async fn test_investment_graph_load() {
+  let program_id = ::Vault::id();
+  let x_mint = Pubkey::new_unique();
+  let vaults: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+  let mut fetcher = MapFetcher::default();
+  fetcher.insert(vaults[0], vault(true, program_id, vec![], x_mint));
+  for i in 1..4 {
+    fetcher.insert(
+      vaults[i],
+      vault(false, program_id, vec![vaults[i - 1]], x_mint),
+    );
+  }
+
+  let graph = InvestmentGraph::load(&mut fetcher, &program_id, &vaults[2..3])
+    .await
+    .unwrap();
+  assert_eq!(
+    graph.vaults().keys().collect::<Vec<_>>(),
+    {
+      let mut expected: Vec<_> = vaults[..3].iter().collect();
+      expected.sort();
+      expected
+    }
+  );
+
+  assert_eq!(
+    child_vault_metas(&mut fetcher, &program_id, &vaults[2])
+      .await
+      .unwrap(),
+    vec![
+      AccountMeta::new_readonly(vaults[1], false),
+      AccountMeta::new_readonly(vaults[0], false),
+    ]
+  );
+  // Chains longer than `MAX_VAULT_DEPTH` are cut short.
+  assert_eq!(
+    child_vault_metas(&mut fetcher, &program_id, &vaults[3])
+      .await
+      .unwrap()
+      .len(),
+    2
+  );
+  assert!(matches!(
+    child_vault_metas(&mut fetcher, &program_id, &Pubkey::new_unique()).await,
+    Err(ClientError::AccountNotFound(_))
+  ));
+}
+
+/// Tests that loading stops at cycles written before they were rejected.
+#[tokio::test]
+async fn test_investment_graph_cycle() {
+  let program_id = ::Vault::id();
+  let x_mint = Pubkey::new_unique();
+  let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+  let mut fetcher = MapFetcher::default();
+  fetcher.insert(a, vault(false, program_id, vec![b], x_mint));
+  fetcher.insert(b, vault(false, program_id, vec![a], x_mint));
+
+  let graph = InvestmentGraph::load(&mut fetcher, &program_id, &[a])
+    .await
+    .unwrap();
+  assert_eq!(graph.vaults().len(), 2);
+  assert_eq!(
+    child_vault_metas(&mut fetcher, &program_id, &a)
+      .await
+      .unwrap(),
+    vec![AccountMeta::new_readonly(b, false)]
+  );
+}
+
+#[derive(Default)]
+struct MapFetcher {
+  accounts: HashMap<Pubkey, FetchedAccount>,
+}
+
+impl MapFetcher {
+  fn insert(&mut self, address: Pubkey, vault: Vault) {
+    let mut data = vec![0; Vault::LEN];
+    Vault::pack(vault, &mut data).unwrap();
+    self.accounts.insert(
+      address,
+      FetchedAccount {
+        owner: ::Vault::id(),
+        data,
+      },
+    );
+  }
+}
+
+#[async_trait(?Send)]
+impl AccountFetcher for MapFetcher {
+  async fn fetch_account(
+    &mut self,
+    address: &Pubkey,
+  ) -> Result<Option<FetchedAccount>, ClientError> {
+    Ok(self.accounts.get(address).cloned())
+  }
+}
+
+fn vault(hodl: bool, strategy_program: Pubkey, invests_in: Vec<Pubkey>, x_mint: Pubkey) -> Vault {
+  Vault {
+    is_initialized: true,
+    hodl,
+    llx_token_mint_id: Pubkey::new_unique(),
+    lx_token_account: Pubkey::new_unique(),
+    x_token_account: if hodl {
+      COption::Some(Pubkey::new_unique())
+    } else {
+      COption::None
+    },
+    strategy_program_id: strategy_program,
+    strategy_program_deposit_instruction: InstructionTemplate::tag(1),
+    strategy_program_withdraw_instruction: InstructionTemplate::tag(2),
+    strategy_accounts: invests_in
+      .into_iter()
+      .map(|pubkey| StrategyAccount::new(pubkey, true))
+      .collect(),
+    x_token_mint_id: x_mint,
+    x_decimals: 6,
+    llx_decimals: 6,
+    governance: Pubkey::new_unique(),
+    total_assets: 0,
+    total_assets_slot: 0,
+    management_fee_bps: 0,
+    fee_recipients: vec![],
+    fees_collected_slot: 0,
+    referral_fee_bps: 0,
+    high_water_share_price: 0,
+    price_feed: COption::None,
+    max_drawdown_bps: 0,
+    referred_shares: 0,
+    referral_fee_index: 0,
+    strategies: vec![],
+    withdrawal_queue: vec![],
+  }
+}
@@ -0,0 +1,676 @@
+#![cfg(feature = "idl")]
+
+use {
+  ::Vault::{
+    idl::{strategy_idl, vault_idl},
+    instruction::{StrategyInstruction, VaultInstruction},
+    state::{
+      AmountEncoding, FeeRecipient, InstructionTemplate, PeekResult, PriceFeed, Referral,
+      Registration, Registry, StrategyAccount, Trigger, TriggerAction, TriggerCondition, Vault,
+    },
+  },
+  serde_json::{Map, Value},
+  solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+  },
+  std::{collections::BTreeSet, fs, path::PathBuf, str::FromStr},
+};
+
+/// Tests that the checked-in IDLs are those generated from the code. Set `UPDATE_IDL` to
+/// regenerate them.
+#[test]
+fn test_idl_up_to_date() {
+  for (idl, file) in [(vault_idl(), "vault.json"), (strategy_idl(), "strategy.json")].iter() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("idl").join(file);
+    let generated = serde_json::to_string_pretty(idl).unwrap() + "\n";
+    if std::env::var_os("UPDATE_IDL").is_some() {
+      fs::write(&path, &generated).unwrap();
+    }
+    assert_eq!(
+      fs::read_to_string(&path).unwrap(),
+      generated,
+      "{} is out of date; rerun with UPDATE_IDL=1",
+      path.display()
+    );
+  }
+}
+
+/// Tests that account layouts in the IDLs add up to the packed lengths.
+#[test]
+fn test_idl_account_layouts() {
+  let vault = vault_idl();
+  assert_eq!(type_size(&vault, &defined("Vault")), Vault::LEN);
+  assert_eq!(type_size(&vault, &defined("Registry")), Registry::LEN);
+  assert_eq!(type_size(&vault, &defined("Registration")), Registration::LEN);
+  assert_eq!(type_size(&vault, &defined("Referral")), Referral::LEN);
+  assert_eq!(type_size(&vault, &defined("Trigger")), Trigger::LEN);
+  assert_eq!(
+    type_size(&vault, &defined("TriggerCondition")),
+    TriggerCondition::LEN
+  );
+  assert_eq!(type_size(&vault, &defined("StrategyAccount")), StrategyAccount::LEN);
+  assert_eq!(type_size(&vault, &defined("FeeRecipient")), FeeRecipient::LEN);
+  assert_eq!(type_size(&vault, &defined("PriceFeed")), PriceFeed::LEN);
+  assert_eq!(
+    type_size(&vault, &defined("InstructionTemplate")),
+    InstructionTemplate::LEN
+  );
+  let strategy = strategy_idl();
+  assert_eq!(type_size(&strategy, &defined("PeekResult")), PeekResult::LEN);
+}
+
+/// Tests that the IDLs' instructions match those built by `instruction.rs`.
+#[test]
+fn test_idl_instructions() {
+  let key = Pubkey::new_unique;
+  let template = InstructionTemplate::tag(0);
+  let vault_instructions = vec![
+    VaultInstruction::initialize_vault(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      true,
+      &key(),
+      vec![],
+      template.clone(),
+      template.clone(),
+    ),
+    VaultInstruction::deposit(&key(), &key(), &key(), &key(), vec![], 1),
+    VaultInstruction::withdraw(&key(), &key(), &key(), &key(), vec![], 1),
+    VaultInstruction::deposit_native(&key(), &key(), &key(), &key(), vec![], 1),
+    VaultInstruction::withdraw_native(&key(), &key(), &key(), &key(), vec![], 1),
+    VaultInstruction::create_vault(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      0,
+      true,
+      vec![],
+      6,
+      template.clone(),
+      template,
+    ),
+    VaultInstruction::register_vault(&key(), &key(), &key(), &key(), &key(), 1, 1),
+    VaultInstruction::deregister_vault(&key(), &key(), &key(), &key(), 0, 0, &key()),
+    VaultInstruction::close_vault(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      COption::Some((key(), key(), key())),
+      &[key()],
+    ),
+    VaultInstruction::roll_up(
+      &key(),
+      vec![
+        AccountMeta::new(key(), false),
+        AccountMeta::new_readonly(key(), false),
+        AccountMeta::new_readonly(key(), false),
+      ],
+    ),
+    VaultInstruction::set_fees(&key(), &key(), &key(), 0, 0, vec![]),
+    VaultInstruction::collect_fees(&key(), &key(), &key(), &key(), &[], &[]),
+    VaultInstruction::create_trigger(
+      &key(),
+      &key(),
+      &key(),
+      0,
+      TriggerCondition::Drawdown { bps: 1000 },
+      TriggerAction::MoveToHodl,
+      1,
+    ),
+    VaultInstruction::execute_trigger(&key(), &key(), &key(), &key(), vec![]),
+    VaultInstruction::set_price_feed(
+      &key(),
+      &key(),
+      &key(),
+      COption::Some(PriceFeed {
+        asset_oracle: key(),
+        x_oracle: key(),
+        asset_decimals: 6,
+      }),
+    ),
+    VaultInstruction::set_max_drawdown(&key(), &key(), &key(), 1000),
+    VaultInstruction::enable_strategy(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      vec![],
+    ),
+    VaultInstruction::migrate_strategy(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      vec![StrategyAccount::new(key(), true)],
+      InstructionTemplate::tag(0),
+      InstructionTemplate::tag(1),
+    ),
+    VaultInstruction::harvest(&key(), &key(), COption::None, vec![]),
+    VaultInstruction::add_strategy(
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      &key(),
+      vec![StrategyAccount::new(key(), true)],
+      InstructionTemplate::tag(0),
+      InstructionTemplate::tag(1),
+      5000,
+      1,
+      1000,
+    ),
+    VaultInstruction::update_strategy(&key(), &key(), &key(), 0, 2500, 0, u64::MAX),
+    VaultInstruction::set_withdrawal_queue(&key(), &key(), &key(), vec![1, 0]),
+  ];
+  check_instructions(&vault_idl(), vault_instructions, &[1, 2, 3, 4]);
+
+  let strategy_instructions = vec![
+    StrategyInstruction::deposit(&key(), &key(), &key(), &key(), &key(), &key(), vec![], 1),
+    StrategyInstruction::withdraw(&key(), &key(), &key(), &key(), &key(), &key(), vec![], 1),
+    StrategyInstruction::peek(&key(), &key(), &key(), vec![], 1, &key()),
+    StrategyInstruction::harvest(&key(), &key(), vec![]),
+    StrategyInstruction::emergency_exit(&key(), &key(), &key(), vec![]),
+  ];
+  check_instructions(&strategy_idl(), strategy_instructions, &[]);
+}
+
+/// Tests that every vault & strategy instruction, with & without its optional trailing
+/// arguments, decodes through the IDL and encodes back to the same bytes the program unpacks.
+#[test]
+fn test_idl_round_trips_instructions() {
+  let key = Pubkey::new_unique;
+  let template = InstructionTemplate {
+    prefix: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    amount_offset: 8,
+    amount_encoding: AmountEncoding::U64Be,
+  };
+  let strategy_accounts = vec![
+    StrategyAccount::new(key(), true),
+    StrategyAccount::new(key(), false),
+  ];
+  let vault_instructions = vec![
+    VaultInstruction::InitializeVault {
+      strategy_program_deposit_instruction: template.clone(),
+      strategy_program_withdraw_instruction: InstructionTemplate::tag(1),
+      hodl: false,
+      strategy_accounts: strategy_accounts.clone(),
+    },
+    VaultInstruction::Deposit {
+      amount: 1,
+      referrer: COption::None,
+    },
+    VaultInstruction::Deposit {
+      amount: 2,
+      referrer: COption::Some(key()),
+    },
+    VaultInstruction::Withdraw {
+      amount: 3,
+      max_loss_bps: COption::None,
+    },
+    VaultInstruction::Withdraw {
+      amount: 4,
+      max_loss_bps: COption::Some(50),
+    },
+    VaultInstruction::DepositNative { lamports: 5 },
+    VaultInstruction::WithdrawNative { amount: 6 },
+    VaultInstruction::CreateVault {
+      nonce: 7,
+      strategy_program_deposit_instruction: template.clone(),
+      strategy_program_withdraw_instruction: template,
+      hodl: true,
+      llx_decimals: 9,
+      strategy_accounts,
+    },
+    VaultInstruction::RegisterVault {
+      mint_registry_page: 1,
+      strategy_registry_page: 2,
+    },
+    VaultInstruction::DeregisterVault,
+    VaultInstruction::CloseVault,
+    VaultInstruction::RollUp,
+    VaultInstruction::SetFees {
+      management_fee_bps: 200,
+      referral_fee_bps: 1000,
+      fee_recipients: vec![FeeRecipient::new(key(), 6000), FeeRecipient::new(key(), 4000)],
+    },
+    VaultInstruction::CollectFees,
+    VaultInstruction::CreateTrigger {
+      nonce: 8,
+      condition: TriggerCondition::PriceBelow {
+        oracle: key(),
+        price: -9,
+      },
+      action: TriggerAction::MoveToHodl,
+      bounty_lamports: 10,
+    },
+    VaultInstruction::ExecuteTrigger,
+    VaultInstruction::SetPriceFeed {
+      price_feed: COption::None,
+    },
+    VaultInstruction::SetPriceFeed {
+      price_feed: COption::Some(PriceFeed {
+        asset_oracle: key(),
+        x_oracle: key(),
+        asset_decimals: 6,
+      }),
+    },
+    VaultInstruction::SetMaxDrawdown {
+      max_drawdown_bps: 1000,
+    },
+    VaultInstruction::EnableStrategy,
+    VaultInstruction::MigrateStrategy {
+      strategy_program_deposit_instruction: InstructionTemplate::tag(2),
+      strategy_program_withdraw_instruction: InstructionTemplate::tag(3),
+      strategy_accounts: vec![StrategyAccount::new(key(), true)],
+    },
+    VaultInstruction::Harvest {
+      strategy: COption::None,
+    },
+    VaultInstruction::Harvest {
+      strategy: COption::Some(2),
+    },
+    VaultInstruction::AddStrategy {
+      strategy_program_deposit_instruction: InstructionTemplate::tag(2),
+      strategy_program_withdraw_instruction: InstructionTemplate::tag(3),
+      debt_ratio_bps: 5000,
+      min_debt_per_harvest: 1,
+      max_debt_per_harvest: 1000,
+      strategy_accounts: vec![StrategyAccount::new(key(), true)],
+    },
+    VaultInstruction::UpdateStrategy {
+      strategy: 1,
+      debt_ratio_bps: 2500,
+      min_debt_per_harvest: 0,
+      max_debt_per_harvest: u64::MAX,
+    },
+    VaultInstruction::SetWithdrawalQueue {
+      withdrawal_queue: vec![2, 0, 1],
+    },
+  ];
+  let vault = vault_idl();
+  let mut covered = BTreeSet::new();
+  for instruction in vault_instructions {
+    covered.insert(vault_discriminant(&instruction));
+    let data = instruction.pack();
+    round_trip(&vault, &data);
+    assert_eq!(VaultInstruction::unpack(&data).unwrap().pack(), data);
+  }
+  assert_eq!(covered, discriminants(&vault));
+
+  let strategy_instructions = vec![
+    StrategyInstruction::Deposit { amount: 1 },
+    StrategyInstruction::Withdraw { amount: 2 },
+    StrategyInstruction::Peek {
+      amount: 3,
+      vault: key(),
+    },
+    StrategyInstruction::Harvest,
+    StrategyInstruction::EmergencyExit,
+  ];
+  let strategy = strategy_idl();
+  let mut covered = BTreeSet::new();
+  for instruction in strategy_instructions {
+    let data = instruction.pack();
+    covered.insert(data[0]);
+    round_trip(&strategy, &data);
+    assert_eq!(StrategyInstruction::unpack(&data).unwrap().pack(), data);
+  }
+  assert_eq!(covered, discriminants(&strategy));
+}
+
+/// The tag of each vault instruction, matched exhaustively so that a new variant can't be added
+/// without a case in `test_idl_round_trips_instructions`.
+fn vault_discriminant(instruction: &VaultInstruction) -> u8 {
+  match instruction {
+    VaultInstruction::InitializeVault { .. } => 0,
+    VaultInstruction::Deposit { .. } => 1,
+    VaultInstruction::Withdraw { .. } => 2,
+    VaultInstruction::DepositNative { .. } => 3,
+    VaultInstruction::WithdrawNative { .. } => 4,
+    VaultInstruction::CreateVault { .. } => 5,
+    VaultInstruction::RegisterVault { .. } => 6,
+    VaultInstruction::DeregisterVault => 7,
+    VaultInstruction::CloseVault => 8,
+    VaultInstruction::RollUp => 9,
+    VaultInstruction::SetFees { .. } => 10,
+    VaultInstruction::CollectFees => 11,
+    VaultInstruction::CreateTrigger { .. } => 12,
+    VaultInstruction::ExecuteTrigger => 13,
+    VaultInstruction::SetPriceFeed { .. } => 14,
+    VaultInstruction::SetMaxDrawdown { .. } => 15,
+    VaultInstruction::EnableStrategy => 16,
+    VaultInstruction::MigrateStrategy { .. } => 17,
+    VaultInstruction::Harvest { .. } => 18,
+    VaultInstruction::AddStrategy { .. } => 19,
+    VaultInstruction::UpdateStrategy { .. } => 20,
+    VaultInstruction::SetWithdrawalQueue { .. } => 21,
+  }
+}
+
+fn discriminants(idl: &Value) -> BTreeSet<u8> {
+  idl["instructions"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|instruction| instruction["discriminant"]["value"].as_u64().unwrap() as u8)
+    .collect()
+}
+
+/// Decodes instruction data through the IDL instruction with its discriminant, checking that
+/// encoding the decoded arguments gives the data back.
+fn round_trip(idl: &Value, data: &[u8]) {
+  let idl_instruction = idl["instructions"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .find(|idl_instruction| {
+      idl_instruction["discriminant"]["value"].as_u64() == Some(data[0] as u64)
+    })
+    .unwrap_or_else(|| panic!("No IDL instruction {}", data[0]));
+  let args = decode_args(idl, idl_instruction, data);
+  assert_eq!(
+    encode_args(idl, idl_instruction, &args),
+    data,
+    "{} {}",
+    idl_instruction["name"],
+    args
+  );
+}
+
+/// Checks each built instruction against the IDL instruction with its discriminant. Accounts of
+/// the `partial` instructions, whose builders take caller-supplied accounts, are compared only as
+/// far as the builder lists them.
+fn check_instructions(
+  idl: &Value,
+  instructions: Vec<Result<Instruction, ProgramError>>,
+  partial: &[u8],
+) {
+  let idl_instructions = idl["instructions"].as_array().unwrap();
+  assert_eq!(idl_instructions.len(), instructions.len());
+  for instruction in instructions {
+    let instruction = instruction.unwrap();
+    let idl_instruction = idl_instructions
+      .iter()
+      .find(|idl_instruction| {
+        idl_instruction["discriminant"]["value"].as_u64() == Some(instruction.data[0] as u64)
+      })
+      .unwrap_or_else(|| panic!("No IDL instruction {}", instruction.data[0]));
+    let name = idl_instruction["name"].as_str().unwrap();
+
+    assert_eq!(
+      encode_args(
+        idl,
+        idl_instruction,
+        &decode_args(idl, idl_instruction, &instruction.data)
+      ),
+      instruction.data,
+      "{} args",
+      name
+    );
+
+    let idl_accounts = idl_instruction["accounts"].as_array().unwrap();
+    if !partial.contains(&instruction.data[0]) {
+      let required = idl_accounts
+        .iter()
+        .filter(|account| account["isOptional"] != Value::Bool(true))
+        .count();
+      assert!(
+        (required..=idl_accounts.len()).contains(&instruction.accounts.len()),
+        "{} accounts",
+        name
+      );
+    }
+    for (meta, idl_account) in instruction.accounts.iter().zip(idl_accounts) {
+      assert_eq!(
+        (meta.is_writable, meta.is_signer),
+        (
+          idl_account["isMut"].as_bool().unwrap(),
+          idl_account["isSigner"].as_bool().unwrap()
+        ),
+        "{}.{}",
+        name,
+        idl_account["name"]
+      );
+    }
+  }
+}
+
+fn defined(name: &str) -> Value {
+  serde_json::json!({ "defined": name })
+}
+
+/// Decodes an instruction's arguments, which must use up its data. Optional arguments may be
+/// left off the end.
+fn decode_args(idl: &Value, idl_instruction: &Value, data: &[u8]) -> Value {
+  let name = &idl_instruction["name"];
+  let mut rest = &data[1..];
+  let mut args = Map::new();
+  for arg in idl_instruction["args"].as_array().unwrap() {
+    if rest.is_empty() && arg["isOptional"] == Value::Bool(true) {
+      break;
+    }
+    let value = decode(idl, &arg["type"], &mut rest);
+    args.insert(arg["name"].as_str().unwrap().to_string(), value);
+  }
+  assert!(rest.is_empty(), "{} has {} bytes left over", name, rest.len());
+  Value::Object(args)
+}
+
+/// Encodes an instruction's arguments after its discriminant, stopping at the first optional
+/// argument not given.
+fn encode_args(idl: &Value, idl_instruction: &Value, args: &Value) -> Vec<u8> {
+  let name = &idl_instruction["name"];
+  let mut buf = vec![idl_instruction["discriminant"]["value"].as_u64().unwrap() as u8];
+  let mut ended = false;
+  for arg in idl_instruction["args"].as_array().unwrap() {
+    match args.get(arg["name"].as_str().unwrap()) {
+      Some(value) => {
+        assert!(!ended, "{}.{} follows a missing argument", name, arg["name"]);
+        encode(idl, &arg["type"], value, &mut buf);
+      }
+      None => {
+        assert_eq!(arg["isOptional"], Value::Bool(true), "{}.{}", name, arg["name"]);
+        ended = true;
+      }
+    }
+  }
+  buf
+}
+
+/// Returns the definition of a defined type in `idl`.
+fn definition<'a>(idl: &'a Value, name: &Value) -> &'a Value {
+  ["accounts", "types"]
+    .iter()
+    .filter_map(|section| idl[section].as_array())
+    .flatten()
+    .find(|definition| definition["name"] == *name)
+    .unwrap_or_else(|| panic!("Undefined type {}", name))
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> &'a [u8] {
+  assert!(data.len() >= len, "{} bytes short", len - data.len());
+  let (taken, rest) = data.split_at(len);
+  *data = rest;
+  taken
+}
+
+/// Decodes an IDL type from the front of `data` into JSON.
+fn decode(idl: &Value, ty: &Value, data: &mut &[u8]) -> Value {
+  match ty {
+    Value::String(primitive) => {
+      let bytes = take(data, type_size(idl, ty));
+      let mut le = [0; 8];
+      match primitive.as_str() {
+        "bool" => match bytes[0] {
+          0 => Value::Bool(false),
+          1 => Value::Bool(true),
+          byte => panic!("Invalid bool {}", byte),
+        },
+        "u8" | "u16" | "u32" | "u64" => {
+          le[..bytes.len()].copy_from_slice(bytes);
+          u64::from_le_bytes(le).into()
+        }
+        "i64" => {
+          le.copy_from_slice(bytes);
+          i64::from_le_bytes(le).into()
+        }
+        "publicKey" => Pubkey::new(bytes).to_string().into(),
+        _ => panic!("Can't decode {}", primitive),
+      }
+    }
+    Value::Object(object) if object.contains_key("array") => {
+      let array = object["array"].as_array().unwrap();
+      (0..array[1].as_u64().unwrap())
+        .map(|_| decode(idl, &array[0], data))
+        .collect()
+    }
+    Value::Object(object) if object.contains_key("option") => match take(data, 1)[0] {
+      0 => Value::Null,
+      1 => decode(idl, &object["option"], data),
+      tag => panic!("Invalid option tag {}", tag),
+    },
+    Value::Object(object) if object.contains_key("vec") => {
+      assert_eq!(object["lenType"], "u8");
+      let len = take(data, 1)[0] as u64;
+      assert!(len <= object["maxLen"].as_u64().unwrap(), "{} items", len);
+      (0..len).map(|_| decode(idl, &object["vec"], data)).collect()
+    }
+    Value::Object(object) if object.contains_key("defined") => {
+      let definition = &definition(idl, &object["defined"])["type"];
+      match definition["kind"].as_str().unwrap() {
+        "struct" => Value::Object(
+          definition["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| {
+              let value = decode(idl, &field["type"], data);
+              (field["name"].as_str().unwrap().to_string(), value)
+            })
+            .collect(),
+        ),
+        "enum" => {
+          let index = take(data, 1)[0] as usize;
+          definition["variants"][index]["name"].clone()
+        }
+        kind => panic!("Unknown kind {}", kind),
+      }
+    }
+    _ => panic!("Unknown type {}", ty),
+  }
+}
+
+/// Encodes JSON decoded by `decode` back into bytes.
+fn encode(idl: &Value, ty: &Value, value: &Value, buf: &mut Vec<u8>) {
+  match ty {
+    Value::String(primitive) => match primitive.as_str() {
+      "bool" => buf.push(value.as_bool().unwrap() as u8),
+      "u8" | "u16" | "u32" | "u64" => {
+        let le = value.as_u64().unwrap().to_le_bytes();
+        buf.extend_from_slice(&le[..type_size(idl, ty)]);
+      }
+      "i64" => buf.extend_from_slice(&value.as_i64().unwrap().to_le_bytes()),
+      "publicKey" => {
+        buf.extend_from_slice(Pubkey::from_str(value.as_str().unwrap()).unwrap().as_ref())
+      }
+      _ => panic!("Can't encode {}", primitive),
+    },
+    Value::Object(object) if object.contains_key("array") => {
+      let array = object["array"].as_array().unwrap();
+      let items = value.as_array().unwrap();
+      assert_eq!(items.len() as u64, array[1].as_u64().unwrap());
+      for item in items {
+        encode(idl, &array[0], item, buf);
+      }
+    }
+    Value::Object(object) if object.contains_key("option") => match value {
+      Value::Null => buf.push(0),
+      value => {
+        buf.push(1);
+        encode(idl, &object["option"], value, buf);
+      }
+    },
+    Value::Object(object) if object.contains_key("vec") => {
+      let items = value.as_array().unwrap();
+      buf.push(items.len() as u8);
+      for item in items {
+        encode(idl, &object["vec"], item, buf);
+      }
+    }
+    Value::Object(object) if object.contains_key("defined") => {
+      let definition = &definition(idl, &object["defined"])["type"];
+      match definition["kind"].as_str().unwrap() {
+        "struct" => {
+          for field in definition["fields"].as_array().unwrap() {
+            encode(idl, &field["type"], &value[field["name"].as_str().unwrap()], buf);
+          }
+        }
+        "enum" => buf.push(
+          definition["variants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|variant| variant["name"] == *value)
+            .unwrap() as u8,
+        ),
+        kind => panic!("Unknown kind {}", kind),
+      }
+    }
+    _ => panic!("Unknown type {}", ty),
+  }
+}
+
+/// Returns the packed size of an IDL type, looking defined types up in `idl`.
+fn type_size(idl: &Value, ty: &Value) -> usize {
+  match ty {
+    Value::String(primitive) => match primitive.as_str() {
+      "bool" | "u8" => 1,
+      "u16" => 2,
+      "u32" => 4,
+      "u64" | "i64" => 8,
+      "u128" => 16,
+      "publicKey" => 32,
+      _ => panic!("Unknown type {}", primitive),
+    },
+    Value::Object(object) if object.contains_key("array") => {
+      let array = object["array"].as_array().unwrap();
+      type_size(idl, &array[0]) * array[1].as_u64().unwrap() as usize
+    }
+    Value::Object(object) if object.contains_key("defined") => {
+      let definition = definition(idl, &object["defined"]);
+      match definition["type"]["kind"].as_str().unwrap() {
+        "struct" => definition["type"]["fields"]
+          .as_array()
+          .unwrap()
+          .iter()
+          .map(|field| type_size(idl, &field["type"]))
+          .sum(),
+        "enum" => 1,
+        kind => panic!("Unknown kind {}", kind),
+      }
+    }
+    _ => panic!("Unknown type {}", ty),
+  }
+}
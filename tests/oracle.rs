@@ -0,0 +1,129 @@
+use {
+  ::Vault::{
+    error::VaultError,
+    oracle::{self, Price},
+  },
+  solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey},
+};
+
+/// Tests that prices read back as written, & that other accounts are rejected.
+#[test]
+fn test_price_layout() {
+  let price = Price {
+    price: -12_345,
+    conf: 67,
+    expo: -5,
+    status: oracle::STATUS_TRADING,
+    publish_slot: 89,
+  };
+  let mut data = vec![0; oracle::PRICE_ACCOUNT_LEN];
+  price.pack(&mut data).unwrap();
+  assert_eq!(Price::unpack(&data), Ok(price));
+
+  assert_eq!(
+    Price::unpack(&data[..oracle::PRICE_ACCOUNT_LEN - 1]),
+    Err(ProgramError::InvalidAccountData)
+  );
+  data[4] = 1; // An older layout
+  assert_eq!(Price::unpack(&data), Err(ProgramError::InvalidAccountData));
+  data[4] = 2;
+  data[8] = 2; // A product account
+  assert_eq!(Price::unpack(&data), Err(ProgramError::InvalidAccountData));
+}
+
+/// Tests that prices are only loaded from accounts an oracle program owns.
+#[test]
+fn test_price_load() {
+  let price = Price {
+    price: 100,
+    status: oracle::STATUS_TRADING,
+    ..Price::default()
+  };
+  let mut data = vec![0; oracle::PRICE_ACCOUNT_LEN];
+  price.pack(&mut data).unwrap();
+  let key = Pubkey::new_unique();
+  for (owner, expected) in [
+    (oracle::pyth_mainnet::id(), Ok(price)),
+    (oracle::pyth_devnet::id(), Ok(price)),
+    (Pubkey::new_unique(), Err(ProgramError::IncorrectProgramId)),
+  ]
+  .iter()
+  {
+    let mut lamports = 0;
+    let account = AccountInfo::new(
+      &key,
+      false,
+      false,
+      &mut lamports,
+      &mut data,
+      owner,
+      false,
+      0,
+    );
+    assert_eq!(Price::load(&account), *expected);
+  }
+}
+
+/// Tests that only trading prices, recently published & known precisely enough, are current.
+#[test]
+fn test_price_check_current() {
+  let price = Price {
+    price: 10_000,
+    conf: 200,
+    expo: -4,
+    status: oracle::STATUS_TRADING,
+    publish_slot: 100,
+  };
+  assert!(price
+    .check_current(100 + oracle::MAX_PRICE_AGE_SLOTS)
+    .is_ok());
+  let stale = [
+    (price, 100 + oracle::MAX_PRICE_AGE_SLOTS + 1),
+    (Price { conf: 201, ..price }, 100),
+    (Price { status: 0, ..price }, 100),
+    (
+      Price {
+        price: 0,
+        conf: 0,
+        ..price
+      },
+      100,
+    ),
+    (
+      Price {
+        price: -1,
+        conf: 0,
+        ..price
+      },
+      100,
+    ),
+  ];
+  for (price, slot) in stale.iter() {
+    assert!(
+      matches!(price.check_current(*slot), Err(VaultError::StalePrice)),
+      "{:?} at {}",
+      price,
+      slot
+    );
+  }
+}
+
+/// Tests valuing one token in another across exponents & decimals.
+#[test]
+fn test_price_value_in() {
+  let price = |price, expo| Price {
+    price,
+    expo,
+    ..Price::default()
+  };
+  // An asset at $2.50 with 9 decimals, in X at $0.50 with 6 decimals.
+  let asset = price(250, -2);
+  let x = price(50_000_000, -8);
+  assert_eq!(asset.value_in(3_000_000_000, 9, &x, 6), Some(15_000_000));
+  assert_eq!(x.value_in(15_000_000, 6, &asset, 9), Some(3_000_000_000));
+  // Rounding down.
+  assert_eq!(asset.value_in(1, 9, &x, 6), Some(0));
+  assert_eq!(asset.value_in(1, 0, &x, 0), Some(5));
+  assert_eq!(asset.value_in(u64::MAX, 0, &x, 0), None);
+  assert_eq!(asset.value_in(1, 0, &price(0, 0), 0), None);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "sim")]
+
+use {
+  ::Vault::{
+    math,
+    sim::{self, FeeConfig, Scenario, SimError, Step},
+  },
+  std::{fs, path::PathBuf},
+};
+
+/// Tests a scenario against hand-computed share prices, fees & PnL.
+#[test]
+fn test_sim_fees() {
+  let scenario = Scenario {
+    x_decimals: 6,
+    llx_decimals: 6,
+    // A tenth of a year per period.
+    period_slots: math::SLOTS_PER_YEAR / 10,
+    fees: FeeConfig {
+      management_bps: 1000,
+    },
+    steps: vec![
+      deposit("alice", 1000),
+      Step::Yield { bps: 1000 },
+      deposit("bob", 110),
+      Step::Withdraw {
+        user: "alice".to_string(),
+        shares: None,
+      },
+    ],
+  };
+  let reports = sim::run(&scenario).unwrap();
+  assert_eq!(reports.len(), 4);
+
+  // 10% returns, then a 10% yearly fee over a tenth of a year minted as 1% more llX.
+  assert_eq!(reports[1].total_assets, 1100);
+  assert_eq!(reports[1].share_supply, 1010);
+  assert_eq!(reports[1].fee_shares, 10);
+  assert_eq!(reports[1].fee_value, 10);
+  assert_eq!(reports[1].user_pnl["alice"], 89);
+
+  // Bob buys in at the diluted price, paying no fee.
+  assert_eq!(reports[2].share_supply, 1111);
+  assert_eq!(reports[2].fee_shares, 0);
+  assert_eq!(reports[2].user_pnl["bob"], 0);
+
+  // Alice is paid her shares' value in full, leaving the fee recipients theirs.
+  assert_eq!(reports[3].total_assets, 121);
+  assert_eq!(reports[3].share_supply, 111);
+  assert_eq!(reports[3].fee_value, 10);
+  assert_eq!(reports[3].user_pnl["alice"], 89);
+  assert_eq!(reports[3].user_pnl["bob"], 0);
+}
+
+/// Tests that shares are priced across the X & llX decimals, as by the processor.
+#[test]
+fn test_sim_mixed_decimals() {
+  let scenario = Scenario {
+    x_decimals: 6,
+    llx_decimals: 9,
+    period_slots: 1,
+    fees: FeeConfig::default(),
+    steps: vec![deposit("alice", 1_000_000), Step::Loss { bps: 5000 }],
+  };
+  let reports = sim::run(&scenario).unwrap();
+  assert_eq!(reports[0].share_supply, 1_000_000_000);
+  assert_eq!(reports[0].share_price, Some(1.0));
+  assert_eq!(reports[1].share_price, Some(0.5));
+  assert_eq!(reports[1].user_pnl["alice"], -500_000);
+}
+
+/// Tests that invalid scenarios are rejected.
+#[test]
+fn test_sim_invalid_scenarios() {
+  let scenario = |fees, steps| Scenario {
+    x_decimals: 6,
+    llx_decimals: 6,
+    period_slots: 1,
+    fees,
+    steps,
+  };
+  assert!(matches!(
+    sim::run(&scenario(
+      FeeConfig::default(),
+      vec![
+        deposit("alice", 10),
+        Step::Withdraw {
+          user: "alice".to_string(),
+          shares: Some(11),
+        },
+      ],
+    )),
+    Err(SimError::InsufficientShares {
+      step: 1,
+      held: 10,
+      shares: 11,
+      ..
+    })
+  ));
+  assert!(matches!(
+    sim::run(&scenario(
+      FeeConfig::default(),
+      vec![Step::Withdraw {
+        user: "bob".to_string(),
+        shares: None,
+      }],
+    )),
+    Err(SimError::InsufficientShares { step: 0, .. })
+  ));
+  assert!(matches!(
+    sim::run(&scenario(
+      FeeConfig::default(),
+      vec![deposit("alice", 10), Step::Loss { bps: 10_001 }],
+    )),
+    Err(SimError::LossTooLarge(1))
+  ));
+  assert!(matches!(
+    sim::run(&scenario(
+      FeeConfig {
+        management_bps: 10_001,
+      },
+      vec![],
+    )),
+    Err(SimError::InvalidFees)
+  ));
+  assert!(matches!(
+    Scenario::from_json(r#"{ "x_decimals": 6, "llx_decimals": 6, "steps": [{ "mint": {} }] }"#),
+    Err(SimError::InvalidScenario(_))
+  ));
+  // The program charges no withdrawal fee.
+  assert!(matches!(
+    Scenario::from_json(
+      r#"{ "x_decimals": 6, "llx_decimals": 6, "fees": { "withdrawal_bps": 50 }, "steps": [] }"#
+    ),
+    Err(SimError::InvalidScenario(_))
+  ));
+}
+
+/// Tests that the checked-in scenarios run, leaving only the fee recipients' llX once all users
+/// withdraw.
+#[test]
+fn test_sim_scenarios() {
+  let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+  for entry in fs::read_dir(dir).unwrap() {
+    let path = entry.unwrap().path();
+    let scenario = Scenario::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+    let reports = sim::run(&scenario).unwrap();
+    let last = reports.last().unwrap();
+    let fee_shares = reports.iter().map(|report| report.fee_shares).sum();
+    assert_eq!(
+      (last.total_assets, last.share_supply),
+      (last.fee_value, fee_shares),
+      "{}",
+      path.display()
+    );
+  }
+}
+
+fn deposit(user: &str, amount: u64) -> Step {
+  Step::Deposit {
+    user: user.to_string(),
+    amount,
+  }
+}